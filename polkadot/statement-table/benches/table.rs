@@ -0,0 +1,167 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use parity_scale_codec::{Decode, Encode};
+use polkadot_statement_table::{
+	generic::{SignedStatement, SignedStatementFor, Statement},
+	proposal::PriorityThenBacking,
+	Config, Context, ProposalConfig, Table,
+};
+
+// validator set sizes, along with the number of groups they are split into.
+const VALIDATOR_SETS: [(u32, u32); 3] = [(100, 20), (300, 60), (500, 100)];
+
+const MINIMUM_BACKING_VOTES: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+struct Candidate {
+	group: u32,
+	// stands in for the commitments, which dominate the size of real candidates.
+	body: [u8; 256],
+}
+
+struct BenchContext {
+	validators: u32,
+	groups: u32,
+}
+
+impl BenchContext {
+	fn group_of(&self, validator: u32) -> u32 {
+		validator % self.groups
+	}
+
+	// every statement which all members of all groups issue in a round: one seconded statement
+	// per group and a validity statement from every other member.
+	fn round(&self) -> Vec<(u32, SignedStatementFor<Self>)> {
+		let mut statements = Vec::new();
+		for group in 0..self.groups {
+			let candidate = Candidate { group, body: [group as u8; 256] };
+			let digest = Self::candidate_digest(&candidate);
+			let mut members = (0..self.validators).filter(|v| self.group_of(*v) == group);
+
+			let seconder = members.next().expect("groups are not empty; qed");
+			statements.push((
+				group,
+				SignedStatement {
+					statement: Statement::Seconded(candidate),
+					signature: seconder.into(),
+					sender: seconder,
+				},
+			));
+			statements.extend(members.map(|member| {
+				(
+					group,
+					SignedStatement {
+						statement: Statement::Valid(digest),
+						signature: member.into(),
+						sender: member,
+					},
+				)
+			}));
+		}
+
+		statements
+	}
+
+	fn table(&self) -> Table<Self> {
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		for (group, statement) in self.round() {
+			table.import_statement(self, group, statement);
+		}
+
+		table
+	}
+}
+
+impl Context for BenchContext {
+	type AuthorityId = u32;
+	type Digest = u64;
+	type GroupId = u32;
+	type Signature = u64;
+	type Candidate = Candidate;
+
+	fn candidate_digest(candidate: &Candidate) -> u64 {
+		u64::from(candidate.group) << 32 | u64::from(candidate.body[0])
+	}
+
+	fn is_member_of(&self, authority: &u32, group: &u32) -> bool {
+		self.group_of(*authority) == *group
+	}
+
+	fn get_group_size(&self, group: &u32) -> Option<usize> {
+		(*group < self.groups).then(|| (self.validators / self.groups) as usize)
+	}
+}
+
+fn import_round(c: &mut Criterion) {
+	let mut group = c.benchmark_group("import_round");
+	for (validators, groups) in VALIDATOR_SETS {
+		let context = BenchContext { validators, groups };
+		let round = context.round();
+
+		group.throughput(Throughput::Elements(round.len() as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(validators), &context, |b, context| {
+			b.iter_batched(
+				|| round.clone(),
+				|round| {
+					let mut table = Table::new(Config { allow_multiple_seconded: false });
+					for (group, statement) in round {
+						table.import_statement(context, group, statement);
+					}
+					table
+				},
+				BatchSize::LargeInput,
+			)
+		});
+	}
+	group.finish();
+}
+
+fn tally_votes(c: &mut Criterion) {
+	let mut group = c.benchmark_group("attested_candidates");
+	for (validators, groups) in VALIDATOR_SETS {
+		let context = BenchContext { validators, groups };
+		let table = context.table();
+
+		group.throughput(Throughput::Elements(groups as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(validators), &context, |b, context| {
+			b.iter(|| table.attested_candidates(context, MINIMUM_BACKING_VOTES))
+		});
+	}
+	group.finish();
+}
+
+fn build_proposal(c: &mut Criterion) {
+	let mut group = c.benchmark_group("build_proposal");
+	for (validators, groups) in VALIDATOR_SETS {
+		let context = BenchContext { validators, groups };
+		let table = context.table();
+		let config =
+			ProposalConfig { max_candidates: Some(groups as usize / 2), ..Default::default() };
+
+		group.throughput(Throughput::Elements(groups as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(validators), &context, |b, context| {
+			b.iter(|| {
+				table.build_proposal(context, MINIMUM_BACKING_VOTES, &config, &PriorityThenBacking)
+			})
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, import_round, tally_votes, build_proposal);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decoding of statements received from peers, and verification of their signatures.
+
+use honggfuzz::fuzz;
+use polkadot_statement_table::{
+	router::batch::StatementBatchFor, test_utils::TestContext, verify::verify_statements, wire,
+};
+
+fn main() {
+	let context = TestContext::builder().with_interleaved_groups(4, 10).build();
+	loop {
+		fuzz!(|data: &[u8]| {
+			let Ok(batch) = wire::decode::<StatementBatchFor<TestContext>>(data) else { return };
+
+			let verified = verify_statements(&context, batch.statements.clone());
+			assert_eq!(verified.valid.len() + verified.invalid.len(), batch.statements.len());
+
+			let encoded = wire::encode(&batch);
+			let decoded = wire::decode::<StatementBatchFor<TestContext>>(&encoded).ok();
+			assert_eq!(decoded, Some(batch));
+		});
+	}
+}
@@ -0,0 +1,97 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Import of statements received from peers into a table.
+//!
+//! The first bytes choose the context and the configuration of the table, the rest are decoded
+//! as a batch of statements. Statements with the high bit of their nonce set keep their fuzzed
+//! signature, the others are signed by their sender. Like peers' statements, the batch is then
+//! verified and only the valid statements are imported, with the group the peer claimed.
+//!
+//! After import, the table must be consistent. Detected misbehavior must be provable, unless a
+//! statement claimed a group other than that of its candidate, in which case the proof may be
+//! rejected as not showing misbehavior, but never for a bad signature.
+
+use honggfuzz::fuzz;
+use polkadot_statement_table::{
+	generic::{GroupAssignment, Statement},
+	proof::{MisbehaviorProof, ProofError},
+	router::batch::StatementBatchFor,
+	test_utils::{sign, TestContext, SIGNING_CONTEXT},
+	verify::verify_statements,
+	wire, Config, Context, Table,
+};
+
+// nonces with this bit set keep the signature they were decoded with.
+const KEEP_SIGNATURE: u8 = 0x80;
+
+fn main() {
+	loop {
+		fuzz!(|data: &[u8]| {
+			let [validators, groups, flags, statements @ ..] = data else { return };
+			let Ok(batch) = wire::decode::<StatementBatchFor<TestContext>>(statements) else {
+				return
+			};
+
+			let context = TestContext::builder()
+				.with_interleaved_groups(1 + u32::from(groups % 4), 1 + u32::from(validators % 16))
+				.build();
+			let allow_multiple_seconded = flags & 1 == 1;
+			let mut table = Table::new(Config { allow_multiple_seconded });
+			let statements = batch
+				.statements
+				.into_iter()
+				.map(|(group_id, statement)| {
+					if statement.signature.nonce & KEEP_SIGNATURE != 0 {
+						return (group_id, statement)
+					}
+					let nonce = statement.signature.nonce;
+					(group_id, sign(statement.sender, statement.statement, nonce))
+				})
+				.collect();
+
+			let mut misattributed = false;
+			for (group_id, statement) in verify_statements(&context, statements).valid {
+				let digest = match &statement.statement {
+					Statement::Seconded(candidate) => TestContext::candidate_digest(candidate),
+					Statement::Valid(digest) => *digest,
+				};
+				misattributed |= context.candidate_group(&digest) != Some(group_id);
+				table.import_statement(&context, group_id, statement);
+			}
+
+			table.assert_invariants(&context);
+			for (authority, misbehavior) in table.get_misbehavior() {
+				for misbehavior in misbehavior {
+					let proof =
+						MisbehaviorProof::new::<TestContext>(*authority, misbehavior.clone());
+					let verified = proof.verify(
+						&context,
+						allow_multiple_seconded,
+						&SIGNING_CONTEXT,
+						|signer, payload, signature| signature.is_valid(signer, payload),
+					);
+					match verified {
+						Ok(()) => {},
+						Err(ProofError::NotMisbehavior | ProofError::UnknownGroup)
+							if misattributed => {},
+						Err(e) => panic!("unprovable misbehavior {:?}: {:?}", misbehavior, e),
+					}
+				}
+			}
+		});
+	}
+}
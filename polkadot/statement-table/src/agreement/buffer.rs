@@ -0,0 +1,134 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Buffering of messages for future rounds.
+//!
+//! Authorities don't move between rounds in lockstep, so messages for rounds the local
+//! authority hasn't reached yet are kept until it does. The buffer is bounded both in how far
+//! ahead messages may be and in how many messages a single authority may have buffered, so
+//! that a misbehaving authority can't exhaust memory. Messages for recent past rounds aren't
+//! buffered, they are imported into the rounds the agreement keeps.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Context, Round, SignedMessageFor};
+
+// Messages for future rounds, bounded according to an `AgreementConfig`.
+pub(crate) struct FutureMessages<C: Context> {
+	max_rounds_ahead: Round,
+	max_per_authority: usize,
+	by_round: BTreeMap<Round, Vec<SignedMessageFor<C>>>,
+	per_authority: HashMap<C::AuthorityId, usize>,
+}
+
+impl<C: Context> FutureMessages<C> {
+	pub(crate) fn new(max_rounds_ahead: Round, max_per_authority: usize) -> Self {
+		FutureMessages {
+			max_rounds_ahead,
+			max_per_authority,
+			by_round: BTreeMap::new(),
+			per_authority: HashMap::new(),
+		}
+	}
+
+	// Buffer a message, given the current round. Returns `false` if the message isn't for a
+	// future round, is too far ahead, or its sender has too many messages buffered already.
+	pub(crate) fn push(&mut self, current: Round, message: SignedMessageFor<C>) -> bool {
+		let round = message.message.round();
+		if round <= current || round - current > self.max_rounds_ahead {
+			return false
+		}
+
+		let count = self.per_authority.entry(message.sender.clone()).or_insert(0);
+		if *count >= self.max_per_authority {
+			return false
+		}
+
+		*count += 1;
+		self.by_round.entry(round).or_default().push(message);
+		true
+	}
+
+	// Take the messages for `round`, dropping those for earlier rounds.
+	pub(crate) fn take_round(&mut self, round: Round) -> Vec<SignedMessageFor<C>> {
+		let later = self.by_round.split_off(&round);
+		let earlier = std::mem::replace(&mut self.by_round, later);
+		for message in earlier.values().flatten() {
+			self.forget(&message.sender);
+		}
+
+		let messages = self.by_round.remove(&round).unwrap_or_default();
+		for message in &messages {
+			self.forget(&message.sender);
+		}
+
+		messages
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		self.per_authority.values().sum()
+	}
+
+	fn forget(&mut self, sender: &C::AuthorityId) {
+		if let Some(count) = self.per_authority.get_mut(sender) {
+			*count -= 1;
+			if *count == 0 {
+				self.per_authority.remove(sender);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agreement::{tests::TestContext, Message, SignedMessage};
+
+	fn prevote(round: Round, sender: u64) -> SignedMessageFor<TestContext> {
+		SignedMessage { message: Message::Prevote(round, None), signature: sender, sender }
+	}
+
+	#[test]
+	fn only_near_future_rounds_are_buffered() {
+		let mut buffer = FutureMessages::<TestContext>::new(2, 10);
+
+		assert!(!buffer.push(3, prevote(2, 0)));
+		assert!(!buffer.push(3, prevote(3, 0)));
+		assert!(buffer.push(3, prevote(4, 0)));
+		assert!(buffer.push(3, prevote(5, 0)));
+		assert!(!buffer.push(3, prevote(6, 0)));
+		assert_eq!(buffer.len(), 2);
+
+		assert_eq!(buffer.take_round(5), vec![prevote(5, 0)]);
+		assert_eq!(buffer.len(), 0);
+	}
+
+	#[test]
+	fn buffered_messages_are_bounded_per_authority() {
+		let mut buffer = FutureMessages::<TestContext>::new(10, 2);
+
+		assert!(buffer.push(0, prevote(1, 0)));
+		assert!(buffer.push(0, prevote(2, 0)));
+		assert!(!buffer.push(0, prevote(3, 0)));
+		assert!(buffer.push(0, prevote(3, 1)));
+
+		// replaying frees up space.
+		assert_eq!(buffer.take_round(1), vec![prevote(1, 0)]);
+		assert!(buffer.push(1, prevote(3, 0)));
+		assert_eq!(buffer.take_round(3), vec![prevote(3, 1), prevote(3, 0)]);
+		assert_eq!(buffer.len(), 0);
+	}
+}
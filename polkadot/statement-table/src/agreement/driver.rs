@@ -0,0 +1,327 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A future driving the agreement to completion.
+//!
+//! [`agree`] is a thin shell around the [`Machine`]: it feeds it messages from a source of
+//! incoming messages and the time told by a [`Clock`], passes broadcasts to a sink for outgoing
+//! ones and sleeps on a [`Timer`] until the next deadline. This is how a block author embeds the
+//! agreement.
+
+use std::{
+	future::Future,
+	pin::Pin,
+	task::{self, Poll},
+	time::Duration,
+};
+
+use futures::{Stream, StreamExt};
+
+use super::{
+	Action, ActionFor, Agreement, AgreementConfig, Clock, CommittedFor, Context, Machine,
+	RoundTimeout, SignedMessageFor, Timer,
+};
+
+/// Parameters of [`agree`].
+pub struct AgreementParams<C, I, O, T, R> {
+	/// The agreement context.
+	pub context: C,
+	/// Messages from other authorities. Their signatures must already be checked.
+	pub incoming: I,
+	/// Called with every message to be broadcast to the other authorities.
+	pub outgoing: O,
+	/// The source of time and delays for round timeouts.
+	pub timer: T,
+	/// Determines how long each round lasts.
+	pub round_timeout: R,
+	/// Configuration of the agreement.
+	pub config: AgreementConfig,
+}
+
+/// Run the agreement until a proposal is committed.
+///
+/// The returned future resolves with the committed proposal and its justification. It keeps
+/// running if `incoming` ends, as rounds still time out, but can't commit anything without
+/// messages from others unless the local authority alone forms a supermajority.
+pub fn agree<C, I, O, T, R>(params: AgreementParams<C, I, O, T, R>) -> Agree<C, I, O, T, R>
+where
+	C: Context,
+	I: Stream<Item = SignedMessageFor<C>> + Unpin,
+	O: FnMut(SignedMessageFor<C>),
+	T: Timer + Clock,
+	R: RoundTimeout,
+{
+	let AgreementParams { context, incoming, outgoing, timer, round_timeout, config } = params;
+	Agree {
+		context,
+		incoming,
+		outgoing,
+		timer,
+		start: Some((round_timeout, config)),
+		machine: None,
+		delay: None,
+		incoming_done: false,
+	}
+}
+
+/// Future returned by [`agree`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Agree<C: Context, I, O, T: Timer, R> {
+	context: C,
+	incoming: I,
+	outgoing: O,
+	timer: T,
+	// What the machine is started with on the first poll.
+	start: Option<(R, AgreementConfig)>,
+	machine: Option<Machine<C, R>>,
+	// The deadline being waited for and the delay until it.
+	delay: Option<(Duration, T::Delay)>,
+	incoming_done: bool,
+}
+
+impl<C: Context, I, O, T: Timer, R: RoundTimeout> Agree<C, I, O, T, R> {
+	/// The underlying state machine, once the future was first polled.
+	pub fn agreement(&self) -> Option<&Agreement<C>> {
+		self.machine.as_ref().map(Machine::agreement)
+	}
+}
+
+// Broadcast messages and return the committed proposal, if any.
+fn apply<C: Context>(
+	outgoing: &mut impl FnMut(SignedMessageFor<C>),
+	actions: Vec<ActionFor<C>>,
+) -> Option<CommittedFor<C>> {
+	let mut committed = None;
+	for action in actions {
+		match action {
+			Action::Broadcast(message) => outgoing(message),
+			Action::Commit(c) => committed = Some(c),
+		}
+	}
+
+	committed
+}
+
+impl<C, I, O, T, R> Future for Agree<C, I, O, T, R>
+where
+	C: Context,
+	I: Stream<Item = SignedMessageFor<C>> + Unpin,
+	O: FnMut(SignedMessageFor<C>),
+	T: Timer + Clock,
+	R: RoundTimeout,
+	Self: Unpin,
+{
+	type Output = CommittedFor<C>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+		let this = self.get_mut();
+		let machine = match (this.machine.as_mut(), this.start.take()) {
+			(Some(machine), _) => machine,
+			(None, start) => {
+				let (round_timeout, config) = start.expect("started only once; qed");
+				let (machine, actions) =
+					Machine::new(&this.context, config, round_timeout, this.timer.now());
+				if let Some(committed) = apply::<C>(&mut this.outgoing, actions) {
+					return Poll::Ready(committed)
+				}
+				this.machine.insert(machine)
+			},
+		};
+
+		loop {
+			while !this.incoming_done {
+				match this.incoming.poll_next_unpin(cx) {
+					Poll::Ready(Some(message)) => {
+						let actions =
+							machine.import_message(&this.context, message, this.timer.now());
+						if let Some(committed) = apply::<C>(&mut this.outgoing, actions) {
+							return Poll::Ready(committed)
+						}
+					},
+					Poll::Ready(None) => this.incoming_done = true,
+					Poll::Pending => break,
+				}
+			}
+
+			let Some(deadline) = machine.next_deadline() else {
+				this.delay = None;
+				return Poll::Pending
+			};
+
+			let delay = match this.delay.as_mut() {
+				Some((at, delay)) if *at == deadline => delay,
+				_ => {
+					let delay = this.timer.delay(deadline.saturating_sub(this.timer.now()));
+					&mut this.delay.insert((deadline, delay)).1
+				},
+			};
+
+			if Pin::new(delay).poll(cx).is_pending() {
+				return Poll::Pending
+			}
+
+			// the timer may fire slightly before the clock reaches the deadline.
+			this.delay = None;
+			let now = this.timer.now().max(deadline);
+			let actions = machine.advance_time(&this.context, now);
+			if let Some(committed) = apply::<C>(&mut this.outgoing, actions) {
+				return Poll::Ready(committed)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agreement::{
+		tests::{contexts, TestContext},
+		ExponentialBackoff, ManualClock,
+	};
+	use futures::channel::mpsc;
+	use std::time::Duration;
+
+	type TestAgree = Agree<
+		TestContext,
+		mpsc::UnboundedReceiver<SignedMessageFor<TestContext>>,
+		Box<dyn FnMut(SignedMessageFor<TestContext>)>,
+		ManualClock,
+		ExponentialBackoff,
+	>;
+
+	// Agreements for each context, connected to each other, on a shared clock.
+	fn connect(
+		contexts: Vec<TestContext>,
+		clock: &ManualClock,
+		round_timeout: &ExponentialBackoff,
+	) -> Vec<TestAgree> {
+		let (senders, receivers): (Vec<_>, Vec<_>) = contexts
+			.iter()
+			.map(|_| mpsc::unbounded::<SignedMessageFor<TestContext>>())
+			.unzip();
+
+		contexts
+			.into_iter()
+			.zip(receivers)
+			.enumerate()
+			.map(|(i, (context, incoming))| {
+				let peers: Vec<_> = senders
+					.iter()
+					.enumerate()
+					.filter(|(j, _)| *j != i)
+					.map(|(_, s)| s.clone())
+					.collect();
+				let outgoing = move |message: SignedMessageFor<TestContext>| {
+					for peer in &peers {
+						// peers which already committed don't listen anymore.
+						let _ = peer.unbounded_send(message.clone());
+					}
+				};
+
+				agree(AgreementParams {
+					context,
+					incoming,
+					outgoing: Box::new(outgoing) as Box<dyn FnMut(_)>,
+					timer: clock.clone(),
+					round_timeout: round_timeout.clone(),
+					config: AgreementConfig::default(),
+				})
+			})
+			.collect()
+	}
+
+	// Poll every agreement which didn't commit yet until all messages in flight are delivered.
+	fn settle(agreements: &mut [TestAgree], committed: &mut [Option<CommittedFor<TestContext>>]) {
+		let waker = futures::task::noop_waker();
+		let mut cx = task::Context::from_waker(&waker);
+		for _ in 0..8 {
+			for (agreement, committed) in agreements.iter_mut().zip(committed.iter_mut()) {
+				if committed.is_none() {
+					if let Poll::Ready(c) = Pin::new(agreement).poll(&mut cx) {
+						*committed = Some(c);
+					}
+				}
+			}
+		}
+	}
+
+	// Run an agreement for each context until all of them commit, letting a round timeout pass
+	// whenever the messages in flight are delivered.
+	fn run(
+		contexts: Vec<TestContext>,
+		round_timeout: ExponentialBackoff,
+	) -> Vec<CommittedFor<TestContext>> {
+		let clock = ManualClock::new();
+		let mut agreements = connect(contexts, &clock, &round_timeout);
+		let mut committed = vec![None; agreements.len()];
+		for _ in 0..16 {
+			settle(&mut agreements, &mut committed);
+			if committed.iter().all(Option::is_some) {
+				return committed.into_iter().flatten().collect()
+			}
+			clock.advance(round_timeout.max);
+		}
+
+		panic!("no agreement after 16 round timeouts");
+	}
+
+	#[test]
+	fn agree_resolves_with_committed_proposal() {
+		let committed = run(contexts(4, &[42, 43, 44, 45], &[42]), ExponentialBackoff::default());
+
+		for c in committed {
+			assert_eq!(c.proposal, 42);
+			assert_eq!(c.justification.round, 0);
+		}
+	}
+
+	#[test]
+	fn agree_times_out_offline_proposer() {
+		let round_timeout =
+			ExponentialBackoff { base: Duration::from_millis(20), max: Duration::from_secs(1) };
+		let committed = run(contexts(4, &[42, 43, 44, 45], &[43]).split_off(1), round_timeout);
+
+		for c in committed {
+			assert_eq!(c.proposal, 43);
+			assert_eq!(c.justification.round, 1);
+		}
+	}
+
+	#[test]
+	fn rounds_time_out_on_manual_clock() {
+		let clock = ManualClock::new();
+		let round_timeout =
+			ExponentialBackoff { base: Duration::from_secs(1), max: Duration::from_secs(8) };
+		// the proposer of round 0 is offline.
+		let contexts = contexts(4, &[42, 43, 44, 45], &[43]).split_off(1);
+		let mut agreements = connect(contexts, &clock, &round_timeout);
+		let mut committed = vec![None; agreements.len()];
+
+		settle(&mut agreements, &mut committed);
+		clock.advance(Duration::from_millis(999));
+		settle(&mut agreements, &mut committed);
+		assert_eq!(committed, vec![None, None, None]);
+		assert!(agreements.iter().all(|a| a.agreement().unwrap().round() == 0));
+
+		clock.advance(Duration::from_millis(1));
+		settle(&mut agreements, &mut committed);
+		for c in committed {
+			let c = c.unwrap();
+			assert_eq!(c.proposal, 43);
+			assert_eq!(c.justification.round, 1);
+		}
+	}
+}
@@ -0,0 +1,128 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Evaluation of proposals.
+//!
+//! The agreement itself doesn't know what a proposal is. Whether an authority prevotes for a
+//! proposal is decided by a [`ProposalEvaluator`], usually a [`TableEvaluator`] which checks the
+//! candidates against the local statement table and then applies chain-specific rules.
+
+use std::collections::HashSet;
+
+use crate::generic::{self, AttestedCandidateFor, Table};
+
+/// Judges whether a proposal is acceptable.
+pub trait ProposalEvaluator<Proposal> {
+	/// Whether the proposal is acceptable.
+	fn evaluate(&self, proposal: &Proposal) -> bool;
+}
+
+impl<Proposal, F: Fn(&Proposal) -> bool> ProposalEvaluator<Proposal> for F {
+	fn evaluate(&self, proposal: &Proposal) -> bool {
+		self(proposal)
+	}
+}
+
+/// Evaluates proposals made of attested candidates against the local statement table.
+///
+/// A proposal is accepted if all of its candidates are distinct, known to the table and
+/// includable according to it, and `rules` accept it.
+pub struct TableEvaluator<'a, Ctx: generic::Context, R> {
+	table: &'a Table<Ctx>,
+	context: &'a Ctx,
+	minimum_backing_votes: u32,
+	rules: R,
+}
+
+impl<'a, Ctx: generic::Context, R> TableEvaluator<'a, Ctx, R> {
+	/// Create a new evaluator, with additional chain-specific `rules`.
+	pub fn new(
+		table: &'a Table<Ctx>,
+		context: &'a Ctx,
+		minimum_backing_votes: u32,
+		rules: R,
+	) -> Self {
+		TableEvaluator { table, context, minimum_backing_votes, rules }
+	}
+}
+
+impl<'a, Ctx, R> ProposalEvaluator<Vec<AttestedCandidateFor<Ctx>>> for TableEvaluator<'a, Ctx, R>
+where
+	Ctx: generic::Context,
+	R: ProposalEvaluator<Vec<AttestedCandidateFor<Ctx>>>,
+{
+	fn evaluate(&self, proposal: &Vec<AttestedCandidateFor<Ctx>>) -> bool {
+		let mut seen = HashSet::with_capacity(proposal.len());
+		let candidates_ok = proposal.iter().all(|attested| {
+			let digest = Ctx::candidate_digest(&attested.candidate);
+			let includable = self
+				.table
+				.candidate_status(&digest, self.context, self.minimum_backing_votes)
+				.map_or(false, |status| status.is_includable());
+
+			includable && seen.insert(digest)
+		});
+
+		candidates_ok && self.rules.evaluate(proposal)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::Config,
+		proposal::{tests::populated_table, PriorityThenBacking, ProposalConfig},
+		test_utils::TestContext,
+	};
+
+	#[test]
+	fn table_evaluator_checks_candidates_then_rules() {
+		let (context, table) = populated_table(&[0, 2]);
+		let proposal =
+			table.build_proposal(&context, 2, &ProposalConfig::default(), &PriorityThenBacking);
+		assert_eq!(proposal.len(), 2);
+
+		let accept_all = |_: &Vec<AttestedCandidateFor<TestContext>>| true;
+		let evaluator = TableEvaluator::new(&table, &context, 2, accept_all);
+		assert!(evaluator.evaluate(&proposal));
+		assert!(evaluator.evaluate(&Vec::new()));
+
+		// duplicate candidates.
+		let mut duplicated = proposal.clone();
+		duplicated.push(proposal[0].clone());
+		assert!(!evaluator.evaluate(&duplicated));
+
+		// not includable according to the local table.
+		let (_, partial_table) = populated_table(&[0]);
+		let partial = TableEvaluator::new(&partial_table, &context, 2, accept_all);
+		assert!(partial.evaluate(&proposal[..1].to_vec()));
+		assert!(!partial.evaluate(&proposal));
+
+		// unknown to the local table.
+		let empty_table = Table::new(Config { allow_multiple_seconded: false });
+		let unknown = TableEvaluator::new(&empty_table, &context, 2, accept_all);
+		assert!(!unknown.evaluate(&proposal));
+
+		// rejected by chain-specific rules.
+		let no_group_zero = |proposal: &Vec<AttestedCandidateFor<TestContext>>| {
+			proposal.iter().all(|c| c.group_id != 0)
+		};
+		let evaluator = TableEvaluator::new(&table, &context, 2, no_group_zero);
+		assert!(!evaluator.evaluate(&proposal));
+		assert!(evaluator.evaluate(&proposal[1..].to_vec()));
+	}
+}
@@ -0,0 +1,161 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Justifications: proofs that agreement on a proposal was reached.
+
+use std::collections::HashSet;
+
+use parity_scale_codec::{Decode, Encode};
+
+use super::{bft_threshold, Context, Message, Round, SignedMessage, SignedMessageFor};
+
+/// A justification for a committed proposal: a supermajority of precommits for its digest.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct Justification<Digest, AuthorityId, Signature> {
+	/// The round in which the proposal was committed.
+	pub round: Round,
+	/// The digest of the committed proposal.
+	pub digest: Digest,
+	/// The signatures of precommits for `digest` in `round`, in the order they were received.
+	pub signatures: Vec<(AuthorityId, Signature)>,
+}
+
+/// Type alias for a justification corresponding to context type.
+pub type JustificationFor<C> =
+	Justification<<C as Context>::Digest, <C as Context>::AuthorityId, <C as Context>::Signature>;
+
+/// Reasons a [`Justification`] can fail verification.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum JustificationError<AuthorityId> {
+	/// A precommit was signed by somebody who isn't an authority.
+	NotAnAuthority(AuthorityId),
+	/// An authority signed more than once.
+	DuplicateSigner(AuthorityId),
+	/// A precommit carries an invalid signature.
+	BadSignature(AuthorityId),
+	/// Not enough precommits for a supermajority.
+	InsufficientSignatures {
+		/// How many precommits are present.
+		have: usize,
+		/// How many precommits are needed.
+		need: usize,
+	},
+}
+
+impl<Digest: Clone, AuthorityId: std::hash::Hash + Eq + Clone, Signature: Clone>
+	Justification<Digest, AuthorityId, Signature>
+{
+	/// Reconstruct the signed precommits making up this justification.
+	pub fn precommits<C>(&self) -> Vec<SignedMessageFor<C>>
+	where
+		C: Context<Digest = Digest, AuthorityId = AuthorityId, Signature = Signature>,
+	{
+		self.signatures
+			.iter()
+			.map(|(sender, signature)| SignedMessage {
+				message: Message::Precommit(self.round, Some(self.digest.clone())),
+				signature: signature.clone(),
+				sender: sender.clone(),
+			})
+			.collect()
+	}
+
+	/// Verify that this justification proves agreement among the authorities of `context`.
+	///
+	/// Every precommit is passed to `check_signature`.
+	pub fn verify<C, F>(
+		&self,
+		context: &C,
+		mut check_signature: F,
+	) -> Result<(), JustificationError<AuthorityId>>
+	where
+		C: Context<Digest = Digest, AuthorityId = AuthorityId, Signature = Signature>,
+		F: FnMut(&SignedMessageFor<C>) -> bool,
+	{
+		let mut seen = HashSet::with_capacity(self.signatures.len());
+		for precommit in self.precommits::<C>() {
+			if !context.is_authority(&precommit.sender) {
+				return Err(JustificationError::NotAnAuthority(precommit.sender))
+			}
+
+			if !seen.insert(precommit.sender.clone()) {
+				return Err(JustificationError::DuplicateSigner(precommit.sender))
+			}
+
+			if !check_signature(&precommit) {
+				return Err(JustificationError::BadSignature(precommit.sender))
+			}
+		}
+
+		let need = bft_threshold(context.num_authorities());
+		if seen.len() < need {
+			return Err(JustificationError::InsufficientSignatures { have: seen.len(), need })
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agreement::tests::TestContext;
+
+	fn context() -> TestContext {
+		TestContext { local_id: 0, n: 4, proposal: None, acceptable: Default::default() }
+	}
+
+	fn good_signature(m: &SignedMessageFor<TestContext>) -> bool {
+		m.signature == m.sender
+	}
+
+	#[test]
+	fn justification_roundtrips_and_verifies() {
+		let justification =
+			Justification { round: 2, digest: 42u64, signatures: vec![(0, 0), (1, 1), (3, 3)] };
+
+		let encoded = justification.encode();
+		let decoded = JustificationFor::<TestContext>::decode(&mut &encoded[..]).unwrap();
+		assert_eq!(decoded, justification);
+
+		assert_eq!(justification.verify(&context(), good_signature), Ok(()));
+		assert_eq!(
+			justification.precommits::<TestContext>()[0],
+			SignedMessage { message: Message::Precommit(2, Some(42)), signature: 0, sender: 0 }
+		);
+	}
+
+	#[test]
+	fn bad_justifications_are_rejected() {
+		let verify = |signatures: Vec<(u64, u64)>| {
+			Justification { round: 0, digest: 42u64, signatures }.verify(&context(), good_signature)
+		};
+
+		assert_eq!(
+			verify(vec![(0, 0), (1, 1)]),
+			Err(JustificationError::InsufficientSignatures { have: 2, need: 3 })
+		);
+		assert_eq!(
+			verify(vec![(0, 0), (1, 1), (1, 1)]),
+			Err(JustificationError::DuplicateSigner(1))
+		);
+		assert_eq!(
+			verify(vec![(0, 0), (1, 1), (9, 9)]),
+			Err(JustificationError::NotAnAuthority(9))
+		);
+		assert_eq!(verify(vec![(0, 0), (1, 1), (2, 3)]), Err(JustificationError::BadSignature(2)));
+	}
+}
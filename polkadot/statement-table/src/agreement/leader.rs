@@ -0,0 +1,82 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Selection of the proposer of each round.
+//!
+//! All honest authorities must agree on who proposes in a round without communicating, so the
+//! proposer is a pure function of the authority set, the parent hash and the round number.
+
+use super::Round;
+
+/// Get the proposer of `round` by rotating round-robin over `authorities`.
+///
+/// The starting point of the rotation is derived from `parent_hash`, so that the same authority
+/// doesn't propose first on every block. `authorities` must be in the same order on all nodes.
+///
+/// Returns `None` if there are no authorities.
+pub fn round_robin_proposer<'a, A>(
+	authorities: &'a [A],
+	parent_hash: &[u8],
+	round: Round,
+) -> Option<&'a A> {
+	if authorities.is_empty() {
+		return None
+	}
+
+	let mut seed = [0u8; 8];
+	let len = parent_hash.len().min(seed.len());
+	seed[..len].copy_from_slice(&parent_hash[..len]);
+
+	let n = authorities.len() as u64;
+	let offset = u64::from_le_bytes(seed) % n;
+	let index = (offset + round as u64 % n) % n;
+
+	authorities.get(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rotation_visits_every_authority() {
+		let authorities = [10u32, 11, 12, 13, 14];
+		let parent_hash = [7u8; 32];
+
+		let mut proposers = (0..authorities.len() as Round)
+			.map(|round| *round_robin_proposer(&authorities, &parent_hash, round).unwrap())
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			round_robin_proposer(&authorities, &parent_hash, 5),
+			round_robin_proposer(&authorities, &parent_hash, 0),
+		);
+
+		proposers.sort();
+		assert_eq!(proposers, authorities);
+	}
+
+	#[test]
+	fn parent_hash_shifts_rotation() {
+		let authorities = [0u32, 1, 2, 3];
+
+		assert_eq!(round_robin_proposer(&authorities, &[0; 32], 0), Some(&0));
+		assert_eq!(round_robin_proposer(&authorities, &[0; 32], 1), Some(&1));
+		assert_eq!(round_robin_proposer(&authorities, &[2; 1], 0), Some(&2));
+		assert_eq!(round_robin_proposer(&authorities, &[2; 1], 3), Some(&1));
+		assert_eq!(round_robin_proposer::<u32>(&[], &[0; 32], 0), None);
+	}
+}
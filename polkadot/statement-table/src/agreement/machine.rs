@@ -0,0 +1,190 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The agreement as a state machine driven by messages and time.
+//!
+//! A [`Machine`] combines the [`Agreement`] with the bookkeeping of round timeouts. Its inputs
+//! are messages and the current time, its outputs the [`Action`](super::Action)s to take and
+//! the time at which it wants to be woken up next. It doesn't do any I/O and doesn't read a
+//! clock, so that it behaves the same given the same inputs, e.g. when explored exhaustively
+//! or run in a simulation. A shell like [`agree`](super::agree) feeds it messages from the
+//! network and the time from a clock, and applies its outputs.
+//!
+//! Time is given as the time since an epoch of the shell's choosing, which must not change
+//! while the machine runs.
+
+use std::time::Duration;
+
+use super::{
+	ActionFor, Agreement, AgreementConfig, Context, Round, RoundTimeout, SignedMessageFor,
+};
+
+// the timeout of a round, unless it fired already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timeout {
+	round: Round,
+	at: Option<Duration>,
+}
+
+/// The [`Agreement`] along with its round timeouts.
+pub struct Machine<C: Context, R> {
+	agreement: Agreement<C>,
+	round_timeout: R,
+	timeout: Timeout,
+}
+
+impl<C: Context, R: RoundTimeout> Machine<C, R> {
+	/// Start the agreement at `now`, returning the actions to take.
+	pub fn new(
+		context: &C,
+		config: AgreementConfig,
+		round_timeout: R,
+		now: Duration,
+	) -> (Self, Vec<ActionFor<C>>) {
+		let (agreement, actions) = Agreement::with_config(context, config);
+		let timeout = Timeout { round: agreement.round(), at: None };
+		let mut machine = Machine { agreement, round_timeout, timeout };
+		machine.start_timeout(now, true);
+		(machine, actions)
+	}
+
+	/// The state of the agreement.
+	pub fn agreement(&self) -> &Agreement<C> {
+		&self.agreement
+	}
+
+	/// The time at which [`Machine::advance_time`] should be called next, if any.
+	pub fn next_deadline(&self) -> Option<Duration> {
+		match self.agreement.committed() {
+			Some(_) => None,
+			None => self.timeout.at,
+		}
+	}
+
+	/// Import a message received at `now`, returning the actions to take.
+	pub fn import_message(
+		&mut self,
+		context: &C,
+		message: SignedMessageFor<C>,
+		now: Duration,
+	) -> Vec<ActionFor<C>> {
+		let actions = self.agreement.import_message(context, message);
+		self.start_timeout(now, false);
+		actions
+	}
+
+	/// Note that the time is `now`, returning the actions to take if the current round timed
+	/// out.
+	pub fn advance_time(&mut self, context: &C, now: Duration) -> Vec<ActionFor<C>> {
+		match self.timeout.at {
+			Some(at) if at <= now => {
+				self.timeout.at = None;
+				let actions = self.agreement.on_timeout(context, self.timeout.round);
+				self.start_timeout(now, false);
+				actions
+			},
+			_ => Vec::new(),
+		}
+	}
+
+	// start the timeout of the current round at `now`, if the round changed.
+	fn start_timeout(&mut self, now: Duration, force: bool) {
+		let round = self.agreement.round();
+		if force || self.timeout.round != round {
+			let at = now.saturating_add(self.round_timeout.round_duration(round));
+			self.timeout = Timeout { round, at: Some(at) };
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agreement::{tests::contexts, Action, ExponentialBackoff, Message};
+
+	fn round_timeout() -> ExponentialBackoff {
+		ExponentialBackoff { base: Duration::from_secs(1), max: Duration::from_secs(8) }
+	}
+
+	#[test]
+	fn rounds_time_out_at_their_deadline() {
+		// the proposer of round 0 is offline.
+		let contexts = contexts(4, &[42, 43, 44, 45], &[43]);
+		let context = &contexts[1];
+		let (mut machine, _) =
+			Machine::new(context, AgreementConfig::default(), round_timeout(), Duration::ZERO);
+		assert_eq!(machine.next_deadline(), Some(Duration::from_secs(1)));
+
+		assert!(machine.advance_time(context, Duration::from_millis(999)).is_empty());
+		let actions = machine.advance_time(context, Duration::from_secs(1));
+		assert!(actions.contains(&Action::Broadcast(context.sign_local(Message::AdvanceRound(0)))));
+		assert_eq!(machine.next_deadline(), None);
+
+		// others wanting to advance move the machine to round 1, which lasts twice as long.
+		for sender in [2, 3] {
+			let message = contexts[sender as usize].sign_local(Message::AdvanceRound(0));
+			machine.import_message(context, message, Duration::from_millis(1500));
+		}
+		assert_eq!(machine.agreement().round(), 1);
+		assert_eq!(machine.next_deadline(), Some(Duration::from_millis(3500)));
+	}
+
+	#[test]
+	fn same_inputs_give_same_outputs() {
+		let contexts = contexts(4, &[42, 43, 44, 45], &[42]);
+		let run = || {
+			let mut machines: Vec<_> = contexts
+				.iter()
+				.map(|context| {
+					Machine::new(
+						context,
+						AgreementConfig::default(),
+						round_timeout(),
+						Duration::ZERO,
+					)
+				})
+				.collect();
+
+			// deliver messages in order until none are left.
+			let mut outputs = Vec::new();
+			let mut pending: Vec<_> = machines
+				.iter_mut()
+				.enumerate()
+				.flat_map(|(from, (_, actions))| {
+					std::mem::take(actions).into_iter().map(move |action| (from, action))
+				})
+				.collect();
+			while !pending.is_empty() {
+				let (from, action) = pending.remove(0);
+				outputs.push(action.clone());
+				let Action::Broadcast(message) = action else { continue };
+				for (to, (machine, _)) in
+					machines.iter_mut().enumerate().filter(|(to, _)| *to != from)
+				{
+					let actions =
+						machine.import_message(&contexts[to], message.clone(), Duration::ZERO);
+					pending.extend(actions.into_iter().map(|action| (to, action)));
+				}
+			}
+
+			outputs
+		};
+
+		let outputs = run();
+		assert_eq!(outputs.iter().filter(|a| matches!(a, Action::Commit(_))).count(), 4);
+		assert_eq!(run(), outputs);
+	}
+}
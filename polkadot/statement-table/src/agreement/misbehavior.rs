@@ -0,0 +1,71 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Misbehavior in the agreement protocol.
+//!
+//! This complements the [`Misbehavior`](crate::generic::Misbehavior) detected by the statement
+//! table. Like it, all misbehavior here carries the conflicting signed messages and is therefore
+//! provable.
+
+use parity_scale_codec::{Decode, Encode};
+
+use super::{Context, Message, MessageFor, Round};
+
+/// Misbehavior: voting for two different digests (or a digest and nil) in the same round.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoubleVote<Digest, Signature> {
+	/// The round in which both votes were cast.
+	pub round: Round,
+	/// The first vote seen.
+	pub first: (Option<Digest>, Signature),
+	/// The second vote seen.
+	pub second: (Option<Digest>, Signature),
+}
+
+/// Different kinds of misbehavior in the agreement protocol.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BftMisbehavior<Digest, Signature> {
+	/// Prevoted twice in the same round.
+	#[codec(index = 0)]
+	DoublePrevote(DoubleVote<Digest, Signature>),
+	/// Precommitted twice in the same round.
+	#[codec(index = 1)]
+	DoublePrecommit(DoubleVote<Digest, Signature>),
+}
+
+/// Type alias for agreement misbehavior corresponding to context type.
+pub type BftMisbehaviorFor<C> = BftMisbehavior<<C as Context>::Digest, <C as Context>::Signature>;
+
+impl<Digest, Signature> BftMisbehavior<Digest, Signature> {
+	/// Deconstruct this misbehavior into the two conflicting `(Message, Signature)` pairs.
+	pub fn deconstruct<C>(self) -> ((MessageFor<C>, Signature), (MessageFor<C>, Signature))
+	where
+		C: Context<Digest = Digest, Signature = Signature>,
+	{
+		match self {
+			Self::DoublePrevote(DoubleVote { round, first, second }) => (
+				(Message::Prevote(round, first.0), first.1),
+				(Message::Prevote(round, second.0), second.1),
+			),
+			Self::DoublePrecommit(DoubleVote { round, first, second }) => (
+				(Message::Precommit(round, first.0), first.1),
+				(Message::Precommit(round, second.0), second.1),
+			),
+		}
+	}
+}
@@ -0,0 +1,1288 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! BFT agreement on a proposal built out of the statement table.
+//!
+//! Agreement proceeds in rounds. In each round a designated authority proposes a set of
+//! candidates, which is usually built with [`Table::build_proposal`](crate::Table). Every
+//! authority evaluates the proposal against its own view of the table and prevotes for it, or
+//! prevotes nil if it doesn't consider the proposal acceptable. Once a supermajority of prevotes
+//! for a proposal is witnessed, authorities precommit to it, and a supermajority of precommits
+//! commits the proposal.
+//!
+//! The [`Agreement`] state machine doesn't do any I/O: messages are fed in and the actions to
+//! take are returned. Signatures on imported messages should be checked by the caller. The
+//! [`Machine`] adds round timeouts driven by the time given to it, and [`agree`] runs it
+//! against the network and a clock.
+//! Authorities voting twice in the same round are reported as [`BftMisbehavior`], which is
+//! collected the same way the table collects its misbehavior.
+//! Messages for future rounds are kept and replayed once the round is reached, as authorities
+//! don't move between rounds in lockstep. How many are kept is bounded by an
+//! [`AgreementConfig`]. Likewise, the votes of the last few rounds are kept, so that late votes
+//! for them still count: late precommits can commit the proposal of an earlier round, and late
+//! prevotes can unlock the local authority.
+//!
+//! Statements of the table aren't tied to rounds, so they aren't buffered here: they are
+//! imported whenever they arrive, and votes on candidates which aren't known yet are handled by
+//! the [`CandidateFetcher`](crate::router::CandidateFetcher) of the router.
+//!
+//! Rounds which don't conclude in time are abandoned according to the durations given by a
+//! [`RoundTimeout`]. An authority whose round timed out signals this to the others with an
+//! [`Message::AdvanceRound`] message, and authorities move on to the next round together once a
+//! supermajority wants to. Seeing more than a third of authorities wanting to move on is enough
+//! for an authority to join them, since at least one of them is honest. This way authorities
+//! whose timers fire at different times still converge on the same round.
+//!
+//! To stay safe across rounds, an authority which precommitted a proposal is locked on it: in
+//! later rounds it proposes and prevotes only that proposal. It is unlocked once it witnesses a
+//! supermajority of prevotes for a different proposal or for nil in a later round, as this
+//! shows that the proposal it is locked on can't have been committed.
+
+use std::{
+	collections::{hash_map::Entry, BTreeMap, HashMap},
+	fmt::Debug,
+	hash::Hash,
+	sync::Arc,
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use buffer::FutureMessages;
+
+mod buffer;
+pub mod driver;
+pub mod evaluation;
+pub mod justification;
+pub mod leader;
+pub mod machine;
+pub mod misbehavior;
+pub mod timer;
+
+pub use driver::{agree, Agree, AgreementParams};
+pub use evaluation::{ProposalEvaluator, TableEvaluator};
+pub use justification::{Justification, JustificationError, JustificationFor};
+pub use leader::round_robin_proposer;
+pub use machine::Machine;
+pub use misbehavior::{BftMisbehavior, BftMisbehaviorFor, DoubleVote};
+pub use timer::{Clock, ExponentialBackoff, RoundTimeout, SystemTimer, Timer};
+#[cfg(any(test, feature = "test-utils"))]
+pub use timer::{ManualClock, ManualDelay};
+
+/// A round number.
+pub type Round = u32;
+
+/// Context for the agreement process.
+pub trait Context {
+	/// An authority ID.
+	type AuthorityId: Debug + Hash + Eq + Clone;
+	/// The digest (hash or other unique attribute) of a proposal.
+	type Digest: Debug + Hash + Eq + Clone;
+	/// A signature type.
+	type Signature: Debug + Eq + Clone;
+	/// The proposal type. In practice this will be a set of attested candidates.
+	type Proposal: Debug + Eq + Clone;
+	/// Judges proposals, see [`TableEvaluator`].
+	type Evaluator: ProposalEvaluator<Self::Proposal>;
+
+	/// Get the digest of a proposal.
+	fn proposal_digest(proposal: &Self::Proposal) -> Self::Digest;
+
+	/// The ID of the local authority.
+	fn local_id(&self) -> Self::AuthorityId;
+
+	/// The number of authorities taking part in the agreement.
+	fn num_authorities(&self) -> usize;
+
+	/// Whether the given ID is one of the authorities taking part in the agreement.
+	fn is_authority(&self, id: &Self::AuthorityId) -> bool;
+
+	/// The authority expected to propose in the given round.
+	///
+	/// This must be the same on all honest nodes, see [`round_robin_proposer`].
+	fn round_proposer(&self, round: Round) -> Self::AuthorityId;
+
+	/// Create the local proposal. Only called when the local authority is the proposer.
+	///
+	/// Returning `None` means there is nothing to propose, e.g. because the table doesn't hold
+	/// any includable candidate yet.
+	fn proposal(&self) -> Option<Self::Proposal>;
+
+	/// The evaluator deciding whether a proposal is acceptable, e.g. because all of its
+	/// candidates are includable according to the local table.
+	fn evaluator(&self) -> &Self::Evaluator;
+
+	/// Sign a message as the local authority.
+	fn sign_local(&self, message: MessageFor<Self>) -> SignedMessageFor<Self>;
+}
+
+/// Messages of the agreement protocol.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub enum Message<Proposal, Digest> {
+	/// Broadcast by the round proposer to put forward a proposal.
+	#[codec(index = 0)]
+	Propose(Round, Proposal),
+	/// Prevote for a proposal with the given digest, or nil.
+	#[codec(index = 1)]
+	Prevote(Round, Option<Digest>),
+	/// Precommit to a proposal with the given digest, or nil.
+	#[codec(index = 2)]
+	Precommit(Round, Option<Digest>),
+	/// Signal that the sender wants to move on from the given round.
+	#[codec(index = 3)]
+	AdvanceRound(Round),
+}
+
+impl<Proposal, Digest> Message<Proposal, Digest> {
+	/// The round the message refers to.
+	pub fn round(&self) -> Round {
+		match *self {
+			Message::Propose(round, _) |
+			Message::Prevote(round, _) |
+			Message::Precommit(round, _) |
+			Message::AdvanceRound(round) => round,
+		}
+	}
+}
+
+/// A signed message.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct SignedMessage<Proposal, Digest, AuthorityId, Signature> {
+	/// The message.
+	pub message: Message<Proposal, Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a message corresponding to context type.
+pub type MessageFor<C> = Message<<C as Context>::Proposal, <C as Context>::Digest>;
+
+/// Type alias for a signed message corresponding to context type.
+pub type SignedMessageFor<C> = SignedMessage<
+	<C as Context>::Proposal,
+	<C as Context>::Digest,
+	<C as Context>::AuthorityId,
+	<C as Context>::Signature,
+>;
+
+/// A committed proposal.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct Committed<Proposal, Digest, AuthorityId, Signature> {
+	/// The committed proposal.
+	pub proposal: Proposal,
+	/// The proof that the proposal was committed.
+	pub justification: Justification<Digest, AuthorityId, Signature>,
+}
+
+/// Type alias for a committed proposal corresponding to context type.
+pub type CommittedFor<C> = Committed<
+	<C as Context>::Proposal,
+	<C as Context>::Digest,
+	<C as Context>::AuthorityId,
+	<C as Context>::Signature,
+>;
+
+/// An action to be taken by the caller of the state machine.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Action<Proposal, Digest, AuthorityId, Signature> {
+	/// Broadcast a message to all other authorities.
+	Broadcast(SignedMessage<Proposal, Digest, AuthorityId, Signature>),
+	/// A proposal was committed. This is the last action of the agreement.
+	Commit(Committed<Proposal, Digest, AuthorityId, Signature>),
+}
+
+/// Type alias for an action corresponding to context type.
+pub type ActionFor<C> = Action<
+	<C as Context>::Proposal,
+	<C as Context>::Digest,
+	<C as Context>::AuthorityId,
+	<C as Context>::Signature,
+>;
+
+/// The step of the current round.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Step {
+	/// Waiting for a proposal.
+	Propose,
+	/// Prevoted, waiting for prevotes of others.
+	Prevote,
+	/// Precommitted, waiting for precommits of others.
+	Precommit,
+	/// A proposal was committed.
+	Committed,
+}
+
+/// Configuration of the agreement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgreementConfig {
+	/// How many rounds ahead of the current one messages are buffered. Messages for rounds
+	/// further ahead are dropped.
+	pub max_rounds_ahead: Round,
+	/// How many messages for future rounds are buffered per authority. Further messages of the
+	/// authority are dropped until the buffered ones are replayed.
+	pub max_buffered_per_authority: usize,
+	/// How many rounds before the current one votes are still imported into. Votes for rounds
+	/// further behind are dropped.
+	pub max_rounds_behind: Round,
+}
+
+impl Default for AgreementConfig {
+	fn default() -> Self {
+		// an honest authority sends at most three buffered messages per round.
+		AgreementConfig {
+			max_rounds_ahead: 8,
+			max_buffered_per_authority: 24,
+			max_rounds_behind: 2,
+		}
+	}
+}
+
+/// The number of votes needed for a supermajority among `n` authorities, tolerating
+/// `(n - 1) / 3` faulty ones.
+pub fn bft_threshold(n: usize) -> usize {
+	n - n.saturating_sub(1) / 3
+}
+
+// Votes of a single kind within a round.
+struct Tally<C: Context> {
+	votes: HashMap<C::AuthorityId, (Option<C::Digest>, C::Signature)>,
+	counts: HashMap<Option<C::Digest>, usize>,
+	// the voters in order of import, so that justifications don't depend on the order of
+	// iteration of `votes`.
+	voters: Vec<C::AuthorityId>,
+}
+
+impl<C: Context> Default for Tally<C> {
+	fn default() -> Self {
+		Tally { votes: HashMap::new(), counts: HashMap::new(), voters: Vec::new() }
+	}
+}
+
+impl<C: Context> Tally<C> {
+	// Returns the earlier vote if the sender already voted differently. Repeated votes are
+	// ignored.
+	fn import(
+		&mut self,
+		sender: C::AuthorityId,
+		digest: Option<C::Digest>,
+		signature: C::Signature,
+	) -> Result<(), (Option<C::Digest>, C::Signature)> {
+		match self.votes.entry(sender) {
+			Entry::Occupied(occupied) => {
+				let (first_digest, first_signature) = occupied.get();
+				if first_digest == &digest {
+					Ok(())
+				} else {
+					Err((first_digest.clone(), first_signature.clone()))
+				}
+			},
+			Entry::Vacant(vacant) => {
+				*self.counts.entry(digest.clone()).or_insert(0) += 1;
+				self.voters.push(vacant.key().clone());
+				vacant.insert((digest, signature));
+				Ok(())
+			},
+		}
+	}
+
+	fn total(&self) -> usize {
+		self.votes.len()
+	}
+
+	// The digest which reached `threshold` votes, if any. Nil never does.
+	fn quorum(&self, threshold: usize) -> Option<&C::Digest> {
+		self.counts
+			.iter()
+			.filter(|(_, count)| **count >= threshold)
+			.find_map(|(digest, _)| digest.as_ref())
+	}
+
+	// Whether nil reached `threshold` votes.
+	fn nil_quorum(&self, threshold: usize) -> bool {
+		self.counts.get(&None).map_or(false, |count| *count >= threshold)
+	}
+
+	// Whether no digest can reach `threshold` votes anymore, given `n` authorities in total.
+	fn quorum_impossible(&self, n: usize, threshold: usize) -> bool {
+		let best = self
+			.counts
+			.iter()
+			.filter(|(digest, _)| digest.is_some())
+			.map(|(_, count)| *count)
+			.max()
+			.unwrap_or(0);
+
+		best + n.saturating_sub(self.total()) < threshold
+	}
+
+	// the signatures of the votes for `digest`, in order of import.
+	fn signatures_for(&self, digest: &C::Digest) -> Vec<(C::AuthorityId, C::Signature)> {
+		self.voters
+			.iter()
+			.filter_map(|voter| match &self.votes[voter] {
+				(Some(d), signature) if d == digest => Some((voter.clone(), signature.clone())),
+				_ => None,
+			})
+			.collect()
+	}
+}
+
+// Data about a single round.
+struct RoundData<C: Context> {
+	proposal: Option<(C::Proposal, C::Digest)>,
+	prevotes: Tally<C>,
+	precommits: Tally<C>,
+}
+
+impl<C: Context> Default for RoundData<C> {
+	fn default() -> Self {
+		RoundData { proposal: None, prevotes: Tally::default(), precommits: Tally::default() }
+	}
+}
+
+// A proposal the local authority precommitted to.
+struct Lock<C: Context> {
+	round: Round,
+	proposal: C::Proposal,
+	digest: C::Digest,
+}
+
+/// Observes the rounds of an [`Agreement`], e.g. to publish them as
+/// [`TableEvent`](crate::events::TableEvent)s.
+pub trait RoundObserver: Send + Sync {
+	/// Agreement advanced to `round`.
+	fn round_advanced(&self, round: Round);
+}
+
+/// The agreement state machine.
+pub struct Agreement<C: Context> {
+	round: Round,
+	step: Step,
+	current: RoundData<C>,
+	// the rounds before the current one which late votes are still imported into.
+	past: BTreeMap<Round, RoundData<C>>,
+	max_rounds_behind: Round,
+	locked: Option<Lock<C>>,
+	future: FutureMessages<C>,
+	// The latest `AdvanceRound` signal of each authority, for the current and future rounds.
+	advance: HashMap<C::AuthorityId, (Round, C::Signature)>,
+	// The highest round the local authority signalled to advance from.
+	advance_sent: Option<Round>,
+	detected_misbehavior: HashMap<C::AuthorityId, Vec<BftMisbehaviorFor<C>>>,
+	committed: Option<CommittedFor<C>>,
+	round_observers: Vec<Arc<dyn RoundObserver>>,
+}
+
+impl<C: Context> Agreement<C> {
+	/// Create a new agreement state machine with the default configuration and start the first
+	/// round.
+	///
+	/// The returned actions must be applied by the caller.
+	pub fn new(context: &C) -> (Self, Vec<ActionFor<C>>) {
+		Self::with_config(context, AgreementConfig::default())
+	}
+
+	/// Create a new agreement state machine and start the first round.
+	///
+	/// The returned actions must be applied by the caller.
+	pub fn with_config(context: &C, config: AgreementConfig) -> (Self, Vec<ActionFor<C>>) {
+		let mut agreement = Agreement {
+			round: 0,
+			step: Step::Propose,
+			current: RoundData::default(),
+			past: BTreeMap::new(),
+			max_rounds_behind: config.max_rounds_behind,
+			locked: None,
+			future: FutureMessages::new(config.max_rounds_ahead, config.max_buffered_per_authority),
+			advance: HashMap::new(),
+			advance_sent: None,
+			detected_misbehavior: HashMap::new(),
+			committed: None,
+			round_observers: Vec::new(),
+		};
+
+		let mut actions = Vec::new();
+		agreement.start_round(context, 0, &mut actions);
+		(agreement, actions)
+	}
+
+	/// Notify `observer` of the rounds agreement advances to from now on, along with the
+	/// observers added before.
+	pub fn add_round_observer(&mut self, observer: Arc<dyn RoundObserver>) {
+		self.round_observers.push(observer);
+	}
+
+	/// The current round.
+	pub fn round(&self) -> Round {
+		self.round
+	}
+
+	/// The step of the current round.
+	pub fn step(&self) -> Step {
+		self.step
+	}
+
+	/// The round and digest of the proposal the local authority is locked on, if any.
+	pub fn locked(&self) -> Option<(Round, &C::Digest)> {
+		self.locked.as_ref().map(|lock| (lock.round, &lock.digest))
+	}
+
+	/// The number of messages buffered for future rounds.
+	pub fn buffered_messages(&self) -> usize {
+		self.future.len()
+	}
+
+	/// The committed proposal, if any.
+	pub fn committed(&self) -> Option<&CommittedFor<C>> {
+		self.committed.as_ref()
+	}
+
+	/// Access all witnessed misbehavior.
+	pub fn get_misbehavior(&self) -> &HashMap<C::AuthorityId, Vec<BftMisbehaviorFor<C>>> {
+		&self.detected_misbehavior
+	}
+
+	/// Drain all witnessed misbehavior.
+	pub fn drain_misbehaviors(&mut self) -> Vec<(C::AuthorityId, BftMisbehaviorFor<C>)> {
+		self.detected_misbehavior
+			.drain()
+			.flat_map(|(id, misbehaviors)| misbehaviors.into_iter().map(move |m| (id.clone(), m)))
+			.collect()
+	}
+
+	/// Import a message. The signature of the message should already be checked.
+	///
+	/// Messages from non-authorities and duplicate votes are ignored, as are messages for rounds
+	/// outside of the limits of the [`AgreementConfig`]. Conflicting votes are noted as
+	/// misbehavior and otherwise ignored.
+	pub fn import_message(
+		&mut self,
+		context: &C,
+		message: SignedMessageFor<C>,
+	) -> Vec<ActionFor<C>> {
+		let mut actions = Vec::new();
+		if self.step == Step::Committed || !context.is_authority(&message.sender) {
+			return actions
+		}
+
+		if let Message::AdvanceRound(round) = message.message {
+			if round >= self.round {
+				self.note_advance(round, message.sender, message.signature);
+				self.process_advance(context, &mut actions);
+			}
+
+			return actions
+		}
+
+		if message.message.round() > self.round {
+			self.future.push(self.round, message);
+			return actions
+		}
+
+		if message.message.round() == self.round {
+			self.import_current(context, message);
+			self.process(context, &mut actions);
+		} else {
+			self.import_past(context, message, &mut actions);
+		}
+
+		actions
+	}
+
+	/// Note that the given round timed out.
+	///
+	/// If it is the current round, the remaining steps are voted on and the wish to advance to
+	/// the next round is signalled. Unless locked, the prevote is nil. The precommit is always
+	/// nil. Timeouts of other rounds are ignored.
+	pub fn on_timeout(&mut self, context: &C, round: Round) -> Vec<ActionFor<C>> {
+		let mut actions = Vec::new();
+		if self.step == Step::Committed || round != self.round {
+			return actions
+		}
+
+		self.signal_advance(context, round, &mut actions);
+		self.process_advance(context, &mut actions);
+		actions
+	}
+
+	// An authority wanting to advance from a round also wants to advance from all earlier ones,
+	// so only the latest signal is kept.
+	fn note_advance(&mut self, round: Round, sender: C::AuthorityId, signature: C::Signature) {
+		match self.advance.entry(sender) {
+			Entry::Occupied(mut occupied) if occupied.get().0 < round => {
+				occupied.insert((round, signature));
+			},
+			Entry::Occupied(_) => {},
+			Entry::Vacant(vacant) => {
+				vacant.insert((round, signature));
+			},
+		}
+	}
+
+	// Signal the wish to advance from `round`, voting nil on the remaining steps if it is the
+	// current round.
+	fn signal_advance(&mut self, context: &C, round: Round, actions: &mut Vec<ActionFor<C>>) {
+		if self.advance_sent.map_or(false, |sent| sent >= round) {
+			return
+		}
+
+		if round == self.round {
+			if self.step == Step::Propose {
+				let vote = self.locked.as_ref().map(|lock| lock.digest.clone());
+				self.step = Step::Prevote;
+				self.broadcast(context, Message::Prevote(round, vote), actions);
+			}
+
+			if self.step == Step::Prevote {
+				self.step = Step::Precommit;
+				self.broadcast(context, Message::Precommit(round, None), actions);
+			}
+		}
+
+		self.broadcast(context, Message::AdvanceRound(round), actions);
+	}
+
+	// Move to a new round if a supermajority wants to, or join the wish to advance if enough
+	// authorities signalled it.
+	fn process_advance(&mut self, context: &C, actions: &mut Vec<ActionFor<C>>) {
+		let n = context.num_authorities();
+		let threshold = bft_threshold(n);
+		let join_threshold = n - threshold + 1;
+
+		while self.step != Step::Committed {
+			let mut rounds = self
+				.advance
+				.values()
+				.map(|(round, _)| *round)
+				.filter(|round| *round >= self.round)
+				.collect::<Vec<_>>();
+			rounds.sort_unstable_by(|a, b| b.cmp(a));
+
+			// the highest round at least `count` authorities want to advance from.
+			let highest_with = |count: usize| rounds.get(count.saturating_sub(1)).copied();
+
+			if let Some(round) = highest_with(threshold) {
+				self.start_round(context, round + 1, actions);
+				continue
+			}
+
+			match highest_with(join_threshold) {
+				Some(round) if self.advance_sent.map_or(true, |sent| sent < round) =>
+					self.signal_advance(context, round, actions),
+				_ => break,
+			}
+		}
+	}
+
+	// Import a message of the current round.
+	fn import_current(&mut self, context: &C, message: SignedMessageFor<C>) {
+		if let Message::AdvanceRound(round) = message.message {
+			self.note_advance(round, message.sender, message.signature);
+		} else if let Some((sender, misbehavior)) = import_vote(&mut self.current, context, message)
+		{
+			self.note_misbehavior(sender, misbehavior);
+		}
+	}
+
+	// Import a late message of one of the kept rounds before the current one, committing the
+	// proposal of the round or unlocking the local authority if the message completes a quorum.
+	fn import_past(
+		&mut self,
+		context: &C,
+		message: SignedMessageFor<C>,
+		actions: &mut Vec<ActionFor<C>>,
+	) {
+		let round = message.message.round();
+		let Some(data) = self.past.get_mut(&round) else { return };
+		if let Some((sender, misbehavior)) = import_vote(data, context, message) {
+			self.note_misbehavior(sender, misbehavior);
+		}
+
+		let threshold = bft_threshold(context.num_authorities());
+		let data = &self.past[&round];
+		if let Some(committed) = committed_in(round, data, threshold) {
+			self.step = Step::Committed;
+			self.committed = Some(committed.clone());
+			actions.push(Action::Commit(committed));
+			return
+		}
+
+		if self.locked.as_ref().map_or(false, |lock| unlocks(lock, round, data, threshold)) {
+			self.locked = None;
+		}
+	}
+
+	fn note_misbehavior(&mut self, sender: C::AuthorityId, misbehavior: BftMisbehaviorFor<C>) {
+		// all misbehavior in agreement is provable and actively malicious.
+		self.detected_misbehavior.entry(sender).or_default().push(misbehavior);
+	}
+
+	// Sign a message, import it locally and note it for broadcast.
+	fn broadcast(&mut self, context: &C, message: MessageFor<C>, actions: &mut Vec<ActionFor<C>>) {
+		let signed = context.sign_local(message);
+		let SignedMessage { message, signature, sender } = signed.clone();
+		match message {
+			Message::Propose(_, proposal) => {
+				let digest = C::proposal_digest(&proposal);
+				self.current.proposal = Some((proposal, digest));
+			},
+			Message::Prevote(_, digest) => {
+				let _ = self.current.prevotes.import(sender, digest, signature);
+			},
+			Message::Precommit(_, digest) => {
+				let _ = self.current.precommits.import(sender, digest, signature);
+			},
+			Message::AdvanceRound(round) => {
+				self.advance_sent = Some(round);
+				self.note_advance(round, sender, signature);
+			},
+		}
+
+		actions.push(Action::Broadcast(signed));
+	}
+
+	fn start_round(&mut self, context: &C, round: Round, actions: &mut Vec<ActionFor<C>>) {
+		if round > self.round {
+			let previous = std::mem::take(&mut self.current);
+			self.past.insert(self.round, previous);
+			self.past = self.past.split_off(&round.saturating_sub(self.max_rounds_behind));
+		}
+		self.round = round;
+		self.step = Step::Propose;
+		self.advance.retain(|_, (advance_round, _)| *advance_round >= round);
+		for observer in &self.round_observers {
+			observer.round_advanced(round);
+		}
+
+		if context.round_proposer(round) == context.local_id() {
+			let locked = self.locked.as_ref().map(|lock| lock.proposal.clone());
+			if let Some(proposal) = locked.or_else(|| context.proposal()) {
+				self.broadcast(context, Message::Propose(round, proposal), actions);
+			}
+		}
+
+		for message in self.future.take_round(round) {
+			self.import_current(context, message);
+		}
+
+		self.process(context, actions);
+	}
+
+	// Drive the state machine as far as possible given the messages imported so far.
+	fn process(&mut self, context: &C, actions: &mut Vec<ActionFor<C>>) {
+		let n = context.num_authorities();
+		let threshold = bft_threshold(n);
+
+		let unlocked = self
+			.locked
+			.as_ref()
+			.map_or(false, |lock| unlocks(lock, self.round, &self.current, threshold));
+		if unlocked {
+			self.locked = None;
+		}
+
+		if self.step == Step::Propose {
+			if let Some((proposal, digest)) = self.current.proposal.as_ref() {
+				let vote = match self.locked.as_ref() {
+					Some(lock) => Some(lock.digest.clone()),
+					None => context.evaluator().evaluate(proposal).then(|| digest.clone()),
+				};
+				self.step = Step::Prevote;
+				self.broadcast(context, Message::Prevote(self.round, vote), actions);
+			}
+		}
+
+		if self.step == Step::Propose || self.step == Step::Prevote {
+			let prevoted = self.current.prevotes.quorum(threshold).cloned();
+			let vote = match (prevoted, self.current.proposal.as_ref()) {
+				(Some(d), Some((proposal, proposed))) if &d == proposed => {
+					self.locked = Some(Lock {
+						round: self.round,
+						proposal: proposal.clone(),
+						digest: d.clone(),
+					});
+					Some(Some(d))
+				},
+				_ if self.current.prevotes.quorum_impossible(n, threshold) => Some(None),
+				_ => None,
+			};
+
+			if let Some(vote) = vote {
+				self.step = Step::Precommit;
+				self.broadcast(context, Message::Precommit(self.round, vote), actions);
+			}
+		}
+
+		match committed_in(self.round, &self.current, threshold) {
+			Some(committed) => {
+				self.step = Step::Committed;
+				self.committed = Some(committed.clone());
+				actions.push(Action::Commit(committed));
+			},
+			_ if self.current.precommits.quorum_impossible(n, threshold) => {
+				let next = self.round + 1;
+				self.start_round(context, next, actions);
+			},
+			_ => {},
+		}
+
+		self.process_advance(context, actions);
+	}
+}
+
+// Import a vote or proposal into the tallies of its round, returning the misbehavior of its
+// sender if it conflicts with an earlier vote.
+fn import_vote<C: Context>(
+	data: &mut RoundData<C>,
+	context: &C,
+	message: SignedMessageFor<C>,
+) -> Option<(C::AuthorityId, BftMisbehaviorFor<C>)> {
+	let SignedMessage { message, signature, sender } = message;
+	match message {
+		Message::Propose(round, proposal) => {
+			if data.proposal.is_none() && sender == context.round_proposer(round) {
+				let digest = C::proposal_digest(&proposal);
+				data.proposal = Some((proposal, digest));
+			}
+			None
+		},
+		Message::Prevote(round, digest) => {
+			let first =
+				data.prevotes.import(sender.clone(), digest.clone(), signature.clone()).err()?;
+			let double_vote = DoubleVote { round, first, second: (digest, signature) };
+			Some((sender, BftMisbehavior::DoublePrevote(double_vote)))
+		},
+		Message::Precommit(round, digest) => {
+			let first = data
+				.precommits
+				.import(sender.clone(), digest.clone(), signature.clone())
+				.err()?;
+			let double_vote = DoubleVote { round, first, second: (digest, signature) };
+			Some((sender, BftMisbehavior::DoublePrecommit(double_vote)))
+		},
+		Message::AdvanceRound(_) => None,
+	}
+}
+
+// The proposal committed in `round`, if a supermajority precommitted its proposal.
+fn committed_in<C: Context>(
+	round: Round,
+	data: &RoundData<C>,
+	threshold: usize,
+) -> Option<CommittedFor<C>> {
+	let digest = data.precommits.quorum(threshold)?;
+	let (proposal, proposed) = data.proposal.as_ref()?;
+	(digest == proposed).then(|| Committed {
+		proposal: proposal.clone(),
+		justification: Justification {
+			round,
+			signatures: data.precommits.signatures_for(digest),
+			digest: digest.clone(),
+		},
+	})
+}
+
+// Whether a supermajority of prevotes in `round` for a different proposal or for nil releases
+// `lock`, which shows that the proposal it is locked on can't have been committed.
+fn unlocks<C: Context>(
+	lock: &Lock<C>,
+	round: Round,
+	data: &RoundData<C>,
+	threshold: usize,
+) -> bool {
+	lock.round < round &&
+		(data.prevotes.nil_quorum(threshold) ||
+			data.prevotes.quorum(threshold).map_or(false, |d| d != &lock.digest))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use std::collections::HashSet;
+
+	impl ProposalEvaluator<u64> for HashSet<u64> {
+		fn evaluate(&self, proposal: &u64) -> bool {
+			self.contains(proposal)
+		}
+	}
+
+	#[derive(Debug, Clone)]
+	pub(crate) struct TestContext {
+		pub(crate) local_id: u64,
+		pub(crate) n: u64,
+		// proposal produced when the local authority is proposer.
+		pub(crate) proposal: Option<u64>,
+		// proposals considered acceptable.
+		pub(crate) acceptable: HashSet<u64>,
+	}
+
+	impl Context for TestContext {
+		type AuthorityId = u64;
+		type Digest = u64;
+		type Signature = u64;
+		type Proposal = u64;
+		type Evaluator = HashSet<u64>;
+
+		fn proposal_digest(proposal: &u64) -> u64 {
+			*proposal
+		}
+
+		fn local_id(&self) -> u64 {
+			self.local_id
+		}
+
+		fn num_authorities(&self) -> usize {
+			self.n as usize
+		}
+
+		fn is_authority(&self, id: &u64) -> bool {
+			*id < self.n
+		}
+
+		fn round_proposer(&self, round: Round) -> u64 {
+			round as u64 % self.n
+		}
+
+		fn proposal(&self) -> Option<u64> {
+			self.proposal
+		}
+
+		fn evaluator(&self) -> &HashSet<u64> {
+			&self.acceptable
+		}
+
+		fn sign_local(&self, message: MessageFor<Self>) -> SignedMessageFor<Self> {
+			SignedMessage { message, signature: self.local_id, sender: self.local_id }
+		}
+	}
+
+	// Run `contexts.len()` agreements, delivering every broadcast to every other node, until no
+	// messages are left.
+	fn run(contexts: &[TestContext]) -> Vec<Agreement<TestContext>> {
+		let mut queue = Vec::new();
+		let mut nodes = Vec::new();
+		for context in contexts {
+			let (agreement, actions) = Agreement::new(context);
+			queue.extend(actions.into_iter().map(|a| (context.local_id, a)));
+			nodes.push(agreement);
+		}
+
+		let mut queue: std::collections::VecDeque<_> = queue.into();
+		while let Some((from, action)) = queue.pop_front() {
+			if let Action::Broadcast(message) = action {
+				for (i, context) in contexts.iter().enumerate() {
+					if context.local_id != from {
+						let actions = nodes[i].import_message(context, message.clone());
+						queue.extend(actions.into_iter().map(|a| (context.local_id, a)));
+					}
+				}
+			}
+		}
+
+		nodes
+	}
+
+	fn broadcast_messages(actions: Vec<ActionFor<TestContext>>) -> Vec<MessageFor<TestContext>> {
+		actions
+			.into_iter()
+			.map(|a| match a {
+				Action::Broadcast(m) => m.message,
+				Action::Commit(_) => panic!("nothing to commit"),
+			})
+			.collect()
+	}
+
+	pub(crate) fn contexts(n: u64, proposals: &[u64], acceptable: &[u64]) -> Vec<TestContext> {
+		(0..n)
+			.map(|i| TestContext {
+				local_id: i,
+				n,
+				proposal: proposals.get(i as usize).copied(),
+				acceptable: acceptable.iter().copied().collect(),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn future_round_messages_are_replayed() {
+		let context = contexts(4, &[], &[42, 43]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+
+		// round 1 proposal arrives before round 0 concluded.
+		let proposal = SignedMessage { message: Message::Propose(1, 43), signature: 1, sender: 1 };
+		assert!(agreement.import_message(&context, proposal).is_empty());
+
+		// round 0 proposer is offline, everybody precommits nil.
+		for sender in [0, 1, 3] {
+			let precommit =
+				SignedMessage { message: Message::Precommit(0, None), signature: sender, sender };
+			agreement.import_message(&context, precommit);
+		}
+
+		assert_eq!(agreement.round(), 1);
+		assert_eq!(agreement.step(), Step::Prevote);
+	}
+
+	#[test]
+	fn timeout_votes_nil_and_signals_advance() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+
+		// timeouts for other rounds are ignored.
+		assert!(agreement.on_timeout(&context, 1).is_empty());
+
+		let actions = agreement.on_timeout(&context, 0);
+		assert_eq!(
+			broadcast_messages(actions),
+			vec![Message::Prevote(0, None), Message::Precommit(0, None), Message::AdvanceRound(0)]
+		);
+		assert_eq!(agreement.round(), 0);
+
+		let advance =
+			|sender| SignedMessage { message: Message::AdvanceRound(0), signature: sender, sender };
+
+		assert!(agreement.import_message(&context, advance(0)).is_empty());
+		assert!(agreement.import_message(&context, advance(1)).is_empty());
+		assert_eq!(agreement.round(), 1);
+		assert_eq!(agreement.step(), Step::Propose);
+	}
+
+	#[test]
+	fn advance_is_joined_and_may_skip_rounds() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+
+		let advance = |round, sender| SignedMessage {
+			message: Message::AdvanceRound(round),
+			signature: sender,
+			sender,
+		};
+
+		// a single authority may be faulty.
+		assert!(agreement.import_message(&context, advance(3, 0)).is_empty());
+
+		// with two of them, at least one is honest: join.
+		let actions = agreement.import_message(&context, advance(3, 1));
+		assert_eq!(broadcast_messages(actions), vec![Message::AdvanceRound(3)]);
+		assert_eq!(agreement.round(), 4);
+	}
+
+	#[test]
+	fn offline_proposer_is_skipped_after_timeout() {
+		// proposer of round 0 doesn't take part.
+		let contexts = contexts(4, &[42, 43, 44, 45], &[43]).split_off(1);
+		let mut nodes = Vec::new();
+		let mut queue = std::collections::VecDeque::new();
+		for context in &contexts {
+			let (agreement, actions) = Agreement::new(context);
+			assert!(actions.is_empty());
+			nodes.push(agreement);
+		}
+
+		for (node, context) in nodes.iter_mut().zip(&contexts) {
+			queue.extend(node.on_timeout(context, 0).into_iter().map(|a| (context.local_id, a)));
+		}
+
+		while let Some((from, action)) = queue.pop_front() {
+			if let Action::Broadcast(message) = action {
+				for (node, context) in nodes.iter_mut().zip(&contexts) {
+					if context.local_id != from {
+						let actions = node.import_message(context, message.clone());
+						queue.extend(actions.into_iter().map(|a| (context.local_id, a)));
+					}
+				}
+			}
+		}
+
+		for node in nodes {
+			let committed = node.committed().expect("all nodes commit");
+			assert_eq!(committed.justification.round, 1);
+			assert_eq!(committed.proposal, 43);
+		}
+	}
+
+	#[test]
+	fn threshold_is_supermajority() {
+		assert_eq!(bft_threshold(1), 1);
+		assert_eq!(bft_threshold(3), 3);
+		assert_eq!(bft_threshold(4), 3);
+		assert_eq!(bft_threshold(7), 5);
+		assert_eq!(bft_threshold(10), 7);
+	}
+
+	#[test]
+	fn acceptable_proposal_is_committed_in_first_round() {
+		let contexts = contexts(4, &[42, 43, 44, 45], &[42]);
+		let nodes = run(&contexts);
+
+		for node in nodes {
+			let committed = node.committed().expect("all nodes commit");
+			assert_eq!(committed.justification.round, 0);
+			assert_eq!(committed.proposal, 42);
+			assert_eq!(committed.justification.digest, 42);
+			assert_eq!(
+				committed.justification.verify(&contexts[0], |m| m.signature == m.sender),
+				Ok(())
+			);
+		}
+	}
+
+	#[test]
+	fn unacceptable_proposal_moves_to_next_round() {
+		let nodes = run(&contexts(4, &[42, 43, 44, 45], &[43]));
+
+		for node in nodes {
+			assert_eq!(node.buffered_messages(), 0);
+			let committed = node.committed().expect("all nodes commit");
+			assert_eq!(committed.justification.round, 1);
+			assert_eq!(committed.proposal, 43);
+		}
+	}
+
+	#[test]
+	fn messages_from_non_proposers_and_non_authorities_are_ignored() {
+		let context = contexts(4, &[], &[42]).remove(1);
+		let (mut agreement, actions) = Agreement::new(&context);
+		assert!(actions.is_empty());
+
+		let message = |round, sender| SignedMessage {
+			message: Message::Propose(round, 42),
+			signature: sender,
+			sender,
+		};
+
+		assert!(agreement.import_message(&context, message(0, 9)).is_empty());
+		// not the proposer of round 0.
+		assert!(agreement.import_message(&context, message(0, 2)).is_empty());
+		assert_eq!(agreement.step(), Step::Propose);
+
+		let actions = agreement.import_message(&context, message(0, 0));
+		assert_eq!(
+			actions,
+			vec![Action::Broadcast(SignedMessage {
+				message: Message::Prevote(0, Some(42)),
+				signature: 1,
+				sender: 1,
+			})]
+		);
+		assert_eq!(agreement.step(), Step::Prevote);
+	}
+
+	// Lock the authority with ID 2 out of 4 on proposal 42 in round 0, and move it to round 1.
+	fn locked_agreement() -> (TestContext, Agreement<TestContext>) {
+		let context = contexts(4, &[], &[42, 43]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		agreement.import_message(&context, message(Message::Propose(0, 42), 0));
+		agreement.import_message(&context, message(Message::Prevote(0, Some(42)), 0));
+		agreement.import_message(&context, message(Message::Prevote(0, Some(42)), 1));
+		assert_eq!(agreement.locked(), Some((0, &42)));
+
+		for sender in [0, 1, 3] {
+			agreement.import_message(&context, message(Message::Precommit(0, None), sender));
+		}
+		assert_eq!(agreement.round(), 1);
+		assert_eq!(agreement.locked(), Some((0, &42)));
+
+		(context, agreement)
+	}
+
+	#[test]
+	fn locked_authority_prevotes_locked_proposal() {
+		let (context, mut agreement) = locked_agreement();
+
+		let proposal = SignedMessage { message: Message::Propose(1, 43), signature: 1, sender: 1 };
+		let actions = agreement.import_message(&context, proposal);
+		assert_eq!(broadcast_messages(actions), vec![Message::Prevote(1, Some(42))]);
+
+		// the locked proposal is prevoted on timeout as well.
+		let (context, mut agreement) = locked_agreement();
+		let actions = agreement.on_timeout(&context, 1);
+		assert_eq!(broadcast_messages(actions)[0], Message::Prevote(1, Some(42)));
+	}
+
+	#[test]
+	fn locked_authority_proposes_locked_proposal() {
+		let (mut context, mut agreement) = locked_agreement();
+		context.proposal = Some(44);
+
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		for sender in [0, 1, 3] {
+			agreement.import_message(&context, message(Message::Precommit(1, None), sender));
+		}
+		assert_eq!(agreement.round(), 2);
+		assert_eq!(agreement.locked(), Some((0, &42)));
+		assert_eq!(agreement.current.proposal, Some((42, 42)));
+	}
+
+	#[test]
+	fn newer_prevote_quorum_for_other_proposal_unlocks() {
+		let (context, mut agreement) = locked_agreement();
+
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		agreement.import_message(&context, message(Message::Propose(1, 43), 1));
+		agreement.import_message(&context, message(Message::Prevote(1, Some(43)), 0));
+		agreement.import_message(&context, message(Message::Prevote(1, Some(43)), 1));
+		assert_eq!(agreement.locked(), Some((0, &42)));
+
+		let actions = agreement.import_message(&context, message(Message::Prevote(1, Some(43)), 3));
+		assert_eq!(broadcast_messages(actions), vec![Message::Precommit(1, Some(43))]);
+		assert_eq!(agreement.locked(), Some((1, &43)));
+	}
+
+	#[test]
+	fn newer_nil_prevote_quorum_unlocks() {
+		let (context, mut agreement) = locked_agreement();
+
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		for sender in [0, 1, 3] {
+			agreement.import_message(&context, message(Message::Prevote(1, None), sender));
+		}
+		assert_eq!(agreement.locked(), None);
+	}
+
+	#[test]
+	fn prevote_quorum_for_locked_proposal_renews_lock() {
+		let (context, mut agreement) = locked_agreement();
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		agreement.import_message(&context, message(Message::Propose(1, 42), 1));
+		agreement.import_message(&context, message(Message::Prevote(1, Some(42)), 0));
+		agreement.import_message(&context, message(Message::Prevote(1, Some(42)), 1));
+		assert_eq!(agreement.locked(), Some((1, &42)));
+	}
+
+	#[test]
+	fn conflicting_votes_are_misbehavior() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+		agreement.import_message(&context, message(Message::Prevote(0, Some(42)), 0));
+		agreement.import_message(&context, message(Message::Prevote(0, Some(42)), 0));
+		agreement.import_message(&context, message(Message::Precommit(0, Some(42)), 0));
+		assert!(agreement.get_misbehavior().is_empty());
+
+		agreement.import_message(&context, message(Message::Prevote(0, None), 0));
+		agreement.import_message(&context, message(Message::Precommit(0, Some(43)), 0));
+
+		let misbehavior = agreement.drain_misbehaviors();
+		assert_eq!(
+			misbehavior,
+			vec![
+				(
+					0,
+					BftMisbehavior::DoublePrevote(DoubleVote {
+						round: 0,
+						first: (Some(42), 0),
+						second: (None, 0),
+					})
+				),
+				(
+					0,
+					BftMisbehavior::DoublePrecommit(DoubleVote {
+						round: 0,
+						first: (Some(42), 0),
+						second: (Some(43), 0),
+					})
+				),
+			]
+		);
+		assert!(agreement.get_misbehavior().is_empty());
+
+		let (first, second) = misbehavior[0].1.clone().deconstruct::<TestContext>();
+		assert_eq!(first, (Message::Prevote(0, Some(42)), 0));
+		assert_eq!(second, (Message::Prevote(0, None), 0));
+	}
+
+	#[test]
+	fn late_precommits_commit_an_earlier_round() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let (mut agreement, _) = Agreement::new(&context);
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+
+		agreement.import_message(&context, message(Message::Propose(0, 42), 0));
+		assert_eq!(agreement.step(), Step::Prevote);
+
+		// the authority moves on before the precommits of round 0 arrive.
+		agreement.import_message(&context, message(Message::AdvanceRound(0), 0));
+		agreement.import_message(&context, message(Message::AdvanceRound(0), 1));
+		assert_eq!(agreement.round(), 1);
+
+		agreement.import_message(&context, message(Message::Precommit(0, Some(42)), 0));
+		agreement.import_message(&context, message(Message::Precommit(0, Some(42)), 1));
+		let actions =
+			agreement.import_message(&context, message(Message::Precommit(0, Some(42)), 3));
+		assert_eq!(agreement.step(), Step::Committed);
+		let committed = agreement.committed().unwrap();
+		assert_eq!((committed.proposal, committed.justification.round), (42, 0));
+		assert!(matches!(&actions[..], [Action::Commit(_)]));
+	}
+
+	#[test]
+	fn votes_far_behind_are_dropped() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let config = AgreementConfig {
+			max_rounds_ahead: 8,
+			max_buffered_per_authority: 24,
+			max_rounds_behind: 2,
+		};
+		let (mut agreement, _) = Agreement::with_config(&context, config);
+		let message = |message, sender| SignedMessage { message, signature: sender, sender };
+
+		agreement.import_message(&context, message(Message::Propose(0, 42), 0));
+		agreement.import_message(&context, message(Message::AdvanceRound(2), 0));
+		agreement.import_message(&context, message(Message::AdvanceRound(2), 1));
+		assert_eq!(agreement.round(), 3);
+
+		for sender in [0, 1, 3] {
+			agreement.import_message(&context, message(Message::Precommit(0, Some(42)), sender));
+		}
+		assert!(agreement.committed().is_none());
+	}
+
+	#[test]
+	fn far_future_messages_are_dropped() {
+		let context = contexts(4, &[], &[42]).remove(2);
+		let config = AgreementConfig {
+			max_rounds_ahead: 2,
+			max_buffered_per_authority: 24,
+			max_rounds_behind: 2,
+		};
+		let (mut agreement, _) = Agreement::with_config(&context, config);
+
+		let proposal = |round| SignedMessage {
+			message: Message::Propose(round, 42),
+			signature: round as u64 % 4,
+			sender: round as u64 % 4,
+		};
+		agreement.import_message(&context, proposal(2));
+		agreement.import_message(&context, proposal(3));
+		assert_eq!(agreement.buffered_messages(), 1);
+
+		// the authority joins a supermajority which wants to leave round 1.
+		let advance =
+			|sender| SignedMessage { message: Message::AdvanceRound(1), signature: sender, sender };
+		agreement.import_message(&context, advance(0));
+		agreement.import_message(&context, advance(1));
+
+		// the round 2 proposal was replayed, the one of round 3 is gone.
+		assert_eq!(agreement.round(), 2);
+		assert_eq!(agreement.step(), Step::Prevote);
+		assert_eq!(agreement.buffered_messages(), 0);
+	}
+}
@@ -0,0 +1,213 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Round timeouts.
+//!
+//! If a round doesn't conclude in time, e.g. because the proposer is offline, the agreement
+//! moves on to the next round. Rounds get longer as agreement fails to conclude, so that
+//! authorities eventually spend long enough in the same round to agree despite network delays.
+//!
+//! Timeouts depend on the time told by a [`Clock`] and on the delays of a [`Timer`], so that
+//! tests can use a [`ManualClock`] to advance time at will instead of sleeping.
+
+use std::{
+	future::Future,
+	sync::OnceLock,
+	time::{Duration, Instant},
+};
+#[cfg(any(test, feature = "test-utils"))]
+use std::{
+	pin::Pin,
+	sync::{Arc, Mutex},
+	task::{self, Poll, Waker},
+};
+
+use super::Round;
+#[cfg(any(test, feature = "test-utils"))]
+use crate::LOCK_PROOF;
+
+/// Determines how long each round lasts.
+pub trait RoundTimeout {
+	/// The duration of the given round.
+	fn round_duration(&self, round: Round) -> Duration;
+}
+
+/// Round durations growing exponentially with the round number, up to a maximum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+	/// The duration of the first round.
+	pub base: Duration,
+	/// The maximum duration of a round.
+	pub max: Duration,
+}
+
+impl Default for ExponentialBackoff {
+	fn default() -> Self {
+		ExponentialBackoff { base: Duration::from_secs(2), max: Duration::from_secs(60) }
+	}
+}
+
+impl RoundTimeout for ExponentialBackoff {
+	fn round_duration(&self, round: Round) -> Duration {
+		let factor = 1u32.checked_shl(round).unwrap_or(u32::MAX);
+		self.base.saturating_mul(factor).min(self.max)
+	}
+}
+
+/// A source of delays.
+pub trait Timer {
+	/// A future resolving once the delay has passed.
+	type Delay: Future<Output = ()> + Unpin;
+
+	/// Create a delay of the given duration.
+	fn delay(&self, duration: Duration) -> Self::Delay;
+}
+
+/// A source of the current time.
+pub trait Clock {
+	/// The time since an arbitrary epoch, which doesn't change for the lifetime of the clock.
+	fn now(&self) -> Duration;
+}
+
+/// A [`Clock`] and [`Timer`] backed by the system clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemTimer;
+
+impl Clock for SystemTimer {
+	fn now(&self) -> Duration {
+		// the epoch is shared by all instances, as they are interchangeable.
+		static EPOCH: OnceLock<Instant> = OnceLock::new();
+		EPOCH.get_or_init(Instant::now).elapsed()
+	}
+}
+
+impl Timer for SystemTimer {
+	type Delay = futures_timer::Delay;
+
+	fn delay(&self, duration: Duration) -> Self::Delay {
+		futures_timer::Delay::new(duration)
+	}
+}
+
+/// A [`Clock`] and [`Timer`] whose time only passes when advanced, for tests.
+///
+/// Clones share their time, so a test can keep a clone to advance the time of the one it hands
+/// out. Time starts at zero.
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+	state: Arc<Mutex<ManualState>>,
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Debug, Default)]
+struct ManualState {
+	now: Duration,
+	// the tasks waiting on delays which didn't pass yet.
+	waiting: Vec<Waker>,
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl ManualClock {
+	/// Create a clock at time zero.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Let `duration` pass, waking the tasks waiting on delays.
+	pub fn advance(&self, duration: Duration) {
+		let waiting = {
+			let mut state = self.state.lock().expect(LOCK_PROOF);
+			state.now = state.now.saturating_add(duration);
+			std::mem::take(&mut state.waiting)
+		};
+
+		waiting.into_iter().for_each(Waker::wake);
+	}
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Clock for ManualClock {
+	fn now(&self) -> Duration {
+		self.state.lock().expect(LOCK_PROOF).now
+	}
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Timer for ManualClock {
+	type Delay = ManualDelay;
+
+	fn delay(&self, duration: Duration) -> Self::Delay {
+		ManualDelay { clock: self.clone(), at: self.now().saturating_add(duration) }
+	}
+}
+
+/// A delay of a [`ManualClock`].
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Debug)]
+pub struct ManualDelay {
+	clock: ManualClock,
+	at: Duration,
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl Future for ManualDelay {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<()> {
+		let mut state = self.clock.state.lock().expect(LOCK_PROOF);
+		if state.now >= self.at {
+			return Poll::Ready(())
+		}
+
+		if !state.waiting.iter().any(|waker| waker.will_wake(cx.waker())) {
+			state.waiting.push(cx.waker().clone());
+		}
+		Poll::Pending
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn manual_delays_pass_when_advanced() {
+		let clock = ManualClock::new();
+		let mut delay = clock.delay(Duration::from_secs(2));
+		let waker = futures::task::noop_waker();
+		let mut cx = task::Context::from_waker(&waker);
+
+		assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+		clock.clone().advance(Duration::from_millis(1999));
+		assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+		clock.advance(Duration::from_millis(1));
+		assert!(Pin::new(&mut delay).poll(&mut cx).is_ready());
+		assert_eq!(clock.now(), Duration::from_secs(2));
+	}
+
+	#[test]
+	fn exponential_backoff_is_capped() {
+		let backoff =
+			ExponentialBackoff { base: Duration::from_millis(500), max: Duration::from_secs(5) };
+
+		assert_eq!(backoff.round_duration(0), Duration::from_millis(500));
+		assert_eq!(backoff.round_duration(1), Duration::from_secs(1));
+		assert_eq!(backoff.round_duration(3), Duration::from_secs(4));
+		assert_eq!(backoff.round_duration(4), Duration::from_secs(5));
+		assert_eq!(backoff.round_duration(40), Duration::from_secs(5));
+	}
+}
@@ -0,0 +1,142 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Alerts on detected misbehavior, for operators.
+//!
+//! Misbehavior is reported on chain through
+//! [`Table::drain_misbehaviors`](crate::Table::drain_misbehaviors). Operators usually want to
+//! hear about it as well, through their own alerting systems. A [`MisbehaviorAlerter`] follows
+//! the [`TableEvents`](crate::events::TableEvents) of a table, renders every
+//! [`TableEvent::MisbehaviorDetected`] into a [`MisbehaviorAlert`] with a human-readable
+//! description, and hands it to a callback. The callback runs on the task following the events,
+//! never while the table is importing. If the events channel is also the
+//! [`RoundObserver`](crate::agreement::RoundObserver) of an
+//! [`Agreement`](crate::agreement::Agreement), the alerter knows the round the misbehavior was
+//! detected in.
+
+use std::fmt::Debug;
+
+use futures::{Stream, StreamExt};
+
+use crate::{agreement::Round, events::TableEvent, generic::MisbehaviorKind};
+
+/// An alert on detected misbehavior.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MisbehaviorAlert<AuthorityId> {
+	/// The misbehaving authority.
+	pub authority: AuthorityId,
+	/// The kind of misbehavior.
+	pub kind: MisbehaviorKind,
+	/// The round the misbehavior was detected in, if known.
+	pub round: Option<Round>,
+	/// A human-readable description of the misbehavior.
+	pub description: String,
+}
+
+/// Renders detected misbehavior into [`MisbehaviorAlert`]s and hands them to a callback.
+pub struct MisbehaviorAlerter<AuthorityId> {
+	round: Option<Round>,
+	callback: Box<dyn FnMut(MisbehaviorAlert<AuthorityId>) + Send>,
+}
+
+impl<AuthorityId: Debug + Clone> MisbehaviorAlerter<AuthorityId> {
+	/// Create an alerter handing alerts to `callback`.
+	pub fn new(callback: impl FnMut(MisbehaviorAlert<AuthorityId>) + Send + 'static) -> Self {
+		MisbehaviorAlerter { round: None, callback: Box::new(callback) }
+	}
+
+	/// Handle an event of the table, alerting on misbehavior and noting the current round,
+	/// which subsequent alerts refer to.
+	pub fn handle<Digest, GroupId>(&mut self, event: &TableEvent<Digest, GroupId, AuthorityId>) {
+		match event {
+			TableEvent::RoundAdvanced { round } => self.round = Some(*round),
+			TableEvent::MisbehaviorDetected { authority, kind } => self.alert(authority, *kind),
+			_ => {},
+		}
+	}
+
+	/// Handle the events of `events` until the stream ends, see [`MisbehaviorAlerter::handle`].
+	pub async fn run<Digest, GroupId>(
+		mut self,
+		mut events: impl Stream<Item = TableEvent<Digest, GroupId, AuthorityId>> + Unpin,
+	) {
+		while let Some(event) = events.next().await {
+			self.handle(&event);
+		}
+	}
+
+	fn alert(&mut self, authority: &AuthorityId, kind: MisbehaviorKind) {
+		let offence = match kind {
+			MisbehaviorKind::ValidityDoubleVote => "cast conflicting validity votes on a candidate",
+			MisbehaviorKind::MultipleCandidates => "seconded more than one candidate in its group",
+			MisbehaviorKind::UnauthorizedStatement =>
+				"issued a statement in a group it is not a member of",
+			MisbehaviorKind::DoubleSign => "signed the same statement twice",
+		};
+		let description = match self.round {
+			Some(round) => format!("Validator {:?} {} in round {}", authority, offence, round),
+			None => format!("Validator {:?} {}", authority, offence),
+		};
+
+		(self.callback)(MisbehaviorAlert {
+			authority: authority.clone(),
+			kind,
+			round: self.round,
+			description,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		agreement::RoundObserver,
+		events::TableEvents,
+		router::tests::{context_and_table, seconded, seconded_other},
+	};
+	use std::sync::{Arc, Mutex};
+
+	#[test]
+	fn misbehavior_is_alerted() {
+		let (context, mut table) = context_and_table();
+		let events = TableEvents::new(2);
+		let subscriber = events.subscribe();
+		table.set_events(events.clone());
+
+		let alerts = Arc::new(Mutex::new(Vec::new()));
+		let sink = alerts.clone();
+		let alerter = MisbehaviorAlerter::new(move |alert| sink.lock().unwrap().push(alert));
+
+		events.round_advanced(3);
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, seconded_other(1));
+		// the alerter runs until the events channel is gone.
+		drop((table, events));
+		futures::executor::block_on(alerter.run(subscriber));
+
+		assert_eq!(
+			*alerts.lock().unwrap(),
+			vec![MisbehaviorAlert {
+				authority: 1,
+				kind: MisbehaviorKind::MultipleCandidates,
+				round: Some(3),
+				description: "Validator 1 seconded more than one candidate in its group in round 3"
+					.into(),
+			}]
+		);
+	}
+}
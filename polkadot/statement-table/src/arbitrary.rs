@@ -0,0 +1,201 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generators of protocol types for property-based tests with `quickcheck`.
+//!
+//! Statements are [`Arbitrary`] for any arbitrary candidates, digests, authorities and
+//! signatures. To exercise a table, an arbitrary [`TestContext`] assigns validators to groups
+//! round-robin, and [`ArbitraryStatements`] generates statements in such a context: mostly from
+//! members of the right group and about a small pool of candidates, so that votes pile up and
+//! misbehavior occurs.
+//!
+//! This module is available to other crates with the `quickcheck` feature.
+
+use quickcheck::{Arbitrary, Gen};
+
+use crate::{
+	generic::{SignedStatement, SignedStatementFor, Statement},
+	test_utils::{sign, GroupId, TestCandidate, TestContext, TestSignature},
+};
+
+// the number of distinct candidate bodies, small enough for candidates to collide.
+const CANDIDATE_POOL: u64 = 4;
+
+impl<C: Arbitrary, D: Arbitrary> Arbitrary for Statement<C, D> {
+	fn arbitrary(g: &mut Gen) -> Self {
+		if bool::arbitrary(g) {
+			Statement::Seconded(C::arbitrary(g))
+		} else {
+			Statement::Valid(D::arbitrary(g))
+		}
+	}
+}
+
+impl<C, D, A, S> Arbitrary for SignedStatement<C, D, A, S>
+where
+	C: Arbitrary,
+	D: Arbitrary,
+	A: Arbitrary,
+	S: Arbitrary,
+{
+	fn arbitrary(g: &mut Gen) -> Self {
+		SignedStatement {
+			statement: Statement::arbitrary(g),
+			signature: S::arbitrary(g),
+			sender: A::arbitrary(g),
+		}
+	}
+}
+
+impl Arbitrary for TestCandidate {
+	fn arbitrary(g: &mut Gen) -> Self {
+		TestCandidate { group_id: u32::arbitrary(g), body: u64::arbitrary(g) % CANDIDATE_POOL }
+	}
+}
+
+impl Arbitrary for TestSignature {
+	fn arbitrary(g: &mut Gen) -> Self {
+		TestSignature {
+			signer: u32::arbitrary(g),
+			payload: Vec::arbitrary(g),
+			nonce: u8::arbitrary(g),
+		}
+	}
+}
+
+impl Arbitrary for TestContext {
+	fn arbitrary(g: &mut Gen) -> Self {
+		let validators = 1 + u32::arbitrary(g) % 10;
+		let groups = 1 + u32::arbitrary(g) % validators.min(4);
+		TestContext::builder().with_interleaved_groups(groups, validators).build()
+	}
+}
+
+/// Statements in an arbitrary [`TestContext`], with valid signatures, in order of import.
+///
+/// A few statements are from validators outside of the context, or about a group other than
+/// that of their sender.
+#[derive(Debug, Clone)]
+pub struct ArbitraryStatements {
+	/// The context of the statements.
+	pub context: TestContext,
+	/// Whether the table accepts multiple seconded candidates per validator, see
+	/// [`Config`](crate::Config).
+	pub allow_multiple_seconded: bool,
+	/// The statements, along with the group of their candidate.
+	pub statements: Vec<(GroupId, SignedStatementFor<TestContext>)>,
+}
+
+impl Arbitrary for ArbitraryStatements {
+	fn arbitrary(g: &mut Gen) -> Self {
+		let context = TestContext::arbitrary(g);
+		let validators = context.authorities().count() as u32;
+		let groups = context.groups().count() as u32;
+		let len = usize::arbitrary(g) % (g.size() + 1);
+		let statements = (0..len)
+			.map(|_| {
+				// one in eight senders is an outsider, and one in eight is in the wrong group.
+				let sender = u32::arbitrary(g) % (validators + 1);
+				let own_group = context.groups_of(sender).next();
+				let group_id = match own_group {
+					Some(group) if u8::arbitrary(g) % 8 != 0 => group,
+					_ => u32::arbitrary(g) % groups,
+				};
+				let sender = if u8::arbitrary(g) % 8 == 0 { validators } else { sender };
+
+				let candidate =
+					TestCandidate { group_id, body: u64::arbitrary(g) % CANDIDATE_POOL };
+				let statement = if bool::arbitrary(g) {
+					Statement::Seconded(candidate)
+				} else {
+					Statement::Valid(candidate.digest())
+				};
+				(group_id, sign(sender, statement, u8::arbitrary(g) % 2))
+			})
+			.collect();
+
+		ArbitraryStatements { context, allow_multiple_seconded: bool::arbitrary(g), statements }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::{Config, Context, Table},
+		inspect::TableInspector,
+		proof::MisbehaviorProof,
+		test_utils::SIGNING_CONTEXT,
+		verify::VerifyContext,
+	};
+	use quickcheck::QuickCheck;
+
+	fn import(statements: &ArbitraryStatements) -> Table<TestContext> {
+		let mut table =
+			Table::new(Config { allow_multiple_seconded: statements.allow_multiple_seconded });
+		for (group_id, statement) in &statements.statements {
+			table.import_statement(&statements.context, *group_id, statement.clone());
+		}
+		table
+	}
+
+	#[test]
+	fn generated_statements_are_verifiable() {
+		fn property(statements: ArbitraryStatements) -> bool {
+			let context = &statements.context;
+			statements.statements.iter().all(|(_, s)| context.verify_statement(s))
+		}
+
+		QuickCheck::new().quickcheck(property as fn(ArbitraryStatements) -> bool)
+	}
+
+	#[test]
+	fn misbehavior_is_only_reported_with_valid_proofs() {
+		fn property(statements: ArbitraryStatements) -> bool {
+			let table = import(&statements);
+			table.assert_invariants(&statements.context);
+
+			table.get_misbehavior().iter().all(|(authority, misbehavior)| {
+				misbehavior.iter().all(|misbehavior| {
+					MisbehaviorProof::new::<TestContext>(*authority, misbehavior.clone())
+						.verify(
+							&statements.context,
+							statements.allow_multiple_seconded,
+							&SIGNING_CONTEXT,
+							|signer, payload, signature| signature.is_valid(signer, payload),
+						)
+						.is_ok()
+				})
+			})
+		}
+
+		QuickCheck::new().quickcheck(property as fn(ArbitraryStatements) -> bool)
+	}
+
+	#[test]
+	fn only_group_members_are_counted() {
+		fn property(statements: ArbitraryStatements) -> bool {
+			let table = import(&statements);
+			let context = &statements.context;
+			TableInspector::new(&table, context, 1).candidates().iter().all(|candidate| {
+				let group_size = context.get_group_size(&candidate.status.group_id);
+				group_size.map_or(false, |size| candidate.status.validity_votes <= size)
+			})
+		}
+
+		QuickCheck::new().quickcheck(property as fn(ArbitraryStatements) -> bool)
+	}
+}
@@ -0,0 +1,330 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Archival of the statements of concluded rounds.
+//!
+//! Once a round concludes, its table is no longer needed for agreement, but its statements may
+//! still be needed for audits and disputes. An [`Archive`] keeps them in a [`TableStore`] in
+//! compact form: each candidate is stored once per round, along with the signatures of the
+//! votes on it and whether it was attested, instead of once per statement seconding it. The
+//! misbehavior detected during the round is kept as well.
+//!
+//! How long rounds are kept is configured with a [`RetentionPolicy`], see
+//! [`Archive::apply_retention`]. Availability votes aren't archived: they are only held by the
+//! [`AvailabilityTable`](crate::availability::AvailabilityTable) of a round and are gone with it,
+//! so there is nothing to drop for them here.
+
+use std::{
+	collections::{HashMap, HashSet},
+	io,
+	marker::PhantomData,
+	ops::ControlFlow,
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	generic::{Context, MisbehaviorFor, Statement, Table, ValidityAttestation},
+	store::{invalid_data, StoreTransaction, TableStore},
+};
+
+const ARCHIVE_PREFIX: &[u8] = b"archive";
+const CANDIDATE_TAG: u8 = 0;
+const MISBEHAVIOR_TAG: u8 = 1;
+
+/// A candidate of a concluded round, along with the votes on it.
+#[derive(Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub struct ArchivedCandidate<Group, Candidate, AuthorityId, Signature> {
+	/// The group ID that the candidate is in.
+	pub group_id: Group,
+	/// The candidate data.
+	pub candidate: Candidate,
+	/// The votes on the candidate, in order of import.
+	pub validity_votes: Vec<(AuthorityId, ValidityAttestation<Signature>)>,
+	/// Whether the candidate was attested when the round concluded.
+	pub attested: bool,
+}
+
+/// Type alias for the archived candidate corresponding to context type.
+pub type ArchivedCandidateFor<Ctx> = ArchivedCandidate<
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// How long an [`Archive`] keeps concluded rounds, trading disk usage for auditability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+	/// The number of most recent sessions whose statements are kept.
+	pub keep_sessions: u32,
+	/// Whether misbehavior is kept after the statements of its round are dropped.
+	pub keep_misbehavior: bool,
+}
+
+impl Default for RetentionPolicy {
+	fn default() -> Self {
+		RetentionPolicy { keep_sessions: 6, keep_misbehavior: true }
+	}
+}
+
+/// An archive of the statements of concluded rounds, identified by `Round`.
+pub struct Archive<Ctx, Round, Store> {
+	store: Store,
+	_marker: PhantomData<fn() -> (Ctx, Round)>,
+}
+
+impl<Ctx: Context, Round: Encode, Store: TableStore> Archive<Ctx, Round, Store>
+where
+	Ctx::Candidate: Encode + Decode,
+	Ctx::Digest: Encode + Decode,
+	Ctx::GroupId: Encode + Decode,
+	Ctx::AuthorityId: Encode + Decode,
+	Ctx::Signature: Encode + Decode,
+{
+	/// Create an archive in `store`.
+	pub fn new(store: Store) -> Self {
+		Archive { store, _marker: PhantomData }
+	}
+
+	/// Archive the table of the concluded `round`, returning the number of archived candidates.
+	///
+	/// Candidates are archived as attested if they have `minimum_backing_votes`, see
+	/// [`Table::attested_candidate`].
+	pub fn archive_round(
+		&self,
+		round: &Round,
+		context: &Ctx,
+		table: Table<Ctx>,
+		minimum_backing_votes: u32,
+	) -> io::Result<usize> {
+		let snapshot = table.snapshot();
+		let mut candidates: Vec<(Ctx::Digest, ArchivedCandidateFor<Ctx>)> = Vec::new();
+		let mut index = HashMap::new();
+		for (group_id, statement) in snapshot.statements {
+			let (digest, attestation) = match statement.statement {
+				Statement::Seconded(candidate) => {
+					let digest = Ctx::candidate_digest(&candidate);
+					if !index.contains_key(&digest) {
+						index.insert(digest.clone(), candidates.len());
+						let attested = table
+							.attested_candidate(&digest, context, minimum_backing_votes)
+							.is_some();
+						candidates.push((
+							digest.clone(),
+							ArchivedCandidate {
+								group_id,
+								candidate,
+								validity_votes: Vec::new(),
+								attested,
+							},
+						));
+					}
+					(digest, ValidityAttestation::Implicit(statement.signature))
+				},
+				Statement::Valid(digest) =>
+					(digest, ValidityAttestation::Explicit(statement.signature)),
+			};
+
+			// every candidate is seconded before it is voted on.
+			if let Some(&i) = index.get(&digest) {
+				candidates[i].1.validity_votes.push((statement.sender, attestation));
+			}
+		}
+
+		let archived = candidates.len();
+		let mut transaction = StoreTransaction::default();
+		for (digest, candidate) in candidates {
+			transaction.put(candidate_key(round, &digest), candidate.encode());
+		}
+		if !snapshot.misbehavior.is_empty() {
+			transaction.put(round_key(round, MISBEHAVIOR_TAG), snapshot.misbehavior.encode());
+		}
+
+		self.store.write(transaction)?;
+		Ok(archived)
+	}
+
+	/// Get an archived candidate of `round` by digest.
+	pub fn candidate(
+		&self,
+		round: &Round,
+		digest: &Ctx::Digest,
+	) -> io::Result<Option<ArchivedCandidateFor<Ctx>>> {
+		self.store
+			.get(&candidate_key(round, digest))?
+			.map(|value| decode(&value))
+			.transpose()
+	}
+
+	/// Get all archived candidates of `round`, ordered by the encoding of their digest.
+	pub fn candidates(&self, round: &Round) -> io::Result<Vec<ArchivedCandidateFor<Ctx>>> {
+		self.store
+			.iter_with_prefix(&round_key(round, CANDIDATE_TAG))?
+			.into_iter()
+			.map(|(_, value)| decode(&value))
+			.collect()
+	}
+
+	/// Get the misbehavior detected during `round`.
+	pub fn misbehavior(
+		&self,
+		round: &Round,
+	) -> io::Result<Vec<(Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>)>> {
+		self.store
+			.get(&round_key(round, MISBEHAVIOR_TAG))?
+			.map_or(Ok(Vec::new()), |value| decode(&value))
+	}
+
+	/// Remove everything archived for `round`.
+	pub fn prune(&self, round: &Round) -> io::Result<()> {
+		let mut transaction = StoreTransaction::default();
+		transaction.delete_prefix(round_key(round, CANDIDATE_TAG));
+		transaction.delete(round_key(round, MISBEHAVIOR_TAG));
+		self.store.write(transaction)
+	}
+
+	/// Access the store.
+	pub fn store(&self) -> &Store {
+		&self.store
+	}
+}
+
+impl<Ctx: Context, Round: Encode + Decode, Store: TableStore> Archive<Ctx, Round, Store> {
+	/// Drop the rounds which `policy` doesn't keep in `current_session` any longer, returning
+	/// how many rounds had anything dropped. `session_of` gives the session of a round.
+	///
+	/// Rounds whose misbehavior is kept by `policy` stay in the store, but aren't counted again
+	/// once their candidates are gone.
+	pub fn apply_retention(
+		&self,
+		policy: &RetentionPolicy,
+		current_session: u32,
+		session_of: impl Fn(&Round) -> u32,
+	) -> io::Result<usize> {
+		let mut dropped = HashSet::new();
+		let mut transaction = StoreTransaction::default();
+		let mut error = None;
+		self.store.keys_with_prefix(ARCHIVE_PREFIX, &mut |key| {
+			let mut rest = &key[ARCHIVE_PREFIX.len()..];
+			let round = match Round::decode(&mut rest) {
+				Ok(round) => round,
+				Err(e) => {
+					error = Some(invalid_data(e));
+					return ControlFlow::Break(())
+				},
+			};
+			let kept = session_of(&round).saturating_add(policy.keep_sessions) > current_session;
+			let dropped_key = match rest.first() {
+				Some(&CANDIDATE_TAG) => true,
+				Some(&MISBEHAVIOR_TAG) => !policy.keep_misbehavior,
+				_ => false,
+			};
+			if kept || !dropped_key || !dropped.insert(round.encode()) {
+				return ControlFlow::Continue(())
+			}
+
+			transaction.delete_prefix(round_key(&round, CANDIDATE_TAG));
+			if !policy.keep_misbehavior {
+				transaction.delete(round_key(&round, MISBEHAVIOR_TAG));
+			}
+			ControlFlow::Continue(())
+		})?;
+		if let Some(e) = error {
+			return Err(e)
+		}
+
+		if !transaction.ops.is_empty() {
+			self.store.write(transaction)?;
+		}
+
+		Ok(dropped.len())
+	}
+}
+
+fn round_key(round: &impl Encode, tag: u8) -> Vec<u8> {
+	let mut key = ARCHIVE_PREFIX.to_vec();
+	round.encode_to(&mut key);
+	key.push(tag);
+	key
+}
+
+fn candidate_key(round: &impl Encode, digest: &impl Encode) -> Vec<u8> {
+	let mut key = round_key(round, CANDIDATE_TAG);
+	digest.encode_to(&mut key);
+	key
+}
+
+fn decode<T: Decode>(mut value: &[u8]) -> io::Result<T> {
+	T::decode(&mut value).map_err(invalid_data)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, seconded_other, valid},
+		store::KvdbStore,
+		test_utils::{TestCandidate, TestContext},
+	};
+	use std::sync::Arc;
+
+	#[test]
+	fn concluded_rounds_are_archived() {
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		let archive = Archive::<TestContext, u32, _>::new(store);
+
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		table.import_statement(&context, 0, seconded_other(1));
+		assert_eq!(archive.archive_round(&7, &context, table, 2).unwrap(), 1);
+
+		let archived = archive.candidate(&7, &100).unwrap().unwrap();
+		assert_eq!(
+			archived,
+			ArchivedCandidate {
+				group_id: 0,
+				candidate: TestCandidate::new(0, 100),
+				validity_votes: vec![
+					(1, ValidityAttestation::Implicit(seconded(1).signature)),
+					(2, ValidityAttestation::Explicit(valid(2).signature)),
+				],
+				attested: true,
+			}
+		);
+		assert_eq!(archive.candidates(&7).unwrap(), vec![archived]);
+		assert_eq!(archive.misbehavior(&7).unwrap()[0].0, 1);
+		assert!(archive.candidates(&8).unwrap().is_empty());
+
+		// the candidates of round 7 are dropped after 2 sessions, but not its misbehavior.
+		let policy = RetentionPolicy { keep_sessions: 2, keep_misbehavior: true };
+		assert_eq!(archive.apply_retention(&policy, 8, |round| *round).unwrap(), 0);
+		assert_eq!(archive.apply_retention(&policy, 9, |round| *round).unwrap(), 1);
+		assert!(archive.candidates(&7).unwrap().is_empty());
+		assert_eq!(archive.misbehavior(&7).unwrap().len(), 1);
+
+		// the kept misbehavior isn't dropped again, until the policy stops keeping it.
+		assert_eq!(archive.apply_retention(&policy, 10, |round| *round).unwrap(), 0);
+		let policy = RetentionPolicy { keep_misbehavior: false, ..policy };
+		assert_eq!(archive.apply_retention(&policy, 10, |round| *round).unwrap(), 1);
+		assert!(archive.misbehavior(&7).unwrap().is_empty());
+
+		archive.prune(&7).unwrap();
+		assert!(archive.candidate(&7, &100).unwrap().is_none());
+		assert!(archive.misbehavior(&7).unwrap().is_empty());
+	}
+}
@@ -0,0 +1,149 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Availability of many candidates attested at once.
+//!
+//! Instead of signing a [`ChunkAttestation`](super::ChunkAttestation) for each candidate, a
+//! guarantor can sign a single [`AvailabilityBitfield`] covering all candidates of a round: bit
+//! `i` is set if the guarantor holds its chunk of the `i`-th candidate. The order of candidates
+//! is agreed on out of band, e.g. the order of the proposal, and set with
+//! [`AvailabilityTable::set_bitfield_candidates`](super::AvailabilityTable::set_bitfield_candidates).
+//! Bitfields commit to that order with a hash of the candidates, so that a bitfield signed for
+//! one order can't be replayed to attest the candidates of another. The table expands bitfields
+//! into a vote on each candidate, so one signature replaces as many as there are candidates.
+
+use parity_scale_codec::{Decode, Encode};
+use sp_core::blake2_256;
+
+use crate::generic::Context;
+
+// distinguishes the signing payloads of bitfields from those of attestations.
+const BITFIELD_SIGNING_MAGIC: [u8; 4] = *b"AVBF";
+
+/// The hash of the digests of the candidates the bits of a bitfield refer to, in order.
+pub fn candidates_hash<Digest: Encode>(candidates: &[Digest]) -> [u8; 32] {
+	blake2_256(&candidates.encode())
+}
+
+/// A bit for each candidate of a round, set if the chunk of the candidate is held.
+#[derive(PartialEq, Eq, Debug, Clone, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AvailabilityBitfield {
+	// the hash of the candidates the bits refer to, see `candidates_hash`.
+	candidates: [u8; 32],
+	// the number of candidates.
+	len: u32,
+	// least significant bit first.
+	bits: Vec<u8>,
+}
+
+impl AvailabilityBitfield {
+	/// A bitfield of the given `candidates`, in order, with the bits set of those for which
+	/// `held` returns true.
+	pub fn from_fn<Digest: Encode>(
+		candidates: &[Digest],
+		mut held: impl FnMut(usize) -> bool,
+	) -> Self {
+		let len = candidates.len();
+		let mut bitfield = AvailabilityBitfield {
+			candidates: candidates_hash(candidates),
+			len: len as u32,
+			bits: vec![0; len.div_ceil(8)],
+		};
+		for index in (0..len).filter(|index| held(*index)) {
+			bitfield.bits[index / 8] |= 1 << (index % 8);
+		}
+		bitfield
+	}
+
+	/// The hash of the candidates the bits refer to, see [`candidates_hash`].
+	pub fn candidates(&self) -> &[u8; 32] {
+		&self.candidates
+	}
+
+	/// The number of candidates the bits refer to.
+	pub fn len(&self) -> usize {
+		self.len as usize
+	}
+
+	/// Whether the bitfield refers to no candidates.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Whether the bit of the candidate with the given `index` is set.
+	pub fn get(&self, index: usize) -> bool {
+		index < self.len() &&
+			self.bits.get(index / 8).map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+	}
+
+	/// The indices of the set bits, in order.
+	pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+		(0..self.len()).filter(|index| self.get(*index))
+	}
+
+	/// The payload to sign for this bitfield.
+	///
+	/// The payload is the magic `AVBF`, the hash of the candidates, their number, the bits and
+	/// `signing_context`, SCALE-encoded.
+	pub fn signing_payload<S: Encode>(&self, signing_context: &S) -> Vec<u8> {
+		(BITFIELD_SIGNING_MAGIC, &self.candidates, self.len, &self.bits, signing_context).encode()
+	}
+}
+
+/// A signed [`AvailabilityBitfield`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedAvailabilityBitfield<AuthorityId, Signature> {
+	/// The bitfield.
+	pub bitfield: AvailabilityBitfield,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a signed bitfield corresponding to context type.
+pub type SignedAvailabilityBitfieldFor<Ctx> =
+	SignedAvailabilityBitfield<<Ctx as Context>::AuthorityId, <Ctx as Context>::Signature>;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bits_are_packed_in_order() {
+		let candidates: Vec<_> = (0..10u64).collect();
+		let bitfield = AvailabilityBitfield::from_fn(&candidates, |index| index % 3 == 0);
+		assert_eq!(bitfield.encode()[32..], [10, 0, 0, 0, 8, 0b0100_1001, 0b10]);
+		assert_eq!(bitfield.ones().collect::<Vec<_>>(), vec![0, 3, 6, 9]);
+		assert!(!bitfield.get(10));
+		assert!(!bitfield.get(100));
+	}
+
+	#[test]
+	fn bitfields_commit_to_the_order_of_candidates() {
+		let candidates = [10u64, 11];
+		let reordered = [11u64, 10];
+		let bitfield = AvailabilityBitfield::from_fn(&candidates, |index| index == 0);
+		assert_eq!(bitfield.candidates(), &candidates_hash(&candidates));
+		assert_ne!(bitfield.candidates(), &candidates_hash(&reordered));
+		assert_ne!(
+			bitfield.signing_payload(&0u64),
+			AvailabilityBitfield::from_fn(&reordered, |index| index == 0).signing_payload(&0u64)
+		);
+	}
+}
@@ -0,0 +1,71 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fetching chunks the local guarantor is missing.
+//!
+//! A guarantor attests to its chunk of a candidate only once it holds it. When the local
+//! guarantor doesn't hold its chunk of a proposed candidate, the [`AvailabilityTable`] asks a
+//! [`ChunkFetcher`] for it, see [`AvailabilityTable::fetch_missing_chunk`]. The fetcher gets it
+//! from the proposer or from other guarantors, e.g. over the network, after which the chunk can
+//! be attested.
+//!
+//! [`AvailabilityTable`]: super::AvailabilityTable
+//! [`AvailabilityTable::fetch_missing_chunk`]: super::AvailabilityTable::fetch_missing_chunk
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::generic::Context;
+
+use super::ChunkIndex;
+
+/// A request for a chunk of a candidate.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct ChunkRequest<Digest, GroupId, AuthorityId> {
+	/// The digest of the candidate.
+	pub candidate: Digest,
+	/// The group the candidate is in.
+	pub group_id: GroupId,
+	/// The chunk requested.
+	pub chunk: ChunkIndex,
+	/// The authorities which are expected to hold the data of the candidate, the proposer
+	/// first, followed by the guarantors which attested chunks of it.
+	pub sources: Vec<AuthorityId>,
+}
+
+/// Type alias for a chunk request corresponding to context type.
+pub type ChunkRequestFor<Ctx> = ChunkRequest<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::AuthorityId,
+>;
+
+/// Fetches the chunks the local guarantor is missing, see
+/// [`AvailabilityTable::set_chunk_fetcher`](super::AvailabilityTable::set_chunk_fetcher).
+///
+/// This is implemented for closures taking the request.
+pub trait ChunkFetcher<Ctx: Context>: Send + Sync {
+	/// Start fetching the chunk described by `request`, without blocking.
+	fn fetch_chunk(&self, request: ChunkRequestFor<Ctx>);
+}
+
+impl<Ctx: Context, F> ChunkFetcher<Ctx> for F
+where
+	F: Fn(ChunkRequestFor<Ctx>) + Send + Sync,
+{
+	fn fetch_chunk(&self, request: ChunkRequestFor<Ctx>) {
+		self(request)
+	}
+}
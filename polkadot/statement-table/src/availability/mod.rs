@@ -0,0 +1,1552 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Availability of the data of candidates, tracked by erasure-coded chunk.
+//!
+//! Before a candidate can be included, its data must be retrievable by everybody, not only by
+//! the group which backed it. The data is erasure-coded into chunks, and each availability
+//! guarantor of the group is assigned one of them to hold. A guarantor holding its chunk
+//! attests to it with a [`ChunkAttestation`]. The data can be reconstructed from any `k` of the
+//! `n` chunks, where the context supplies `k` with the [`ErasureParams`] of the group, so a
+//! candidate is available once `k` distinct chunks are attested by distinct guarantors.
+//!
+//! An [`AvailabilityTable`] collects the attestations of a round. Like the
+//! [`Table`](crate::Table), it expects signatures to be checked already, and attestations of
+//! chunks which aren't assigned to their sender are reported as [`AvailabilityMisbehavior`].
+//! Attestations don't name the group of their candidate, which is the group it was proposed in,
+//! see [`AvailabilityTable::note_proposed`], or else its group in the statement table, so
+//! attestations of candidates which are neither proposed nor in the statement table are ignored.
+//!
+//! Guarantors can also attest to their chunks of all candidates of a round at once with an
+//! [`AvailabilityBitfield`], which the table expands into a vote on each candidate.
+//!
+//! A guarantor which loses its chunk, e.g. to a disk failure, revokes its attestation with a
+//! signed [`ChunkRevocation`], and the chunk no longer counts towards availability. Attestations
+//! carry a sequence number which revocations name, so a revocation only strikes the attestation
+//! it was made for, and a guarantor attesting the chunk again afterwards does so with a higher
+//! sequence number. The attestation and its revocation both stay recorded, see
+//! [`AvailabilityTable::revocations`], so that guarantors repeatedly claiming and revoking chunks
+//! can be audited.
+//!
+//! In the sampling mode of a [`SamplingConfig`], guarantors instead attest to a few chunks
+//! sampled with the randomness of the round, and candidates are available with a statistical
+//! guarantee, see [`sampling`].
+//!
+//! A guarantor missing its chunk of a proposed candidate fetches it through a [`ChunkFetcher`]
+//! before attesting it.
+//!
+//! The data of candidates and the chunks held are kept in an [`AvailabilityStore`]. Validators
+//! without the data of an available candidate reconstruct it from fetched chunks, see
+//! [`AvailabilityTable::reconstruct`].
+//!
+//! Candidates which are proposed must become available within the deadline of an
+//! [`AvailabilityConfig`], or they are considered unavailable for the rest of the round, so
+//! that proposals stop waiting on them. Like the [`Machine`](crate::agreement::Machine) of the
+//! agreement, the table doesn't read a clock: the time is passed in as the time since an epoch
+//! of the caller's choosing.
+
+use std::{
+	collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
+	hash::BuildHasher,
+	sync::Arc,
+	time::Duration,
+};
+
+use bytes::Bytes;
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	agreement::Round,
+	events::{TableEvent, TableEventsFor},
+	generic::{CandidateStatusFor, Context, Table},
+};
+
+pub mod bitfield;
+pub mod fetch;
+pub mod reconstruct;
+pub mod sampling;
+pub mod store;
+
+pub use bitfield::{
+	candidates_hash, AvailabilityBitfield, SignedAvailabilityBitfield,
+	SignedAvailabilityBitfieldFor,
+};
+pub use fetch::{ChunkFetcher, ChunkRequest, ChunkRequestFor};
+pub use reconstruct::{ErasureCodec, ReconstructionError};
+pub use sampling::{
+	SamplingAttestation, SamplingConfig, SignedSamplingAttestation, SignedSamplingAttestationFor,
+};
+pub use store::{AvailabilityStore, MemoryAvailabilityStore};
+
+// distinguishes the signing payloads of attestations from those of statements.
+const ATTESTATION_SIGNING_MAGIC: [u8; 4] = *b"AVBL";
+const REVOCATION_SIGNING_MAGIC: [u8; 4] = *b"AVRV";
+
+// the number of distinct misbehaviors noted of each authority, as one of them proves it.
+const MAX_MISBEHAVIOR_PER_AUTHORITY: usize = 16;
+
+// the number of revocations recorded of each candidate, the oldest of which are dropped.
+const MAX_REVOCATIONS_PER_CANDIDATE: usize = 64;
+
+/// The index of a chunk of the erasure-coded data of a candidate.
+pub type ChunkIndex = u32;
+
+/// A [`Context`] which knows the guarantors of the availability of candidates.
+///
+/// An authority can be a guarantor of several groups at once, holding a chunk of the candidates
+/// of each.
+pub trait AvailabilityContext: Context {
+	/// The chunk of candidates of `group` which `authority` holds, if it is a guarantor of
+	/// their availability.
+	fn assigned_chunk(
+		&self,
+		authority: &Self::AuthorityId,
+		group: &Self::GroupId,
+	) -> Option<ChunkIndex>;
+
+	/// The groups `authority` is a guarantor of, along with the chunk it holds of the
+	/// candidates of each.
+	fn assignments(&self, authority: &Self::AuthorityId) -> Vec<(Self::GroupId, ChunkIndex)>;
+
+	/// The parameters of the erasure code the data of candidates of `group` is encoded with,
+	/// if known.
+	fn erasure_params(&self, group: &Self::GroupId) -> Option<ErasureParams>;
+}
+
+/// The parameters of an erasure code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErasureParams {
+	/// The number of chunks the data is encoded into, one for each guarantor.
+	pub total_chunks: u32,
+	/// The number of chunks the data is split into before encoding, `k`, which is the number of
+	/// distinct chunks it can be reconstructed from.
+	pub data_chunks: u32,
+}
+
+impl ErasureParams {
+	/// The number of distinct chunks the data can be reconstructed from.
+	///
+	/// This is the number of data chunks, but at least one, so that no data is available
+	/// without any chunk.
+	pub fn recovery_threshold(&self) -> u32 {
+		self.data_chunks.max(1)
+	}
+}
+
+/// How far a candidate is from being available.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AvailabilityStatus<Group> {
+	/// The group that the candidate is in.
+	pub group_id: Group,
+	/// How many distinct chunks are attested by distinct guarantors.
+	///
+	/// In sampling mode, this is the number of distinct chunks sampled successfully instead.
+	pub attested_chunks: usize,
+	/// How many chunks the data of the candidate is encoded into.
+	///
+	/// This is `0` if the erasure code of the group is unknown.
+	pub total_chunks: usize,
+	/// How many distinct chunks are needed to reconstruct the data of the candidate.
+	///
+	/// In sampling mode, this is the number of distinct chunks to sample instead, see
+	/// [`SamplingConfig::required_samples`]. This is `usize::MAX` if the erasure code of the
+	/// group is unknown.
+	pub recovery_threshold: usize,
+}
+
+impl<Group> AvailabilityStatus<Group> {
+	/// Whether the data of the candidate can be reconstructed from the attested chunks.
+	pub fn is_available(&self) -> bool {
+		self.attested_chunks >= self.recovery_threshold
+	}
+
+	/// How many more chunks need to be attested for the candidate to be available.
+	pub fn missing_chunks(&self) -> usize {
+		self.recovery_threshold.saturating_sub(self.attested_chunks)
+	}
+}
+
+/// An attestation that the sender holds a chunk of the data of a candidate.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkAttestation<Digest> {
+	/// The digest of the candidate.
+	pub candidate: Digest,
+	/// The chunk held.
+	pub chunk: ChunkIndex,
+	/// The number of times the sender attested the chunk before, each of which it revoked.
+	pub sequence: u32,
+}
+
+impl<Digest: Encode> ChunkAttestation<Digest> {
+	/// The payload to sign for this attestation.
+	///
+	/// The payload is the magic `AVBL`, the digest, the chunk, the sequence number and
+	/// `signing_context`, SCALE-encoded, so that it can't be mistaken for the payload of a
+	/// statement.
+	pub fn signing_payload<S: Encode>(&self, signing_context: &S) -> Vec<u8> {
+		(ATTESTATION_SIGNING_MAGIC, &self.candidate, self.chunk, self.sequence, signing_context)
+			.encode()
+	}
+}
+
+/// A signed [`ChunkAttestation`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedChunkAttestation<Digest, AuthorityId, Signature> {
+	/// The attestation.
+	pub attestation: ChunkAttestation<Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a signed attestation corresponding to context type.
+pub type SignedChunkAttestationFor<Ctx> = SignedChunkAttestation<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// A revocation of an earlier [`ChunkAttestation`] by its sender, which no longer holds the
+/// chunk.
+///
+/// A chunk attested by a bitfield is revoked as an attestation with sequence number `0`.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkRevocation<Digest> {
+	/// The attestation revoked.
+	pub revoked: ChunkAttestation<Digest>,
+}
+
+impl<Digest: Encode> ChunkRevocation<Digest> {
+	/// The payload to sign for this revocation.
+	///
+	/// The payload is the magic `AVRV`, followed by the digest, the chunk, the sequence number
+	/// and `signing_context` as for an attestation.
+	pub fn signing_payload<S: Encode>(&self, signing_context: &S) -> Vec<u8> {
+		let ChunkAttestation { candidate, chunk, sequence } = &self.revoked;
+		(REVOCATION_SIGNING_MAGIC, candidate, chunk, sequence, signing_context).encode()
+	}
+}
+
+/// A signed [`ChunkRevocation`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedChunkRevocation<Digest, AuthorityId, Signature> {
+	/// The revocation.
+	pub revocation: ChunkRevocation<Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a signed revocation corresponding to context type.
+pub type SignedChunkRevocationFor<Ctx> = SignedChunkRevocation<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// What a chunk was attested with.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ChunkEvidence<Digest, AuthorityId, Signature> {
+	/// An attestation of the chunk alone.
+	Attestation(SignedChunkAttestation<Digest, AuthorityId, Signature>),
+	/// A bitfield, shared by all chunks it attests.
+	Bitfield(Arc<SignedAvailabilityBitfield<AuthorityId, Signature>>),
+}
+
+/// Type alias for the evidence of a chunk corresponding to context type.
+pub type ChunkEvidenceFor<Ctx> = ChunkEvidence<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+impl<Digest, AuthorityId, Signature> ChunkEvidence<Digest, AuthorityId, Signature> {
+	/// The guarantor which attested the chunk.
+	pub fn sender(&self) -> &AuthorityId {
+		match self {
+			ChunkEvidence::Attestation(attestation) => &attestation.sender,
+			ChunkEvidence::Bitfield(bitfield) => &bitfield.sender,
+		}
+	}
+
+	/// The sequence number of the attestation of the chunk, `0` for bitfields.
+	pub fn sequence(&self) -> u32 {
+		match self {
+			ChunkEvidence::Attestation(attestation) => attestation.attestation.sequence,
+			ChunkEvidence::Bitfield(_) => 0,
+		}
+	}
+}
+
+/// Misbehavior in attesting availability.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AvailabilityMisbehavior<Digest, AuthorityId, Signature> {
+	/// Attested a chunk which isn't assigned to the sender, e.g. by a validator which isn't a
+	/// guarantor of the group of the candidate.
+	#[codec(index = 0)]
+	UnassignedChunk(SignedChunkAttestation<Digest, AuthorityId, Signature>),
+	/// Set bits of candidates of groups the sender isn't a guarantor of.
+	#[codec(index = 1)]
+	UnassignedBitfield(SignedAvailabilityBitfield<AuthorityId, Signature>),
+	/// Sampled other chunks than the sender had to sample with the randomness of the round, or
+	/// sampled without being a guarantor of the group of the candidate.
+	#[codec(index = 2)]
+	InvalidSample(SignedSamplingAttestation<Digest, AuthorityId, Signature>),
+}
+
+/// Type alias for availability misbehavior corresponding to context type.
+pub type AvailabilityMisbehaviorFor<Ctx> = AvailabilityMisbehavior<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// A summary of import of an attestation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AvailabilitySummary<Digest, Group> {
+	/// The digest of the candidate referenced.
+	pub candidate: Digest,
+	/// The group that the candidate is in.
+	pub group_id: Group,
+	/// How many distinct chunks are currently attested, or in sampling mode, how many distinct
+	/// chunks were sampled successfully.
+	pub attested_chunks: usize,
+}
+
+/// The duties of a guarantor in one of its groups, see [`AvailabilityTable::duties`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupDuties<Digest> {
+	/// The chunk the guarantor holds of the candidates of the group.
+	pub chunk: ChunkIndex,
+	/// The candidates whose chunk the guarantor attested.
+	pub attested: Vec<Digest>,
+	/// The proposed candidates whose chunk the guarantor has yet to attest, in order of their
+	/// deadline, which aren't unavailable already.
+	pub pending: Vec<Digest>,
+}
+
+/// Configuration of an [`AvailabilityTable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvailabilityConfig {
+	/// How long after being proposed candidates have to become available.
+	pub deadline: Duration,
+	/// Whether candidates are available by sampling rather than by attested chunks, and with
+	/// which confidence.
+	pub sampling: Option<SamplingConfig>,
+}
+
+impl Default for AvailabilityConfig {
+	fn default() -> Self {
+		AvailabilityConfig { deadline: Duration::from_secs(12), sampling: None }
+	}
+}
+
+// the attestations of the chunks of a candidate.
+struct CandidateChunks<Ctx: Context> {
+	group_id: Ctx::GroupId,
+	chunks: BTreeMap<ChunkIndex, ChunkEvidenceFor<Ctx>>,
+	// the revoked attestations, along with their revocation, in order of revocation.
+	revoked: Vec<(ChunkEvidenceFor<Ctx>, SignedChunkRevocationFor<Ctx>)>,
+	// the lowest sequence number attestations of each revoked chunk need.
+	min_sequence: BTreeMap<ChunkIndex, u32>,
+	// the samples of distinct guarantors, in sampling mode.
+	samples: Vec<SignedSamplingAttestationFor<Ctx>>,
+	// when the candidate has to be available by, once it was proposed.
+	deadline: Option<Duration>,
+	unavailable: bool,
+}
+
+impl<Ctx: Context> CandidateChunks<Ctx> {
+	fn new(group_id: Ctx::GroupId) -> Self {
+		CandidateChunks {
+			group_id,
+			chunks: BTreeMap::new(),
+			revoked: Vec::new(),
+			min_sequence: BTreeMap::new(),
+			samples: Vec::new(),
+			deadline: None,
+			unavailable: false,
+		}
+	}
+
+	// the number of distinct chunks sampled by any guarantor.
+	fn sampled_chunks(&self) -> usize {
+		let chunks: BTreeSet<_> =
+			self.samples.iter().flat_map(|sample| &sample.attestation.chunks).collect();
+		chunks.len()
+	}
+}
+
+/// The chunks of candidates attested in a round.
+pub struct AvailabilityTable<Ctx: Context> {
+	candidates: HashMap<Ctx::Digest, CandidateChunks<Ctx>>,
+	detected_misbehavior: HashMap<Ctx::AuthorityId, Vec<AvailabilityMisbehaviorFor<Ctx>>>,
+	config: AvailabilityConfig,
+	events: Option<TableEventsFor<Ctx>>,
+	chunk_fetcher: Option<Arc<dyn ChunkFetcher<Ctx>>>,
+	// the candidates the bits of bitfields refer to, in order, and their hash once set.
+	bitfield_candidates: Vec<Ctx::Digest>,
+	bitfield_candidates_hash: Option<[u8; 32]>,
+	// the randomness guarantors choose the chunks they sample with, in sampling mode.
+	sampling_randomness: [u8; 32],
+}
+
+impl<Ctx: Context> Default for AvailabilityTable<Ctx> {
+	fn default() -> Self {
+		AvailabilityTable::with_config(AvailabilityConfig::default())
+	}
+}
+
+impl<Ctx: Context> AvailabilityTable<Ctx> {
+	/// Create an empty table from an `AvailabilityConfig`.
+	pub fn with_config(config: AvailabilityConfig) -> Self {
+		AvailabilityTable {
+			candidates: HashMap::new(),
+			detected_misbehavior: HashMap::new(),
+			config,
+			events: None,
+			chunk_fetcher: None,
+			bitfield_candidates: Vec::new(),
+			bitfield_candidates_hash: None,
+			sampling_randomness: [0; 32],
+		}
+	}
+}
+
+impl<Ctx: AvailabilityContext> AvailabilityTable<Ctx> {
+	/// Create an empty table with the default configuration.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Publish the candidates which become unavailable to `events`.
+	pub fn set_events(&mut self, events: TableEventsFor<Ctx>) {
+		self.events = Some(events);
+	}
+
+	/// Fetch the chunks the local guarantor is missing with `fetcher`.
+	pub fn set_chunk_fetcher(&mut self, fetcher: Arc<dyn ChunkFetcher<Ctx>>) {
+		self.chunk_fetcher = Some(fetcher);
+	}
+
+	/// Fetch the chunk of the candidate with the given `digest` of `group_id` which is assigned
+	/// to the local guarantor `local`, unless it attested it already, from the candidate's
+	/// `proposer` or its other guarantors.
+	///
+	/// Returns whether a fetch was started, which needs a [`ChunkFetcher`] to be set.
+	pub fn fetch_missing_chunk(
+		&self,
+		context: &Ctx,
+		local: &Ctx::AuthorityId,
+		digest: &Ctx::Digest,
+		group_id: &Ctx::GroupId,
+		proposer: Ctx::AuthorityId,
+	) -> bool {
+		let (Some(fetcher), Some(chunk)) =
+			(&self.chunk_fetcher, context.assigned_chunk(local, group_id))
+		else {
+			return false
+		};
+
+		let attested = self.candidates.get(digest).map(|data| &data.chunks);
+		let held = |chunks: &BTreeMap<_, ChunkEvidenceFor<Ctx>>| {
+			chunks.get(&chunk).map_or(false, |evidence| evidence.sender() == local)
+		};
+		if attested.map_or(false, held) {
+			return false
+		}
+
+		let mut sources = vec![proposer];
+		for sender in attested.into_iter().flat_map(|chunks| chunks.values().map(|e| e.sender())) {
+			if sender != local && !sources.contains(sender) {
+				sources.push(sender.clone());
+			}
+		}
+
+		let request =
+			ChunkRequest { candidate: digest.clone(), group_id: group_id.clone(), chunk, sources };
+		fetcher.fetch_chunk(request);
+		true
+	}
+
+	/// Import a signed attestation of a chunk of a proposed candidate or a candidate of the
+	/// statement `table`.
+	///
+	/// Returns a summary of the candidate if the attestation is of a chunk not attested
+	/// before. Attestations of candidates which are neither are ignored, and
+	/// attestations of chunks which aren't assigned to their sender in the group of the
+	/// candidate are noted as misbehavior.
+	pub fn import_attestation<S: BuildHasher + Clone>(
+		&mut self,
+		context: &Ctx,
+		table: &Table<Ctx, S>,
+		attestation: SignedChunkAttestationFor<Ctx>,
+	) -> Option<AvailabilitySummary<Ctx::Digest, Ctx::GroupId>> {
+		let group_id = self.candidate_group(table, &attestation.attestation.candidate)?;
+		if context.assigned_chunk(&attestation.sender, &group_id) !=
+			Some(attestation.attestation.chunk)
+		{
+			let sender = attestation.sender.clone();
+			self.note_misbehavior(sender, AvailabilityMisbehavior::UnassignedChunk(attestation));
+			return None
+		}
+
+		let ChunkAttestation { candidate, chunk, .. } = attestation.attestation.clone();
+		self.note_chunk(candidate, group_id, chunk, ChunkEvidence::Attestation(attestation))
+	}
+
+	/// Set the candidates the bits of bitfields refer to, in order, e.g. at the start of a
+	/// round.
+	pub fn set_bitfield_candidates(&mut self, candidates: Vec<Ctx::Digest>)
+	where
+		Ctx::Digest: Encode,
+	{
+		self.bitfield_candidates_hash = Some(candidates_hash(&candidates));
+		self.bitfield_candidates = candidates;
+	}
+
+	/// Import a signed bitfield, as an attestation of the sender's chunk of each candidate
+	/// whose bit is set.
+	///
+	/// Returns a summary of each candidate of which a chunk not attested before is attested.
+	/// Bitfields of candidates other than those set with
+	/// [`AvailabilityTable::set_bitfield_candidates`], in that order, are ignored, as are the bits
+	/// of candidates of unknown group, as for [`AvailabilityTable::import_attestation`].
+	/// Bitfields setting bits of candidates the sender isn't a guarantor of are noted as
+	/// misbehavior.
+	pub fn import_bitfield<S: BuildHasher + Clone>(
+		&mut self,
+		context: &Ctx,
+		table: &Table<Ctx, S>,
+		bitfield: SignedAvailabilityBitfieldFor<Ctx>,
+	) -> Vec<AvailabilitySummary<Ctx::Digest, Ctx::GroupId>> {
+		if bitfield.bitfield.len() != self.bitfield_candidates.len() ||
+			self.bitfield_candidates_hash.as_ref() != Some(bitfield.bitfield.candidates())
+		{
+			return Vec::new()
+		}
+
+		let bitfield = Arc::new(bitfield);
+		let mut unassigned = false;
+		let mut summaries = Vec::new();
+		for index in bitfield.bitfield.ones() {
+			let digest = self.bitfield_candidates[index].clone();
+			let Some(group_id) = self.candidate_group(table, &digest) else { continue };
+			let Some(chunk) = context.assigned_chunk(&bitfield.sender, &group_id) else {
+				unassigned = true;
+				continue
+			};
+
+			let evidence = ChunkEvidence::Bitfield(bitfield.clone());
+			summaries.extend(self.note_chunk(digest, group_id, chunk, evidence));
+		}
+
+		if unassigned {
+			let misbehavior = AvailabilityMisbehavior::UnassignedBitfield((*bitfield).clone());
+			self.note_misbehavior(bitfield.sender.clone(), misbehavior);
+		}
+
+		summaries
+	}
+
+	// note misbehavior of `sender`, unless it was noted before or enough of it was.
+	fn note_misbehavior(
+		&mut self,
+		sender: Ctx::AuthorityId,
+		misbehavior: AvailabilityMisbehaviorFor<Ctx>,
+	) {
+		let noted = self.detected_misbehavior.entry(sender).or_default();
+		if noted.len() < MAX_MISBEHAVIOR_PER_AUTHORITY && !noted.contains(&misbehavior) {
+			noted.push(misbehavior);
+		}
+	}
+
+	// the group of the candidate with the given `digest`: the group it was proposed in, or else
+	// its group in the statement `table`.
+	fn candidate_group<S: BuildHasher + Clone>(
+		&self,
+		table: &Table<Ctx, S>,
+		digest: &Ctx::Digest,
+	) -> Option<Ctx::GroupId> {
+		match self.candidates.get(digest) {
+			Some(data) => Some(data.group_id.clone()),
+			None => table.candidate_group(digest).cloned(),
+		}
+	}
+
+	// count an assigned chunk as attested, unless it was before or the attestation predates a
+	// revocation of the chunk. `group_id` is the group of the candidate, see `candidate_group`.
+	fn note_chunk(
+		&mut self,
+		candidate: Ctx::Digest,
+		group_id: Ctx::GroupId,
+		chunk: ChunkIndex,
+		evidence: ChunkEvidenceFor<Ctx>,
+	) -> Option<AvailabilitySummary<Ctx::Digest, Ctx::GroupId>> {
+		let data = self
+			.candidates
+			.entry(candidate.clone())
+			.or_insert_with(|| CandidateChunks::new(group_id));
+		if data.min_sequence.get(&chunk).map_or(false, |min| evidence.sequence() < *min) {
+			return None
+		}
+
+		match data.chunks.entry(chunk) {
+			Entry::Occupied(_) => return None,
+			Entry::Vacant(vacant) => {
+				vacant.insert(evidence);
+			},
+		}
+
+		Some(AvailabilitySummary {
+			candidate,
+			group_id: data.group_id.clone(),
+			attested_chunks: data.chunks.len(),
+		})
+	}
+
+	/// Set the randomness of the round which guarantors choose the chunks they sample with,
+	/// see [`SamplingConfig::guarantor_chunks`]. It must not be known to proposers before they
+	/// propose, e.g. randomness revealed once the candidates of the round are backed.
+	pub fn set_sampling_randomness(&mut self, randomness: [u8; 32]) {
+		self.sampling_randomness = randomness;
+	}
+
+	/// Import a signed sampling attestation of a proposed candidate or a candidate of the
+	/// statement `table`.
+	///
+	/// Returns a summary of the candidate if the samples of the sender weren't imported before.
+	/// Samples are ignored unless the table is in sampling mode, as are samples of candidates
+	/// neither proposed nor in the statement table. Samples of other chunks than the sender had to
+	/// sample with the randomness set with [`AvailabilityTable::set_sampling_randomness`] are
+	/// noted as misbehavior.
+	pub fn import_sample<S: BuildHasher + Clone>(
+		&mut self,
+		context: &Ctx,
+		table: &Table<Ctx, S>,
+		sample: SignedSamplingAttestationFor<Ctx>,
+	) -> Option<AvailabilitySummary<Ctx::Digest, Ctx::GroupId>>
+	where
+		Ctx::Digest: Encode,
+		Ctx::AuthorityId: Encode,
+	{
+		let sampling = self.config.sampling?;
+		let group_id = self.candidate_group(table, &sample.attestation.candidate)?;
+		let is_valid = context.assigned_chunk(&sample.sender, &group_id).is_some() &&
+			context.erasure_params(&group_id).map_or(false, |params| {
+				let chunks = sampling.guarantor_chunks(
+					params,
+					&self.sampling_randomness,
+					&sample.attestation.candidate,
+					&sample.sender,
+				);
+				chunks == sample.attestation.chunks
+			});
+		if !is_valid {
+			self.note_misbehavior(
+				sample.sender.clone(),
+				AvailabilityMisbehavior::InvalidSample(sample),
+			);
+			return None
+		}
+
+		let candidate = sample.attestation.candidate.clone();
+		let data = self
+			.candidates
+			.entry(candidate.clone())
+			.or_insert_with(|| CandidateChunks::new(group_id));
+		if data.samples.iter().any(|imported| imported.sender == sample.sender) {
+			return None
+		}
+
+		data.samples.push(sample);
+		Some(AvailabilitySummary {
+			candidate,
+			group_id: data.group_id.clone(),
+			attested_chunks: data.sampled_chunks(),
+		})
+	}
+
+	/// Import a signed revocation of an attestation imported before, either of the chunk alone
+	/// or as part of a bitfield.
+	///
+	/// Returns a summary of the candidate if the revoked attestation was counted, which it no
+	/// longer is. Revocations of any other attestation, e.g. replayed revocations of an earlier
+	/// attestation of the same chunk, are ignored. The chunk can be attested again afterwards,
+	/// e.g. once it was fetched again, with a higher sequence number than the revoked
+	/// attestation. Only the latest revocations of each candidate are recorded.
+	pub fn import_revocation(
+		&mut self,
+		revocation: SignedChunkRevocationFor<Ctx>,
+	) -> Option<AvailabilitySummary<Ctx::Digest, Ctx::GroupId>> {
+		let ChunkAttestation { candidate, chunk, sequence } = &revocation.revocation.revoked;
+		let data = self.candidates.get_mut(candidate)?;
+		let Entry::Occupied(attested) = data.chunks.entry(*chunk) else { return None };
+		if attested.get().sender() != &revocation.sender || attested.get().sequence() != *sequence {
+			return None
+		}
+
+		let evidence = attested.remove();
+		data.min_sequence.insert(*chunk, sequence.saturating_add(1));
+		let summary = AvailabilitySummary {
+			candidate: candidate.clone(),
+			group_id: data.group_id.clone(),
+			attested_chunks: data.chunks.len(),
+		};
+		if data.revoked.len() == MAX_REVOCATIONS_PER_CANDIDATE {
+			data.revoked.remove(0);
+		}
+		data.revoked.push((evidence, revocation));
+		Some(summary)
+	}
+
+	/// The revoked attestations of the candidate with the given `digest`, along with their
+	/// revocation, in order of revocation.
+	pub fn revocations(
+		&self,
+		digest: &Ctx::Digest,
+	) -> &[(ChunkEvidenceFor<Ctx>, SignedChunkRevocationFor<Ctx>)] {
+		self.candidates.get(digest).map_or(&[], |data| &data.revoked[..])
+	}
+
+	/// The chunks of the candidate with the given `digest` which are attested, in order, along
+	/// with the guarantors attesting them.
+	pub fn attested_chunks(
+		&self,
+		digest: &Ctx::Digest,
+	) -> impl Iterator<Item = (ChunkIndex, &Ctx::AuthorityId)> + '_ {
+		self.candidates.get(digest).into_iter().flat_map(|data| {
+			data.chunks.iter().map(|(chunk, evidence)| (*chunk, evidence.sender()))
+		})
+	}
+
+	/// Get the availability status of the candidate with the given `digest`, if any of its
+	/// chunks are attested.
+	pub fn candidate_status(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+	) -> Option<AvailabilityStatus<Ctx::GroupId>> {
+		let data = self.candidates.get(digest)?;
+		let params = context.erasure_params(&data.group_id);
+		if self.config.sampling.is_some() {
+			return Some(AvailabilityStatus {
+				group_id: data.group_id.clone(),
+				attested_chunks: data.sampled_chunks(),
+				total_chunks: params.map_or(0, |params| params.total_chunks as usize),
+				recovery_threshold: params
+					.map_or(usize::MAX, |params| self.required_chunks(params)),
+			})
+		}
+
+		// only chunks of the code count, each guarantor once.
+		let total_chunks = params.map_or(0, |params| params.total_chunks);
+		let mut guarantors = HashSet::new();
+		let attested_chunks = data
+			.chunks
+			.iter()
+			.filter(|(chunk, evidence)| {
+				**chunk < total_chunks && guarantors.insert(evidence.sender())
+			})
+			.count();
+
+		Some(AvailabilityStatus {
+			group_id: data.group_id.clone(),
+			attested_chunks,
+			total_chunks: total_chunks as usize,
+			recovery_threshold: params.map_or(usize::MAX, |params| self.required_chunks(params)),
+		})
+	}
+
+	/// Fill in the availability of the candidate with the given `digest` in its `status`, as got
+	/// from [`Table::candidate_status`], so that it tells how far the candidate is from inclusion
+	/// in both respects.
+	///
+	/// The availability is left unknown if the erasure code of the group of the candidate is.
+	pub fn complete_status(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+		status: &mut CandidateStatusFor<Ctx>,
+	) {
+		let Some(params) = context.erasure_params(&status.group_id) else { return };
+		let (available, threshold) = match self.candidate_status(digest, context) {
+			Some(availability) => (availability.attested_chunks, availability.recovery_threshold),
+			None => (0, self.required_chunks(params)),
+		};
+		status.available_chunks = Some(available);
+		status.availability_threshold = Some(threshold);
+	}
+
+	// the number of distinct chunks to attest, or to sample in sampling mode, for a candidate
+	// encoded with `params` to be available.
+	fn required_chunks(&self, params: ErasureParams) -> usize {
+		match &self.config.sampling {
+			Some(sampling) => sampling.required_samples(params) as usize,
+			None => params.recovery_threshold() as usize,
+		}
+	}
+
+	/// Whether enough distinct chunks of the candidate with the given `digest` are attested to
+	/// reconstruct its data.
+	pub fn is_available(&self, digest: &Ctx::Digest, context: &Ctx) -> bool {
+		self.candidate_status(digest, context)
+			.map_or(false, |status| status.is_available())
+	}
+
+	/// Get the data of the available candidate with the given `digest`, reconstructing it from
+	/// the chunks in `store` with `codec` unless the data is stored already.
+	///
+	/// Reconstructed data is checked against the digest and stored in `round`.
+	pub fn reconstruct(
+		&self,
+		context: &Ctx,
+		codec: &dyn ErasureCodec<Ctx>,
+		store: &dyn AvailabilityStore<Ctx::Digest>,
+		round: Round,
+		digest: &Ctx::Digest,
+	) -> Result<Bytes, ReconstructionError> {
+		if let Some(data) = store.data(digest)? {
+			return Ok(data)
+		}
+
+		let params = self
+			.candidates
+			.get(digest)
+			.filter(|_| self.is_available(digest, context))
+			.and_then(|data| context.erasure_params(&data.group_id))
+			.ok_or(ReconstructionError::NotAvailable)?;
+
+		let chunks: Vec<_> = store
+			.chunks(digest)?
+			.into_iter()
+			.filter(|(chunk, _)| *chunk < params.total_chunks)
+			.collect();
+		let need = params.recovery_threshold() as usize;
+		if chunks.len() < need {
+			return Err(ReconstructionError::MissingChunks { have: chunks.len(), need })
+		}
+
+		let data = codec.reconstruct(params, &chunks).ok_or(ReconstructionError::Undecodable)?;
+		if !codec.verify(digest, &data) {
+			return Err(ReconstructionError::DigestMismatch)
+		}
+
+		store.put_data(round, digest.clone(), data.clone())?;
+		Ok(data)
+	}
+
+	/// Note that the candidate with the given `digest` of `group_id` was proposed at `now`,
+	/// starting its deadline unless it was proposed before.
+	///
+	/// Chunks attested as chunks of another group no longer count for the candidate.
+	pub fn note_proposed(&mut self, digest: Ctx::Digest, group_id: Ctx::GroupId, now: Duration) {
+		let data = self
+			.candidates
+			.entry(digest)
+			.or_insert_with(|| CandidateChunks::new(group_id.clone()));
+		if data.group_id != group_id {
+			*data = CandidateChunks::new(group_id);
+		}
+		data.deadline.get_or_insert(now.saturating_add(self.config.deadline));
+	}
+
+	/// Note that the time is `now`, returning the candidates which became unavailable, as they
+	/// missed their deadline.
+	pub fn advance_time(&mut self, context: &Ctx, now: Duration) -> Vec<Ctx::Digest> {
+		let missed: Vec<_> = self
+			.candidates
+			.iter()
+			.filter(|(_, data)| {
+				!data.unavailable && data.deadline.map_or(false, |deadline| deadline <= now)
+			})
+			.map(|(digest, _)| digest.clone())
+			.filter(|digest| !self.is_available(digest, context))
+			.collect();
+
+		for digest in &missed {
+			let data = self.candidates.get_mut(digest).expect("collected from the candidates; qed");
+			data.unavailable = true;
+			if let Some(events) = &self.events {
+				events.publish(|| TableEvent::CandidateUnavailable {
+					digest: digest.clone(),
+					group_id: data.group_id.clone(),
+				});
+			}
+		}
+
+		missed
+	}
+
+	/// The earliest deadline of the candidates which aren't available yet, at which
+	/// [`AvailabilityTable::advance_time`] should be called.
+	pub fn next_deadline(&self, context: &Ctx) -> Option<Duration> {
+		self.candidates
+			.iter()
+			.filter(|(_, data)| !data.unavailable)
+			.filter_map(|(digest, data)| data.deadline.map(|deadline| (digest, deadline)))
+			.filter(|(digest, _)| !self.is_available(digest, context))
+			.map(|(_, deadline)| deadline)
+			.min()
+	}
+
+	/// The duties of `authority` in each group it is a guarantor of.
+	pub fn duties(
+		&self,
+		context: &Ctx,
+		authority: &Ctx::AuthorityId,
+	) -> BTreeMap<Ctx::GroupId, GroupDuties<Ctx::Digest>> {
+		let mut duties: BTreeMap<_, _> = context
+			.assignments(authority)
+			.into_iter()
+			.map(|(group_id, chunk)| {
+				(group_id, GroupDuties { chunk, attested: Vec::new(), pending: Vec::new() })
+			})
+			.collect();
+
+		let mut pending = Vec::new();
+		for (digest, data) in &self.candidates {
+			let Some(group) = duties.get_mut(&data.group_id) else { continue };
+			if data.chunks.get(&group.chunk).map_or(false, |e| e.sender() == authority) {
+				group.attested.push(digest.clone());
+			} else if let (Some(deadline), false) = (data.deadline, data.unavailable) {
+				pending.push((deadline, &data.group_id, digest));
+			}
+		}
+
+		pending.sort_by_key(|(deadline, _, _)| *deadline);
+		for (_, group_id, digest) in pending {
+			if let Some(group) = duties.get_mut(group_id) {
+				group.pending.push(digest.clone());
+			}
+		}
+
+		duties
+	}
+
+	/// Whether the candidate with the given `digest` missed its deadline, so that it shouldn't
+	/// be considered for the rest of the round.
+	pub fn is_unavailable(&self, digest: &Ctx::Digest) -> bool {
+		self.candidates.get(digest).map_or(false, |data| data.unavailable)
+	}
+
+	/// The candidates which missed their deadline, to be left out of proposals with
+	/// [`ProposalConfig::unavailable`](crate::ProposalConfig::unavailable).
+	pub fn unavailable_candidates(&self) -> impl Iterator<Item = &Ctx::Digest> + '_ {
+		self.candidates
+			.iter()
+			.filter(|(_, data)| data.unavailable)
+			.map(|(digest, _)| digest)
+	}
+
+	/// Get all witnessed misbehavior.
+	pub fn get_misbehavior(
+		&self,
+	) -> &HashMap<Ctx::AuthorityId, Vec<AvailabilityMisbehaviorFor<Ctx>>> {
+		&self.detected_misbehavior
+	}
+
+	/// Forget all candidates and misbehavior, e.g. at the start of a round.
+	pub fn clear(&mut self) {
+		self.candidates.clear();
+		self.detected_misbehavior.clear();
+		self.bitfield_candidates.clear();
+		self.bitfield_candidates_hash = None;
+		self.sampling_randomness = [0; 32];
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		events::TableEvents,
+		generic::{Config, Statement},
+		test_utils::{Digest, GroupId, TestCandidate, TestContext, SIGNING_CONTEXT},
+	};
+	use std::sync::Mutex;
+
+	// groups `0` and `1` of four guarantors each, of which two chunks are needed.
+	fn context() -> TestContext {
+		TestContext::builder().with_groups(2, 4).build()
+	}
+
+	// the digest of a candidate, which is its body for group `0`.
+	fn digest(group_id: GroupId, body: u64) -> Digest {
+		TestCandidate::new(group_id, body).digest()
+	}
+
+	// a statement table which has the candidates with the given groups and bodies.
+	fn backing(context: &TestContext, candidates: &[(GroupId, u64)]) -> Table<TestContext> {
+		let mut table = Table::new(Config { allow_multiple_seconded: true });
+		for &(group_id, body) in candidates {
+			let seconder = context.members(group_id).next().unwrap();
+			let statement = Statement::Seconded(TestCandidate::new(group_id, body));
+			table.import_statement(context, group_id, context.sign(seconder, statement));
+		}
+		table
+	}
+
+	#[test]
+	fn candidate_status_combines_validity_and_availability() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let members: Vec<_> = context.members(0).collect();
+
+		let mut status = backing.candidate_status(&10, &context, 2).unwrap();
+		assert_eq!(status.missing_chunks(), None);
+		table.complete_status(&10, &context, &mut status);
+		assert_eq!((status.available_chunks, status.availability_threshold), (Some(0), Some(2)));
+		assert_eq!(status.missing_validity_votes(), 1);
+		assert!(status.flagged_invalid_by.is_empty());
+
+		table
+			.import_attestation(&context, &backing, context.attest(members[0], 10, 0))
+			.unwrap();
+		table.complete_status(&10, &context, &mut status);
+		assert_eq!(status.missing_chunks(), Some(1));
+	}
+
+	#[test]
+	fn candidates_are_available_with_enough_distinct_chunks() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let members: Vec<_> = context.members(0).collect();
+
+		let first = context.attest(members[0], 10, 0);
+		let summary = table.import_attestation(&context, &backing, first.clone()).unwrap();
+		assert_eq!(summary.attested_chunks, 1);
+		assert!(!table.is_available(&10, &context));
+
+		// attesting the same chunk again doesn't count.
+		assert!(table.import_attestation(&context, &backing, first).is_none());
+		assert!(!table.is_available(&10, &context));
+
+		let summary = table
+			.import_attestation(&context, &backing, context.attest(members[2], 10, 0))
+			.unwrap();
+		assert_eq!(summary.attested_chunks, 2);
+		assert!(table.is_available(&10, &context));
+		assert_eq!(
+			table.candidate_status(&10, &context),
+			Some(AvailabilityStatus {
+				group_id: 0,
+				attested_chunks: 2,
+				total_chunks: 4,
+				recovery_threshold: 2,
+			})
+		);
+		assert_eq!(
+			table.attested_chunks(&10).collect::<Vec<_>>(),
+			vec![(0, &members[0]), (2, &members[2])]
+		);
+		assert!(table.get_misbehavior().is_empty());
+	}
+
+	#[test]
+	fn unassigned_chunks_are_misbehavior() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let outsider = context.members(1).next().unwrap();
+
+		// a guarantor of another group.
+		let attestation = context.attest(outsider, 10, 0);
+		assert!(table.import_attestation(&context, &backing, attestation.clone()).is_none());
+
+		// a guarantor of the group claiming the chunk of another.
+		let member = context.members(0).next().unwrap();
+		let mut wrong_chunk = context.attest(member, 10, 0);
+		wrong_chunk.attestation.chunk = 1;
+		assert!(table.import_attestation(&context, &backing, wrong_chunk.clone()).is_none());
+
+		// misbehavior is noted once, and attestations of unknown candidates are ignored.
+		assert!(table.import_attestation(&context, &backing, wrong_chunk.clone()).is_none());
+		assert!(table
+			.import_attestation(&context, &backing, context.attest(outsider, 11, 0))
+			.is_none());
+
+		assert!(table.attested_chunks(&10).next().is_none());
+		assert_eq!(
+			table.get_misbehavior()[&outsider],
+			vec![AvailabilityMisbehavior::UnassignedChunk(attestation)]
+		);
+		assert_eq!(
+			table.get_misbehavior()[&member],
+			vec![AvailabilityMisbehavior::UnassignedChunk(wrong_chunk)]
+		);
+	}
+
+	#[test]
+	fn candidates_missing_their_deadline_are_unavailable() {
+		let context = context();
+		let eleven = digest(1, 11);
+		let backing = backing(&context, &[(0, 10), (1, 11)]);
+		let mut table = AvailabilityTable::with_config(AvailabilityConfig {
+			deadline: Duration::from_secs(6),
+			..Default::default()
+		});
+		let events = TableEvents::new(2);
+		let mut subscriber = events.subscribe();
+		table.set_events(events);
+
+		table.note_proposed(10, 0, Duration::ZERO);
+		table.note_proposed(eleven, 1, Duration::from_secs(1));
+		let members: Vec<_> = context.members(0).collect();
+		for member in &members[..2] {
+			table.import_attestation(&context, &backing, context.attest(*member, 10, 0));
+		}
+		assert_eq!(table.next_deadline(&context), Some(Duration::from_secs(7)));
+
+		assert!(table.advance_time(&context, Duration::from_millis(6999)).is_empty());
+		assert_eq!(table.advance_time(&context, Duration::from_secs(7)), vec![eleven]);
+		assert!(table.is_unavailable(&eleven));
+		assert!(!table.is_unavailable(&10));
+		assert_eq!(table.unavailable_candidates().collect::<Vec<_>>(), vec![&eleven]);
+		assert_eq!(table.next_deadline(&context), None);
+
+		// late attestations don't make a candidate available again this round.
+		for member in context.members(1).collect::<Vec<_>>() {
+			table.import_attestation(&context, &backing, context.attest(member, eleven, 1));
+		}
+		assert!(table.is_unavailable(&eleven));
+		assert!(table.advance_time(&context, Duration::from_secs(60)).is_empty());
+
+		assert_eq!(
+			subscriber.try_next().unwrap(),
+			Some(TableEvent::CandidateUnavailable { digest: eleven, group_id: 1 })
+		);
+		assert!(subscriber.try_next().is_err());
+	}
+
+	#[test]
+	fn revoked_chunks_stop_counting() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let members: Vec<_> = context.members(0).collect();
+		for member in &members[..2] {
+			table.import_attestation(&context, &backing, context.attest(*member, 10, 0));
+		}
+		assert!(table.is_available(&10, &context));
+
+		let first = context.attest(members[0], 10, 0);
+
+		// only the sender of an attestation can revoke it.
+		let mut forged = context.revoke(&first);
+		forged.sender = members[1];
+		assert!(table.import_revocation(forged).is_none());
+
+		let revocation = context.revoke(&first);
+		let summary = table.import_revocation(revocation.clone()).unwrap();
+		assert_eq!(summary.attested_chunks, 1);
+		assert!(!table.is_available(&10, &context));
+		assert!(table.import_revocation(revocation.clone()).is_none());
+
+		// the revoked attestation can't be replayed, but the chunk can be attested again.
+		assert!(table.import_attestation(&context, &backing, first.clone()).is_none());
+		let second = context.reattest(members[0], 10, 0, 1);
+		table.import_attestation(&context, &backing, second.clone()).unwrap();
+		assert!(table.is_available(&10, &context));
+
+		// replaying the earlier revocation doesn't strike the new attestation.
+		assert!(table.import_revocation(revocation.clone()).is_none());
+		assert!(table.is_available(&10, &context));
+		assert_eq!(
+			table.revocations(&10),
+			&[(ChunkEvidence::Attestation(first), revocation.clone())]
+		);
+
+		// revoking it again is recorded along with the first revocation.
+		let second_revocation = context.revoke(&second);
+		table.import_revocation(second_revocation.clone()).unwrap();
+		assert!(!table.is_available(&10, &context));
+		assert_eq!(table.revocations(&10).len(), 2);
+		assert_eq!(
+			table.revocations(&10)[1],
+			(ChunkEvidence::Attestation(second), second_revocation)
+		);
+	}
+
+	#[test]
+	fn missing_chunks_are_fetched() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let members: Vec<_> = context.members(0).collect();
+		let (local, proposer) = (members[3], members[0]);
+		assert!(!table.fetch_missing_chunk(&context, &local, &10, &0, proposer));
+
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let fetched = requests.clone();
+		let fetcher = move |request| fetched.lock().unwrap().push(request);
+		table.set_chunk_fetcher(Arc::new(fetcher));
+
+		for member in [proposer, members[1]] {
+			table.import_attestation(&context, &backing, context.attest(member, 10, 0));
+		}
+		assert!(table.fetch_missing_chunk(&context, &local, &10, &0, proposer));
+		assert_eq!(
+			requests.lock().unwrap().pop(),
+			Some(ChunkRequest {
+				candidate: 10,
+				group_id: 0,
+				chunk: 3,
+				sources: vec![proposer, members[1]],
+			})
+		);
+
+		// neither the guarantors holding their chunk nor others fetch anything.
+		table.import_attestation(&context, &backing, context.attest(local, 10, 0));
+		assert!(!table.fetch_missing_chunk(&context, &local, &10, &0, proposer));
+		let outsider = context.members(1).next().unwrap();
+		assert!(!table.fetch_missing_chunk(&context, &outsider, &10, &0, proposer));
+		assert!(requests.lock().unwrap().is_empty());
+	}
+
+	#[test]
+	fn bitfields_are_expanded_into_attestations() {
+		let context = context();
+		let eleven = digest(1, 11);
+		let backing = backing(&context, &[(0, 10), (1, 11), (0, 12)]);
+		let mut table = AvailabilityTable::new();
+		// the group of `13` is unknown, as the statement table doesn't have it.
+		let candidates = vec![10, eleven, 12, 13];
+		table.set_bitfield_candidates(candidates.clone());
+		let members: Vec<_> = context.members(0).collect();
+		let outsider = context.members(1).next().unwrap();
+		let bitfield = |sender, bits: &[usize]| {
+			let bitfield = AvailabilityBitfield::from_fn(&candidates, |i| bits.contains(&i));
+			context.sign_bitfield(sender, bitfield)
+		};
+
+		// bitfields of other candidates, or of the same ones in another order, are ignored.
+		let mut longer = candidates.clone();
+		longer.push(14);
+		let reordered = vec![12, eleven, 10, 13];
+		for other in [&longer, &reordered, &candidates[..2].to_vec()] {
+			let bitfield = AvailabilityBitfield::from_fn(other, |_| true);
+			let summaries = table.import_bitfield(
+				&context,
+				&backing,
+				context.sign_bitfield(members[0], bitfield),
+			);
+			assert!(summaries.is_empty());
+		}
+		assert!(table.candidate_status(&10, &context).is_none());
+
+		let summaries = table.import_bitfield(&context, &backing, bitfield(members[0], &[0, 2, 3]));
+		assert_eq!(summaries.iter().map(|s| s.candidate).collect::<Vec<_>>(), vec![10, 12]);
+		assert!(table.import_bitfield(&context, &backing, bitfield(members[0], &[0])).is_empty());
+
+		// bitfields and attestations count alike.
+		table.import_attestation(&context, &backing, context.attest(members[1], 12, 0));
+		table.import_bitfield(&context, &backing, bitfield(members[2], &[0]));
+		assert!(table.is_available(&10, &context));
+		assert!(table.is_available(&12, &context));
+
+		let unassigned = bitfield(outsider, &[0, 1]);
+		let summaries = table.import_bitfield(&context, &backing, unassigned.clone());
+		assert_eq!(summaries.iter().map(|s| s.candidate).collect::<Vec<_>>(), vec![eleven]);
+		assert_eq!(
+			table.get_misbehavior()[&outsider],
+			vec![AvailabilityMisbehavior::UnassignedBitfield(unassigned)]
+		);
+
+		// a chunk attested by a bitfield is revoked on its own.
+		table
+			.import_revocation(context.revoke(&context.attest(members[0], 10, 0)))
+			.unwrap();
+		assert!(!table.is_available(&10, &context));
+		assert!(table.is_available(&12, &context));
+		assert!(matches!(table.revocations(&10), [(ChunkEvidence::Bitfield(_), _)]));
+	}
+
+	#[test]
+	fn candidates_are_available_by_sampling() {
+		// 8 guarantors, of which 4 chunks reconstruct the data.
+		let context = TestContext::builder().with_groups(2, 8).with_data_chunks(0, 4).build();
+		let backing = backing(&context, &[(0, 10)]);
+		let sampling = SamplingConfig { samples_per_guarantor: 2, security_bits: 4 };
+		let mut table = AvailabilityTable::with_config(AvailabilityConfig {
+			sampling: Some(sampling),
+			..Default::default()
+		});
+		let randomness = [7; 32];
+		table.set_sampling_randomness(randomness);
+		let params = context.erasure_params(&0).unwrap();
+		let members: Vec<_> = context.members(0).collect();
+		let outsider = context.members(1).next().unwrap();
+		let chunks = |sender| sampling.guarantor_chunks(params, &randomness, &10u64, &sender);
+		let sample = |sender, chunks: Vec<ChunkIndex>| {
+			context.sign_sample(sender, SamplingAttestation { candidate: 10, chunks })
+		};
+
+		// attested chunks don't count in sampling mode.
+		table.import_attestation(&context, &backing, context.attest(members[0], 10, 0));
+		table.import_attestation(&context, &backing, context.attest(members[1], 10, 0));
+		assert!(!table.is_available(&10, &context));
+
+		let summary = table
+			.import_sample(&context, &backing, sample(members[0], chunks(members[0])))
+			.unwrap();
+		assert_eq!(summary.attested_chunks, 2);
+		assert!(table
+			.import_sample(&context, &backing, sample(members[0], chunks(members[0])))
+			.is_none());
+		assert!(!table.is_available(&10, &context));
+
+		// three distinct chunks give 4 bits of confidence, however many guarantors sample them.
+		let mut sampled: BTreeSet<_> = chunks(members[0]).into_iter().collect();
+		for member in &members[1..] {
+			let summary = table
+				.import_sample(&context, &backing, sample(*member, chunks(*member)))
+				.unwrap();
+			sampled.extend(chunks(*member));
+			assert_eq!(summary.attested_chunks, sampled.len());
+			assert_eq!(table.is_available(&10, &context), sampled.len() >= 3);
+		}
+		assert_eq!(
+			table.candidate_status(&10, &context),
+			Some(AvailabilityStatus {
+				group_id: 0,
+				attested_chunks: sampled.len(),
+				total_chunks: 8,
+				recovery_threshold: 3,
+			})
+		);
+
+		// guarantors can't choose the chunks they sample.
+		let mut table = AvailabilityTable::with_config(AvailabilityConfig {
+			sampling: Some(sampling),
+			..Default::default()
+		});
+		table.set_sampling_randomness(randomness);
+		let chosen = (0..8).find(|chunk| !chunks(members[0]).contains(chunk)).unwrap();
+		let invalid = [
+			sample(members[0], vec![chosen, chunks(members[0])[1]]),
+			sample(members[0], chunks(members[0])[..1].to_vec()),
+			sample(members[0], chunks(members[1])),
+			sample(outsider, chunks(outsider)),
+		];
+		for invalid in invalid {
+			assert!(table.import_sample(&context, &backing, invalid).is_none());
+		}
+		assert_eq!(table.get_misbehavior()[&members[0]].len(), 3);
+		assert!(matches!(
+			table.get_misbehavior()[&outsider][..],
+			[AvailabilityMisbehavior::InvalidSample(_)]
+		));
+
+		// nor sample with other randomness.
+		table.set_sampling_randomness([8; 32]);
+		assert!(table
+			.import_sample(&context, &backing, sample(members[1], chunks(members[1])))
+			.is_none());
+
+		// samples are ignored outside of sampling mode.
+		let mut table = AvailabilityTable::new();
+		assert!(table
+			.import_sample(&context, &backing, sample(members[0], chunks(members[0])))
+			.is_none());
+		assert!(table.get_misbehavior().is_empty());
+	}
+
+	// splits data into `k` pieces, chunk `i` holding piece `i % k`; data starts with its digest.
+	struct RepetitionCodec;
+
+	impl ErasureCodec<TestContext> for RepetitionCodec {
+		fn reconstruct(
+			&self,
+			params: ErasureParams,
+			chunks: &[(ChunkIndex, Bytes)],
+		) -> Option<Bytes> {
+			let k = params.recovery_threshold();
+			let pieces: Option<Vec<_>> = (0..k)
+				.map(|piece| chunks.iter().find(|(chunk, _)| chunk % k == piece))
+				.map(|found| found.map(|(_, data)| data.to_vec()))
+				.collect();
+			pieces.map(|pieces| Bytes::from(pieces.concat()))
+		}
+
+		fn verify(&self, digest: &u64, data: &[u8]) -> bool {
+			data.get(..8) == Some(&digest.to_le_bytes()[..])
+		}
+	}
+
+	#[test]
+	fn available_candidates_are_reconstructed() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let store = MemoryAvailabilityStore::new();
+		let mut table = AvailabilityTable::new();
+		let members: Vec<_> = context.members(0).collect();
+		let [first, second] = [&10u64.to_le_bytes()[..4], &10u64.to_le_bytes()[4..]];
+		let reconstruct = |table: &AvailabilityTable<_>| {
+			table.reconstruct(&context, &RepetitionCodec, &store, 1, &10)
+		};
+
+		store.put_chunk(1, 10, 0, Bytes::copy_from_slice(first)).unwrap();
+		assert!(matches!(reconstruct(&table), Err(ReconstructionError::NotAvailable)));
+
+		for member in &members[..2] {
+			table.import_attestation(&context, &backing, context.attest(*member, 10, 0));
+		}
+		assert!(matches!(
+			reconstruct(&table),
+			Err(ReconstructionError::MissingChunks { have: 1, need: 2 })
+		));
+
+		// chunks beyond the code don't count, and these two hold the same piece.
+		store.put_chunk(1, 10, 4, Bytes::copy_from_slice(second)).unwrap();
+		store.put_chunk(1, 10, 2, Bytes::copy_from_slice(first)).unwrap();
+		assert!(matches!(reconstruct(&table), Err(ReconstructionError::Undecodable)));
+
+		store.put_chunk(1, 10, 1, Bytes::copy_from_slice(first)).unwrap();
+		assert!(matches!(reconstruct(&table), Err(ReconstructionError::DigestMismatch)));
+		assert_eq!(store.data(&10).unwrap(), None);
+
+		store.put_chunk(1, 10, 3, Bytes::copy_from_slice(second)).unwrap();
+		store.put_chunk(1, 10, 1, Bytes::copy_from_slice(second)).unwrap();
+		let data = reconstruct(&table).unwrap();
+		assert_eq!(&data[..], &10u64.to_le_bytes()[..]);
+		assert_eq!(store.data(&10).unwrap(), Some(data));
+	}
+
+	#[test]
+	fn guarantors_serve_several_groups() {
+		// authority `3` is the last guarantor of group `0` and the first of group `1`.
+		let context = TestContext::builder()
+			.with_group(0, [0, 1, 2, 3])
+			.with_group(1, [3, 4, 5, 6])
+			.build();
+		let (eleven, twelve) = (digest(1, 11), digest(1, 12));
+		let backing = backing(&context, &[(0, 10), (1, 11), (1, 12)]);
+		let mut table = AvailabilityTable::new();
+		assert_eq!(context.assignments(&3), vec![(0, 3), (1, 0)]);
+
+		table.note_proposed(10, 0, Duration::from_secs(1));
+		table.note_proposed(eleven, 1, Duration::from_secs(2));
+		table.note_proposed(twelve, 1, Duration::from_secs(3));
+		assert!(table.import_attestation(&context, &backing, context.attest(3, 10, 0)).is_some());
+		assert!(table
+			.import_attestation(&context, &backing, context.attest(3, eleven, 1))
+			.is_some());
+
+		// the chunk of group `1` doesn't count for candidates of group `0`, and attesting it is
+		// misbehavior.
+		assert!(table.import_attestation(&context, &backing, context.attest(3, 10, 1)).is_none());
+		assert_eq!(table.candidate_status(&10, &context).unwrap().attested_chunks, 1);
+
+		let duties = table.duties(&context, &3);
+		assert_eq!(
+			duties.into_iter().collect::<Vec<_>>(),
+			vec![
+				(0, GroupDuties { chunk: 3, attested: vec![10], pending: vec![] }),
+				(1, GroupDuties { chunk: 0, attested: vec![eleven], pending: vec![twelve] }),
+			]
+		);
+
+		// quorums are counted in each group apart.
+		table.import_attestation(&context, &backing, context.attest(0, 10, 0));
+		table.import_attestation(&context, &backing, context.attest(4, eleven, 1));
+		assert!(table.is_available(&10, &context));
+		assert!(table.is_available(&eleven, &context));
+		assert!(!table.is_available(&twelve, &context));
+		assert_eq!(table.get_misbehavior()[&3].len(), 1);
+
+		// proposing a candidate as one of another group drops the chunks attested before.
+		table.note_proposed(10, 1, Duration::from_secs(4));
+		let status = table.candidate_status(&10, &context).unwrap();
+		assert_eq!((status.group_id, status.attested_chunks), (1, 0));
+		assert!(table.import_attestation(&context, &backing, context.attest(4, 10, 1)).is_some());
+	}
+
+	#[test]
+	fn candidates_of_unknown_group_are_ignored() {
+		let context = context();
+		let backing = backing(&context, &[(0, 10)]);
+		let mut table = AvailabilityTable::new();
+		let member = context.members(1).next().unwrap();
+
+		// the statement table doesn't have candidate `11`, so its group is unknown.
+		let eleven = digest(1, 11);
+		assert!(table
+			.import_attestation(&context, &backing, context.attest(member, eleven, 1))
+			.is_none());
+		assert!(table.candidate_status(&eleven, &context).is_none());
+		assert!(table.get_misbehavior().is_empty());
+
+		// until it is proposed.
+		table.note_proposed(eleven, 1, Duration::from_secs(1));
+		let summary = table
+			.import_attestation(&context, &backing, context.attest(member, eleven, 1))
+			.unwrap();
+		assert_eq!((summary.group_id, summary.attested_chunks), (1, 1));
+	}
+
+	#[test]
+	fn recovery_threshold_follows_the_code_of_the_group() {
+		// group `1` uses a code of which three chunks are needed instead of two.
+		let context = TestContext::builder().with_groups(2, 4).with_data_chunks(1, 3).build();
+		let eleven = digest(1, 11);
+		let backing = backing(&context, &[(0, 10), (1, 11)]);
+		let mut table = AvailabilityTable::new();
+		for (group_id, digest) in [(0, 10), (1, eleven)] {
+			for member in context.members(group_id).take(2).collect::<Vec<_>>() {
+				table.import_attestation(
+					&context,
+					&backing,
+					context.attest(member, digest, group_id),
+				);
+			}
+		}
+
+		assert!(table.is_available(&10, &context));
+		assert!(!table.is_available(&eleven, &context));
+		assert_eq!(table.candidate_status(&eleven, &context).unwrap().missing_chunks(), 1);
+
+		let threshold =
+			|data_chunks| ErasureParams { total_chunks: 4, data_chunks }.recovery_threshold();
+		assert_eq!(threshold(0), 1);
+		assert_eq!(threshold(3), 3);
+	}
+
+	#[test]
+	fn attestations_are_signed_apart_from_statements() {
+		let attestation = ChunkAttestation { candidate: 10u64, chunk: 0, sequence: 0 };
+		let payload = attestation.signing_payload(&SIGNING_CONTEXT);
+		assert_eq!(&payload[..4], b"AVBL");
+		assert_ne!(
+			payload,
+			ChunkAttestation { candidate: 10u64, chunk: 1, sequence: 0 }
+				.signing_payload(&SIGNING_CONTEXT)
+		);
+	}
+}
@@ -0,0 +1,67 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reconstruction of the data of candidates from their chunks.
+//!
+//! Validators which didn't receive the data of a candidate from its proposer, e.g. to check
+//! its validity, reconstruct it from the chunks held by guarantors once it is available. The
+//! chunks are fetched into an [`AvailabilityStore`](super::AvailabilityStore), and
+//! [`AvailabilityTable::reconstruct`](super::AvailabilityTable::reconstruct) decodes them with an
+//! [`ErasureCodec`], checking the data against the digest of the candidate before storing it.
+
+use std::io;
+
+use bytes::Bytes;
+
+use super::{ChunkIndex, ErasureParams};
+use crate::generic::Context;
+
+/// The erasure code the data of candidates is encoded with.
+pub trait ErasureCodec<Ctx: Context>: Send + Sync {
+	/// Reconstruct data encoded with `params` from distinct `chunks`, at least as many as the
+	/// recovery threshold, if they can be decoded.
+	fn reconstruct(&self, params: ErasureParams, chunks: &[(ChunkIndex, Bytes)]) -> Option<Bytes>;
+
+	/// Whether `data` is the data of the candidate with the given `digest`.
+	fn verify(&self, digest: &Ctx::Digest, data: &[u8]) -> bool;
+}
+
+/// Errors reconstructing the data of a candidate.
+#[derive(Debug)]
+pub enum ReconstructionError {
+	/// Not enough chunks of the candidate are attested for it to be available, or the erasure
+	/// code of its group is unknown.
+	NotAvailable,
+	/// Not enough chunks of the candidate are stored yet.
+	MissingChunks {
+		/// How many distinct chunks are stored.
+		have: usize,
+		/// How many distinct chunks are needed.
+		need: usize,
+	},
+	/// The stored chunks couldn't be decoded.
+	Undecodable,
+	/// The decoded data doesn't match the digest of the candidate, so some chunks are bad.
+	DigestMismatch,
+	/// The store failed.
+	Store(io::Error),
+}
+
+impl From<io::Error> for ReconstructionError {
+	fn from(e: io::Error) -> Self {
+		ReconstructionError::Store(e)
+	}
+}
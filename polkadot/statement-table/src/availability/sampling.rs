@@ -0,0 +1,226 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Availability by sampling.
+//!
+//! Chains which prefer cheaper availability with statistical guarantees can put the
+//! [`AvailabilityTable`](super::AvailabilityTable) in sampling mode with a [`SamplingConfig`].
+//! Guarantors then fetch a few chunks of each candidate, chosen with
+//! [`SamplingConfig::guarantor_chunks`] from randomness of the round which the proposer of the
+//! candidate can't predict, and attest to them with a signed [`SamplingAttestation`]. Everybody
+//! knows the randomness, so the table checks that guarantors sampled the chunks they had to
+//! instead of chunks of their choosing.
+//!
+//! Data which can't be reconstructed lacks all but at most `k - 1` of its `n` chunks, so `m`
+//! distinct random chunks of it are all present with probability at most
+//! `(k - 1) / n * (k - 2) / (n - 1) * ...`. A candidate is considered available once enough
+//! distinct chunks were sampled that data this incomplete would have passed them all with
+//! probability under `2^-security_bits`, see [`SamplingConfig::required_samples`]. Samples of
+//! the same chunk by several guarantors count once. Like attestations of chunks, samples are
+//! only as good as the guarantors reporting them.
+
+use parity_scale_codec::{Decode, Encode};
+use sp_core::blake2_256;
+
+use super::{ChunkIndex, ErasureParams};
+use crate::generic::Context;
+
+// distinguishes the signing payloads of samples from those of attestations.
+const SAMPLING_SIGNING_MAGIC: [u8; 4] = *b"AVSM";
+
+/// Configuration of the sampling mode of an [`AvailabilityTable`](super::AvailabilityTable).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingConfig {
+	/// How many distinct chunks each guarantor samples, at most all of them.
+	pub samples_per_guarantor: u32,
+	/// The confidence in the availability of candidates: data which can't be reconstructed is
+	/// considered available with probability under `2^-security_bits`.
+	pub security_bits: u32,
+}
+
+impl Default for SamplingConfig {
+	fn default() -> Self {
+		SamplingConfig { samples_per_guarantor: 4, security_bits: 32 }
+	}
+}
+
+impl SamplingConfig {
+	/// How many distinct chunks each guarantor samples of data encoded with `params`.
+	pub fn samples(&self, params: ErasureParams) -> u32 {
+		self.samples_per_guarantor.min(params.total_chunks)
+	}
+
+	/// How many distinct chunks of data encoded with `params` have to be sampled successfully
+	/// to make it available, at most as many as reconstruct it.
+	///
+	/// This only uses integer arithmetic, so that all validators agree on it.
+	pub fn required_samples(&self, params: ErasureParams) -> u32 {
+		let threshold = params.recovery_threshold();
+		if threshold > params.total_chunks {
+			// the data can't be reconstructed at all.
+			return threshold
+		}
+
+		// the pass rate of `samples` distinct chunks of data lacking all but `threshold - 1` of
+		// them is `mantissa / 2^(63 + shift)`, with `mantissa` in `[2^63, 2^64)`. Rounding up
+		// never understates it.
+		let mut mantissa = 1u128 << 63;
+		let mut shift = 0;
+		for samples in 1..threshold {
+			let present = (threshold - samples) as u128;
+			let remaining = (params.total_chunks - samples + 1) as u128;
+			mantissa = (mantissa * present).div_ceil(remaining);
+			while mantissa < 1 << 63 {
+				mantissa <<= 1;
+				shift += 1;
+			}
+			if shift > self.security_bits {
+				return samples
+			}
+		}
+
+		threshold
+	}
+
+	/// The chunks `guarantor` has to sample of the candidate with the given `digest`, encoded
+	/// with `params`, given the `randomness` of the round.
+	pub fn guarantor_chunks<Digest: Encode, AuthorityId: Encode>(
+		&self,
+		params: ErasureParams,
+		randomness: &[u8; 32],
+		digest: &Digest,
+		guarantor: &AuthorityId,
+	) -> Vec<ChunkIndex> {
+		self.sample_chunks(params, &(randomness, digest, guarantor).encode())
+	}
+
+	/// Choose the chunks to sample of data encoded with `params`.
+	///
+	/// `entropy` must be unpredictable to the proposer of the candidate, or it could withhold
+	/// exactly the chunks which are sampled.
+	pub fn sample_chunks(&self, params: ErasureParams, entropy: &[u8]) -> Vec<ChunkIndex> {
+		let samples = self.samples(params) as usize;
+		let mut chunks = Vec::with_capacity(samples);
+		let mut counter = 0u64;
+		while chunks.len() < samples {
+			let hash = blake2_256(&(entropy, counter).encode());
+			let chunk =
+				u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]) % params.total_chunks;
+			if !chunks.contains(&chunk) {
+				chunks.push(chunk);
+			}
+			counter += 1;
+		}
+
+		chunks.sort_unstable();
+		chunks
+	}
+}
+
+/// An attestation that the sender fetched the given chunks of the data of a candidate.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingAttestation<Digest> {
+	/// The digest of the candidate.
+	pub candidate: Digest,
+	/// The sampled chunks.
+	pub chunks: Vec<ChunkIndex>,
+}
+
+impl<Digest: Encode> SamplingAttestation<Digest> {
+	/// The payload to sign for this attestation.
+	///
+	/// The payload is the magic `AVSM`, the attestation and `signing_context`, SCALE-encoded.
+	pub fn signing_payload<S: Encode>(&self, signing_context: &S) -> Vec<u8> {
+		(SAMPLING_SIGNING_MAGIC, self, signing_context).encode()
+	}
+}
+
+/// A signed [`SamplingAttestation`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedSamplingAttestation<Digest, AuthorityId, Signature> {
+	/// The attestation.
+	pub attestation: SamplingAttestation<Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a signed sampling attestation corresponding to context type.
+pub type SignedSamplingAttestationFor<Ctx> = SignedSamplingAttestation<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn required_samples_grow_with_confidence() {
+		let config = |security_bits| SamplingConfig { samples_per_guarantor: 4, security_bits };
+
+		// 3 of 8 chunks of unrecoverable data pass, so 2 distinct ones with probability 3/28,
+		// and 3 with probability 1/56.
+		let params = ErasureParams { total_chunks: 8, data_chunks: 4 };
+		assert_eq!(config(3).required_samples(params), 2);
+		assert_eq!(config(4).required_samples(params), 3);
+		assert_eq!(config(5).required_samples(params), 3);
+		// but 4 distinct chunks reconstruct the data.
+		assert_eq!(config(32).required_samples(params), 4);
+
+		// at most 333 of 1000 chunks pass, 20 distinct ones with probability under 2^-32.
+		let params = ErasureParams { total_chunks: 1000, data_chunks: 334 };
+		assert_eq!(config(32).required_samples(params), 20);
+
+		// data of more chunks than there are is never available.
+		let params = ErasureParams { total_chunks: 2, data_chunks: 3 };
+		assert_eq!(config(32).required_samples(params), 3);
+
+		// any chunk reconstructs data of up to three chunks.
+		assert_eq!(
+			config(32).required_samples(ErasureParams { total_chunks: 3, data_chunks: 1 }),
+			1
+		);
+	}
+
+	#[test]
+	fn sampled_chunks_are_distinct() {
+		let config = SamplingConfig::default();
+		let params = ErasureParams { total_chunks: 10, data_chunks: 4 };
+		let chunks = config.sample_chunks(params, b"entropy");
+		let mut distinct = chunks.clone();
+		distinct.dedup();
+		assert_eq!(distinct.len(), 4);
+		assert!(chunks.iter().all(|chunk| *chunk < 10));
+		assert_ne!(chunks, config.sample_chunks(params, b"other entropy"));
+
+		// guarantors sample different chunks.
+		let randomness = [7; 32];
+		assert_ne!(
+			config.guarantor_chunks(params, &randomness, &10u64, &0u32),
+			config.guarantor_chunks(params, &randomness, &10u64, &1u32)
+		);
+
+		// all chunks of small codes are sampled.
+		let params = ErasureParams { total_chunks: 3, data_chunks: 1 };
+		assert_eq!(config.sample_chunks(params, b"entropy"), vec![0, 1, 2]);
+	}
+}
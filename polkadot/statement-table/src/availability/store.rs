@@ -0,0 +1,195 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage of the data of candidates and their chunks.
+//!
+//! Statements only reference candidates by digest, so the data of candidates, e.g. their
+//! proofs of validity, and the erasure-coded chunks of it held by guarantors are kept aside in
+//! an [`AvailabilityStore`] until they are no longer needed. Data is stored along with the round
+//! of agreement it was stored in, and pruned by round once the rounds are finished.
+//!
+//! A [`MemoryAvailabilityStore`] keeps everything in memory, for tests and for validators which
+//! don't need to survive restarts.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+	io,
+	sync::Mutex,
+};
+
+use bytes::Bytes;
+
+use super::ChunkIndex;
+use crate::{agreement::Round, LOCK_PROOF};
+
+/// A store of the data of candidates and their chunks, by digest.
+pub trait AvailabilityStore<Digest>: Send + Sync {
+	/// Store the `data` of the candidate with the given `digest` in `round`.
+	fn put_data(&self, round: Round, digest: Digest, data: Bytes) -> io::Result<()>;
+
+	/// Get the data of the candidate with the given `digest`.
+	fn data(&self, digest: &Digest) -> io::Result<Option<Bytes>>;
+
+	/// Store `chunk` of the data of the candidate with the given `digest` in `round`.
+	fn put_chunk(
+		&self,
+		round: Round,
+		digest: Digest,
+		chunk: ChunkIndex,
+		data: Bytes,
+	) -> io::Result<()>;
+
+	/// Get `chunk` of the data of the candidate with the given `digest`.
+	fn chunk(&self, digest: &Digest, chunk: ChunkIndex) -> io::Result<Option<Bytes>>;
+
+	/// Get all chunks stored of the data of the candidate with the given `digest`, in order.
+	fn chunks(&self, digest: &Digest) -> io::Result<Vec<(ChunkIndex, Bytes)>>;
+
+	/// Remove the data and chunks of candidates last stored in rounds before `round`.
+	fn prune(&self, round: Round) -> io::Result<()>;
+}
+
+// the data and chunks of a candidate.
+struct StoredCandidate {
+	// the latest round anything of the candidate was stored in.
+	round: Round,
+	data: Option<Bytes>,
+	chunks: BTreeMap<ChunkIndex, Bytes>,
+}
+
+/// An [`AvailabilityStore`] in memory.
+pub struct MemoryAvailabilityStore<Digest> {
+	candidates: Mutex<HashMap<Digest, StoredCandidate>>,
+}
+
+impl<Digest> Default for MemoryAvailabilityStore<Digest> {
+	fn default() -> Self {
+		MemoryAvailabilityStore { candidates: Mutex::new(HashMap::new()) }
+	}
+}
+
+impl<Digest: Hash + Eq> MemoryAvailabilityStore<Digest> {
+	/// Create an empty store.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The number of candidates with data or chunks stored.
+	pub fn len(&self) -> usize {
+		self.candidates.lock().expect(LOCK_PROOF).len()
+	}
+
+	/// Whether nothing is stored.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// apply `f` to the candidate with the given `digest`, noting it was stored in `round`.
+	fn update(&self, round: Round, digest: Digest, f: impl FnOnce(&mut StoredCandidate)) {
+		let mut candidates = self.candidates.lock().expect(LOCK_PROOF);
+		let candidate = candidates.entry(digest).or_insert_with(|| StoredCandidate {
+			round,
+			data: None,
+			chunks: BTreeMap::new(),
+		});
+		candidate.round = candidate.round.max(round);
+		f(candidate);
+	}
+}
+
+impl<Digest: Hash + Eq + Send> AvailabilityStore<Digest> for MemoryAvailabilityStore<Digest> {
+	fn put_data(&self, round: Round, digest: Digest, data: Bytes) -> io::Result<()> {
+		self.update(round, digest, |candidate| candidate.data = Some(data));
+		Ok(())
+	}
+
+	fn data(&self, digest: &Digest) -> io::Result<Option<Bytes>> {
+		let candidates = self.candidates.lock().expect(LOCK_PROOF);
+		Ok(candidates.get(digest).and_then(|candidate| candidate.data.clone()))
+	}
+
+	fn put_chunk(
+		&self,
+		round: Round,
+		digest: Digest,
+		chunk: ChunkIndex,
+		data: Bytes,
+	) -> io::Result<()> {
+		self.update(round, digest, |candidate| {
+			candidate.chunks.insert(chunk, data);
+		});
+		Ok(())
+	}
+
+	fn chunk(&self, digest: &Digest, chunk: ChunkIndex) -> io::Result<Option<Bytes>> {
+		let candidates = self.candidates.lock().expect(LOCK_PROOF);
+		Ok(candidates
+			.get(digest)
+			.and_then(|candidate| candidate.chunks.get(&chunk).cloned()))
+	}
+
+	fn chunks(&self, digest: &Digest) -> io::Result<Vec<(ChunkIndex, Bytes)>> {
+		let candidates = self.candidates.lock().expect(LOCK_PROOF);
+		Ok(candidates
+			.get(digest)
+			.into_iter()
+			.flat_map(|candidate| candidate.chunks.iter())
+			.map(|(chunk, data)| (*chunk, data.clone()))
+			.collect())
+	}
+
+	fn prune(&self, round: Round) -> io::Result<()> {
+		self.candidates
+			.lock()
+			.expect(LOCK_PROOF)
+			.retain(|_, candidate| candidate.round >= round);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn data_is_pruned_by_round() {
+		let store = MemoryAvailabilityStore::new();
+		store.put_data(1, 10u64, Bytes::from_static(b"pov")).unwrap();
+		store.put_chunk(1, 10, 2, Bytes::from_static(b"second")).unwrap();
+		store.put_chunk(1, 10, 0, Bytes::from_static(b"first")).unwrap();
+		store.put_chunk(1, 11, 0, Bytes::from_static(b"other")).unwrap();
+
+		assert_eq!(store.data(&10).unwrap(), Some(Bytes::from_static(b"pov")));
+		assert_eq!(store.data(&11).unwrap(), None);
+		assert_eq!(store.chunk(&10, 2).unwrap(), Some(Bytes::from_static(b"second")));
+		assert_eq!(store.chunk(&10, 1).unwrap(), None);
+		assert_eq!(
+			store.chunks(&10).unwrap(),
+			vec![(0, Bytes::from_static(b"first")), (2, Bytes::from_static(b"second"))]
+		);
+
+		// storing a chunk again keeps the candidate for a later round.
+		store.put_chunk(3, 11, 1, Bytes::from_static(b"late")).unwrap();
+		store.prune(2).unwrap();
+		assert_eq!(store.len(), 1);
+		assert_eq!(store.data(&10).unwrap(), None);
+		assert_eq!(store.chunks(&11).unwrap().len(), 2);
+
+		store.prune(4).unwrap();
+		assert!(store.is_empty());
+	}
+}
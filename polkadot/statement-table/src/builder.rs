@@ -0,0 +1,271 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Building tables.
+//!
+//! A [`TableBuilder`] sets up a [`Table`] along with its limits, backing thresholds, hasher and
+//! observers in one go, instead of creating it with [`Table::new`] and calling its setters one by
+//! one.
+
+use std::{
+	collections::{hash_map::RandomState, BTreeMap},
+	hash::BuildHasher,
+	sync::Arc,
+};
+
+use crate::{
+	events::TableEventsFor,
+	generic::{CandidateProvider, Config, Context, MemoryLimit, Table, TableLimits},
+	metrics::Metrics,
+	recording::Recorder,
+};
+
+/// A builder of a [`Table`], see [`Table::builder`].
+pub struct TableBuilder<Ctx: Context, S = RandomState> {
+	config: Config,
+	hasher: S,
+	limits: TableLimits,
+	backing_thresholds: BTreeMap<Ctx::GroupId, u32>,
+	memory_limit: Option<MemoryLimit<Ctx::Candidate>>,
+	metrics: Metrics,
+	events: Option<TableEventsFor<Ctx>>,
+	candidate_provider: Option<Arc<dyn CandidateProvider<Ctx>>>,
+	recorder: Option<Arc<Recorder<Ctx>>>,
+}
+
+impl<Ctx: Context> Default for TableBuilder<Ctx> {
+	fn default() -> Self {
+		TableBuilder {
+			config: Config::default(),
+			hasher: RandomState::new(),
+			limits: TableLimits::default(),
+			backing_thresholds: BTreeMap::new(),
+			memory_limit: None,
+			metrics: Metrics::default(),
+			events: None,
+			candidate_provider: None,
+			recorder: None,
+		}
+	}
+}
+
+impl<Ctx: Context> TableBuilder<Ctx> {
+	/// A builder of a table with the default configuration, without limits or observers.
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl<Ctx: Context, S: BuildHasher + Clone> TableBuilder<Ctx, S> {
+	/// Configure the table with `config`.
+	pub fn with_config(mut self, config: Config) -> Self {
+		self.config = config;
+		self
+	}
+
+	/// Hash keys with `hasher`, see [`Table::with_hasher`].
+	pub fn with_hasher<H: BuildHasher + Clone>(self, hasher: H) -> TableBuilder<Ctx, H> {
+		let TableBuilder {
+			config,
+			hasher: _,
+			limits,
+			backing_thresholds,
+			memory_limit,
+			metrics,
+			events,
+			candidate_provider,
+			recorder,
+		} = self;
+		TableBuilder {
+			config,
+			hasher,
+			limits,
+			backing_thresholds,
+			memory_limit,
+			metrics,
+			events,
+			candidate_provider,
+			recorder,
+		}
+	}
+
+	/// Let each authority second at most `max` candidates in a group, see
+	/// [`TableLimits::max_candidates_per_authority`].
+	pub fn with_max_candidates_per_authority(mut self, max: usize) -> Self {
+		self.limits.max_candidates_per_authority = Some(max);
+		self
+	}
+
+	/// Buffer at most `max` statements for candidates whose body is pending, see
+	/// [`TableLimits::max_buffered_votes`].
+	pub fn with_max_buffered_votes(mut self, max: usize) -> Self {
+		self.limits.max_buffered_votes = Some(max);
+		self
+	}
+
+	/// Require `votes` validity votes for candidates of `group_id` to be includable, see
+	/// [`Table::set_backing_threshold`].
+	pub fn with_backing_threshold(mut self, group_id: Ctx::GroupId, votes: u32) -> Self {
+		self.backing_thresholds.insert(group_id, votes);
+		self
+	}
+
+	/// Bound the memory used by the table, see [`Table::set_memory_limit`].
+	pub fn with_memory_limit(mut self, limit: MemoryLimit<Ctx::Candidate>) -> Self {
+		self.memory_limit = Some(limit);
+		self
+	}
+
+	/// Record the metrics of the table into `metrics`, see [`Table::set_metrics`].
+	pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+		self.metrics = metrics;
+		self
+	}
+
+	/// Publish the events of the table to `events`, see [`Table::set_events`].
+	pub fn with_events(mut self, events: TableEventsFor<Ctx>) -> Self {
+		self.events = Some(events);
+		self
+	}
+
+	/// Get candidates from `provider` instead of keeping them in memory, see
+	/// [`Table::set_candidate_provider`].
+	pub fn with_candidate_provider(mut self, provider: Arc<dyn CandidateProvider<Ctx>>) -> Self {
+		self.candidate_provider = Some(provider);
+		self
+	}
+
+	/// Record the imported statements with `recorder`, see [`Table::set_recorder`].
+	pub fn with_recorder(mut self, recorder: Arc<Recorder<Ctx>>) -> Self {
+		self.recorder = Some(recorder);
+		self
+	}
+
+	/// Build the table.
+	pub fn build(self) -> Table<Ctx, S> {
+		let mut table = Table::with_hasher(self.config, self.hasher);
+		table.set_limits(self.limits);
+		for (group_id, votes) in self.backing_thresholds {
+			table.set_backing_threshold(group_id, votes);
+		}
+		table.set_memory_limit(self.memory_limit);
+		table.set_metrics(self.metrics);
+		if let Some(events) = self.events {
+			table.set_events(events);
+		}
+		if let Some(provider) = self.candidate_provider {
+			table.set_candidate_provider(provider);
+		}
+		if let Some(recorder) = self.recorder {
+			table.set_recorder(recorder);
+		}
+
+		table
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		events::TableEvents,
+		generic::{ImportError, MisbehaviorKind, SignedStatement, Statement},
+		test_utils::{TestCandidate, TestContext},
+	};
+
+	#[test]
+	fn built_tables_are_limited() {
+		let context = TestContext::builder().with_groups(1, 3).build();
+		let events = TableEvents::new(2);
+		let mut subscriber = events.subscribe();
+		let mut table = Table::builder()
+			.with_config(Config { allow_multiple_seconded: true })
+			.with_hasher(RandomState::new())
+			.with_max_candidates_per_authority(1)
+			.with_max_buffered_votes(1)
+			.with_events(events)
+			.build();
+
+		let seconded = |body, sender| {
+			context.sign(sender, Statement::Seconded(TestCandidate { group_id: 0, body }))
+		};
+		assert!(table.try_import_statement(&context, 0, seconded(1, 0)).is_ok());
+		assert_eq!(
+			table.try_import_statement(&context, 0, seconded(2, 0)),
+			Err(ImportError::TableFull)
+		);
+		assert!(subscriber.try_next().unwrap().is_some());
+
+		// other authorities still second candidates, and vote on known ones.
+		assert!(table.try_import_statement(&context, 0, seconded(2, 1)).is_ok());
+		assert!(table.try_import_statement(&context, 0, seconded(1, 2)).is_ok());
+		let valid = context.sign(1, Statement::Valid(1));
+		assert!(table.try_import_statement(&context, 0, valid).is_ok());
+
+		let announce = |table: &mut Table<_>, digest, SignedStatement { signature, sender, .. }| {
+			table.note_announcement(&context, 0, digest, sender, signature)
+		};
+		assert!(announce(&mut table, 3, seconded(3, 1)));
+		assert!(!announce(&mut table, 4, seconded(4, 2)));
+		assert_eq!(table.pending_bodies(), vec![(3, 0)]);
+	}
+
+	#[test]
+	fn equivocations_are_noted_at_the_limit() {
+		let context = TestContext::builder().with_groups(1, 3).build();
+		let mut table = Table::builder().with_max_candidates_per_authority(1).build();
+		let seconded =
+			|body| context.sign(0, Statement::Seconded(TestCandidate { group_id: 0, body }));
+
+		assert!(table.try_import_statement(&context, 0, seconded(1)).is_ok());
+		assert_eq!(
+			table.try_import_statement(&context, 0, seconded(2)),
+			Err(ImportError::Misbehavior(MisbehaviorKind::MultipleCandidates))
+		);
+	}
+
+	#[test]
+	fn backing_thresholds_are_overridden_per_group() {
+		let context = TestContext::builder().with_groups(2, 3).build();
+		let mut table = Table::builder().with_backing_threshold(1, 3).build();
+		for group_id in [0, 1] {
+			let members: Vec<_> = context.members(group_id).collect();
+			let candidate = TestCandidate { group_id, body: 1 };
+			let digest = candidate.digest();
+			table.import_statement(
+				&context,
+				group_id,
+				context.sign(members[0], Statement::Seconded(candidate)),
+			);
+			table.import_statement(
+				&context,
+				group_id,
+				context.sign(members[1], Statement::Valid(digest)),
+			);
+		}
+
+		let includable = |table: &Table<TestContext>| {
+			let mut groups: Vec<_> =
+				table.attested_candidates(&context, 2).into_iter().map(|c| c.group_id).collect();
+			groups.sort();
+			groups
+		};
+		assert_eq!(includable(&table), vec![0]);
+
+		table.set_backing_threshold(0, 3);
+		assert!(includable(&table).is_empty());
+	}
+}
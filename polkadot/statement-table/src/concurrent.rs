@@ -0,0 +1,302 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A statement table which can be imported into from multiple threads.
+//!
+//! A [`Table`] needs exclusive access for every import. A [`ConcurrentTable`] splits the
+//! candidates into shards by digest, each of them a [`Table`] behind a lock of its own, so that
+//! statements about different candidates can be imported in parallel.
+//!
+//! Validity statements only touch the shard of their candidate. Detecting that an authority
+//! seconded multiple candidates needs state across shards, so seconded statements are checked
+//! against the candidates seconded in all shards first. Seconded statements are rare compared
+//! to validity statements, so they are serialized for that check.
+
+use std::{
+	collections::{
+		hash_map::{Entry, RandomState},
+		HashMap,
+	},
+	hash::BuildHasher,
+	sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+	generic::{
+		AttestedCandidateFor, CandidateStatusFor, Config, Context, Misbehavior, MisbehaviorFor,
+		MultipleCandidates, SignedStatementFor, Statement, Summary, Table, TableSnapshot,
+	},
+	LOCK_PROOF,
+};
+
+// the state shared by all shards.
+struct Shared<Ctx: Context> {
+	// the candidate seconded by each authority in each group.
+	seconded: HashMap<(Ctx::AuthorityId, Ctx::GroupId), (Ctx::Digest, Ctx::Signature)>,
+	// misbehavior which was detected across shards.
+	misbehavior: HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>>,
+}
+
+/// A statement table sharded by candidate digest.
+pub struct ConcurrentTable<Ctx: Context> {
+	shards: Vec<Mutex<Table<Ctx>>>,
+	shard_hasher: RandomState,
+	// always locked before any shard.
+	shared: Mutex<Shared<Ctx>>,
+	config: Config,
+}
+
+impl<Ctx: Context> ConcurrentTable<Ctx> {
+	/// Create a new table from a `Config`, with `shards` shards.
+	pub fn new(config: Config, shards: usize) -> Self {
+		ConcurrentTable {
+			shards: (0..shards.max(1))
+				.map(|_| {
+					Mutex::new(Table::new(Config {
+						allow_multiple_seconded: config.allow_multiple_seconded,
+					}))
+				})
+				.collect(),
+			shard_hasher: RandomState::new(),
+			shared: Mutex::new(Shared { seconded: HashMap::new(), misbehavior: HashMap::new() }),
+			config,
+		}
+	}
+
+	/// Import a signed statement, as with [`Table::import_statement`].
+	///
+	/// Statements about candidates in different shards don't contend with each other.
+	pub fn import_statement(
+		&self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<Summary<Ctx::Digest, Ctx::GroupId>> {
+		let digest = statement.digest::<Ctx>();
+
+		// seconded statements by non-members are misbehavior the shard detects on its own.
+		let is_seconded = statement.statement.is_seconded();
+		if !is_seconded ||
+			self.config.allow_multiple_seconded ||
+			!context.is_member_of(&statement.sender, &group_id)
+		{
+			return self.shard(&digest).import_statement(context, group_id, statement)
+		}
+
+		let mut shared = self.shared.lock().expect(LOCK_PROOF);
+		let key = (statement.sender.clone(), group_id.clone());
+		match shared.seconded.entry(key) {
+			Entry::Occupied(occ) if occ.get().0 != digest => {
+				let (old_digest, old_signature) = occ.get().clone();
+				let old_candidate = self.shard(&old_digest).shared_candidate(&old_digest).expect(
+					"seconded candidates are noted here only while they are imported into \
+						their shard, which creates their entry; qed",
+				);
+
+				let misbehavior = Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: ((*old_candidate).clone(), old_signature),
+					second: match statement.statement {
+						Statement::Seconded(candidate) => (candidate, statement.signature),
+						Statement::Valid(_) => unreachable!("checked to be seconded above; qed"),
+					},
+				});
+				shared.misbehavior.entry(statement.sender).or_default().push(misbehavior);
+				return None
+			},
+			Entry::Occupied(_) => {},
+			Entry::Vacant(vacant) => {
+				vacant.insert((digest.clone(), statement.signature.clone()));
+			},
+		}
+
+		// the shared lock is held until the candidate is in its shard.
+		self.shard(&digest).import_statement(context, group_id, statement)
+	}
+
+	/// Get a shared handle to a candidate by digest.
+	pub fn get_candidate(&self, digest: &Ctx::Digest) -> Option<Arc<Ctx::Candidate>> {
+		self.shard(digest).shared_candidate(digest)
+	}
+
+	/// Get the status of the candidate with the given `digest`, see [`Table::candidate_status`].
+	pub fn candidate_status(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Option<CandidateStatusFor<Ctx>> {
+		self.shard(digest).candidate_status(digest, context, minimum_backing_votes)
+	}
+
+	/// Get all candidates which are currently includable, in no particular order.
+	pub fn attested_candidates(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Vec<AttestedCandidateFor<Ctx>> {
+		self.shards
+			.iter()
+			.flat_map(|shard| {
+				shard
+					.lock()
+					.expect(LOCK_PROOF)
+					.attested_candidates(context, minimum_backing_votes)
+			})
+			.collect()
+	}
+
+	/// Get all witnessed misbehavior, merged across shards.
+	pub fn get_misbehavior(&self) -> HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>> {
+		let shared = self.shared.lock().expect(LOCK_PROOF);
+		let mut misbehavior = shared.misbehavior.clone();
+		for shard in &self.shards {
+			for (authority, found) in shard.lock().expect(LOCK_PROOF).get_misbehavior() {
+				misbehavior.entry(authority.clone()).or_default().extend(found.iter().cloned());
+			}
+		}
+
+		misbehavior
+	}
+
+	/// Take all witnessed misbehavior, merged across shards.
+	pub fn drain_misbehaviors(&self) -> Vec<(Ctx::AuthorityId, MisbehaviorFor<Ctx>)> {
+		let mut shared = self.shared.lock().expect(LOCK_PROOF);
+		let mut misbehavior: Vec<_> = shared
+			.misbehavior
+			.drain()
+			.flat_map(|(authority, found)| found.into_iter().map(move |m| (authority.clone(), m)))
+			.collect();
+		for shard in &self.shards {
+			misbehavior.extend(shard.lock().expect(LOCK_PROOF).drain_misbehaviors());
+		}
+
+		misbehavior
+	}
+
+	/// Merge the shards into a single [`Table`].
+	///
+	/// Statements of different shards are imported into the merged table one shard after
+	/// another, so their order of import is only kept within each shard.
+	pub fn into_table(self, context: &Ctx) -> Table<Ctx> {
+		let shared = self.shared.into_inner().expect(LOCK_PROOF);
+		let mut statements = Vec::new();
+		let mut announced = Vec::new();
+		let mut misbehavior = shared.misbehavior;
+		for shard in self.shards {
+			let snapshot = shard.into_inner().expect(LOCK_PROOF).snapshot();
+			statements.extend(snapshot.statements);
+			announced.extend(snapshot.announced);
+			for (authority, found) in snapshot.misbehavior {
+				misbehavior.entry(authority).or_default().extend(found);
+			}
+		}
+
+		let snapshot = TableSnapshot {
+			allow_multiple_seconded: self.config.allow_multiple_seconded,
+			statements,
+			announced,
+			misbehavior: misbehavior.into_iter().collect(),
+		};
+		Table::restore(context, snapshot)
+	}
+
+	fn shard(&self, digest: &Ctx::Digest) -> MutexGuard<'_, Table<Ctx>> {
+		let index = self.shard_hasher.hash_one(digest) as usize % self.shards.len();
+		self.shards[index].lock().expect(LOCK_PROOF)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{sign, TestCandidate, TestContext};
+
+	// authorities `0..8` in group `0`.
+	fn context() -> TestContext {
+		TestContext::builder().with_group(0, 0..8).build()
+	}
+
+	fn seconded(body: u64, sender: u32) -> SignedStatementFor<TestContext> {
+		sign(sender, Statement::Seconded(TestCandidate::new(0, body)), 0)
+	}
+
+	fn valid(body: u64, sender: u32) -> SignedStatementFor<TestContext> {
+		sign(sender, Statement::Valid(body), 0)
+	}
+
+	#[test]
+	fn statements_are_imported_from_many_threads() {
+		let context = context();
+		let table = ConcurrentTable::new(Config { allow_multiple_seconded: true }, 4);
+
+		std::thread::scope(|scope| {
+			for seconder in 0..8 {
+				let (context, table) = (&context, &table);
+				let body = u64::from(seconder);
+				scope.spawn(move || {
+					table.import_statement(context, 0, seconded(body, seconder));
+					for sender in (0..8).filter(|sender| *sender != seconder) {
+						table.import_statement(context, 0, valid(body, sender));
+					}
+				});
+			}
+		});
+
+		for body in 0..8 {
+			let status = table.candidate_status(&body, &context, 8).unwrap();
+			assert_eq!(status.validity_votes, 8);
+			assert_eq!(*table.get_candidate(&body).unwrap(), TestCandidate::new(0, body));
+		}
+		assert_eq!(table.attested_candidates(&context, 8).len(), 8);
+
+		let merged = table.into_table(&context);
+		assert_eq!(merged.attested_candidates(&context, 8).len(), 8);
+	}
+
+	#[test]
+	fn multiple_candidates_are_detected_across_shards() {
+		let context = context();
+		let table = ConcurrentTable::new(Config { allow_multiple_seconded: false }, 4);
+
+		for body in 0..4 {
+			table.import_statement(&context, 0, seconded(body, 1));
+		}
+		assert!(table.get_candidate(&0).is_some());
+		assert!(table.get_candidate(&1).is_none());
+
+		// a validity vote on a seconded candidate conflicts within its shard.
+		table.import_statement(&context, 0, valid(0, 1));
+
+		let misbehavior = table.get_misbehavior();
+		assert_eq!(misbehavior[&1].len(), 4);
+		assert_eq!(
+			misbehavior[&1][0],
+			Misbehavior::MultipleCandidates(MultipleCandidates {
+				first: (TestCandidate::new(0, 0), seconded(0, 1).signature),
+				second: (TestCandidate::new(0, 1), seconded(1, 1).signature),
+			})
+		);
+
+		let merged = ConcurrentTable::new(Config { allow_multiple_seconded: false }, 4);
+		merged.import_statement(&context, 0, seconded(0, 1));
+		merged.import_statement(&context, 0, seconded(1, 1));
+		let merged = merged.into_table(&context);
+		assert_eq!(merged.get_misbehavior()[&1].len(), 1);
+
+		assert_eq!(table.drain_misbehaviors().len(), 4);
+		assert!(table.get_misbehavior().is_empty());
+	}
+}
@@ -0,0 +1,222 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Differential tests of the implementations of the table.
+//!
+//! The same statements are imported into a reference [`Table`] and into each of the other
+//! implementations, which must then be indistinguishable from the reference: every import has
+//! the same result, and the candidates, their statuses, the misbehavior and the proposals built
+//! out of them are the same. Orders which are unspecified, e.g. of attested candidates, are
+//! normalized before comparing.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use parity_scale_codec::Encode;
+
+use crate::{
+	concurrent::ConcurrentTable,
+	generic::{
+		AttestedCandidateFor, CandidateStatusFor, Config, SignedStatementFor, Summary, Table,
+	},
+	proposal::{PriorityThenBacking, ProposalConfig},
+	store::{KvdbStore, PersistentTable},
+	test_utils::{AuthorityId, Digest, GroupId, TestContext, Workload, WorkloadConfig},
+};
+
+type Import = (GroupId, SignedStatementFor<TestContext>);
+
+// what can be observed of a table after importing statements.
+#[derive(Debug, PartialEq, Eq)]
+struct Observation {
+	summaries: Vec<Option<Summary<Digest, GroupId>>>,
+	candidates: BTreeMap<Digest, (Option<CandidateStatusFor<TestContext>>, bool)>,
+	attested: Vec<AttestedCandidateFor<TestContext>>,
+	// encoded, as misbehavior isn't ordered.
+	misbehavior: BTreeMap<AuthorityId, Vec<Vec<u8>>>,
+	proposal: Vec<AttestedCandidateFor<TestContext>>,
+}
+
+impl Observation {
+	fn of_table(
+		context: &TestContext,
+		table: &Table<TestContext>,
+		imports: &[Import],
+		summaries: Vec<Option<Summary<Digest, GroupId>>>,
+	) -> Self {
+		let votes = context.minimum_backing_votes();
+		Observation {
+			summaries,
+			candidates: digests(imports)
+				.map(|digest| {
+					let known = table.get_candidate(&digest).is_some();
+					(digest, (table.candidate_status(&digest, context, votes), known))
+				})
+				.collect(),
+			attested: sorted(table.attested_candidates(context, votes)),
+			misbehavior: encoded(
+				table.get_misbehavior().iter().map(|(a, m)| (*a, m.iter().cloned())),
+			),
+			proposal: table.build_proposal(
+				context,
+				votes,
+				&ProposalConfig::default(),
+				&PriorityThenBacking,
+			),
+		}
+	}
+}
+
+// the digests of the candidates referenced by `imports`.
+fn digests(imports: &[Import]) -> impl Iterator<Item = Digest> + '_ {
+	imports.iter().map(|(_, statement)| statement.digest::<TestContext>())
+}
+
+fn sorted(
+	mut attested: Vec<AttestedCandidateFor<TestContext>>,
+) -> Vec<AttestedCandidateFor<TestContext>> {
+	attested.sort_by_key(|attested| attested.candidate);
+	attested
+}
+
+fn encoded<M: Encode>(
+	misbehavior: impl Iterator<Item = (AuthorityId, impl Iterator<Item = M>)>,
+) -> BTreeMap<AuthorityId, Vec<Vec<u8>>> {
+	let mut encoded = BTreeMap::<_, Vec<_>>::new();
+	for (authority, found) in misbehavior {
+		encoded.entry(authority).or_default().extend(found.map(|m| m.encode()));
+	}
+	encoded.values_mut().for_each(|found| found.sort());
+	encoded
+}
+
+fn reference(
+	allow_multiple_seconded: bool,
+	context: &TestContext,
+	imports: &[Import],
+) -> Observation {
+	let mut table = Table::new(Config { allow_multiple_seconded });
+	let summaries = imports
+		.iter()
+		.map(|(group, statement)| table.import_statement(context, *group, statement.clone()))
+		.collect();
+	Observation::of_table(context, &table, imports, summaries)
+}
+
+fn concurrent(
+	allow_multiple_seconded: bool,
+	shards: usize,
+	context: &TestContext,
+	imports: &[Import],
+) -> Observation {
+	let table = ConcurrentTable::new(Config { allow_multiple_seconded }, shards);
+	let summaries = imports
+		.iter()
+		.map(|(group, statement)| table.import_statement(context, *group, statement.clone()))
+		.collect();
+
+	// the sharded table answers queries on its own, and the merged table builds proposals.
+	let votes = context.minimum_backing_votes();
+	let candidates = digests(imports)
+		.map(|digest| {
+			let known = table.get_candidate(&digest).is_some();
+			(digest, (table.candidate_status(&digest, context, votes), known))
+		})
+		.collect();
+	let attested = sorted(table.attested_candidates(context, votes));
+	let misbehavior = encoded(table.get_misbehavior().into_iter().map(|(a, m)| (a, m.into_iter())));
+
+	let merged = table.into_table(context);
+	let proposal = Observation::of_table(context, &merged, imports, Vec::new()).proposal;
+	Observation { summaries, candidates, attested, misbehavior, proposal }
+}
+
+fn persistent(
+	allow_multiple_seconded: bool,
+	context: &TestContext,
+	imports: &[Import],
+) -> Observation {
+	let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+	let mut table =
+		PersistentTable::open(context, Config { allow_multiple_seconded }, store).unwrap();
+	let summaries = imports
+		.iter()
+		.map(|(group, statement)| {
+			table.import_statement(context, *group, statement.clone()).unwrap()
+		})
+		.collect();
+	Observation::of_table(context, table.table(), imports, summaries)
+}
+
+fn restored(
+	allow_multiple_seconded: bool,
+	context: &TestContext,
+	imports: &[Import],
+) -> Observation {
+	let mut table = Table::new(Config { allow_multiple_seconded });
+	let summaries = imports
+		.iter()
+		.map(|(group, statement)| table.import_statement(context, *group, statement.clone()))
+		.collect();
+	let restored = Table::restore(context, table.snapshot());
+	Observation::of_table(context, &restored, imports, summaries)
+}
+
+// check every implementation against the reference on the given workload.
+fn check(allow_multiple_seconded: bool, workload: WorkloadConfig) {
+	let workload = Workload::new(workload);
+	let context = workload.context().clone();
+	let imports: Vec<_> = workload.collect();
+
+	let expected = reference(allow_multiple_seconded, &context, &imports);
+	assert!(!expected.attested.is_empty());
+	assert!(!expected.misbehavior.is_empty());
+	for shards in [1, 4] {
+		assert_eq!(
+			concurrent(allow_multiple_seconded, shards, &context, &imports),
+			expected,
+			"{shards} shards"
+		);
+	}
+	assert_eq!(persistent(allow_multiple_seconded, &context, &imports), expected, "persistent");
+	assert_eq!(restored(allow_multiple_seconded, &context, &imports), expected, "restored");
+}
+
+fn workload(seed: u64) -> WorkloadConfig {
+	WorkloadConfig {
+		validators: 30,
+		group_size: 5,
+		candidates: 60,
+		multiple_seconded: true,
+		duplicate_percent: 10,
+		invalid_percent: 10,
+		seed,
+	}
+}
+
+#[test]
+fn implementations_agree_with_multiple_seconded() {
+	for seed in 0..4 {
+		check(true, workload(seed));
+	}
+}
+
+#[test]
+fn implementations_agree_on_misbehavior() {
+	// validators second many candidates, which is misbehavior here.
+	for seed in 0..4 {
+		check(false, workload(seed));
+	}
+}
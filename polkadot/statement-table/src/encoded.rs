@@ -0,0 +1,128 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Candidates kept in encoded form.
+//!
+//! Candidates can be hundreds of kilobytes large. Decoding a statement normally copies its
+//! candidate out of the buffer the statement was received in, and the table then keeps that
+//! copy. A [`Context`](crate::generic::Context) can use [`EncodedCandidate`] as its candidate
+//! type instead: messages decoded with [`decode_from_bytes`] then refer to the receive buffer
+//! rather than copying it, and the table keeps the buffer alive for as long as it holds the
+//! candidate.
+
+use bytes::Bytes;
+use parity_scale_codec::{Decode, Encode, Error as CodecError, Input, Output};
+
+pub use parity_scale_codec::decode_from_bytes;
+
+/// A candidate in encoded form.
+///
+/// It is encoded as a byte vector holding the encoding of the candidate. When decoded with
+/// [`decode_from_bytes`], it shares the input buffer.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EncodedCandidate(Bytes);
+
+impl EncodedCandidate {
+	/// Encode a candidate.
+	pub fn new<C: Encode>(candidate: &C) -> Self {
+		EncodedCandidate(candidate.encode().into())
+	}
+
+	/// Get the encoded candidate.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+
+	/// Decode the candidate.
+	pub fn decode<C: Decode>(&self) -> Result<C, CodecError> {
+		C::decode(&mut self.as_bytes())
+	}
+}
+
+impl From<Bytes> for EncodedCandidate {
+	fn from(bytes: Bytes) -> Self {
+		EncodedCandidate(bytes)
+	}
+}
+
+impl Encode for EncodedCandidate {
+	fn size_hint(&self) -> usize {
+		self.0.size_hint()
+	}
+
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		self.0.encode_to(dest)
+	}
+}
+
+impl Decode for EncodedCandidate {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, CodecError> {
+		Bytes::decode(input).map(EncodedCandidate)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::{Config, Context, SignedStatement, Statement, Table},
+		test_utils::{AuthorityId, Digest, GroupId, TestCandidate, TestSignature},
+	};
+
+	struct EncodedContext;
+
+	impl Context for EncodedContext {
+		type AuthorityId = AuthorityId;
+		type Digest = Digest;
+		type GroupId = GroupId;
+		type Signature = TestSignature;
+		type Candidate = EncodedCandidate;
+
+		fn candidate_digest(candidate: &EncodedCandidate) -> Digest {
+			candidate.decode::<TestCandidate>().map_or(0, |c| c.digest())
+		}
+
+		fn is_member_of(&self, _authority: &AuthorityId, group: &GroupId) -> bool {
+			*group == 2
+		}
+
+		fn get_group_size(&self, _group: &GroupId) -> Option<usize> {
+			Some(1)
+		}
+	}
+
+	#[test]
+	fn candidates_share_receive_buffer() {
+		let candidate = TestCandidate::new(2, 100);
+		let statement = SignedStatement {
+			statement: Statement::<_, Digest>::Seconded(EncodedCandidate::new(&candidate)),
+			signature: TestSignature { signer: 1, payload: Vec::new(), nonce: 0 },
+			sender: 1,
+		};
+		let buffer = Bytes::from(statement.encode());
+
+		let decoded: SignedStatement<EncodedCandidate, Digest, AuthorityId, TestSignature> =
+			decode_from_bytes(buffer.clone()).unwrap();
+		assert_eq!(decoded, statement);
+
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		table.import_statement(&EncodedContext, 2, decoded);
+
+		let shared = table.get_candidate(&candidate.digest()).unwrap();
+		assert!(buffer.as_ptr_range().contains(&shared.as_bytes().as_ptr()));
+		assert_eq!(shared.decode(), Ok(candidate));
+	}
+}
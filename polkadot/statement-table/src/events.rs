@@ -0,0 +1,284 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Structured events for external monitors.
+//!
+//! Monitoring tools and other subsystems can follow the progress of agreement through a
+//! [`TableEvents`] channel, without depending on the internals of the table. A table set up
+//! with [`Table::set_events`](crate::Table::set_events) publishes the candidates which are
+//! proposed and become includable, and the misbehavior it detects. Set up as the
+//! [`RoundObserver`] of an [`Agreement`](crate::agreement::Agreement), the same channel also
+//! publishes the rounds agreement advances to.
+//!
+//! The progress of the groups towards inclusion can be followed through the events with a
+//! [`ProgressTracker`](crate::progress::ProgressTracker).
+//!
+//! Every subscriber has a buffer of [`SUBSCRIBER_BUFFER`] events. Events published while the
+//! buffer of a subscriber is full are dropped for that subscriber, so that a slow monitor can't
+//! grow the memory of the node.
+
+use std::{
+	collections::HashSet,
+	hash::Hash,
+	sync::{Arc, Mutex},
+};
+
+use futures::channel::mpsc;
+
+use crate::{
+	agreement::{Round, RoundObserver},
+	generic::{Context, MisbehaviorKind},
+	LOCK_PROOF,
+};
+
+/// The number of events buffered for each subscriber, beyond which events are dropped.
+pub const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// An event of interest to monitors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableEvent<Digest, GroupId, AuthorityId> {
+	/// A candidate was seconded for the first time.
+	CandidateProposed {
+		/// The digest of the candidate.
+		digest: Digest,
+		/// The group the candidate is in.
+		group_id: GroupId,
+	},
+	/// A candidate received enough validity votes to be included.
+	CandidateIncludable {
+		/// The digest of the candidate.
+		digest: Digest,
+		/// The group the candidate is in.
+		group_id: GroupId,
+	},
+	/// A candidate was removed from the table, e.g. evicted to stay within its memory limit.
+	CandidateRemoved {
+		/// The digest of the candidate.
+		digest: Digest,
+		/// The group the candidate is in.
+		group_id: GroupId,
+	},
+	/// A proposed candidate didn't become available in time, see
+	/// [`AvailabilityTable::advance_time`](crate::availability::AvailabilityTable::advance_time).
+	CandidateUnavailable {
+		/// The digest of the candidate.
+		digest: Digest,
+		/// The group the candidate is in.
+		group_id: GroupId,
+	},
+	/// An authority was detected misbehaving.
+	MisbehaviorDetected {
+		/// The misbehaving authority.
+		authority: AuthorityId,
+		/// The kind of misbehavior.
+		kind: MisbehaviorKind,
+	},
+	/// Agreement advanced to a new round.
+	RoundAdvanced {
+		/// The new round.
+		round: Round,
+	},
+}
+
+/// Type alias for a table event corresponding to context type.
+pub type TableEventFor<Ctx> =
+	TableEvent<<Ctx as Context>::Digest, <Ctx as Context>::GroupId, <Ctx as Context>::AuthorityId>;
+
+struct Inner<Digest, GroupId, AuthorityId> {
+	subscribers: Vec<mpsc::Sender<TableEvent<Digest, GroupId, AuthorityId>>>,
+	// the candidates which were published as includable already in the current round.
+	includable: HashSet<Digest>,
+}
+
+/// A broadcast channel of [`TableEvent`]s. Clones publish to the same subscribers.
+pub struct TableEvents<Digest, GroupId, AuthorityId> {
+	minimum_backing_votes: u32,
+	inner: Arc<Mutex<Inner<Digest, GroupId, AuthorityId>>>,
+}
+
+/// Type alias for a table event channel corresponding to context type.
+pub type TableEventsFor<Ctx> =
+	TableEvents<<Ctx as Context>::Digest, <Ctx as Context>::GroupId, <Ctx as Context>::AuthorityId>;
+
+impl<Digest, GroupId, AuthorityId> Clone for TableEvents<Digest, GroupId, AuthorityId> {
+	fn clone(&self) -> Self {
+		TableEvents { minimum_backing_votes: self.minimum_backing_votes, inner: self.inner.clone() }
+	}
+}
+
+impl<Digest: Hash + Eq + Clone, GroupId: Hash + Eq + Clone, AuthorityId: Clone>
+	TableEvents<Digest, GroupId, AuthorityId>
+{
+	/// Create a channel, considering candidates includable once they have
+	/// `minimum_backing_votes`.
+	pub fn new(minimum_backing_votes: u32) -> Self {
+		TableEvents {
+			minimum_backing_votes,
+			inner: Arc::new(Mutex::new(Inner {
+				subscribers: Vec::new(),
+				includable: HashSet::new(),
+			})),
+		}
+	}
+
+	/// Get a stream of all future events, except for those published while its buffer is full.
+	pub fn subscribe(&self) -> mpsc::Receiver<TableEvent<Digest, GroupId, AuthorityId>> {
+		let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+		self.inner.lock().expect(LOCK_PROOF).subscribers.push(tx);
+		rx
+	}
+
+	/// The number of validity votes candidates need to be considered includable.
+	pub fn minimum_backing_votes(&self) -> u32 {
+		self.minimum_backing_votes
+	}
+
+	// publish the event built by `event`, if anyone is subscribed.
+	pub(crate) fn publish(&self, event: impl FnOnce() -> TableEvent<Digest, GroupId, AuthorityId>) {
+		let mut inner = self.inner.lock().expect(LOCK_PROOF);
+		if inner.subscribers.is_empty() {
+			return
+		}
+
+		let event = event();
+		inner.subscribers.retain_mut(|tx| match tx.try_send(event.clone()) {
+			Ok(()) => true,
+			// the event is dropped for a subscriber which is lagging behind.
+			Err(error) => error.is_full(),
+		});
+	}
+
+	// publish that a candidate was proposed.
+	pub(crate) fn note_proposed(&self, digest: &Digest, group_id: &GroupId) {
+		self.publish(|| TableEvent::CandidateProposed {
+			digest: digest.clone(),
+			group_id: group_id.clone(),
+		});
+	}
+
+	// publish that a candidate is includable, unless it was published already in the current
+	// round.
+	pub(crate) fn note_includable(&self, digest: &Digest, group_id: &GroupId) {
+		let newly_includable =
+			self.inner.lock().expect(LOCK_PROOF).includable.insert(digest.clone());
+		if newly_includable {
+			self.publish(|| TableEvent::CandidateIncludable {
+				digest: digest.clone(),
+				group_id: group_id.clone(),
+			});
+		}
+	}
+
+	// publish that a candidate was removed from the table.
+	pub(crate) fn note_removed(&self, digest: &Digest, group_id: &GroupId) {
+		self.inner.lock().expect(LOCK_PROOF).includable.remove(digest);
+		self.publish(|| TableEvent::CandidateRemoved {
+			digest: digest.clone(),
+			group_id: group_id.clone(),
+		});
+	}
+}
+
+impl<Digest, GroupId, AuthorityId> RoundObserver for TableEvents<Digest, GroupId, AuthorityId>
+where
+	Digest: Hash + Eq + Clone + Send,
+	GroupId: Hash + Eq + Clone + Send,
+	AuthorityId: Clone + Send,
+{
+	fn round_advanced(&self, round: Round) {
+		self.inner.lock().expect(LOCK_PROOF).includable.clear();
+		self.publish(|| TableEvent::RoundAdvanced { round });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		agreement::{tests::contexts, Agreement, Message, SignedMessage},
+		router::tests::{context_and_table, seconded, seconded_other, valid},
+	};
+
+	#[test]
+	fn monitors_receive_events() {
+		let (context, mut table) = context_and_table();
+		let events = TableEvents::new(2);
+		let mut subscriber = events.subscribe();
+		table.set_events(events.clone());
+
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		table.import_statement(&context, 0, seconded_other(1));
+
+		// agreement joins two authorities advancing from round 3.
+		let agreement_context = contexts(4, &[], &[42]).remove(2);
+		let (mut agreement, _) = Agreement::new(&agreement_context);
+		agreement.add_round_observer(Arc::new(events.clone()));
+		let other_events = TableEvents::<u64, u32, u32>::new(2);
+		let mut other_subscriber = other_events.subscribe();
+		agreement.add_round_observer(Arc::new(other_events));
+		for sender in [0, 1] {
+			let message =
+				SignedMessage { message: Message::AdvanceRound(3), signature: sender, sender };
+			agreement.import_message(&agreement_context, message);
+		}
+
+		let received: Vec<_> =
+			std::iter::from_fn(|| subscriber.try_next().ok().flatten()).collect();
+		assert_eq!(
+			received,
+			vec![
+				TableEvent::CandidateProposed { digest: 100, group_id: 0 },
+				TableEvent::CandidateIncludable { digest: 100, group_id: 0 },
+				TableEvent::MisbehaviorDetected {
+					authority: 1,
+					kind: MisbehaviorKind::MultipleCandidates
+				},
+				TableEvent::RoundAdvanced { round: 4 },
+			]
+		);
+		assert_eq!(
+			other_subscriber.try_next().unwrap(),
+			Some(TableEvent::RoundAdvanced { round: 4 })
+		);
+
+		// includable candidates are published again in a new round.
+		events.round_advanced(5);
+		assert!(events.inner.lock().unwrap().includable.is_empty());
+
+		// dropped subscribers are forgotten.
+		drop(subscriber);
+		events.round_advanced(2);
+		assert!(events.inner.lock().unwrap().subscribers.is_empty());
+	}
+
+	#[test]
+	fn lagging_subscribers_miss_events() {
+		let events = TableEvents::<u64, u32, u32>::new(2);
+		let mut subscriber = events.subscribe();
+		for round in 0..SUBSCRIBER_BUFFER as u32 + 10 {
+			events.round_advanced(round);
+		}
+
+		let received = std::iter::from_fn(|| subscriber.try_next().ok().flatten()).count();
+		assert!(received < SUBSCRIBER_BUFFER + 10);
+		assert_eq!(events.inner.lock().unwrap().subscribers.len(), 1);
+
+		// the subscriber receives events again once it caught up.
+		events.round_advanced(0);
+		assert_eq!(subscriber.try_next().unwrap(), Some(TableEvent::RoundAdvanced { round: 0 }));
+	}
+}
@@ -25,44 +25,48 @@
 //! has signed validity statements, the candidate may be marked includable.
 
 use std::{
-	collections::hash_map::{self, Entry, HashMap},
-	fmt::Debug,
-	hash::Hash,
+	borrow::Cow,
+	cmp::Reverse,
+	collections::{
+		btree_map::BTreeMap,
+		hash_map::{self, Entry, HashMap, RandomState},
+		HashSet,
+	},
+	fmt::{self, Debug},
+	hash::BuildHasher,
+	mem,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, OnceLock,
+	},
+	time::{Duration, Instant},
 };
 
-use primitives::{
-	effective_minimum_backing_votes, ValidatorSignature,
-	ValidityAttestation as PrimitiveValidityAttestation,
-};
+use primitives::effective_minimum_backing_votes;
 
 use parity_scale_codec::{Decode, Encode};
-const LOG_TARGET: &str = "parachain::statement-table";
-
-/// Context for the statement table.
-pub trait Context {
-	/// An authority ID
-	type AuthorityId: Debug + Hash + Eq + Clone;
-	/// The digest (hash or other unique attribute) of a candidate.
-	type Digest: Debug + Hash + Eq + Clone;
-	/// The group ID type
-	type GroupId: Debug + Hash + Ord + Eq + Clone;
-	/// A signature type.
-	type Signature: Debug + Eq + Clone;
-	/// Candidate type. In practice this will be a candidate receipt.
-	type Candidate: Debug + Ord + Eq + Clone;
-
-	/// get the digest of a candidate.
-	fn candidate_digest(candidate: &Self::Candidate) -> Self::Digest;
-
-	/// Whether a authority is a member of a group.
-	/// Members are meant to submit candidates and vote on validity.
-	fn is_member_of(&self, authority: &Self::AuthorityId, group: &Self::GroupId) -> bool;
+use smallvec::{smallvec, SmallVec};
+use sp_core::blake2_256;
+
+pub use crate::statement::{
+	signing_payload, AttestationError, AttestedCandidate, AttestedCandidateFor, CandidateStatus,
+	CandidateStatusFor, CompactSignedStatement, CompactSignedStatementFor, CompactStatement,
+	Context, DoubleSign, GroupAssignment, Misbehavior, MisbehaviorFor, MisbehaviorKind,
+	MultipleCandidates, SignedStatement, SignedStatementFor, Statement, UnauthorizedStatement,
+	ValidatorSet, ValidityAttestation, ValidityDoubleVote,
+};
 
-	/// Get a validator group size.
-	fn get_group_size(&self, group: &Self::GroupId) -> Option<usize>;
-}
+use crate::{
+	builder::TableBuilder,
+	events::{TableEvent, TableEventsFor},
+	metrics::Metrics,
+	recording::Recorder,
+	stats::{OperationStats, Stats, TableStats},
+};
+const LOG_TARGET: &str = "parachain::statement-table";
 
 /// Table configuration.
+#[derive(Debug, Default, Clone)]
 pub struct Config {
 	/// When this is true, the table will allow multiple seconded candidates
 	/// per authority. This flag means that higher-level code is responsible for
@@ -70,116 +74,70 @@ pub struct Config {
 	pub allow_multiple_seconded: bool,
 }
 
-/// Statements circulated among peers.
-#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
-pub enum Statement<Candidate, Digest> {
-	/// Broadcast by an authority to indicate that this is its candidate for inclusion.
-	///
-	/// Broadcasting two different candidate messages per round is not allowed.
-	#[codec(index = 1)]
-	Seconded(Candidate),
-	/// Broadcast by a authority to attest that the candidate with given digest is valid.
-	#[codec(index = 2)]
-	Valid(Digest),
+/// A bound on the memory used by a table, see [`Table::set_memory_limit`].
+#[derive(Debug)]
+pub struct MemoryLimit<Candidate> {
+	/// The maximum approximate number of bytes used by the candidates and votes in the table.
+	pub max_bytes: usize,
+	/// Estimate the heap memory owned by a candidate, on top of its inline size.
+	pub candidate_heap_size: fn(&Candidate) -> usize,
 }
 
-/// A signed statement.
-#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
-pub struct SignedStatement<Candidate, Digest, AuthorityId, Signature> {
-	/// The statement.
-	pub statement: Statement<Candidate, Digest>,
-	/// The signature.
-	pub signature: Signature,
-	/// The sender.
-	pub sender: AuthorityId,
+impl<Candidate> Clone for MemoryLimit<Candidate> {
+	fn clone(&self) -> Self {
+		*self
+	}
 }
 
-/// Misbehavior: voting more than one way on candidate validity.
-///
-/// Since there are three possible ways to vote, a double vote is possible in
-/// three possible combinations (unordered)
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub enum ValidityDoubleVote<Candidate, Digest, Signature> {
-	/// Implicit vote by issuing and explicitly voting validity.
-	IssuedAndValidity((Candidate, Signature), (Digest, Signature)),
-}
+impl<Candidate> Copy for MemoryLimit<Candidate> {}
 
-impl<Candidate, Digest, Signature> ValidityDoubleVote<Candidate, Digest, Signature> {
-	/// Deconstruct this misbehavior into two `(Statement, Signature)` pairs, erasing the
-	/// information about precisely what the problem was.
-	pub fn deconstruct<Ctx>(
-		self,
-	) -> ((Statement<Candidate, Digest>, Signature), (Statement<Candidate, Digest>, Signature))
-	where
-		Ctx: Context<Candidate = Candidate, Digest = Digest, Signature = Signature>,
-		Candidate: Debug + Ord + Eq + Clone,
-		Digest: Debug + Hash + Eq + Clone,
-		Signature: Debug + Eq + Clone,
-	{
-		match self {
-			Self::IssuedAndValidity((c, s1), (d, s2)) =>
-				((Statement::Seconded(c), s1), (Statement::Valid(d), s2)),
-		}
+impl<Candidate> MemoryLimit<Candidate> {
+	/// Bound the table to `max_bytes`, accounting candidates by their inline size only.
+	pub fn new(max_bytes: usize) -> Self {
+		MemoryLimit { max_bytes, candidate_heap_size: |_| 0 }
 	}
 }
 
-/// Misbehavior: multiple signatures on same statement.
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub enum DoubleSign<Candidate, Digest, Signature> {
-	/// On candidate.
-	Seconded(Candidate, Signature, Signature),
-	/// On validity.
-	Validity(Digest, Signature, Signature),
+impl<Candidate: Encode> MemoryLimit<Candidate> {
+	/// Bound the table to `max_bytes`, estimating the heap memory of candidates by their
+	/// encoded size.
+	pub fn encoded(max_bytes: usize) -> Self {
+		MemoryLimit { max_bytes, candidate_heap_size: |candidate| candidate.encoded_size() }
+	}
 }
 
-impl<Candidate, Digest, Signature> DoubleSign<Candidate, Digest, Signature> {
-	/// Deconstruct this misbehavior into a statement with two signatures, erasing the information
-	/// about precisely where in the process the issue was detected.
-	pub fn deconstruct(self) -> (Statement<Candidate, Digest>, Signature, Signature) {
-		match self {
-			Self::Seconded(candidate, a, b) => (Statement::Seconded(candidate), a, b),
-			Self::Validity(digest, a, b) => (Statement::Valid(digest), a, b),
-		}
-	}
+/// Bounds on the contents of a table, see [`Table::set_limits`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TableLimits {
+	/// The maximum number of candidates each authority seconds in a group, when it is allowed
+	/// to second several, see [`Config`]. Statements seconding further candidates which aren't
+	/// in the table are rejected with [`ImportError::TableFull`], so that no authority can
+	/// crowd out the candidates of others.
+	pub max_candidates_per_authority: Option<usize>,
+	/// The maximum number of statements buffered for candidates whose body is pending, see
+	/// [`Table::note_announcement`]. Further announcements are dropped.
+	pub max_buffered_votes: Option<usize>,
 }
 
-/// Misbehavior: declaring multiple candidates.
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub struct MultipleCandidates<Candidate, Signature> {
-	/// The first candidate seen.
-	pub first: (Candidate, Signature),
-	/// The second candidate seen.
-	pub second: (Candidate, Signature),
+/// Provides the candidates which a table doesn't keep in memory, see
+/// [`Table::set_candidate_provider`].
+///
+/// This is implemented for closures from the digest of a candidate.
+pub trait CandidateProvider<Ctx: Context>: Send + Sync {
+	/// Get the candidate with the given `digest`, or `None` if it is unavailable.
+	fn candidate(&self, digest: &Ctx::Digest) -> Option<Arc<Ctx::Candidate>>;
 }
 
-/// Misbehavior: submitted statement for wrong group.
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub struct UnauthorizedStatement<Candidate, Digest, AuthorityId, Signature> {
-	/// A signed statement which was submitted without proper authority.
-	pub statement: SignedStatement<Candidate, Digest, AuthorityId, Signature>,
+impl<Ctx: Context, F> CandidateProvider<Ctx> for F
+where
+	F: Fn(&Ctx::Digest) -> Option<Arc<Ctx::Candidate>> + Send + Sync,
+{
+	fn candidate(&self, digest: &Ctx::Digest) -> Option<Arc<Ctx::Candidate>> {
+		self(digest)
+	}
 }
 
-/// Different kinds of misbehavior. All of these kinds of malicious misbehavior
-/// are easily provable and extremely disincentivized.
-#[derive(PartialEq, Eq, Debug, Clone)]
-pub enum Misbehavior<Candidate, Digest, AuthorityId, Signature> {
-	/// Voted invalid and valid on validity.
-	ValidityDoubleVote(ValidityDoubleVote<Candidate, Digest, Signature>),
-	/// Submitted multiple candidates.
-	MultipleCandidates(MultipleCandidates<Candidate, Signature>),
-	/// Submitted a message that was unauthorized.
-	UnauthorizedStatement(UnauthorizedStatement<Candidate, Digest, AuthorityId, Signature>),
-	/// Submitted two valid signatures for the same message.
-	DoubleSign(DoubleSign<Candidate, Digest, Signature>),
-}
-
-/// Type alias for misbehavior corresponding to context type.
-pub type MisbehaviorFor<Ctx> = Misbehavior<
-	<Ctx as Context>::Candidate,
-	<Ctx as Context>::Digest,
-	<Ctx as Context>::AuthorityId,
-	<Ctx as Context>::Signature,
->;
+type SharedProvider<Ctx> = Arc<dyn CandidateProvider<Ctx>>;
 
 // Kinds of votes for validity on a particular candidate.
 #[derive(Clone, PartialEq, Eq)]
@@ -190,8 +148,13 @@ enum ValidityVote<Signature: Eq + Clone> {
 	Valid(Signature),
 }
 
+/// A point in the sequence of statements imported into a [`Table`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+pub struct StatementMarker(u64);
+
 /// A summary of import of a statement.
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Summary<Digest, Group> {
 	/// The digest of the candidate referenced.
 	pub candidate: Digest,
@@ -201,56 +164,128 @@ pub struct Summary<Digest, Group> {
 	pub validity_votes: usize,
 }
 
-/// A validity attestation.
-#[derive(Clone, PartialEq, Decode, Encode)]
-pub enum ValidityAttestation<Signature> {
-	/// implicit validity attestation by issuing.
-	/// This corresponds to issuance of a `Candidate` statement.
-	Implicit(Signature),
-	/// An explicit attestation. This corresponds to issuance of a
-	/// `Valid` statement.
-	Explicit(Signature),
+// the candidate of a statement being imported. statements imported by reference lend their
+// candidate, which is only copied once the table needs to keep it.
+enum IncomingCandidate<'a, Candidate> {
+	Shared(Arc<Candidate>),
+	Borrowed(&'a Candidate),
 }
 
-impl Into<PrimitiveValidityAttestation> for ValidityAttestation<ValidatorSignature> {
-	fn into(self) -> PrimitiveValidityAttestation {
+impl<Candidate> Clone for IncomingCandidate<'_, Candidate> {
+	fn clone(&self) -> Self {
 		match self {
-			Self::Implicit(s) => PrimitiveValidityAttestation::Implicit(s),
-			Self::Explicit(s) => PrimitiveValidityAttestation::Explicit(s),
+			IncomingCandidate::Shared(candidate) => IncomingCandidate::Shared(candidate.clone()),
+			IncomingCandidate::Borrowed(candidate) => IncomingCandidate::Borrowed(candidate),
 		}
 	}
 }
 
-/// An attested-to candidate.
-#[derive(Clone, PartialEq, Decode, Encode)]
-pub struct AttestedCandidate<Group, Candidate, AuthorityId, Signature> {
-	/// The group ID that the candidate is in.
-	pub group_id: Group,
-	/// The candidate data.
-	pub candidate: Candidate,
-	/// Validity attestations.
-	pub validity_votes: Vec<(AuthorityId, ValidityAttestation<Signature>)>,
+impl<Candidate: Clone> IncomingCandidate<'_, Candidate> {
+	fn get(&self) -> &Candidate {
+		match self {
+			IncomingCandidate::Shared(candidate) => candidate,
+			IncomingCandidate::Borrowed(candidate) => candidate,
+		}
+	}
+
+	fn into_shared(self) -> Arc<Candidate> {
+		match self {
+			IncomingCandidate::Shared(candidate) => candidate,
+			IncomingCandidate::Borrowed(candidate) => Arc::new(candidate.clone()),
+		}
+	}
+
+	fn into_owned(self) -> Candidate {
+		match self {
+			IncomingCandidate::Shared(candidate) => Arc::unwrap_or_clone(candidate),
+			IncomingCandidate::Borrowed(candidate) => candidate.clone(),
+		}
+	}
 }
 
 /// Stores votes and data about a candidate.
+///
+/// The candidate is shared, so that handing it out doesn't require copying it.
 pub struct CandidateData<Ctx: Context> {
 	group_id: Ctx::GroupId,
-	candidate: Ctx::Candidate,
-	validity_votes: HashMap<Ctx::AuthorityId, ValidityVote<Ctx::Signature>>,
+	// empty if the table has a candidate provider, until the candidate is borrowed with
+	// `get_candidate`.
+	candidate: OnceLock<Arc<Ctx::Candidate>>,
+	validity_votes: VoteMap<Ctx::AuthorityId, ValidityVote<Ctx::Signature>>,
+	// the number of `ValidityVote::Issued` in `validity_votes`, kept up to date on import.
+	seconded_votes: usize,
+	// the summed `Context::vote_weight` of the authorities in `validity_votes`, kept up to date
+	// on import. invalidity and availability aren't voted on in this table, so there is nothing
+	// else to tally.
+	validity_weight: u64,
+	// the estimated heap memory owned by the candidate.
+	heap_size: usize,
+	// the estimated heap memory owned by the candidate since it was borrowed from the candidate
+	// provider, which isn't in `heap_size`.
+	borrowed_size: OnceLock<usize>,
+	// the position in the import log of the first vote on the candidate.
+	imported_at: u64,
+	// the positions in the import log of `validity_votes`, in the same order.
+	logged_at: SmallVec<[u64; EXPECTED_GROUP_SIZE]>,
+	// when the candidate was first seconded, if votes were timed then, see `Table::is_timed`.
+	proposed_at: Option<Instant>,
+	// how long after `proposed_at` each of `validity_votes` was imported, in the same order.
+	voted_after: SmallVec<[Option<Duration>; EXPECTED_GROUP_SIZE]>,
+	// whether the age of the candidate was recorded into the metrics, see
+	// `Table::count_includable`. unlike `includable_votes`, it isn't reset by reverts.
+	age_recorded: bool,
+	// the number of validity votes at which the candidate was counted as includable into the
+	// metrics, see `Table::count_includable`.
+	includable_votes: Option<usize>,
+	// the minimum backing votes of the group of the candidate, if overridden, see
+	// `Table::set_backing_threshold`.
+	backing_threshold: Option<u32>,
+}
+
+impl<Ctx: Context> Clone for CandidateData<Ctx> {
+	fn clone(&self) -> Self {
+		CandidateData {
+			group_id: self.group_id.clone(),
+			candidate: self.candidate.clone(),
+			validity_votes: self.validity_votes.clone(),
+			seconded_votes: self.seconded_votes,
+			validity_weight: self.validity_weight,
+			heap_size: self.heap_size,
+			borrowed_size: self.borrowed_size.clone(),
+			imported_at: self.imported_at,
+			logged_at: self.logged_at.clone(),
+			proposed_at: self.proposed_at,
+			voted_after: self.voted_after.clone(),
+			age_recorded: self.age_recorded,
+			includable_votes: self.includable_votes,
+			backing_threshold: self.backing_threshold,
+		}
+	}
 }
 
 impl<Ctx: Context> CandidateData<Ctx> {
 	/// Yield a full attestation for a candidate.
-	/// If the candidate can be included, it will return `Some`.
+	/// If the candidate can be included and is held in memory, it will return `Some`.
 	pub fn attested(
 		&self,
 		validity_threshold: usize,
 	) -> Option<AttestedCandidate<Ctx::GroupId, Ctx::Candidate, Ctx::AuthorityId, Ctx::Signature>> {
+		self.attest(validity_threshold, || self.candidate.get().cloned())
+	}
+
+	// attest the candidate, hydrating it with `candidate` only if it can be included.
+	fn attest(
+		&self,
+		validity_threshold: usize,
+		candidate: impl FnOnce() -> Option<Arc<Ctx::Candidate>>,
+	) -> Option<AttestedCandidateFor<Ctx>> {
 		let valid_votes = self.validity_votes.len();
 		if valid_votes < validity_threshold {
 			return None
 		}
 
+		let candidate = candidate()?;
+
 		let validity_votes = self
 			.validity_votes
 			.iter()
@@ -263,11 +298,39 @@ impl<Ctx: Context> CandidateData<Ctx> {
 
 		Some(AttestedCandidate {
 			group_id: self.group_id.clone(),
-			candidate: self.candidate.clone(),
+			candidate: Arc::unwrap_or_clone(candidate),
 			validity_votes,
 		})
 	}
 
+	// the candidate, from memory or else from `provider`.
+	fn hydrate(
+		&self,
+		digest: &Ctx::Digest,
+		provider: Option<&dyn CandidateProvider<Ctx>>,
+	) -> Option<Arc<Ctx::Candidate>> {
+		self.candidate.get().cloned().or_else(|| provider?.candidate(digest))
+	}
+
+	// borrow the candidate, keeping it in memory from then on if it is provided, and counting
+	// its heap memory in `borrowed`.
+	fn borrow(
+		&self,
+		digest: &Ctx::Digest,
+		provider: Option<&dyn CandidateProvider<Ctx>>,
+		borrowed: &BorrowedMemory<Ctx::Candidate>,
+	) -> Option<&Ctx::Candidate> {
+		if self.candidate.get().is_none() {
+			let candidate = provider?.candidate(digest)?;
+			let size = (borrowed.candidate_heap_size)(&candidate);
+			if self.candidate.set(candidate).is_ok() && self.borrowed_size.set(size).is_ok() {
+				borrowed.bytes.fetch_add(size, Ordering::Relaxed);
+			}
+		}
+
+		self.candidate.get().map(|candidate| &**candidate)
+	}
+
 	fn summary(&self, digest: Ctx::Digest) -> Summary<Ctx::Digest, Ctx::GroupId> {
 		Summary {
 			candidate: digest,
@@ -275,16 +338,104 @@ impl<Ctx: Context> CandidateData<Ctx> {
 			validity_votes: self.validity_votes.len(),
 		}
 	}
+
+	fn status(&self, context: &Ctx, minimum_backing_votes: u32) -> CandidateStatusFor<Ctx> {
+		CandidateStatus {
+			group_id: self.group_id.clone(),
+			validity_votes: self.validity_votes.len(),
+			seconded_votes: self.seconded_votes,
+			validity_weight: self.validity_weight,
+			validity_threshold: context.get_group_size(&self.group_id).map_or(usize::MAX, |len| {
+				let minimum_backing_votes = self.backing_threshold.unwrap_or(minimum_backing_votes);
+				effective_minimum_backing_votes(len, minimum_backing_votes)
+			}),
+			available_chunks: None,
+			availability_threshold: None,
+			flagged_invalid_by: Vec::new(),
+		}
+	}
+}
+
+// a candidate which was announced by digest but whose body is still pending.
+struct Announced<Ctx: Context> {
+	group_id: Ctx::GroupId,
+	// the authorities which seconded the candidate, with their signatures.
+	seconders: Vec<(Ctx::AuthorityId, Ctx::Signature)>,
+}
+
+impl<Ctx: Context> Clone for Announced<Ctx> {
+	fn clone(&self) -> Self {
+		Announced { group_id: self.group_id.clone(), seconders: self.seconders.clone() }
+	}
+}
+
+// the expected number of members of a group. collections of votes of this size are kept
+// inline, so that importing votes on a candidate doesn't allocate for typical groups.
+const EXPECTED_GROUP_SIZE: usize = 5;
+
+// a map of the votes on a candidate. groups are small, so this is a vector searched linearly.
+#[derive(Debug, Clone)]
+struct VoteMap<AuthorityId, Vote> {
+	votes: SmallVec<[(AuthorityId, Vote); EXPECTED_GROUP_SIZE]>,
+}
+
+impl<AuthorityId: Eq, Vote> VoteMap<AuthorityId, Vote> {
+	fn new() -> Self {
+		VoteMap { votes: SmallVec::new() }
+	}
+
+	fn len(&self) -> usize {
+		self.votes.len()
+	}
+
+	fn is_empty(&self) -> bool {
+		self.votes.is_empty()
+	}
+
+	fn iter(&self) -> impl Iterator<Item = (&AuthorityId, &Vote)> {
+		self.votes.iter().map(|(authority, vote)| (authority, vote))
+	}
+
+	fn get(&self, authority: &AuthorityId) -> Option<&Vote> {
+		self.votes.iter().find(|(a, _)| a == authority).map(|(_, vote)| vote)
+	}
+
+	fn contains_key(&self, authority: &AuthorityId) -> bool {
+		self.get(authority).is_some()
+	}
+
+	// remove the vote at `index` in order of insertion.
+	fn remove(&mut self, index: usize) -> Vote {
+		self.votes.remove(index).1
+	}
+
+	// returns the previous vote of the authority, if any.
+	fn insert(&mut self, authority: AuthorityId, vote: Vote) -> Option<Vote> {
+		match self.votes.iter_mut().find(|(a, _)| *a == authority) {
+			Some((_, existing)) => Some(mem::replace(existing, vote)),
+			None => {
+				self.votes.push((authority, vote));
+				None
+			},
+		}
+	}
 }
 
 // authority metadata
 struct AuthorityData<Ctx: Context> {
-	proposals: Vec<(Ctx::Digest, Ctx::Signature)>,
+	// an authority usually seconds a single candidate per group.
+	proposals: SmallVec<[(Ctx::Digest, Ctx::Signature); 1]>,
 }
 
 impl<Ctx: Context> Default for AuthorityData<Ctx> {
 	fn default() -> Self {
-		AuthorityData { proposals: Vec::new() }
+		AuthorityData { proposals: SmallVec::new() }
+	}
+}
+
+impl<Ctx: Context> Clone for AuthorityData<Ctx> {
+	fn clone(&self) -> Self {
+		AuthorityData { proposals: self.proposals.clone() }
 	}
 }
 
@@ -294,25 +445,562 @@ pub type ImportResult<Ctx> = Result<
 	MisbehaviorFor<Ctx>,
 >;
 
+/// Why a statement wasn't imported, see [`Table::try_import_statement`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportError {
+	/// The context doesn't know the signer as a member of the group of the candidate. This is
+	/// noted as misbehavior.
+	UnknownSigner,
+	/// The statement votes on a candidate which isn't in the table.
+	UnknownCandidate,
+	/// The same statement was imported before.
+	Duplicate,
+	/// The statement conflicts with statements of the signer imported before, which is noted
+	/// as misbehavior of the given kind.
+	Misbehavior(MisbehaviorKind),
+	/// The statement seconds a candidate the context doesn't accept in the group, see
+	/// [`Context::is_acceptable`].
+	RejectedByContext,
+	/// The statement would prove misbehavior, but the candidate needed for the proof couldn't
+	/// be provided.
+	CandidateUnavailable,
+	/// The candidate of the statement was evicted right away to stay within the memory limit.
+	Evicted,
+	/// The statement seconds a new candidate, but its signer seconded as many candidates in
+	/// the group as the [`TableLimits`] allow.
+	TableFull,
+}
+
+// why a vote wasn't counted.
+enum Rejected<Ctx: Context> {
+	Ignored(ImportError),
+	Misbehavior(MisbehaviorFor<Ctx>),
+}
+
+impl<Ctx: Context> From<MisbehaviorFor<Ctx>> for Rejected<Ctx> {
+	fn from(misbehavior: MisbehaviorFor<Ctx>) -> Self {
+		Rejected::Misbehavior(misbehavior)
+	}
+}
+
+type VoteResult<Ctx> =
+	Result<Summary<<Ctx as Context>::Digest, <Ctx as Context>::GroupId>, Rejected<Ctx>>;
+
+/// A snapshot of a [`Table`], see [`Table::snapshot`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableSnapshot<Candidate, Digest, GroupId, AuthorityId, Signature> {
+	/// Whether multiple seconded candidates per authority are allowed, see [`Config`].
+	pub allow_multiple_seconded: bool,
+	/// The imported statements in order of import, along with the group of their candidate.
+	pub statements: Vec<(GroupId, SignedStatement<Candidate, Digest, AuthorityId, Signature>)>,
+	/// The announced candidates whose body is pending, along with their group and the
+	/// authorities which seconded them.
+	pub announced: Vec<(Digest, GroupId, Vec<(AuthorityId, Signature)>)>,
+	/// The detected misbehavior.
+	pub misbehavior:
+		Vec<(AuthorityId, Vec<Misbehavior<Candidate, Digest, AuthorityId, Signature>>)>,
+}
+
+/// Type alias for a table snapshot corresponding to context type.
+pub type TableSnapshotFor<Ctx> = TableSnapshot<
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// A dump of the state of a [`Table`] for debugging, see [`Table::export_debug`].
+///
+/// Next to the statements the table can be restored from, this has the state derived from
+/// them, so that the dump can be inspected without replaying it. It is meant to be attached to
+/// bug reports, e.g. pretty-printed with `{:#?}` or serialized with the `serde` feature.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableDump<Candidate, Digest, GroupId, AuthorityId, Signature> {
+	/// The statements, announcements and misbehavior the table can be restored from.
+	pub snapshot: TableSnapshot<Candidate, Digest, GroupId, AuthorityId, Signature>,
+	/// The status of every candidate, in order of import.
+	pub candidates: Vec<(Digest, CandidateStatus<GroupId, AuthorityId>)>,
+	/// The commitment to the statements in the table, see [`Table::state_root`].
+	pub state_root: [u8; 32],
+	/// The approximate memory used by the table, see [`Table::memory_usage`].
+	pub memory_usage: usize,
+}
+
+/// Type alias for a table dump corresponding to context type.
+pub type TableDumpFor<Ctx> = TableDump<
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
 /// Stores votes
-pub struct Table<Ctx: Context> {
-	authority_data: HashMap<Ctx::AuthorityId, AuthorityData<Ctx>>,
-	detected_misbehavior: HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>>,
-	candidate_votes: HashMap<Ctx::Digest, CandidateData<Ctx>>,
+///
+/// Authority IDs and digests are hashed on every import. By default they are hashed with the
+/// DoS-resistant [`RandomState`]; a faster hasher can be chosen with [`Table::with_hasher`]
+/// where keys can't be chosen by an attacker, or are keyed randomly by the hasher.
+pub struct Table<Ctx: Context, S = RandomState> {
+	// the proposals of each authority by group, as an authority may legitimately propose in more
+	// than one group. they are kept when their candidates are evicted.
+	authority_data: HashMap<Ctx::AuthorityId, BTreeMap<Ctx::GroupId, AuthorityData<Ctx>>, S>,
+	detected_misbehavior: HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>, S>,
+	// shared with views of the table, and copied on write while shared.
+	candidate_votes: HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	announced: HashMap<Ctx::Digest, Announced<Ctx>, S>,
+	// the number of seconders of `announced`.
+	pending_votes: usize,
+	// Every vote in the table, keyed by its position in order of import, see
+	// `statements_since`. Votes on evicted candidates are removed.
+	import_log: BTreeMap<u64, (Ctx::Digest, Ctx::AuthorityId)>,
+	// the position of the next imported vote.
+	next_position: u64,
+	// the candidates in order of eviction, keyed by their number of votes and the reverse of
+	// their position of import, see `set_memory_limit`.
+	eviction_order: BTreeMap<(usize, Reverse<u64>), Ctx::Digest>,
 	config: Config,
+	memory_limit: Option<MemoryLimit<Ctx::Candidate>>,
+	// the memory used by the candidates and votes, except for `borrowed_memory`.
+	memory_usage: usize,
+	borrowed_memory: Arc<BorrowedMemory<Ctx::Candidate>>,
+	limits: TableLimits,
+	// the minimum backing votes of groups, where overridden.
+	backing_thresholds: HashMap<Ctx::GroupId, u32, S>,
+	stats: Arc<Stats>,
+	track_vote_latency: bool,
+	vote_latency: HashMap<Ctx::AuthorityId, OperationStats, S>,
+	metrics: Metrics,
+	events: Option<TableEventsFor<Ctx>>,
+	candidate_provider: Option<SharedProvider<Ctx>>,
+	recorder: Option<Arc<Recorder<Ctx>>>,
+}
+
+/// A fork of the table, which imports statements independently of it from then on, e.g. to
+/// explore alternative branches in tests.
+///
+/// Like a [`TableView`], the fork shares the votes on each candidate with the table until
+/// either imports a vote on it, but the rest of the table, e.g. its log of statements and the
+/// misbehavior detected, is copied. The fork shares the candidate provider of the table, but
+/// has its own [`Table::stats`] and no metrics, events or recorder, so that nothing it imports
+/// is observed as imported by the table.
+impl<Ctx: Context, S: Clone> Clone for Table<Ctx, S> {
+	fn clone(&self) -> Self {
+		Table {
+			authority_data: self.authority_data.clone(),
+			detected_misbehavior: self.detected_misbehavior.clone(),
+			candidate_votes: self.candidate_votes.clone(),
+			announced: self.announced.clone(),
+			pending_votes: self.pending_votes,
+			import_log: self.import_log.clone(),
+			next_position: self.next_position,
+			eviction_order: self.eviction_order.clone(),
+			config: self.config.clone(),
+			memory_limit: self.memory_limit,
+			memory_usage: self.memory_usage,
+			borrowed_memory: Arc::new(BorrowedMemory {
+				bytes: AtomicUsize::new(self.borrowed_memory.get()),
+				candidate_heap_size: self.borrowed_memory.candidate_heap_size,
+			}),
+			limits: self.limits,
+			backing_thresholds: self.backing_thresholds.clone(),
+			stats: Arc::new((*self.stats).clone()),
+			track_vote_latency: self.track_vote_latency,
+			vote_latency: self.vote_latency.clone(),
+			metrics: Metrics::default(),
+			events: None,
+			candidate_provider: self.candidate_provider.clone(),
+			recorder: None,
+		}
+	}
+}
+
+/// A summary of the table, leaving out candidates, votes and signatures.
+impl<Ctx: Context, S> Debug for Table<Ctx, S> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Table")
+			.field("candidates", &self.candidate_votes.len())
+			.field("statements", &self.import_log.len())
+			.field("pending_bodies", &self.announced.len())
+			.field("misbehaving_authorities", &self.detected_misbehavior.len())
+			.field("memory_usage", &(self.memory_usage + self.borrowed_memory.get()))
+			.field("config", &self.config)
+			.finish_non_exhaustive()
+	}
 }
 
 impl<Ctx: Context> Table<Ctx> {
 	/// Create a new `Table` from a `Config`.
+	///
+	/// This is a shortcut for a [`TableBuilder`] without limits or observers.
 	pub fn new(config: Config) -> Self {
+		Table::with_hasher(config, RandomState::new())
+	}
+
+	/// A builder of a table with the default configuration.
+	pub fn builder() -> TableBuilder<Ctx> {
+		TableBuilder::new()
+	}
+
+	/// Restore a table from a snapshot taken with [`Table::snapshot`].
+	///
+	/// The statements are imported again in their original order, so the restored table is in
+	/// the same state as the one the snapshot was taken of.
+	pub fn restore(context: &Ctx, snapshot: TableSnapshotFor<Ctx>) -> Self {
+		Table::restore_with_hasher(context, snapshot, RandomState::new())
+	}
+
+	/// Replay a dump taken with [`Table::export_debug`], e.g. one attached to a bug report.
+	///
+	/// Only the statements, announcements and misbehavior of the dump are imported. The state
+	/// derived from them can be compared with the original by exporting the replayed table.
+	pub fn import_debug(context: &Ctx, dump: TableDumpFor<Ctx>) -> Self {
+		Table::restore(context, dump.snapshot)
+	}
+}
+
+impl<Ctx: Context, S: BuildHasher + Clone> Table<Ctx, S> {
+	/// Create a new `Table` from a `Config`, hashing keys with `hasher`.
+	pub fn with_hasher(config: Config, hasher: S) -> Self {
 		Table {
-			authority_data: HashMap::default(),
-			detected_misbehavior: HashMap::default(),
-			candidate_votes: HashMap::default(),
+			authority_data: HashMap::with_hasher(hasher.clone()),
+			detected_misbehavior: HashMap::with_hasher(hasher.clone()),
+			candidate_votes: HashMap::with_hasher(hasher.clone()),
+			announced: HashMap::with_hasher(hasher.clone()),
+			pending_votes: 0,
+			import_log: BTreeMap::new(),
+			next_position: 0,
+			eviction_order: BTreeMap::new(),
 			config,
+			memory_limit: None,
+			memory_usage: 0,
+			borrowed_memory: Arc::new(BorrowedMemory::new(|_| 0)),
+			limits: TableLimits::default(),
+			backing_thresholds: HashMap::with_hasher(hasher.clone()),
+			stats: Arc::new(Stats::default()),
+			track_vote_latency: false,
+			vote_latency: HashMap::with_hasher(hasher),
+			metrics: Metrics::default(),
+			events: None,
+			candidate_provider: None,
+			recorder: None,
+		}
+	}
+
+	/// Get the counters of the work done by the table.
+	pub fn stats(&self) -> TableStats {
+		self.stats.get()
+	}
+
+	/// Get the time from candidates being first seconded to `authority` voting on them, for
+	/// every candidate it voted on after another authority seconded it.
+	///
+	/// Votes are only timed while [`Table::set_vote_latency_tracking`] is enabled or metrics are
+	/// recorded, so that the clock isn't read on every import otherwise.
+	pub fn vote_latency(&self, authority: &Ctx::AuthorityId) -> OperationStats {
+		self.vote_latency.get(authority).copied().unwrap_or_default()
+	}
+
+	/// Time the votes of authorities, see [`Table::vote_latency`].
+	pub fn set_vote_latency_tracking(&mut self, enabled: bool) {
+		self.track_vote_latency = enabled;
+	}
+
+	// whether imported votes are timed.
+	fn is_timed(&self) -> bool {
+		self.track_vote_latency || self.metrics.is_enabled()
+	}
+
+	/// Reset the counters of the work done by the table, e.g. at the start of a round.
+	pub fn reset_stats(&self) {
+		self.stats.reset()
+	}
+
+	/// Get the approximate number of bytes used by the candidates and votes in the table.
+	///
+	/// The heap memory owned by candidates is only accounted for if a [`MemoryLimit`] which
+	/// estimates it is set. This includes candidates kept in memory since they were borrowed
+	/// from the candidate provider with [`Table::get_candidate`], which count towards the limit
+	/// from the next import on.
+	pub fn memory_usage(&self) -> usize {
+		self.memory_usage + self.borrowed_memory.get()
+	}
+
+	/// Bound the number of candidates and buffered statements in the table.
+	///
+	/// Contents already in the table are kept, even if they exceed the new limits.
+	pub fn set_limits(&mut self, limits: TableLimits) {
+		self.limits = limits;
+	}
+
+	/// Require `votes` validity votes for candidates of `group_id` to be includable, instead
+	/// of the `minimum_backing_votes` passed to queries, e.g. for groups known to be
+	/// understaffed. As always, no more votes are required than the group has members.
+	pub fn set_backing_threshold(&mut self, group_id: Ctx::GroupId, votes: u32) {
+		for data in self.candidate_votes.values_mut() {
+			if data.group_id == group_id {
+				Arc::make_mut(data).backing_threshold = Some(votes);
+			}
+		}
+		self.backing_thresholds.insert(group_id, votes);
+	}
+
+	/// Bound the memory used by the table, or remove the bound with `None`.
+	///
+	/// When an import takes the table over the limit, candidates are evicted until it is back
+	/// within the limit: the ones with the fewest validity votes first and, among those, the
+	/// most recently imported ones first. This keeps a flood of statements about fresh
+	/// candidates from exhausting memory, while keeping well-backed candidates.
+	pub fn set_memory_limit(&mut self, limit: Option<MemoryLimit<Ctx::Candidate>>) {
+		let candidate_heap_size: fn(&Ctx::Candidate) -> usize =
+			limit.as_ref().map_or(|_| 0, |limit| limit.candidate_heap_size);
+		self.memory_limit = limit;
+		self.memory_usage = 0;
+		// borrowed candidates are accounted for like the others from now on.
+		self.borrowed_memory = Arc::new(BorrowedMemory::new(candidate_heap_size));
+		for data in self.candidate_votes.values_mut() {
+			let data = Arc::make_mut(data);
+			data.heap_size =
+				data.candidate.get().map_or(0, |candidate| candidate_heap_size(candidate));
+			data.borrowed_size = OnceLock::new();
+			self.memory_usage += candidate_cost::<Ctx>(data.heap_size) +
+				data.validity_votes.len() * vote_cost::<Ctx>();
+		}
+
+		self.enforce_memory_limit();
+	}
+
+	// evict candidates until the table is within its memory limit.
+	fn enforce_memory_limit(&mut self) {
+		let max_bytes = match self.memory_limit {
+			Some(ref limit) => limit.max_bytes,
+			None => return,
+		};
+
+		while self.memory_usage() > max_bytes {
+			let victim = self.eviction_order.values().next().cloned();
+
+			match victim {
+				Some(digest) => self.evict_candidate(&digest),
+				None => break,
+			}
+		}
+	}
+
+	/// Check the consistency of the internal state of the table, panicking if it is corrupted.
+	///
+	/// This checks that every proposal of an authority refers to a candidate of its group, that
+	/// every vote is from a member of the group of its candidate, and that the tallies, the
+	/// import log, the eviction order and the memory usage match the votes. It is meant for tests
+	/// and fuzzing, and is only available with the `check-invariants` feature.
+	#[cfg(any(test, feature = "check-invariants"))]
+	pub fn assert_invariants(&self, context: &Ctx) {
+		let proposals = self.authority_data.iter().flat_map(|(authority, groups)| {
+			groups.iter().map(move |(group, data)| (authority, group, data))
+		});
+		for (authority, group_id, data) in proposals {
+			assert!(
+				!data.proposals.is_empty(),
+				"{:?} has no proposals in {:?}",
+				authority,
+				group_id
+			);
+			// proposals outlive evicted candidates, and an evicted candidate seconded again by
+			// another authority comes back without the votes of its earlier proposers.
+			for (digest, signature) in &data.proposals {
+				let Some(votes) = self.candidate_votes.get(digest) else { continue };
+				assert_eq!(&votes.group_id, group_id, "candidate {:?} is in another group", digest);
+				// an authority which seconds a candidate it voted valid on already double votes,
+				// and its vote stays the explicit one.
+				let voted = match votes.validity_votes.get(authority) {
+					Some(ValidityVote::Issued(s)) => s == signature,
+					Some(ValidityVote::Valid(_)) | None => true,
+				};
+				assert!(voted, "{:?} seconded {:?} twice", authority, digest);
+			}
+		}
+
+		let mut memory_usage = 0;
+		let mut borrowed = 0;
+		let mut logged = 0;
+		for (digest, data) in &self.candidate_votes {
+			let mut seconded_votes = 0;
+			let mut validity_weight = 0;
+			for (authority, vote) in data.validity_votes.iter() {
+				validity_weight += context.vote_weight(authority);
+				assert!(
+					context.is_member_of(authority, &data.group_id),
+					"{:?} voted on {:?} outside of its group",
+					authority,
+					digest,
+				);
+				if let ValidityVote::Issued(_) = vote {
+					seconded_votes += 1;
+					let proposed = self
+						.authority_data
+						.get(authority)
+						.and_then(|groups| groups.get(&data.group_id))
+						.is_some_and(|data| data.proposals.iter().any(|(d, _)| d == digest));
+					assert!(proposed, "{:?} seconded {:?} without proposing it", authority, digest);
+				}
+			}
+			assert_eq!(data.seconded_votes, seconded_votes, "tally of {:?} is off", digest);
+			assert_eq!(data.validity_weight, validity_weight, "weight of {:?} is off", digest);
+			assert_eq!(
+				data.voted_after.len(),
+				data.validity_votes.len(),
+				"votes on {:?} aren't timed",
+				digest,
+			);
+
+			assert_eq!(
+				data.logged_at.len(),
+				data.validity_votes.len(),
+				"votes on {:?} aren't logged",
+				digest,
+			);
+			for ((authority, _), position) in data.validity_votes.iter().zip(&data.logged_at) {
+				assert_eq!(
+					self.import_log.get(position),
+					Some(&(digest.clone(), authority.clone())),
+					"vote of {:?} on {:?} is logged elsewhere",
+					authority,
+					digest,
+				);
+			}
+			logged += data.logged_at.len();
+
+			let order = (data.validity_votes.len(), Reverse(data.imported_at));
+			assert_eq!(
+				self.eviction_order.get(&order),
+				Some(digest),
+				"{:?} is out of eviction order",
+				digest,
+			);
+
+			memory_usage += candidate_cost::<Ctx>(data.heap_size) +
+				data.validity_votes.len() * vote_cost::<Ctx>();
+			borrowed += data.borrowed_size.get().copied().unwrap_or(0);
+		}
+		assert_eq!(self.memory_usage, memory_usage, "memory usage is off");
+		assert_eq!(self.borrowed_memory.get(), borrowed, "memory of borrowed candidates is off");
+		assert_eq!(self.import_log.len(), logged, "the import log has stale votes");
+		assert_eq!(
+			self.eviction_order.len(),
+			self.candidate_votes.len(),
+			"the eviction order has stale candidates",
+		);
+	}
+
+	// remove a candidate along with its votes to stay within the memory limit. proposals of the
+	// candidate are kept, so that authorities seconding another candidate afterwards are still
+	// caught.
+	fn evict_candidate(&mut self, digest: &Ctx::Digest) {
+		if let Some(data) = self.remove_candidate(digest) {
+			gum::debug!(
+				target: LOG_TARGET,
+				candidate = ?digest,
+				validity_votes = data.validity_votes.len(),
+				"Evicted candidate to stay within the memory limit",
+			);
+		}
+	}
+
+	// remove a candidate along with its votes and their entries in the import log.
+	fn remove_candidate(&mut self, digest: &Ctx::Digest) -> Option<Arc<CandidateData<Ctx>>> {
+		let data = self.candidate_votes.remove(digest)?;
+		self.memory_usage = self.memory_usage.saturating_sub(
+			candidate_cost::<Ctx>(data.heap_size) + data.validity_votes.len() * vote_cost::<Ctx>(),
+		);
+		if let Some(size) = data.borrowed_size.get() {
+			self.borrowed_memory.release(*size);
+		}
+		if data.includable_votes.is_some() {
+			self.metrics.on_includable_changed(false);
+		}
+		if let Some(events) = &self.events {
+			events.note_removed(digest, &data.group_id);
+		}
+		self.eviction_order
+			.remove(&(data.validity_votes.len(), Reverse(data.imported_at)));
+		for position in &data.logged_at {
+			self.import_log.remove(position);
+		}
+
+		Some(data)
+	}
+
+	// undo the import of the votes imported after `marker`, e.g. when they couldn't be
+	// persisted. candidates imported along with them are removed as well, but proposals,
+	// evictions and misbehavior detected since are not undone.
+	pub(crate) fn revert_to(&mut self, context: &Ctx, marker: StatementMarker) {
+		let reverted = self.import_log.split_off(&marker.0);
+		for (position, (digest, authority)) in reverted.into_iter().rev() {
+			let votes = match self.candidate_votes.get_mut(&digest) {
+				Some(votes) => Arc::make_mut(votes),
+				None => continue,
+			};
+			let index = match votes.logged_at.iter().position(|logged| *logged == position) {
+				Some(index) => index,
+				None => continue,
+			};
+
+			self.eviction_order
+				.remove(&(votes.validity_votes.len(), Reverse(votes.imported_at)));
+			let vote = votes.validity_votes.remove(index);
+			votes.voted_after.remove(index);
+			votes.logged_at.remove(index);
+			votes.validity_weight -= context.vote_weight(&authority);
+			self.memory_usage = self.memory_usage.saturating_sub(vote_cost::<Ctx>());
+			self.eviction_order
+				.insert((votes.validity_votes.len(), Reverse(votes.imported_at)), digest.clone());
+			if votes.includable_votes.map_or(false, |n| votes.validity_votes.len() < n) {
+				votes.includable_votes = None;
+				self.metrics.on_includable_changed(false);
+			}
+
+			// like those of evicted candidates, the proposal is kept.
+			if let ValidityVote::Issued(_) = vote {
+				votes.seconded_votes -= 1;
+			}
+
+			if votes.validity_votes.is_empty() && votes.imported_at >= marker.0 {
+				self.remove_candidate(&digest);
+			}
 		}
 	}
 
+	/// Stop keeping the candidates imported from now on in memory, and get them from `provider`
+	/// whenever they are needed instead.
+	///
+	/// Only the digests of candidates are kept, with their votes. Candidates are needed for
+	/// proofs of misbehavior, attestations, statements seconding them and snapshots. The
+	/// provider should be able to provide every candidate imported into the table, e.g. from
+	/// the store the candidates are kept in anyway. Candidates it can't provide are left out of
+	/// the results, and misbehavior whose proof would need them goes unnoticed.
+	///
+	/// This keeps the memory used by the table small when it tracks many candidates.
+	pub fn set_candidate_provider(&mut self, provider: Arc<dyn CandidateProvider<Ctx>>) {
+		self.candidate_provider = Some(provider);
+	}
+
+	/// Record the metrics of the table into `metrics`.
+	pub fn set_metrics(&mut self, metrics: Metrics) {
+		self.metrics = metrics;
+	}
+
+	/// Publish the events of the table to `events`.
+	pub fn set_events(&mut self, events: TableEventsFor<Ctx>) {
+		self.events = Some(events);
+	}
+
+	/// Record the statements imported into the table with `recorder`.
+	pub fn set_recorder(&mut self, recorder: Arc<Recorder<Ctx>>) {
+		self.recorder = Some(recorder);
+	}
+
 	/// Get the attested candidate for `digest`.
 	///
 	/// Returns `Some(_)` if the candidate exists and is includable.
@@ -322,12 +1010,14 @@ impl<Ctx: Context> Table<Ctx> {
 		context: &Ctx,
 		minimum_backing_votes: u32,
 	) -> Option<AttestedCandidate<Ctx::GroupId, Ctx::Candidate, Ctx::AuthorityId, Ctx::Signature>> {
-		self.candidate_votes.get(digest).and_then(|data| {
-			let v_threshold = context.get_group_size(&data.group_id).map_or(usize::MAX, |len| {
-				effective_minimum_backing_votes(len, minimum_backing_votes)
-			});
-			data.attested(v_threshold)
-		})
+		attested_candidate(
+			&self.candidate_votes,
+			self.candidate_provider.as_deref(),
+			&self.stats,
+			digest,
+			context,
+			minimum_backing_votes,
+		)
 	}
 
 	/// Import a signed statement. Signatures should be checked for validity, and the
@@ -336,39 +1026,706 @@ impl<Ctx: Context> Table<Ctx> {
 	/// Validity and invalidity statements are only valid if the corresponding
 	/// candidate has already been imported.
 	///
-	/// If this returns `None`, the statement was either duplicate or invalid.
+	/// Returns a summary of the candidate if the statement was imported, or `None` if it
+	/// wasn't, see [`Table::try_import_statement`] for why.
 	pub fn import_statement(
 		&mut self,
 		context: &Ctx,
 		group_id: Ctx::GroupId,
 		statement: SignedStatement<Ctx::Candidate, Ctx::Digest, Ctx::AuthorityId, Ctx::Signature>,
 	) -> Option<Summary<Ctx::Digest, Ctx::GroupId>> {
-		let SignedStatement { statement, signature, sender: signer } = statement;
-		let res = match statement {
-			Statement::Seconded(candidate) =>
-				self.import_candidate(context, signer.clone(), candidate, signature, group_id),
-			Statement::Valid(digest) =>
-				self.validity_vote(context, signer.clone(), digest, ValidityVote::Valid(signature)),
-		};
+		self.try_import_statement(context, group_id, statement).ok()
+	}
 
-		match res {
-			Ok(maybe_summary) => maybe_summary,
-			Err(misbehavior) => {
-				// all misbehavior in agreement is provable and actively malicious.
-				// punishments may be cumulative.
-				self.detected_misbehavior.entry(signer).or_default().push(misbehavior);
-				None
-			},
-		}
+	/// Import a signed statement, like [`Table::import_statement`], returning why it wasn't
+	/// imported otherwise.
+	pub fn try_import_statement(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: SignedStatement<Ctx::Candidate, Ctx::Digest, Ctx::AuthorityId, Ctx::Signature>,
+	) -> Result<Summary<Ctx::Digest, Ctx::GroupId>, ImportError> {
+		self.import_signed(context, group_id, Cow::Owned(statement))
 	}
 
-	/// Get a candidate by digest.
+	/// Import a signed statement by reference, like [`Table::import_statement`].
+	///
+	/// Only what the table keeps is copied out of the statement: the sender and signature of a
+	/// vote, and the candidate of a statement seconding one when it is new to the table and not
+	/// served by a candidate provider, or when it is needed as proof of misbehavior. The
+	/// statement can then be forwarded as it is.
+	pub fn import_statement_ref(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Option<Summary<Ctx::Digest, Ctx::GroupId>> {
+		self.try_import_statement_ref(context, group_id, statement).ok()
+	}
+
+	/// Import a signed statement by reference, like [`Table::import_statement_ref`], returning
+	/// why it wasn't imported otherwise.
+	pub fn try_import_statement_ref(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Result<Summary<Ctx::Digest, Ctx::GroupId>, ImportError> {
+		self.import_signed(context, group_id, Cow::Borrowed(statement))
+	}
+
+	fn import_signed(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: Cow<'_, SignedStatementFor<Ctx>>,
+	) -> Result<Summary<Ctx::Digest, Ctx::GroupId>, ImportError> {
+		let started = Instant::now();
+		let recorded = self
+			.recorder
+			.is_some()
+			.then(|| (group_id.clone(), statement.clone().into_owned()));
+		let seconded = statement.statement.is_seconded();
+		#[cfg(feature = "tracing")]
+		let _span = tracing::debug_span!(
+			target: LOG_TARGET,
+			"import_statement",
+			digest = ?statement.digest::<Ctx>(),
+			signer = ?statement.sender,
+			group = ?group_id,
+			seconded,
+		)
+		.entered();
+		let (statement, signature, signer) = match statement {
+			Cow::Owned(SignedStatement { statement, signature, sender }) => {
+				let statement = match statement {
+					Statement::Seconded(candidate) =>
+						Statement::Seconded(IncomingCandidate::Shared(Arc::new(candidate))),
+					Statement::Valid(digest) => Statement::Valid(digest),
+				};
+				(statement, signature, sender)
+			},
+			Cow::Borrowed(SignedStatement { statement, signature, sender }) => {
+				let statement = match statement {
+					Statement::Seconded(candidate) =>
+						Statement::Seconded(IncomingCandidate::Borrowed(candidate)),
+					Statement::Valid(digest) => Statement::Valid(digest.clone()),
+				};
+				(statement, signature.clone(), sender.clone())
+			},
+		};
+		let summary = match statement {
+			Statement::Seconded(candidate) =>
+				self.import_seconded(context, group_id, signer, candidate, signature),
+			Statement::Valid(digest) => {
+				let res = self.validity_vote(
+					context,
+					signer.clone(),
+					digest,
+					ValidityVote::Valid(signature),
+					None,
+				);
+				self.note_import_result(signer, res)
+			},
+		};
+
+		if let Ok(summary) = &summary {
+			self.metrics.on_statement_imported(seconded);
+			self.note_includable(context, summary);
+			self.count_includable(context, &summary.candidate);
+		}
+		#[cfg(feature = "tracing")]
+		match &summary {
+			Ok(summary) => tracing::debug!(
+				target: LOG_TARGET,
+				outcome = "imported",
+				validity_votes = summary.validity_votes,
+				"Imported statement",
+			),
+			Err(error) => tracing::debug!(
+				target: LOG_TARGET,
+				outcome = "ignored",
+				reason = ?error,
+				"Statement not imported",
+			),
+		}
+		self.metrics.on_import(started.elapsed());
+		self.stats.imports.record(started);
+		if let (Some(recorder), Some((group_id, statement))) = (&self.recorder, recorded) {
+			recorder.record(group_id, statement, summary.is_ok());
+		}
+		summary
+	}
+
+	// import a statement seconding a candidate.
+	fn import_seconded(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		signer: Ctx::AuthorityId,
+		candidate: IncomingCandidate<'_, Ctx::Candidate>,
+		signature: Ctx::Signature,
+	) -> Result<Summary<Ctx::Digest, Ctx::GroupId>, ImportError> {
+		let digest = Ctx::candidate_digest(candidate.get());
+		let res =
+			self.import_candidate(context, signer.clone(), candidate.clone(), signature, group_id);
+
+		// a statement seconding an announced candidate provides its body, which is shared with
+		// the copy the import stored rather than copied again.
+		let res = match res {
+			Ok(summary) => match self.take_announced(&digest) {
+				Some(announced) => {
+					let candidate =
+						self.shared_candidate(&digest).unwrap_or_else(|| candidate.into_shared());
+					Ok(self.import_announced(context, candidate, announced).unwrap_or(summary))
+				},
+				None => Ok(summary),
+			},
+			Err(misbehavior) => Err(misbehavior),
+		};
+
+		self.note_import_result(signer, res)
+	}
+
+	// publish that the candidate of `summary` is includable, if it is.
+	fn note_includable(&self, context: &Ctx, summary: &Summary<Ctx::Digest, Ctx::GroupId>) {
+		let (events, data) = match (&self.events, self.candidate_votes.get(&summary.candidate)) {
+			(Some(events), Some(data)) => (events, data),
+			_ => return,
+		};
+
+		if data.status(context, events.minimum_backing_votes()).is_includable() {
+			events.note_includable(&summary.candidate, &summary.group_id);
+		}
+	}
+
+	// count the candidate with the given `digest` into the includable candidates of the metrics,
+	// the first time it is includable, and record its age the first time ever. this is only done
+	// on import, so that forks and views sharing the candidate don't record it.
+	fn count_includable(&mut self, context: &Ctx, digest: &Ctx::Digest) {
+		let minimum_backing_votes = match self.metrics.minimum_backing_votes() {
+			Some(minimum_backing_votes) => minimum_backing_votes,
+			None => return,
+		};
+		let data = match self.candidate_votes.get_mut(digest) {
+			Some(data) => data,
+			None => return,
+		};
+		if data.includable_votes.is_none() &&
+			data.status(context, minimum_backing_votes).is_includable()
+		{
+			let votes = data.validity_votes.len();
+			let data = Arc::make_mut(data);
+			data.includable_votes = Some(votes);
+			self.metrics.on_includable_changed(true);
+
+			if !data.age_recorded {
+				data.age_recorded = true;
+				let threshold = data.status(context, minimum_backing_votes).validity_threshold;
+				if let (Some(quorum_after), Some(proposed_at)) =
+					(data.voted_after[threshold.max(1) - 1], data.proposed_at)
+				{
+					self.metrics.on_includable(quorum_after, proposed_at.elapsed());
+				}
+			}
+		}
+	}
+
+	// remove the announcement of the candidate with the given `digest`.
+	fn take_announced(&mut self, digest: &Ctx::Digest) -> Option<Announced<Ctx>> {
+		let announced = self.announced.remove(digest)?;
+		self.pending_votes -= announced.seconders.len();
+		self.metrics.set_pending_votes(self.pending_votes);
+		Some(announced)
+	}
+
+	fn note_import_result(
+		&mut self,
+		signer: Ctx::AuthorityId,
+		res: VoteResult<Ctx>,
+	) -> Result<Summary<Ctx::Digest, Ctx::GroupId>, ImportError> {
+		match res {
+			Ok(summary) => {
+				self.enforce_memory_limit();
+				// the candidate may have been evicted right away.
+				if !self.candidate_votes.contains_key(&summary.candidate) {
+					return Err(ImportError::Evicted)
+				}
+
+				Ok(summary)
+			},
+			Err(Rejected::Ignored(error)) => Err(error),
+			Err(Rejected::Misbehavior(misbehavior)) => {
+				// all misbehavior in agreement is provable and actively malicious.
+				// punishments may be cumulative.
+				#[cfg(feature = "tracing")]
+				tracing::debug!(
+					target: LOG_TARGET,
+					signer = ?signer,
+					kind = misbehavior.kind().as_str(),
+					"Detected misbehavior",
+				);
+				self.metrics.on_misbehavior(&misbehavior);
+				if let Some(events) = &self.events {
+					events.publish(|| TableEvent::MisbehaviorDetected {
+						authority: signer.clone(),
+						kind: misbehavior.kind(),
+					});
+				}
+				let error = match misbehavior {
+					Misbehavior::UnauthorizedStatement(_) => ImportError::UnknownSigner,
+					_ => ImportError::Misbehavior(misbehavior.kind()),
+				};
+				self.detected_misbehavior.entry(signer).or_default().push(misbehavior);
+				Err(error)
+			},
+		}
+	}
+
+	/// Get the status of the candidate with the given `digest`.
+	///
+	/// Returns `None` if the candidate is unknown.
+	pub fn candidate_status(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Option<CandidateStatusFor<Ctx>> {
+		candidate_status(&self.candidate_votes, &self.stats, digest, context, minimum_backing_votes)
+	}
+
+	/// Get all candidates which are currently includable, in no particular order.
+	pub fn attested_candidates(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Vec<AttestedCandidateFor<Ctx>> {
+		self.candidate_votes
+			.keys()
+			.filter_map(|digest| self.attested_candidate(digest, context, minimum_backing_votes))
+			.collect()
+	}
+
+	/// Get the candidates which `authority` is expected to vote on but hasn't yet.
+	///
+	/// These are the candidates in groups `authority` is a member of, for which no statement
+	/// from `authority` has been imported. The order is unspecified.
+	pub fn candidates_awaiting_vote(
+		&self,
+		context: &Ctx,
+		authority: &Ctx::AuthorityId,
+	) -> Vec<(Ctx::Digest, Ctx::GroupId)> {
+		candidates_awaiting_vote(&self.candidate_votes, context, authority)
+	}
+
+	/// Whether importing `statement` about a candidate of `group_id` could reveal misbehavior of
+	/// its sender, because the table has a statement of the sender on the same candidate or, if
+	/// it seconds a candidate, another candidate the sender seconded in the group.
+	pub fn may_conflict(
+		&self,
+		group_id: &Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> bool {
+		let digest = statement.digest::<Ctx>();
+		let voted = self
+			.candidate_votes
+			.get(&digest)
+			.map_or(false, |data| data.validity_votes.contains_key(&statement.sender));
+		let seconded_other = statement.statement.is_seconded() &&
+			!self.config.allow_multiple_seconded &&
+			self.authority_data
+				.get(&statement.sender)
+				.and_then(|groups| groups.get(group_id))
+				.map_or(false, |data| data.proposals.iter().any(|(d, _)| *d != digest));
+
+		voted || seconded_other
+	}
+
+	/// Get the candidate `authority` proposed, along with its signature seconding it.
+	///
+	/// Authorities propose a single candidate per group, unless the table allows multiple
+	/// seconded candidates. If `authority` proposed several, this is the first it proposed in
+	/// the lowest of its groups.
+	pub fn proposed_by(
+		&self,
+		authority: &Ctx::AuthorityId,
+	) -> Option<(&Ctx::Digest, &Ctx::Signature)> {
+		self.authority_data
+			.get(authority)?
+			.values()
+			.find_map(|data| data.proposals.first())
+			.map(|(digest, signature)| (digest, signature))
+	}
+
+	/// Get the candidate proposed in `group_id`, i.e. the first candidate of the group which
+	/// was imported and is still in the table.
+	pub fn proposal_of_group(&self, group_id: &Ctx::GroupId) -> Option<&Ctx::Digest> {
+		proposal_of_group(&self.candidate_votes, group_id)
+	}
+
+	/// Get a statement seconding the candidate with the given `digest`, along with the group of
+	/// the candidate.
+	///
+	/// This is what peers which don't know the candidate yet need to import it.
+	pub fn seconded_statement(
+		&self,
+		digest: &Ctx::Digest,
+	) -> Option<(Ctx::GroupId, SignedStatementFor<Ctx>)> {
+		seconded_statement(&self.candidate_votes, self.candidate_provider.as_deref(), digest)
+	}
+
+	/// Note that `sender` seconded the candidate with the given `digest` in `group_id`, without
+	/// having the candidate itself. The signature should be checked, as for
+	/// [`Table::import_statement`].
+	///
+	/// If the candidate is known already, the announcement is imported as a statement seconding
+	/// it. Otherwise the candidate is pending until its body is provided, either with
+	/// [`Table::import_body`] or with any statement seconding it.
+	///
+	/// Returns whether the body of the candidate is pending.
+	pub fn note_announcement(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		digest: Ctx::Digest,
+		sender: Ctx::AuthorityId,
+		signature: Ctx::Signature,
+	) -> bool {
+		if let Some(candidate) = self.shared_candidate(&digest) {
+			let candidate = IncomingCandidate::Shared(candidate);
+			let _ = self.import_seconded(context, group_id, sender, candidate, signature);
+			self.count_includable(context, &digest);
+			return false
+		}
+
+		if !context.is_member_of(&sender, &group_id) {
+			return false
+		}
+
+		if self.limits.max_buffered_votes.map_or(false, |max| self.pending_votes >= max) {
+			return false
+		}
+
+		let announced = self
+			.announced
+			.entry(digest)
+			.or_insert_with(|| Announced { group_id, seconders: Vec::new() });
+		if !announced.seconders.iter().any(|(s, _)| s == &sender) {
+			announced.seconders.push((sender, signature));
+			self.pending_votes += 1;
+			self.metrics.set_pending_votes(self.pending_votes);
+		}
+
+		true
+	}
+
+	/// Whether the candidate with the given `digest` was announced, but its body is still
+	/// pending.
+	pub fn is_body_pending(&self, digest: &Ctx::Digest) -> bool {
+		self.announced.contains_key(digest)
+	}
+
+	/// Get the candidates which were announced but whose body is still pending, along with their
+	/// group. The order is unspecified.
+	pub fn pending_bodies(&self) -> Vec<(Ctx::Digest, Ctx::GroupId)> {
+		self.announced
+			.iter()
+			.map(|(digest, announced)| (digest.clone(), announced.group_id.clone()))
+			.collect()
+	}
+
+	/// Provide the body of an announced candidate, importing the announcements as statements
+	/// seconding it.
+	///
+	/// Returns `None` if the candidate wasn't pending, or none of the announcements could be
+	/// imported.
+	pub fn import_body(
+		&mut self,
+		context: &Ctx,
+		candidate: Ctx::Candidate,
+	) -> Option<Summary<Ctx::Digest, Ctx::GroupId>> {
+		let digest = Ctx::candidate_digest(&candidate);
+		let announced = self.take_announced(&digest)?;
+		let summary = self.import_announced(context, Arc::new(candidate), announced)?;
+		self.count_includable(context, &digest);
+		Some(summary)
+	}
+
+	/// Get a marker for the current point in the sequence of imported statements.
+	pub fn marker(&self) -> StatementMarker {
+		StatementMarker(self.next_position)
+	}
+
+	/// Get all statements imported after `marker`, in order of import, along with the group of
+	/// the candidate they refer to, and a marker after the last of them.
+	///
+	/// Statements which were detected as misbehavior are not included.
+	/// [`StatementMarker::default`] refers to the beginning.
+	pub fn statements_since(
+		&self,
+		marker: StatementMarker,
+	) -> (Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>, StatementMarker) {
+		self.statements_since_limited(marker, usize::MAX)
+	}
+
+	/// Get at most `limit` statements imported after `marker`, like
+	/// [`statements_since`](Self::statements_since). The returned marker is after the last of
+	/// them, so that the rest can be fetched with it.
+	pub fn statements_since_limited(
+		&self,
+		marker: StatementMarker,
+		limit: usize,
+	) -> (Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>, StatementMarker) {
+		let mut statements = Vec::new();
+		for (position, (digest, sender)) in self.import_log.range(marker.0..) {
+			if statements.len() == limit {
+				return (statements, StatementMarker(*position))
+			}
+			statements.extend(self.logged_statement(digest, sender, None));
+		}
+
+		(statements, self.marker())
+	}
+
+	/// Iterate over the candidates in the table, along with their group, in no particular
+	/// order. Only candidates of `group` are included, if given.
+	pub fn candidates<'a>(
+		&'a self,
+		group: Option<&'a Ctx::GroupId>,
+	) -> impl Iterator<Item = (&'a Ctx::Digest, &'a Ctx::GroupId)> + 'a {
+		candidates(&self.candidate_votes, group)
+	}
+
+	/// Iterate over the authorities which voted on the candidate with the given `digest`, in
+	/// order of their vote, along with whether they seconded it rather than voting it valid.
+	pub fn candidate_votes<'a>(
+		&'a self,
+		digest: &Ctx::Digest,
+	) -> impl Iterator<Item = (&'a Ctx::AuthorityId, bool)> + 'a {
+		self.candidate_votes.get(digest).into_iter().flat_map(|data| {
+			data.validity_votes
+				.iter()
+				.map(|(authority, vote)| (authority, matches!(vote, ValidityVote::Issued(_))))
+		})
+	}
+
+	/// Iterate over the statements in the table, in order of import, along with the group of
+	/// the candidate they refer to. Only statements on candidates of `group` are included, if
+	/// given.
+	///
+	/// Like [`Table::statements_since`], but statements are only built as they are iterated.
+	pub fn statements<'a>(
+		&'a self,
+		group: Option<&'a Ctx::GroupId>,
+	) -> impl Iterator<Item = (Ctx::GroupId, SignedStatementFor<Ctx>)> + 'a {
+		self.import_log
+			.values()
+			.filter_map(move |(digest, sender)| self.logged_statement(digest, sender, group))
+	}
+
+	/// Iterate over the authorities which voted on candidates in the table, each once, in order
+	/// of their first vote. Only votes on candidates of `group` are considered, if given.
+	pub fn validators_with_votes<'a>(
+		&'a self,
+		group: Option<&'a Ctx::GroupId>,
+	) -> impl Iterator<Item = &'a Ctx::AuthorityId> + 'a {
+		let mut seen = HashSet::new();
+		self.import_log
+			.values()
+			.filter(move |(digest, sender)| {
+				self.candidate_votes.get(digest).map_or(false, |data| {
+					group.map_or(true, |group| group == &data.group_id) &&
+						data.validity_votes.contains_key(sender)
+				})
+			})
+			.map(|(_, sender)| sender)
+			.filter(move |sender| seen.insert(*sender))
+	}
+
+	// the statement of `sender` on the candidate with the given `digest`, if it is in the table
+	// and the candidate is of `group`, if given.
+	fn logged_statement(
+		&self,
+		digest: &Ctx::Digest,
+		sender: &Ctx::AuthorityId,
+		group: Option<&Ctx::GroupId>,
+	) -> Option<(Ctx::GroupId, SignedStatementFor<Ctx>)> {
+		let data = self.candidate_votes.get(digest)?;
+		if group.map_or(false, |group| group != &data.group_id) {
+			return None
+		}
+
+		let (statement, signature) = match data.validity_votes.get(sender)? {
+			ValidityVote::Issued(s) => {
+				let candidate = data.hydrate(digest, self.candidate_provider.as_deref())?;
+				(Statement::Seconded(Arc::unwrap_or_clone(candidate)), s)
+			},
+			ValidityVote::Valid(s) => (Statement::Valid(digest.clone()), s),
+		};
+
+		let statement =
+			SignedStatement { statement, signature: signature.clone(), sender: sender.clone() };
+		Some((data.group_id.clone(), statement))
+	}
+
+	// import the statements of the seconders of an announced candidate, returning the last
+	// summary.
+	fn import_announced(
+		&mut self,
+		context: &Ctx,
+		candidate: Arc<Ctx::Candidate>,
+		announced: Announced<Ctx>,
+	) -> Option<Summary<Ctx::Digest, Ctx::GroupId>> {
+		let Announced { group_id, seconders } = announced;
+		seconders
+			.into_iter()
+			.filter_map(|(sender, signature)| {
+				self.import_seconded(
+					context,
+					group_id.clone(),
+					sender,
+					IncomingCandidate::Shared(candidate.clone()),
+					signature,
+				)
+				.ok()
+			})
+			.last()
+	}
+
+	/// Compute a commitment to the statements in the table: the root of a binary merkle tree
+	/// over the sorted hashes of all imported statements, along with their group.
+	///
+	/// Tables holding the same statements have the same root, regardless of the order in which
+	/// they were imported. This lets validators cheaply check whether they converged on the same
+	/// statements. The root of an empty table is all zeroes.
+	pub fn state_root(&self) -> [u8; 32]
+	where
+		Ctx::Candidate: Encode,
+		Ctx::Digest: Encode,
+		Ctx::GroupId: Encode,
+		Ctx::AuthorityId: Encode,
+		Ctx::Signature: Encode,
+	{
+		let mut layer = self
+			.statements_since(StatementMarker::default())
+			.0
+			.iter()
+			.map(|statement| blake2_256(&statement.encode()))
+			.collect::<Vec<_>>();
+		layer.sort_unstable();
+
+		while layer.len() > 1 {
+			layer = layer
+				.chunks(2)
+				.map(|pair| match pair {
+					[left, right] => blake2_256(&[&left[..], &right[..]].concat()),
+					[single] => *single,
+					_ => unreachable!("chunks are of length 1 or 2; qed"),
+				})
+				.collect();
+		}
+
+		layer.first().copied().unwrap_or_default()
+	}
+
+	/// Take a snapshot of the table, from which it can be restored with [`Table::restore`].
+	///
+	/// This lets a node persist its progress, e.g. to pick up where it left off after a restart.
+	pub fn snapshot(&self) -> TableSnapshotFor<Ctx> {
+		TableSnapshot {
+			allow_multiple_seconded: self.config.allow_multiple_seconded,
+			statements: self.statements_since(StatementMarker::default()).0,
+			announced: self
+				.announced
+				.iter()
+				.map(|(digest, announced)| {
+					(digest.clone(), announced.group_id.clone(), announced.seconders.clone())
+				})
+				.collect(),
+			misbehavior: self
+				.detected_misbehavior
+				.iter()
+				.map(|(authority, misbehavior)| (authority.clone(), misbehavior.clone()))
+				.collect(),
+		}
+	}
+
+	/// Dump the state of the table for debugging, with the status of candidates computed with
+	/// `minimum_backing_votes`. See [`TableDump`].
+	pub fn export_debug(&self, context: &Ctx, minimum_backing_votes: u32) -> TableDumpFor<Ctx>
+	where
+		Ctx::Candidate: Encode,
+		Ctx::Digest: Encode,
+		Ctx::GroupId: Encode,
+		Ctx::AuthorityId: Encode,
+		Ctx::Signature: Encode,
+	{
+		let mut candidates: Vec<_> = self.candidate_votes.iter().collect();
+		candidates.sort_by_key(|(_, data)| data.imported_at);
+
+		TableDump {
+			snapshot: self.snapshot(),
+			candidates: candidates
+				.into_iter()
+				.map(|(digest, data)| (digest.clone(), data.status(context, minimum_backing_votes)))
+				.collect(),
+			state_root: self.state_root(),
+			memory_usage: self.memory_usage,
+		}
+	}
+
+	/// Restore a table from a snapshot, hashing keys with `hasher`. See [`Table::restore`].
+	pub fn restore_with_hasher(context: &Ctx, snapshot: TableSnapshotFor<Ctx>, hasher: S) -> Self {
+		let TableSnapshot { allow_multiple_seconded, statements, announced, misbehavior } =
+			snapshot;
+
+		let mut table = Table::with_hasher(Config { allow_multiple_seconded }, hasher);
+		for (group_id, statement) in statements {
+			table.import_statement(context, group_id, statement);
+		}
+
+		for (digest, group_id, seconders) in announced {
+			table.pending_votes += seconders.len();
+			table.announced.insert(digest, Announced { group_id, seconders });
+		}
+		table.detected_misbehavior.extend(misbehavior);
+		table
+	}
+
+	/// Whether the table has the candidate with the given digest, whether or not it is kept in
+	/// memory.
+	pub fn contains_candidate(&self, digest: &Ctx::Digest) -> bool {
+		self.candidate_votes.contains_key(digest)
+	}
+
+	/// The group of the candidate with the given digest, if the table has it.
+	pub fn candidate_group(&self, digest: &Ctx::Digest) -> Option<&Ctx::GroupId> {
+		self.candidate_votes.get(digest).map(|data| &data.group_id)
+	}
+
+	/// Get a candidate by digest.
+	///
+	/// A candidate which is not kept in memory because of [`Table::set_candidate_provider`] is
+	/// kept from then on, so prefer [`Table::shared_candidate`] for those.
 	pub fn get_candidate(&self, digest: &Ctx::Digest) -> Option<&Ctx::Candidate> {
-		self.candidate_votes.get(digest).map(|d| &d.candidate)
+		self.candidate_votes.get(digest)?.borrow(
+			digest,
+			self.candidate_provider.as_deref(),
+			&self.borrowed_memory,
+		)
+	}
+
+	/// Get a shared handle to a candidate by digest.
+	///
+	/// Unlike cloning the result of [`Table::get_candidate`], this doesn't copy the candidate.
+	pub fn shared_candidate(&self, digest: &Ctx::Digest) -> Option<Arc<Ctx::Candidate>> {
+		self.candidate_votes
+			.get(digest)?
+			.hydrate(digest, self.candidate_provider.as_deref())
 	}
 
 	/// Access all witnessed misbehavior.
-	pub fn get_misbehavior(&self) -> &HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>> {
+	pub fn get_misbehavior(&self) -> &HashMap<Ctx::AuthorityId, Vec<MisbehaviorFor<Ctx>>, S> {
 		&self.detected_misbehavior
 	}
 
@@ -383,94 +1740,135 @@ impl<Ctx: Context> Table<Ctx> {
 		&mut self,
 		context: &Ctx,
 		authority: Ctx::AuthorityId,
-		candidate: Ctx::Candidate,
+		candidate: IncomingCandidate<'_, Ctx::Candidate>,
 		signature: Ctx::Signature,
 		group: Ctx::GroupId,
-	) -> ImportResult<Ctx> {
+	) -> VoteResult<Ctx> {
 		if !context.is_member_of(&authority, &group) {
 			gum::debug!(target: LOG_TARGET,  authority = ?authority, group = ?group, "New `Misbehavior::UnauthorizedStatement`, candidate backed by validator that doesn't belong to expected group" );
 			return Err(Misbehavior::UnauthorizedStatement(UnauthorizedStatement {
 				statement: SignedStatement {
 					signature,
-					statement: Statement::Seconded(candidate),
+					statement: Statement::Seconded(candidate.into_owned()),
 					sender: authority,
 				},
-			}))
+			})
+			.into())
 		}
 
-		// check that authority hasn't already specified another candidate.
-		let digest = Ctx::candidate_digest(&candidate);
+		if !context.is_acceptable(candidate.get(), &group) {
+			return Err(Rejected::Ignored(ImportError::RejectedByContext))
+		}
 
-		let new_proposal = match self.authority_data.entry(authority.clone()) {
-			Entry::Occupied(mut occ) => {
+		// check that authority hasn't already specified another candidate in this group, before
+		// checking its limit, so that equivocations are always noted.
+		let digest = Ctx::candidate_digest(candidate.get());
+		let max_candidates = self.limits.max_candidates_per_authority.unwrap_or(usize::MAX);
+		let known = self.candidate_votes.contains_key(&digest);
+		match self
+			.authority_data
+			.get_mut(&authority)
+			.and_then(|groups| groups.get_mut(&group))
+		{
+			Some(existing) => {
 				// if digest is different, fetch candidate and
 				// note misbehavior.
-				let existing = occ.get_mut();
 
 				if !self.config.allow_multiple_seconded && existing.proposals.len() == 1 {
 					let (old_digest, old_sig) = &existing.proposals[0];
 
 					if old_digest != &digest {
-						const EXISTENCE_PROOF: &str =
-							"when proposal first received from authority, candidate \
-							votes entry is created. proposal here is `Some`, therefore \
-							candidate votes entry exists; qed";
-
-						let old_candidate = self
-							.candidate_votes
-							.get(old_digest)
-							.expect(EXISTENCE_PROOF)
-							.candidate
-							.clone();
+						// the first candidate may have been evicted since.
+						let provider = self.candidate_provider.as_deref();
+						let old_candidate = match self.candidate_votes.get(old_digest) {
+							Some(data) => data.hydrate(old_digest, provider),
+							None => provider.and_then(|provider| provider.candidate(old_digest)),
+						};
+						let old_candidate = match old_candidate {
+							Some(candidate) => Arc::unwrap_or_clone(candidate),
+							None => return unavailable_candidate::<Ctx>(old_digest),
+						};
 
 						return Err(Misbehavior::MultipleCandidates(MultipleCandidates {
 							first: (old_candidate, old_sig.clone()),
-							second: (candidate, signature.clone()),
-						}))
+							second: (candidate.into_owned(), signature.clone()),
+						})
+						.into())
+					}
+				} else if !existing.proposals.iter().any(|(ref od, _)| od == &digest) {
+					if existing.proposals.len() >= max_candidates && !known {
+						return Err(Rejected::Ignored(ImportError::TableFull))
 					}
-
-					false
-				} else if self.config.allow_multiple_seconded &&
-					existing.proposals.iter().any(|(ref od, _)| od == &digest)
-				{
-					false
-				} else {
 					existing.proposals.push((digest.clone(), signature.clone()));
-					true
 				}
 			},
-			Entry::Vacant(vacant) => {
-				vacant
-					.insert(AuthorityData { proposals: vec![(digest.clone(), signature.clone())] });
-				true
+			None => {
+				if max_candidates == 0 && !known {
+					return Err(Rejected::Ignored(ImportError::TableFull))
+				}
+				self.authority_data.entry(authority.clone()).or_default().insert(
+					group.clone(),
+					AuthorityData { proposals: smallvec![(digest.clone(), signature.clone())] },
+				);
 			},
-		};
+		}
+
+		// the candidate is imported again if it was evicted. otherwise, it is kept for the proof
+		// of a conflicting vote.
+		let mut incoming = Some(candidate);
+		let timed = self.is_timed();
+		if let Entry::Vacant(vacant) = self.candidate_votes.entry(digest.clone()) {
+			const INCOMING_PROOF: &str = "taken only here, at most once; qed";
+			let candidate = incoming.take().expect(INCOMING_PROOF);
+			let held = OnceLock::new();
+			if self.candidate_provider.is_none() {
+				let _ = held.set(candidate.into_shared());
+			}
 
-		// NOTE: altering this code may affect the existence proof above. ensure it remains
-		// valid.
-		if new_proposal {
-			self.candidate_votes
-				.entry(digest.clone())
-				.or_insert_with(move || CandidateData {
-					group_id: group,
-					candidate,
-					validity_votes: HashMap::new(),
-				});
+			let heap_size = match (self.memory_limit.as_ref(), held.get()) {
+				(Some(limit), Some(candidate)) => (limit.candidate_heap_size)(candidate),
+				_ => 0,
+			};
+			self.memory_usage += candidate_cost::<Ctx>(heap_size);
+			if let Some(events) = &self.events {
+				events.note_proposed(&digest, &group);
+			}
+			self.eviction_order.insert((0, Reverse(self.next_position)), digest.clone());
+			let backing_threshold = self.backing_thresholds.get(&group).copied();
+			vacant.insert(Arc::new(CandidateData {
+				group_id: group,
+				candidate: held,
+				validity_votes: VoteMap::new(),
+				seconded_votes: 0,
+				validity_weight: 0,
+				heap_size,
+				borrowed_size: OnceLock::new(),
+				imported_at: self.next_position,
+				logged_at: SmallVec::new(),
+				proposed_at: timed.then(Instant::now),
+				voted_after: SmallVec::new(),
+				age_recorded: false,
+				includable_votes: None,
+				backing_threshold,
+			}));
 		}
 
-		self.validity_vote(context, authority, digest, ValidityVote::Issued(signature))
+		self.validity_vote(context, authority, digest, ValidityVote::Issued(signature), incoming)
 	}
 
+	// import a validity vote. the candidate of a statement seconding it is given as `incoming`,
+	// in case it is needed for a proof of misbehavior.
 	fn validity_vote(
 		&mut self,
 		context: &Ctx,
 		from: Ctx::AuthorityId,
 		digest: Ctx::Digest,
 		vote: ValidityVote<Ctx::Signature>,
-	) -> ImportResult<Ctx> {
+		incoming: Option<IncomingCandidate<'_, Ctx::Candidate>>,
+	) -> VoteResult<Ctx> {
 		let votes = match self.candidate_votes.get_mut(&digest) {
-			None => return Ok(None),
-			Some(votes) => votes,
+			None => return Err(Rejected::Ignored(ImportError::UnknownCandidate)),
+			Some(votes) => Arc::make_mut(votes),
 		};
 
 		// check that this authority actually can vote in this group.
@@ -489,43 +1887,349 @@ impl<Ctx: Context> Table<Ctx> {
 					sender: from,
 					statement: Statement::Valid(digest),
 				},
-			}))
+			})
+			.into())
 		}
 
-		// check for double votes.
-		match votes.validity_votes.entry(from.clone()) {
-			Entry::Occupied(occ) => {
-				let make_vdv = |v| Misbehavior::ValidityDoubleVote(v);
-				let make_ds = |ds| Misbehavior::DoubleSign(ds);
-				return if occ.get() != &vote {
-					Err(match (occ.get().clone(), vote) {
-						// valid vote conflicting with candidate statement
-						(ValidityVote::Issued(iss), ValidityVote::Valid(good)) |
-						(ValidityVote::Valid(good), ValidityVote::Issued(iss)) =>
-							make_vdv(ValidityDoubleVote::IssuedAndValidity(
-								(votes.candidate.clone(), iss),
-								(digest, good),
-							)),
-
-						// two signatures on same candidate
-						(ValidityVote::Issued(a), ValidityVote::Issued(b)) =>
-							make_ds(DoubleSign::Seconded(votes.candidate.clone(), a, b)),
-
-						// two signatures on same validity vote
-						(ValidityVote::Valid(a), ValidityVote::Valid(b)) =>
-							make_ds(DoubleSign::Validity(digest, a, b)),
-					})
-				} else {
-					Ok(None)
-				}
-			},
-			Entry::Vacant(vacant) => {
-				vacant.insert(vote);
+		// check for double votes.
+		match votes.validity_votes.get(&from) {
+			Some(existing) => {
+				let make_vdv = |v| Misbehavior::ValidityDoubleVote(v);
+				let make_ds = |ds| Misbehavior::DoubleSign(ds);
+				if existing == &vote {
+					self.metrics.on_duplicate_dropped();
+					return Err(Rejected::Ignored(ImportError::Duplicate))
+				}
+
+				// the proofs of all but conflicting validity votes contain the candidate, which
+				// is the incoming one if it is at hand.
+				let candidate = match (existing, &vote, incoming) {
+					(ValidityVote::Valid(_), ValidityVote::Valid(_), _) => None,
+					(_, _, Some(candidate)) => Some(candidate.into_owned()),
+					(_, _, None) =>
+						match votes.hydrate(&digest, self.candidate_provider.as_deref()) {
+							Some(candidate) => Some(Arc::unwrap_or_clone(candidate)),
+							None => return unavailable_candidate::<Ctx>(&digest),
+						},
+				};
+				const HYDRATED_PROOF: &str = "candidate is hydrated for these votes above; qed";
+
+				return Err(Rejected::Misbehavior(match (existing.clone(), vote) {
+					// valid vote conflicting with candidate statement
+					(ValidityVote::Issued(iss), ValidityVote::Valid(good)) |
+					(ValidityVote::Valid(good), ValidityVote::Issued(iss)) =>
+						make_vdv(ValidityDoubleVote::IssuedAndValidity(
+							(candidate.expect(HYDRATED_PROOF), iss),
+							(digest, good),
+						)),
+
+					// two signatures on same candidate
+					(ValidityVote::Issued(a), ValidityVote::Issued(b)) =>
+						make_ds(DoubleSign::Seconded(candidate.expect(HYDRATED_PROOF), a, b)),
+
+					// two signatures on same validity vote
+					(ValidityVote::Valid(a), ValidityVote::Valid(b)) =>
+						make_ds(DoubleSign::Validity(digest, a, b)),
+				}))
+			},
+			None => {
+				if let ValidityVote::Issued(_) = vote {
+					votes.seconded_votes += 1;
+				}
+				votes.validity_weight += context.vote_weight(&from);
+				let voted_after = votes.proposed_at.map(|proposed_at| proposed_at.elapsed());
+				if let Some(latency) = voted_after.filter(|_| !votes.validity_votes.is_empty()) {
+					self.vote_latency.entry(from.clone()).or_default().record(latency);
+				}
+				let position = self.next_position;
+				self.next_position += 1;
+				let order = (votes.validity_votes.len(), Reverse(votes.imported_at));
+				self.eviction_order.remove(&order);
+				self.eviction_order.insert((order.0 + 1, order.1), digest.clone());
+				votes.validity_votes.insert(from.clone(), vote);
+				votes.voted_after.push(voted_after);
+				votes.logged_at.push(position);
+				self.import_log.insert(position, (digest.clone(), from));
+				self.memory_usage += vote_cost::<Ctx>();
+			},
+		}
+
+		Ok(votes.summary(digest))
+	}
+}
+
+/// An immutable view of the candidates in a [`Table`], see [`Table::view`].
+///
+/// The view only exposes queries, so it can be handed to consumers which must not change the
+/// state of agreement, e.g. the proposal builder, RPC and metrics. Clones of a view are as
+/// cheap as views.
+pub struct TableView<Ctx: Context, S = RandomState> {
+	candidate_votes: HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	stats: Arc<Stats>,
+	candidate_provider: Option<SharedProvider<Ctx>>,
+	borrowed_memory: Arc<BorrowedMemory<Ctx::Candidate>>,
+}
+
+impl<Ctx: Context, S: BuildHasher + Clone> Table<Ctx, S> {
+	/// Take an immutable view of the candidates in the table.
+	///
+	/// This is cheap: the view shares the candidates and their votes with the table, which only
+	/// copies the votes on a candidate when it imports a vote on it while a view still refers to
+	/// them. This lets a proposal be built from a view on one thread, while statements keep
+	/// being imported into the table on another.
+	pub fn view(&self) -> TableView<Ctx, S> {
+		TableView {
+			candidate_votes: self.candidate_votes.clone(),
+			stats: self.stats.clone(),
+			candidate_provider: self.candidate_provider.clone(),
+			borrowed_memory: self.borrowed_memory.clone(),
+		}
+	}
+}
+
+impl<Ctx: Context, S: Clone> Clone for TableView<Ctx, S> {
+	fn clone(&self) -> Self {
+		TableView {
+			candidate_votes: self.candidate_votes.clone(),
+			stats: self.stats.clone(),
+			candidate_provider: self.candidate_provider.clone(),
+			borrowed_memory: self.borrowed_memory.clone(),
+		}
+	}
+}
+
+impl<Ctx: Context, S: BuildHasher> TableView<Ctx, S> {
+	/// Get the stats of the table the view was taken of, see [`Table::stats`].
+	pub fn stats(&self) -> TableStats {
+		self.stats.get()
+	}
+
+	/// Iterate over the candidates in the view, see [`Table::candidates`].
+	pub fn candidates<'a>(
+		&'a self,
+		group: Option<&'a Ctx::GroupId>,
+	) -> impl Iterator<Item = (&'a Ctx::Digest, &'a Ctx::GroupId)> + 'a {
+		candidates(&self.candidate_votes, group)
+	}
+
+	/// Get the candidate proposed in `group_id`, see [`Table::proposal_of_group`].
+	pub fn proposal_of_group(&self, group_id: &Ctx::GroupId) -> Option<&Ctx::Digest> {
+		proposal_of_group(&self.candidate_votes, group_id)
+	}
+
+	/// Get the candidates which `authority` hasn't voted on yet, see
+	/// [`Table::candidates_awaiting_vote`].
+	pub fn candidates_awaiting_vote(
+		&self,
+		context: &Ctx,
+		authority: &Ctx::AuthorityId,
+	) -> Vec<(Ctx::Digest, Ctx::GroupId)> {
+		candidates_awaiting_vote(&self.candidate_votes, context, authority)
+	}
+
+	/// Get a statement seconding the candidate with the given `digest`, see
+	/// [`Table::seconded_statement`].
+	pub fn seconded_statement(
+		&self,
+		digest: &Ctx::Digest,
+	) -> Option<(Ctx::GroupId, SignedStatementFor<Ctx>)> {
+		seconded_statement(&self.candidate_votes, self.candidate_provider.as_deref(), digest)
+	}
+
+	/// Get the attested candidate for `digest`, see [`Table::attested_candidate`].
+	pub fn attested_candidate(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Option<AttestedCandidateFor<Ctx>> {
+		attested_candidate(
+			&self.candidate_votes,
+			self.candidate_provider.as_deref(),
+			&self.stats,
+			digest,
+			context,
+			minimum_backing_votes,
+		)
+	}
+
+	/// Get all candidates which are includable, in no particular order.
+	pub fn attested_candidates(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Vec<AttestedCandidateFor<Ctx>> {
+		self.candidate_votes
+			.keys()
+			.filter_map(|digest| self.attested_candidate(digest, context, minimum_backing_votes))
+			.collect()
+	}
+
+	/// Get the status of the candidate with the given `digest`, see [`Table::candidate_status`].
+	pub fn candidate_status(
+		&self,
+		digest: &Ctx::Digest,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+	) -> Option<CandidateStatusFor<Ctx>> {
+		candidate_status(&self.candidate_votes, &self.stats, digest, context, minimum_backing_votes)
+	}
+
+	/// Get a candidate by digest.
+	pub fn get_candidate(&self, digest: &Ctx::Digest) -> Option<&Ctx::Candidate> {
+		self.candidate_votes.get(digest)?.borrow(
+			digest,
+			self.candidate_provider.as_deref(),
+			&self.borrowed_memory,
+		)
+	}
+
+	/// Iterate over the digests of all candidates in the view.
+	pub(crate) fn candidate_digests(&self) -> impl Iterator<Item = &Ctx::Digest> {
+		self.candidate_votes.keys()
+	}
+
+	pub(crate) fn counters(&self) -> &Stats {
+		&self.stats
+	}
+}
+
+fn attested_candidate<Ctx: Context, S: BuildHasher>(
+	candidate_votes: &HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	provider: Option<&dyn CandidateProvider<Ctx>>,
+	stats: &Stats,
+	digest: &Ctx::Digest,
+	context: &Ctx,
+	minimum_backing_votes: u32,
+) -> Option<AttestedCandidateFor<Ctx>> {
+	let started = Instant::now();
+	let attested = candidate_votes.get(digest).and_then(|data| {
+		data.attest(data.status(context, minimum_backing_votes).validity_threshold, || {
+			data.hydrate(digest, provider)
+		})
+	});
+
+	stats.tallies.record(started);
+	attested
+}
+
+fn candidate_status<Ctx: Context, S: BuildHasher>(
+	candidate_votes: &HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	stats: &Stats,
+	digest: &Ctx::Digest,
+	context: &Ctx,
+	minimum_backing_votes: u32,
+) -> Option<CandidateStatusFor<Ctx>> {
+	let started = Instant::now();
+	let status = candidate_votes
+		.get(digest)
+		.map(|data| data.status(context, minimum_backing_votes));
+
+	stats.tallies.record(started);
+	status
+}
+
+fn candidates<'a, Ctx: Context, S>(
+	candidate_votes: &'a HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	group: Option<&'a Ctx::GroupId>,
+) -> impl Iterator<Item = (&'a Ctx::Digest, &'a Ctx::GroupId)> + 'a {
+	candidate_votes
+		.iter()
+		.map(|(digest, data)| (digest, &data.group_id))
+		.filter(move |(_, group_id)| group.map_or(true, |group| group == *group_id))
+}
+
+fn proposal_of_group<'a, Ctx: Context, S>(
+	candidate_votes: &'a HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	group_id: &Ctx::GroupId,
+) -> Option<&'a Ctx::Digest> {
+	candidate_votes
+		.iter()
+		.filter(|(_, data)| &data.group_id == group_id)
+		.min_by_key(|(_, data)| data.imported_at)
+		.map(|(digest, _)| digest)
+}
+
+fn candidates_awaiting_vote<Ctx: Context, S>(
+	candidate_votes: &HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	context: &Ctx,
+	authority: &Ctx::AuthorityId,
+) -> Vec<(Ctx::Digest, Ctx::GroupId)> {
+	candidate_votes
+		.iter()
+		.filter(|(_, data)| {
+			!data.validity_votes.contains_key(authority) &&
+				context.is_member_of(authority, &data.group_id)
+		})
+		.map(|(digest, data)| (digest.clone(), data.group_id.clone()))
+		.collect()
+}
+
+fn seconded_statement<Ctx: Context, S: BuildHasher>(
+	candidate_votes: &HashMap<Ctx::Digest, Arc<CandidateData<Ctx>>, S>,
+	provider: Option<&dyn CandidateProvider<Ctx>>,
+	digest: &Ctx::Digest,
+) -> Option<(Ctx::GroupId, SignedStatementFor<Ctx>)> {
+	let data = candidate_votes.get(digest).filter(|data| data.seconded_votes > 0)?;
+	let candidate = data.hydrate(digest, provider)?;
+	data.validity_votes.iter().find_map(|(sender, vote)| match vote {
+		ValidityVote::Issued(signature) => Some((
+			data.group_id.clone(),
+			SignedStatement {
+				statement: Statement::Seconded((*candidate).clone()),
+				signature: signature.clone(),
+				sender: sender.clone(),
 			},
-		}
+		)),
+		ValidityVote::Valid(_) => None,
+	})
+}
+
+// note that the candidate with the given `digest` couldn't be provided, so an import is skipped.
+fn unavailable_candidate<Ctx: Context>(digest: &Ctx::Digest) -> VoteResult<Ctx> {
+	gum::warn!(
+		target: LOG_TARGET,
+		candidate = ?digest,
+		"Candidate needed for a misbehavior proof is unavailable from the candidate provider",
+	);
+	Err(Rejected::Ignored(ImportError::CandidateUnavailable))
+}
+
+// the heap memory of candidates kept in memory since they were borrowed from the candidate
+// provider. views of a table borrow candidates as well, so it is shared with them.
+struct BorrowedMemory<Candidate> {
+	bytes: AtomicUsize,
+	candidate_heap_size: fn(&Candidate) -> usize,
+}
+
+impl<Candidate> BorrowedMemory<Candidate> {
+	fn new(candidate_heap_size: fn(&Candidate) -> usize) -> Self {
+		BorrowedMemory { bytes: AtomicUsize::new(0), candidate_heap_size }
+	}
 
-		Ok(Some(votes.summary(digest)))
+	fn get(&self) -> usize {
+		self.bytes.load(Ordering::Relaxed)
 	}
+
+	// note that a candidate which borrowed `size` bytes was removed.
+	fn release(&self, size: usize) {
+		let _ = self.bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bytes| {
+			Some(bytes.saturating_sub(size))
+		});
+	}
+}
+
+// the approximate memory used by a candidate without its votes.
+fn candidate_cost<Ctx: Context>(heap_size: usize) -> usize {
+	mem::size_of::<Ctx::Digest>() +
+		mem::size_of::<CandidateData<Ctx>>() +
+		mem::size_of::<Ctx::Candidate>() +
+		heap_size
+}
+
+// the approximate memory used by a validity vote.
+fn vote_cost<Ctx: Context>() -> usize {
+	mem::size_of::<Ctx::AuthorityId>() + mem::size_of::<ValidityVote<Ctx::Signature>>()
 }
 
 type Drain<'a, Ctx> = hash_map::Drain<'a, <Ctx as Context>::AuthorityId, Vec<MisbehaviorFor<Ctx>>>;
@@ -862,8 +2566,19 @@ mod tests {
 
 		let mut candidate = CandidateData::<TestContext> {
 			group_id: GroupId(4),
-			candidate: Candidate(4, 12345),
-			validity_votes: HashMap::new(),
+			candidate: OnceLock::from(Arc::new(Candidate(4, 12345))),
+			validity_votes: VoteMap::new(),
+			seconded_votes: 0,
+			validity_weight: 0,
+			heap_size: 0,
+			borrowed_size: OnceLock::new(),
+			imported_at: 0,
+			logged_at: SmallVec::new(),
+			proposed_at: None,
+			voted_after: SmallVec::new(),
+			age_recorded: false,
+			includable_votes: None,
+			backing_threshold: None,
 		};
 
 		assert!(candidate.attested(validity_threshold).is_none());
@@ -985,4 +2700,1238 @@ mod tests {
 		assert_eq!(summary.group_id, GroupId(2));
 		assert_eq!(summary.validity_votes, 2);
 	}
+
+	// tests in a context whose types can be encoded, as snapshots, dumps and proofs need.
+	mod simple_context {
+		use super::*;
+		use crate::test_utils::simple::{
+			AuthorityId, Candidate, Digest, GroupId, Signature, TestContext,
+		};
+
+		#[test]
+		fn invariants_hold_after_misbehavior() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(3))]
+					.into_iter()
+					.collect(),
+			};
+			let statement = |statement, sender| SignedStatement {
+				statement,
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+
+			let mut table = create_single_seconded();
+			let seconded = statement(Statement::Seconded(Candidate(2, 100)), 1);
+			assert!(table.import_statement(&context, GroupId(2), seconded).is_some());
+
+			let statements = [
+				// a second candidate of the same authority.
+				(GroupId(2), statement(Statement::Seconded(Candidate(2, 200)), 1)),
+				// a candidate of another group than the sender's.
+				(GroupId(2), statement(Statement::Seconded(Candidate(2, 300)), 2)),
+				// a vote on a candidate the sender seconded.
+				(GroupId(2), statement(Statement::Valid(Digest(100)), 1)),
+			];
+			for (group_id, statement) in statements {
+				assert!(table.import_statement(&context, group_id, statement).is_none());
+				table.assert_invariants(&context);
+			}
+			assert_eq!(table.get_misbehavior()[&AuthorityId(1)].len(), 2);
+			assert_eq!(table.get_misbehavior()[&AuthorityId(2)].len(), 1);
+		}
+
+		#[test]
+		fn rejected_statements_report_why() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(3));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			let seconded = |candidate, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, candidate)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+
+			assert!(table.try_import_statement(&context, GroupId(2), seconded(100, 1)).is_ok());
+			assert_eq!(
+				table.try_import_statement(&context, GroupId(2), seconded(100, 1)),
+				Err(ImportError::Duplicate)
+			);
+			assert_eq!(
+				table.try_import_statement(&context, GroupId(2), seconded(999, 1)),
+				Err(ImportError::Misbehavior(MisbehaviorKind::MultipleCandidates))
+			);
+			assert_eq!(
+				table.try_import_statement(&context, GroupId(2), seconded(100, 2)),
+				Err(ImportError::UnknownSigner)
+			);
+
+			let unscheduled = SignedStatement {
+				statement: Statement::Seconded(Candidate(0, 200)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			assert_eq!(
+				table.try_import_statement(&context, GroupId(2), unscheduled),
+				Err(ImportError::RejectedByContext)
+			);
+			assert!(table.get_candidate(&Digest(200)).is_none());
+			assert_eq!(table.get_misbehavior()[&AuthorityId(1)].len(), 1);
+
+			let unknown = SignedStatement {
+				statement: Statement::Valid(Digest(42)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			assert_eq!(
+				table.try_import_statement(&context, GroupId(2), unknown),
+				Err(ImportError::UnknownCandidate)
+			);
+		}
+
+		#[test]
+		fn contents_are_iterated_by_group() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map.insert(AuthorityId(3), GroupId(3));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			let seconded = |group, candidate, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(group, candidate)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			let valid = SignedStatement {
+				statement: Statement::Valid(Digest(100)),
+				signature: Signature(2),
+				sender: AuthorityId(2),
+			};
+
+			table.import_statement(&context, GroupId(2), seconded(2, 100, 1));
+			table.import_statement(&context, GroupId(3), seconded(3, 200, 3));
+			table.import_statement(&context, GroupId(2), valid.clone());
+
+			let mut candidates: Vec<_> = table.candidates(None).collect();
+			candidates.sort_by_key(|(digest, _)| digest.0);
+			assert_eq!(candidates, vec![(&Digest(100), &GroupId(2)), (&Digest(200), &GroupId(3))]);
+			assert_eq!(
+				table.candidates(Some(&GroupId(3))).collect::<Vec<_>>(),
+				vec![(&Digest(200), &GroupId(3))]
+			);
+
+			assert_eq!(table.statements(None).count(), 3);
+			assert_eq!(
+				table.statements(Some(&GroupId(2))).collect::<Vec<_>>(),
+				vec![(GroupId(2), seconded(2, 100, 1)), (GroupId(2), valid)]
+			);
+			assert_eq!(
+				table.validators_with_votes(None).collect::<Vec<_>>(),
+				vec![&AuthorityId(1), &AuthorityId(3), &AuthorityId(2)]
+			);
+			assert_eq!(
+				table.validators_with_votes(Some(&GroupId(3))).collect::<Vec<_>>(),
+				vec![&AuthorityId(3)]
+			);
+		}
+
+		#[test]
+		fn forks_import_independently() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			let seconded = |candidate, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, candidate)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			let events = crate::events::TableEvents::new(2);
+			let mut subscriber = events.subscribe();
+			let recorder = Arc::new(Recorder::default());
+			table.set_events(events);
+			table.set_recorder(recorder.clone());
+			table.import_statement(&context, GroupId(2), seconded(100, 1));
+
+			let mut published =
+				|| std::iter::from_fn(|| subscriber.try_next().ok().flatten()).count();
+			published();
+
+			// the fork is not observed.
+			let mut fork = table.clone();
+			fork.import_statement(&context, GroupId(2), seconded(100, 2));
+			assert_eq!(recorder.recording().statements.len(), 1);
+			assert_eq!(published(), 0);
+
+			table.import_statement(&context, GroupId(2), seconded(999, 1));
+			assert_eq!(recorder.recording().statements.len(), 2);
+			assert_eq!(published(), 1);
+
+			assert_eq!(table.statements(None).count(), 1);
+			assert_eq!(fork.statements(None).count(), 2);
+			assert!(table.detected_misbehavior.contains_key(&AuthorityId(1)));
+			assert!(fork.detected_misbehavior.is_empty());
+			assert_eq!(fork.stats().imports.count, 2);
+			assert_eq!(table.stats().imports.count, 2);
+
+			// signatures and candidates are left out.
+			assert_eq!(
+				format!("{:?}", fork),
+				format!(
+					"Table {{ candidates: 1, statements: 2, pending_bodies: 0, \
+				misbehaving_authorities: 0, memory_usage: {}, \
+				config: Config {{ allow_multiple_seconded: false }}, .. }}",
+					fork.memory_usage(),
+				),
+			);
+		}
+
+		#[test]
+		fn proposals_are_looked_up_by_validator_and_group() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map
+				},
+			};
+
+			let mut table = create_many_seconded();
+			let seconded = |candidate, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, candidate)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			assert_eq!(table.proposed_by(&AuthorityId(1)), None);
+			assert_eq!(table.proposal_of_group(&GroupId(2)), None);
+
+			table.import_statement(&context, GroupId(2), seconded(100, 1));
+			table.import_statement(&context, GroupId(2), seconded(200, 2));
+			table.import_statement(&context, GroupId(2), seconded(300, 1));
+
+			assert_eq!(table.proposed_by(&AuthorityId(1)), Some((&Digest(100), &Signature(1))));
+			assert_eq!(table.proposed_by(&AuthorityId(2)), Some((&Digest(200), &Signature(2))));
+			assert_eq!(table.proposed_by(&AuthorityId(3)), None);
+			assert_eq!(table.proposal_of_group(&GroupId(2)), Some(&Digest(100)));
+			assert_eq!(table.proposal_of_group(&GroupId(3)), None);
+		}
+
+		#[test]
+		fn views_answer_queries_as_of_when_they_were_taken() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			let statement = SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			table.import_statement(&context, GroupId(2), statement.clone());
+
+			let view = table.view().clone();
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 200)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+
+			assert_eq!(table.candidates(None).count(), 2);
+			assert_eq!(
+				view.candidates(None).collect::<Vec<_>>(),
+				vec![(&Digest(100), &GroupId(2))]
+			);
+			assert_eq!(view.proposal_of_group(&GroupId(2)), Some(&Digest(100)));
+			assert_eq!(view.seconded_statement(&Digest(100)), Some((GroupId(2), statement)));
+			assert_eq!(
+				view.candidates_awaiting_vote(&context, &AuthorityId(2)),
+				vec![(Digest(100), GroupId(2))]
+			);
+			assert_eq!(view.stats().imports.count, 2);
+		}
+
+		#[test]
+		fn attested_candidate_verifies_and_roundtrips() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map.insert(AuthorityId(3), GroupId(3));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+
+			let attested = table.attested_candidate(&Digest(100), &context, 2).unwrap();
+			let encoded = attested.encode();
+			let decoded = AttestedCandidateFor::<TestContext>::decode(&mut &encoded[..]).unwrap();
+			assert_eq!(decoded, attested);
+
+			let mut statements = attested.signed_statements::<TestContext>();
+			statements.sort_by_key(|s| s.sender.0);
+			assert_eq!(
+				statements,
+				vec![
+					SignedStatement {
+						statement: Statement::Seconded(Candidate(2, 100)),
+						signature: Signature(1),
+						sender: AuthorityId(1),
+					},
+					SignedStatement {
+						statement: Statement::Valid(Digest(100)),
+						signature: Signature(2),
+						sender: AuthorityId(2),
+					},
+				]
+			);
+
+			let good_signature =
+				|s: &SignedStatement<_, _, AuthorityId, Signature>| s.signature.0 == s.sender.0;
+			assert_eq!(attested.verify(&context, 2, good_signature), Ok(()));
+			assert_eq!(
+				attested.verify(&context, 2, |s| s.sender != AuthorityId(2)),
+				Err(AttestationError::BadSignature(AuthorityId(2)))
+			);
+
+			let mut duplicate = attested.clone();
+			duplicate.validity_votes.push(duplicate.validity_votes[0].clone());
+			assert!(matches!(
+				duplicate.verify(&context, 2, good_signature),
+				Err(AttestationError::DuplicateVote(_))
+			));
+
+			let mut unauthorized = attested.clone();
+			unauthorized
+				.validity_votes
+				.push((AuthorityId(3), ValidityAttestation::Explicit(Signature(3))));
+			assert_eq!(
+				unauthorized.verify(&context, 2, good_signature),
+				Err(AttestationError::UnauthorizedVote(AuthorityId(3)))
+			);
+
+			let mut insufficient = attested;
+			insufficient.validity_votes.pop();
+			assert_eq!(
+				insufficient.verify(&context, 2, good_signature),
+				Err(AttestationError::InsufficientVotes { have: 1, need: 2 })
+			);
+		}
+
+		#[test]
+		fn candidate_status_tracks_progress() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map.insert(AuthorityId(3), GroupId(2));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			assert!(table.candidate_status(&Digest(100), &context, 3).is_none());
+
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+
+			let status = table.candidate_status(&Digest(100), &context, 3).unwrap();
+			assert_eq!(
+				status,
+				CandidateStatus {
+					group_id: GroupId(2),
+					validity_votes: 1,
+					seconded_votes: 1,
+					validity_weight: 1,
+					validity_threshold: 3,
+					available_chunks: None,
+					availability_threshold: None,
+					flagged_invalid_by: Vec::new(),
+				}
+			);
+			assert!(!status.is_includable());
+			assert_eq!(status.missing_validity_votes(), 2);
+
+			for i in 2..=3 {
+				table.import_statement(
+					&context,
+					GroupId(2),
+					SignedStatement {
+						statement: Statement::Valid(Digest(100)),
+						signature: Signature(i),
+						sender: AuthorityId(i),
+					},
+				);
+			}
+
+			let status = table.candidate_status(&Digest(100), &context, 3).unwrap();
+			assert!(status.is_includable());
+			assert_eq!(status.missing_validity_votes(), 0);
+			assert_eq!((status.validity_votes, status.seconded_votes), (3, 1));
+			assert_eq!(status.validity_weight, 3);
+
+			// A higher configured minimum is capped at the group size.
+			assert!(table.candidate_status(&Digest(100), &context, 5).unwrap().is_includable());
+		}
+
+		#[test]
+		fn candidates_awaiting_vote_works() {
+			let context = TestContext {
+				authorities: {
+					let mut map = HashMap::new();
+					map.insert(AuthorityId(1), GroupId(2));
+					map.insert(AuthorityId(2), GroupId(2));
+					map.insert(AuthorityId(3), GroupId(3));
+					map
+				},
+			};
+
+			let mut table = create_single_seconded();
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+
+			assert!(table.candidates_awaiting_vote(&context, &AuthorityId(1)).is_empty());
+			assert!(table.candidates_awaiting_vote(&context, &AuthorityId(3)).is_empty());
+			assert_eq!(
+				table.candidates_awaiting_vote(&context, &AuthorityId(2)),
+				vec![(Digest(100), GroupId(2))]
+			);
+
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+			assert!(table.candidates_awaiting_vote(&context, &AuthorityId(2)).is_empty());
+		}
+
+		#[test]
+		fn candidates_in_distinct_groups_are_not_misbehavior() {
+			// authority 1 is a member of both groups 2 and 3.
+			#[derive(Debug)]
+			struct MultiGroupContext;
+
+			impl Context for MultiGroupContext {
+				type AuthorityId = AuthorityId;
+				type Digest = Digest;
+				type Candidate = Candidate;
+				type GroupId = GroupId;
+				type Signature = Signature;
+
+				fn candidate_digest(candidate: &Candidate) -> Digest {
+					Digest(candidate.1)
+				}
+
+				fn is_member_of(&self, authority: &AuthorityId, group: &GroupId) -> bool {
+					authority == &AuthorityId(1) && (group == &GroupId(2) || group == &GroupId(3))
+				}
+
+				fn get_group_size(&self, _group: &GroupId) -> Option<usize> {
+					Some(1)
+				}
+			}
+
+			let context = MultiGroupContext;
+			let mut table = create_single_seconded();
+
+			for (group, body) in [(2, 100), (3, 200)] {
+				table.import_statement(
+					&context,
+					GroupId(group),
+					SignedStatement {
+						statement: Statement::Seconded(Candidate(group, body)),
+						signature: Signature(1),
+						sender: AuthorityId(1),
+					},
+				);
+			}
+
+			assert!(!table.detected_misbehavior.contains_key(&AuthorityId(1)));
+			assert!(table.attested_candidate(&Digest(100), &context, 1).is_some());
+			assert!(table.attested_candidate(&Digest(200), &context, 1).is_some());
+
+			// a second candidate in the same group is still misbehavior.
+			table.import_statement(
+				&context,
+				GroupId(3),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(3, 300)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+			assert_eq!(
+				table.detected_misbehavior[&AuthorityId(1)][0],
+				Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: (Candidate(3, 200), Signature(1)),
+					second: (Candidate(3, 300), Signature(1)),
+				})
+			);
+		}
+
+		#[test]
+		fn statements_since_marker() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+			let start = table.marker();
+
+			let seconded = SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			table.import_statement(&context, GroupId(2), seconded.clone());
+			let after_seconded = table.marker();
+
+			let valid = SignedStatement {
+				statement: Statement::Valid(Digest(100)),
+				signature: Signature(2),
+				sender: AuthorityId(2),
+			};
+			table.import_statement(&context, GroupId(2), valid.clone());
+
+			// duplicates and misbehavior are not logged.
+			table.import_statement(&context, GroupId(2), valid.clone());
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement { signature: Signature(3), ..valid.clone() },
+			);
+
+			let (statements, end) = table.statements_since(start);
+			assert_eq!(statements, vec![(GroupId(2), seconded), (GroupId(2), valid.clone())]);
+			assert_eq!(end, table.marker());
+
+			assert_eq!(table.statements_since(after_seconded).0, vec![(GroupId(2), valid)]);
+			assert_eq!(table.statements_since(end), (Vec::new(), end));
+		}
+
+		#[test]
+		fn seconded_statement_provides_announced_body() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+
+			// not a member of the group.
+			assert!(!table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(100),
+				AuthorityId(3),
+				Signature(3)
+			));
+			assert!(table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(100),
+				AuthorityId(2),
+				Signature(2)
+			));
+			assert!(table.is_body_pending(&Digest(100)));
+			assert!(table.get_candidate(&Digest(100)).is_none());
+
+			let seconded = SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			let summary = table.import_statement(&context, GroupId(2), seconded).unwrap();
+			assert_eq!(summary.validity_votes, 2);
+			assert!(!table.is_body_pending(&Digest(100)));
+
+			// announcements of known candidates are imported right away.
+			assert!(!table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(100),
+				AuthorityId(2),
+				Signature(2)
+			));
+			assert_eq!(table.import_body(&context, Candidate(2, 100)), None);
+		}
+
+		#[test]
+		fn candidates_are_shared_not_copied() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+			assert!(table.shared_candidate(&Digest(100)).is_none());
+
+			let seconded = SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			table.import_statement(&context, GroupId(2), seconded);
+			let shared = table.shared_candidate(&Digest(100)).unwrap();
+			assert_eq!(*shared, Candidate(2, 100));
+
+			// seconding a known candidate keeps the stored one.
+			assert!(!table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(100),
+				AuthorityId(2),
+				Signature(2)
+			));
+			let summary = table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+			assert!(summary.is_none());
+			assert_eq!(table.get_candidate(&Digest(100)).unwrap().1, 100);
+			assert!(Arc::ptr_eq(&shared, &table.shared_candidate(&Digest(100)).unwrap()));
+			assert_eq!(Arc::strong_count(&shared), 2);
+		}
+
+		#[test]
+		fn announced_candidates_imported_by_reference_are_copied_once() {
+			thread_local! {
+				static CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+			}
+
+			// a candidate which counts how often it is copied.
+			#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+			struct CountedCandidate(u64);
+
+			impl Clone for CountedCandidate {
+				fn clone(&self) -> Self {
+					CLONES.with(|clones| clones.set(clones.get() + 1));
+					CountedCandidate(self.0)
+				}
+			}
+
+			struct CountingContext(TestContext);
+
+			impl Context for CountingContext {
+				type AuthorityId = AuthorityId;
+				type Digest = Digest;
+				type Candidate = CountedCandidate;
+				type GroupId = GroupId;
+				type Signature = Signature;
+
+				fn candidate_digest(candidate: &CountedCandidate) -> Digest {
+					Digest(candidate.0)
+				}
+
+				fn is_member_of(&self, authority: &AuthorityId, group: &GroupId) -> bool {
+					self.0.is_member_of(authority, group)
+				}
+
+				fn get_group_size(&self, group: &GroupId) -> Option<usize> {
+					self.0.get_group_size(group)
+				}
+			}
+
+			let context = CountingContext(TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			});
+			let mut table = create_single_seconded();
+			assert!(table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(100),
+				AuthorityId(2),
+				Signature(2)
+			));
+
+			let seconded = SignedStatement {
+				statement: Statement::Seconded(CountedCandidate(100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			let summary = table.import_statement_ref(&context, GroupId(2), &seconded).unwrap();
+			assert_eq!(summary.validity_votes, 2);
+			assert!(!table.is_body_pending(&Digest(100)));
+			// the announced seconder shares the copy stored for the statement.
+			assert_eq!(CLONES.with(|clones| clones.get()), 1);
+		}
+
+		#[test]
+		fn debug_dump_is_replayed() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 101)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			);
+
+			let dump = table.export_debug(&context, 2);
+			assert_eq!(dump.candidates.len(), 1);
+			assert_eq!(dump.candidates[0].0, Digest(100));
+			assert!(dump.candidates[0].1.is_includable());
+			assert_eq!(dump.snapshot.misbehavior.len(), 1);
+			assert!(format!("{:#?}", dump).contains("MultipleCandidates"));
+
+			let replayed = Table::import_debug(&context, dump.clone());
+			assert_eq!(replayed.export_debug(&context, 2), dump);
+		}
+
+		#[test]
+		fn candidates_are_hydrated_from_provider() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let stored = Arc::new(Candidate(2, 100));
+			let provided = stored.clone();
+
+			let mut table = create_single_seconded();
+			table.set_candidate_provider(Arc::new(move |digest: &Digest| {
+				(*digest == Digest(100)).then(|| provided.clone())
+			}));
+
+			let seconded = |candidate, sender| SignedStatement {
+				statement: Statement::Seconded(candidate),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			table.import_statement(&context, GroupId(2), seconded(Candidate(2, 100), 1));
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+			assert!(table.candidate_votes[&Digest(100)].candidate.get().is_none());
+
+			// attestations and proofs of misbehavior hydrate the candidate.
+			let attested = table.view().attested_candidates(&context, 2);
+			assert_eq!(attested[0].candidate, Candidate(2, 100));
+			table.import_statement(&context, GroupId(2), seconded(Candidate(2, 101), 1));
+			assert_eq!(
+				table.detected_misbehavior[&AuthorityId(1)][0],
+				Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: (Candidate(2, 100), Signature(1)),
+					second: (Candidate(2, 101), Signature(1)),
+				})
+			);
+			assert!(Arc::ptr_eq(&stored, &table.shared_candidate(&Digest(100)).unwrap()));
+			assert!(table.candidate_votes[&Digest(100)].candidate.get().is_none());
+
+			// borrowing the candidate keeps it, and counts its memory.
+			table.set_memory_limit(Some(MemoryLimit {
+				max_bytes: usize::MAX,
+				candidate_heap_size: |_| 7,
+			}));
+			let usage = table.memory_usage();
+			assert_eq!(table.get_candidate(&Digest(100)), Some(&Candidate(2, 100)));
+			assert!(table.candidate_votes[&Digest(100)].candidate.get().is_some());
+			assert_eq!(table.memory_usage(), usage + 7);
+			table.assert_invariants(&context);
+
+			table.set_memory_limit(Some(MemoryLimit { max_bytes: 0, candidate_heap_size: |_| 7 }));
+			assert_eq!(table.memory_usage(), 0);
+			table.assert_invariants(&context);
+		}
+
+		#[test]
+		fn conflicting_votes_are_proven_with_the_incoming_candidate() {
+			let context =
+				TestContext { authorities: [(AuthorityId(1), GroupId(2))].into_iter().collect() };
+			let mut table = create_single_seconded();
+			table.set_candidate_provider(Arc::new(|_: &Digest| None));
+
+			let seconded = |signature| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(signature),
+				sender: AuthorityId(1),
+			};
+			table.import_statement(&context, GroupId(2), seconded(1));
+			assert!(table.get_candidate(&Digest(100)).is_none());
+
+			// the candidate can't be hydrated, but comes with the second statement.
+			table.import_statement(&context, GroupId(2), seconded(2));
+			assert_eq!(
+				table.detected_misbehavior[&AuthorityId(1)],
+				vec![Misbehavior::DoubleSign(DoubleSign::Seconded(
+					Candidate(2, 100),
+					Signature(1),
+					Signature(2),
+				))]
+			);
+		}
+
+		#[test]
+		fn misbehavior_roundtrips() {
+			type TestMisbehavior = MisbehaviorFor<TestContext>;
+
+			let statement = SignedStatement {
+				statement: Statement::Valid(Digest(100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			let misbehaviors = vec![
+				Misbehavior::ValidityDoubleVote(ValidityDoubleVote::IssuedAndValidity(
+					(Candidate(2, 100), Signature(1)),
+					(Digest(100), Signature(2)),
+				)),
+				Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: (Candidate(2, 100), Signature(1)),
+					second: (Candidate(2, 999), Signature(1)),
+				}),
+				Misbehavior::UnauthorizedStatement(UnauthorizedStatement { statement }),
+				Misbehavior::DoubleSign(DoubleSign::Validity(
+					Digest(100),
+					Signature(1),
+					Signature(2),
+				)),
+			];
+
+			for misbehavior in misbehaviors {
+				let encoded = misbehavior.encode();
+				assert_eq!(TestMisbehavior::decode(&mut &encoded[..]).unwrap(), misbehavior);
+			}
+		}
+
+		#[test]
+		fn compact_statements_share_encoding() {
+			let valid = SignedStatement {
+				statement: Statement::Valid(Digest(100)),
+				signature: Signature(2),
+				sender: AuthorityId(2),
+			};
+			let seconded = SignedStatement {
+				statement: Statement::<_, Digest>::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+
+			let compact =
+				CompactSignedStatementFor::<TestContext>::try_from(valid.clone()).unwrap();
+			assert_eq!(compact.encode(), valid.encode());
+			// kind tag, digest, signature and sender.
+			assert_eq!(compact.encoded_size(), 1 + 8 + 8 + 8);
+			assert_eq!(
+				SignedStatementFor::<TestContext>::decode(&mut &compact.encode()[..]).unwrap(),
+				valid
+			);
+			assert_eq!(SignedStatementFor::<TestContext>::from(compact), valid);
+
+			let compact = CompactSignedStatementFor::<TestContext>::try_from(seconded.clone());
+			assert_eq!(compact, Err(seconded));
+		}
+
+		#[test]
+		fn table_is_restored_from_snapshot() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+
+			let seconded = |body, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, body)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			table.import_statement(&context, GroupId(2), seconded(100, 1));
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+			// misbehavior.
+			table.import_statement(&context, GroupId(2), seconded(999, 1));
+			table.note_announcement(
+				&context,
+				GroupId(2),
+				Digest(200),
+				AuthorityId(2),
+				Signature(2),
+			);
+
+			let snapshot = table.snapshot();
+			let encoded = snapshot.encode();
+			let decoded = TableSnapshotFor::<TestContext>::decode(&mut &encoded[..]).unwrap();
+			assert_eq!(decoded, snapshot);
+
+			let restored = Table::restore(&context, decoded);
+			assert_eq!(restored.snapshot(), snapshot);
+			assert_eq!(restored.marker(), table.marker());
+			assert_eq!(restored.get_misbehavior(), table.get_misbehavior());
+			assert_eq!(
+				restored.candidate_status(&Digest(100), &context, 2),
+				table.candidate_status(&Digest(100), &context, 2)
+			);
+			assert!(restored.is_body_pending(&Digest(200)));
+			restored.assert_invariants(&context);
+		}
+
+		#[test]
+		fn least_backed_candidates_are_evicted_over_memory_limit() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_many_seconded();
+
+			let seconded = |body| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, body)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			table.import_statement(&context, GroupId(2), seconded(100));
+			table.import_statement(
+				&context,
+				GroupId(2),
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+			);
+			let backed_usage = table.memory_usage();
+			table.import_statement(&context, GroupId(2), seconded(200));
+			let candidate_usage = table.memory_usage() - backed_usage;
+			assert!(candidate_usage > 0);
+
+			// room for the backed candidate and one more.
+			table.set_memory_limit(Some(MemoryLimit::new(backed_usage + candidate_usage)));
+			assert_eq!(table.memory_usage(), backed_usage + candidate_usage);
+
+			// the newest of the least-backed candidates is dropped first.
+			assert!(table.import_statement(&context, GroupId(2), seconded(300)).is_none());
+			assert!(table.get_candidate(&Digest(300)).is_none());
+			assert!(table.get_candidate(&Digest(200)).is_some());
+			assert_eq!(table.memory_usage(), backed_usage + candidate_usage);
+
+			// accounting for the heap memory of candidates, only the backed candidate fits.
+			table.set_memory_limit(Some(MemoryLimit {
+				max_bytes: backed_usage + candidate_usage,
+				candidate_heap_size: |_| 1,
+			}));
+			assert!(table.get_candidate(&Digest(200)).is_none());
+			assert!(table.get_candidate(&Digest(100)).is_some());
+			assert_eq!(table.statements_since(StatementMarker::default()).0.len(), 2);
+
+			// evicted candidates can be seconded again.
+			table.set_memory_limit(None);
+			let summary = table.import_statement(&context, GroupId(2), seconded(200)).unwrap();
+			assert_eq!(summary.validity_votes, 1);
+			assert!(table.get_misbehavior().is_empty());
+			table.assert_invariants(&context);
+		}
+
+		#[test]
+		fn proposals_outlive_evicted_candidates() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+
+			let seconded = |body, sender| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, body)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			};
+			table.import_statement(&context, GroupId(2), seconded(100, 1));
+			let usage = table.memory_usage();
+			table.import_statement(&context, GroupId(2), seconded(200, 2));
+			let marker = table.marker();
+
+			// the newest candidate is evicted, along with its statement.
+			table.set_memory_limit(Some(MemoryLimit::new(usage)));
+			assert!(table.get_candidate(&Digest(200)).is_none());
+			assert_eq!(table.statements_since(StatementMarker::default()).0.len(), 1);
+			assert_eq!(table.import_log.len(), 1);
+			assert_eq!(table.marker(), marker);
+			assert_eq!(table.proposed_by(&AuthorityId(2)), Some((&Digest(200), &Signature(2))));
+			table.assert_invariants(&context);
+
+			// seconding another candidate still conflicts with the evicted one, which can't be
+			// proven without the candidate.
+			table.set_memory_limit(None);
+			assert!(table.import_statement(&context, GroupId(2), seconded(300, 2)).is_none());
+			assert!(table.get_candidate(&Digest(300)).is_none());
+			assert!(table.get_misbehavior().is_empty());
+
+			table.set_candidate_provider(Arc::new(|digest: &Digest| {
+				Some(Arc::new(Candidate(2, digest.0)))
+			}));
+			assert!(table.import_statement(&context, GroupId(2), seconded(300, 2)).is_none());
+			assert_eq!(
+				table.get_misbehavior()[&AuthorityId(2)],
+				vec![Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: (Candidate(2, 200), Signature(2)),
+					second: (Candidate(2, 300), Signature(2)),
+				})]
+			);
+
+			// the evicted candidate is imported again when its proposer seconds it again.
+			let summary = table.import_statement(&context, GroupId(2), seconded(200, 2)).unwrap();
+			assert_eq!(summary.validity_votes, 1);
+			table.assert_invariants(&context);
+		}
+
+		#[test]
+		fn table_with_custom_hasher_behaves_the_same() {
+			type FixedState = std::hash::BuildHasherDefault<hash_map::DefaultHasher>;
+
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let mut table = create_single_seconded();
+			let mut custom = Table::with_hasher(
+				Config { allow_multiple_seconded: false },
+				FixedState::default(),
+			);
+
+			let statements = [
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 100)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+				SignedStatement {
+					statement: Statement::Valid(Digest(100)),
+					signature: Signature(2),
+					sender: AuthorityId(2),
+				},
+				SignedStatement {
+					statement: Statement::Seconded(Candidate(2, 999)),
+					signature: Signature(1),
+					sender: AuthorityId(1),
+				},
+			];
+			for statement in statements {
+				assert_eq!(
+					custom.import_statement(&context, GroupId(2), statement.clone()),
+					table.import_statement(&context, GroupId(2), statement),
+				);
+			}
+
+			assert_eq!(custom.snapshot(), table.snapshot());
+			assert_eq!(custom.state_root(), table.state_root());
+			let restored =
+				Table::restore_with_hasher(&context, custom.snapshot(), FixedState::default());
+			assert_eq!(restored.snapshot(), table.snapshot());
+		}
+
+		#[test]
+		fn signing_payload_separates_kinds_and_contexts() {
+			let seconded = Statement::Seconded(Candidate(2, 100));
+			let valid = Statement::Valid(Digest(100));
+
+			let payload = signing_payload::<TestContext, _>(&seconded, &(1u32, 42u64));
+			assert_eq!(payload, (*b"BKNG", 1u8, Digest(100), 1u32, 42u64).encode());
+			// only the kind differs from a validity statement on the same candidate.
+			let valid_payload = signing_payload::<TestContext, _>(&valid, &(1u32, 42u64));
+			assert_ne!(payload, valid_payload);
+			assert_eq!(payload[5..], valid_payload[5..]);
+
+			assert_ne!(payload, signing_payload::<TestContext, _>(&seconded, &(2u32, 42u64)));
+			assert_ne!(payload, signing_payload::<TestContext, _>(&seconded, &(1u32, 43u64)));
+		}
+
+		#[test]
+		fn statements_are_keyed_by_their_digest() {
+			let seconded = SignedStatement {
+				statement: Statement::Seconded(Candidate(2, 100)),
+				signature: Signature(1),
+				sender: AuthorityId(1),
+			};
+			let valid = SignedStatement {
+				statement: Statement::Valid(Digest(100)),
+				signature: Signature(2),
+				sender: AuthorityId(2),
+			};
+			assert_eq!(seconded.digest::<TestContext>(), Digest(100));
+			assert_eq!(valid.digest::<TestContext>(), Digest(100));
+			assert_eq!(seconded.statement.candidate(), Some(&Candidate(2, 100)));
+			assert!(!valid.statement.is_seconded());
+
+			let seen: HashSet<_> =
+				[seconded.clone(), valid.clone(), seconded].into_iter().collect();
+			assert_eq!(seen.len(), 2);
+			assert!(seen.contains(&valid));
+		}
+
+		#[test]
+		fn statements_imported_by_reference_match_owned_imports() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let statements = [
+				(1, Statement::Seconded(Candidate(2, 100))),
+				(2, Statement::Valid(Digest(100))),
+				(2, Statement::Valid(Digest(100))),
+				// a second candidate of the same authority, which is misbehavior.
+				(1, Statement::Seconded(Candidate(2, 200))),
+			]
+			.map(|(sender, statement)| SignedStatement {
+				statement,
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			});
+
+			let mut owned = create_single_seconded();
+			let mut borrowed = create_single_seconded();
+			for statement in &statements {
+				assert_eq!(
+					borrowed.try_import_statement_ref(&context, GroupId(2), statement),
+					owned.try_import_statement(&context, GroupId(2), statement.clone()),
+				);
+			}
+
+			assert_eq!(borrowed.snapshot(), owned.snapshot());
+			assert_eq!(borrowed.get_misbehavior(), owned.get_misbehavior());
+			assert_eq!(borrowed.get_misbehavior().len(), 1);
+		}
+
+		#[test]
+		fn state_root_is_independent_of_import_order() {
+			let context = TestContext {
+				authorities: [(AuthorityId(1), GroupId(2)), (AuthorityId(2), GroupId(2))]
+					.into_iter()
+					.collect(),
+			};
+			let statements = [(1, 100), (2, 200)].map(|(sender, body)| SignedStatement {
+				statement: Statement::Seconded(Candidate(2, body)),
+				signature: Signature(sender),
+				sender: AuthorityId(sender),
+			});
+
+			let mut table_a = create_single_seconded();
+			let mut table_b = create_single_seconded();
+			assert_eq!(table_a.state_root(), [0; 32]);
+
+			table_a.import_statement(&context, GroupId(2), statements[0].clone());
+			let partial_root = table_a.state_root();
+			table_a.import_statement(&context, GroupId(2), statements[1].clone());
+			table_b.import_statement(&context, GroupId(2), statements[1].clone());
+			table_b.import_statement(&context, GroupId(2), statements[0].clone());
+
+			assert_eq!(table_a.state_root(), table_b.state_root());
+			assert_ne!(table_a.state_root(), partial_root);
+			assert_ne!(partial_root, [0; 32]);
+		}
+	}
 }
@@ -0,0 +1,306 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Queries on the state of a table, e.g. for a debug RPC.
+//!
+//! A [`TableInspector`] answers questions about a table in terms of plain response types,
+//! which can be serialized with the `serde` feature, so that an RPC layer doesn't depend on the
+//! internals of the table. [`TableInspector::format_table`] renders the state of a table as a
+//! report for humans instead, e.g. to log when a round times out.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+	generic::{CandidateStatus, Context, MisbehaviorFor, MisbehaviorKind, Table, ValidatorSet},
+	stats::OperationStats,
+};
+
+/// A candidate in the table, along with its status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandidateInfo<Digest, GroupId, AuthorityId> {
+	/// The digest of the candidate.
+	pub digest: Digest,
+	/// The status of the candidate.
+	pub status: CandidateStatus<GroupId, AuthorityId>,
+	/// Whether the candidate is includable.
+	pub includable: bool,
+}
+
+/// Type alias for candidate info corresponding to context type.
+pub type CandidateInfoFor<Ctx> = CandidateInfo<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::AuthorityId,
+>;
+
+/// How an authority took part in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidatorParticipation<AuthorityId> {
+	/// The authority.
+	pub authority: AuthorityId,
+	/// The number of candidates the authority seconded.
+	pub seconded: usize,
+	/// The number of candidates the authority explicitly voted valid.
+	pub valid: usize,
+	/// The number of candidates in the groups of the authority it hasn't voted on yet.
+	pub awaiting_vote: usize,
+	/// The time the authority took to vote, see [`Table::vote_latency`].
+	pub vote_latency: OperationStats,
+	/// The number of times the authority was detected misbehaving.
+	pub misbehavior: usize,
+}
+
+/// Misbehavior detected in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MisbehaviorInfo<AuthorityId, Misbehavior> {
+	/// The misbehaving authority.
+	pub authority: AuthorityId,
+	/// The kind of misbehavior.
+	pub kind: MisbehaviorKind,
+	/// The proof of the misbehavior.
+	pub proof: Misbehavior,
+}
+
+/// Type alias for misbehavior info corresponding to context type.
+pub type MisbehaviorInfoFor<Ctx> =
+	MisbehaviorInfo<<Ctx as Context>::AuthorityId, MisbehaviorFor<Ctx>>;
+
+/// Answers queries on the state of a table.
+pub struct TableInspector<'a, Ctx: Context> {
+	table: &'a Table<Ctx>,
+	context: &'a Ctx,
+	minimum_backing_votes: u32,
+}
+
+impl<'a, Ctx: ValidatorSet> TableInspector<'a, Ctx> {
+	/// Inspect `table`, considering candidates includable once they have
+	/// `minimum_backing_votes`.
+	pub fn new(table: &'a Table<Ctx>, context: &'a Ctx, minimum_backing_votes: u32) -> Self {
+		TableInspector { table, context, minimum_backing_votes }
+	}
+
+	/// List the candidates in the table with their status, ordered by group, in no particular
+	/// order within a group.
+	pub fn candidates(&self) -> Vec<CandidateInfoFor<Ctx>> {
+		let mut candidates: Vec<_> = self
+			.table
+			.candidates(None)
+			.filter_map(|(digest, _)| {
+				let status = self.table.candidate_status(
+					digest,
+					self.context,
+					self.minimum_backing_votes,
+				)?;
+				Some(CandidateInfo {
+					includable: status.is_includable(),
+					digest: digest.clone(),
+					status,
+				})
+			})
+			.collect();
+		candidates.sort_by(|a, b| a.status.group_id.cmp(&b.status.group_id));
+		candidates
+	}
+
+	/// List the validators of the context with their participation, in the order of the
+	/// context, including those which didn't issue any statement. Authorities outside of the
+	/// validators of the context which misbehaved come last.
+	pub fn validators(&self) -> Vec<ValidatorParticipation<Ctx::AuthorityId>> {
+		let mut votes: HashMap<&Ctx::AuthorityId, (usize, usize)> = HashMap::new();
+		for (digest, _) in self.table.candidates(None) {
+			for (authority, seconded) in self.table.candidate_votes(digest) {
+				let (seconded_count, valid_count) = votes.entry(authority).or_default();
+				if seconded {
+					*seconded_count += 1;
+				} else {
+					*valid_count += 1;
+				}
+			}
+		}
+
+		let misbehavior = self.table.get_misbehavior();
+		let validators = self.context.validators();
+		let outsiders = misbehavior
+			.keys()
+			.filter(|authority| !validators.contains(authority))
+			.cloned()
+			.collect::<Vec<_>>();
+		validators
+			.into_iter()
+			.chain(outsiders)
+			.map(|authority| {
+				let (seconded, valid) = votes.get(&authority).copied().unwrap_or_default();
+				ValidatorParticipation {
+					seconded,
+					valid,
+					awaiting_vote: self
+						.table
+						.candidates_awaiting_vote(self.context, &authority)
+						.len(),
+					misbehavior: misbehavior.get(&authority).map_or(0, Vec::len),
+					vote_latency: self.table.vote_latency(&authority),
+					authority,
+				}
+			})
+			.collect()
+	}
+
+	/// Render the candidates in the table as a report grouped by group.
+	///
+	/// For every candidate, the report lists the votes collected and needed, the authorities
+	/// which voted, and the members of its group which haven't.
+	pub fn format_table(&self) -> String {
+		let candidates = self.candidates();
+
+		// writing to a string doesn't fail.
+		let mut report = String::new();
+		let groups = candidates.chunk_by(|a, b| a.status.group_id == b.status.group_id);
+		let _ = writeln!(report, "{} group(s) with candidates", groups.clone().count());
+		for candidates in groups {
+			let group_id = &candidates[0].status.group_id;
+			let members = self.context.group_members(group_id);
+			let _ = writeln!(report, "group {:?}: {} candidate(s)", group_id, candidates.len());
+			for info in candidates {
+				let voted: Vec<_> = self
+					.table
+					.candidate_votes(&info.digest)
+					.map(|(authority, _)| authority)
+					.collect();
+				let missing: Vec<_> =
+					members.iter().filter(|member| !voted.contains(member)).collect();
+				let threshold = match info.status.validity_threshold {
+					usize::MAX => "?".to_string(),
+					threshold => threshold.to_string(),
+				};
+
+				let _ = writeln!(
+					report,
+					"  candidate {:?}: {}/{} votes, {}",
+					info.digest,
+					info.status.validity_votes,
+					threshold,
+					if info.includable { "includable" } else { "pending" },
+				);
+				let _ = writeln!(report, "    voted: {:?}", voted);
+				let _ = writeln!(report, "    missing: {:?}", missing);
+			}
+		}
+
+		report
+	}
+
+	/// List the misbehavior detected in the table, which wasn't drained yet.
+	pub fn misbehavior(&self) -> Vec<MisbehaviorInfoFor<Ctx>> {
+		self.table
+			.get_misbehavior()
+			.iter()
+			.flat_map(|(authority, misbehavior)| {
+				misbehavior.iter().map(move |proof| MisbehaviorInfo {
+					authority: authority.clone(),
+					kind: proof.kind(),
+					proof: proof.clone(),
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, seconded_other, valid},
+		test_utils::TestContext,
+	};
+
+	#[test]
+	fn table_state_is_inspected() {
+		// validator 3 stays silent.
+		let (_, mut table) = context_and_table();
+		let context = TestContext::builder().with_group(0, [1, 2, 3]).build();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		table.import_statement(&context, 0, seconded_other(1));
+
+		let inspector = TableInspector::new(&table, &context, 2);
+		let candidates = inspector.candidates();
+		assert_eq!(candidates.len(), 1);
+		assert_eq!(candidates[0].digest, 100);
+		assert_eq!(candidates[0].status.validity_votes, 2);
+		assert!(candidates[0].includable);
+
+		let validators = inspector.validators();
+		assert_eq!(
+			validators,
+			vec![
+				ValidatorParticipation {
+					authority: 1,
+					seconded: 1,
+					valid: 0,
+					awaiting_vote: 0,
+					misbehavior: 1,
+					vote_latency: Default::default(),
+				},
+				ValidatorParticipation {
+					authority: 2,
+					seconded: 0,
+					valid: 1,
+					awaiting_vote: 0,
+					misbehavior: 0,
+					vote_latency: Default::default(),
+				},
+				ValidatorParticipation {
+					authority: 3,
+					seconded: 0,
+					valid: 0,
+					awaiting_vote: 1,
+					misbehavior: 0,
+					vote_latency: Default::default(),
+				},
+			]
+		);
+
+		let misbehavior = inspector.misbehavior();
+		assert_eq!(misbehavior.len(), 1);
+		assert_eq!(misbehavior[0].authority, 1);
+		assert_eq!(misbehavior[0].kind, MisbehaviorKind::MultipleCandidates);
+	}
+
+	#[test]
+	fn table_is_formatted() {
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+
+		let report = TableInspector::new(&table, &context, 2).format_table();
+		assert_eq!(
+			report,
+			"1 group(s) with candidates\n\
+			 group 0: 1 candidate(s)\n\
+			 \x20 candidate 100: 1/2 votes, pending\n\
+			 \x20   voted: [1]\n\
+			 \x20   missing: [2]\n"
+		);
+
+		table.import_statement(&context, 0, valid(2));
+		let report = TableInspector::new(&table, &context, 2).format_table();
+		assert!(report.contains("candidate 100: 2/2 votes, includable"));
+		assert!(report.contains("voted: [1, 2]"));
+		assert!(report.contains("missing: []\n"));
+	}
+}
@@ -26,46 +26,144 @@
 //! Each parachain is associated with two sets of authorities: those which can
 //! propose and attest to validity of candidates, and those who can only attest
 //! to availability.
+//!
+//! Without the `std` feature, only [`statement`] and [`proof`] are built, for the runtime to check
+//! statements, misbehavior proofs and attested candidates.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod agreement;
+#[cfg(feature = "std")]
+pub mod alert;
+#[cfg(all(feature = "std", any(test, feature = "quickcheck")))]
+pub mod arbitrary;
+#[cfg(feature = "std")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub mod availability;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod concurrent;
+#[cfg(feature = "std")]
+pub mod encoded;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
 pub mod generic;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod mapped;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod migration;
+#[cfg(feature = "std")]
+pub mod network;
+#[cfg(feature = "std")]
+pub mod outbox;
+#[cfg(feature = "std")]
+pub mod progress;
+pub mod proof;
+#[cfg(feature = "std")]
+pub mod proposal;
+#[cfg(feature = "std")]
+pub mod recording;
+#[cfg(feature = "std")]
+pub mod router;
+pub mod statement;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod test_utils;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod wal;
+#[cfg(feature = "std")]
+pub mod wire;
+
+#[cfg(all(test, feature = "std"))]
+mod differential;
+#[cfg(all(test, feature = "std"))]
+mod simnet;
 
-pub use generic::{Config, Context, Table};
+// the proof of unwrapping the result of taking a lock.
+#[cfg(feature = "std")]
+const LOCK_PROOF: &str = "poisoned only if a holder panicked; qed";
+
+#[cfg(feature = "std")]
+pub use builder::TableBuilder;
+#[cfg(feature = "std")]
+pub use generic::{Config, Table};
+#[cfg(feature = "std")]
+pub use network::{Network, NetworkEvent};
+#[cfg(feature = "std")]
+pub use proposal::{ProposalConfig, SelectionStrategy};
+#[cfg(feature = "std")]
+pub use router::{GossipRouter, StatementGossip};
+pub use statement::Context;
 
 /// Concrete instantiations suitable for v2 primitives.
 pub mod v2 {
-	use crate::generic;
+	use crate::{proof, statement};
 	use primitives::{
 		CandidateHash, CommittedCandidateReceipt, CompactStatement as PrimitiveStatement,
-		CoreIndex, ValidatorIndex, ValidatorSignature,
+		ValidatorIndex, ValidatorSignature,
 	};
 
 	/// Statements about candidates on the network.
-	pub type Statement = generic::Statement<CommittedCandidateReceipt, CandidateHash>;
+	pub type Statement = statement::Statement<CommittedCandidateReceipt, CandidateHash>;
 
 	/// Signed statements about candidates.
-	pub type SignedStatement = generic::SignedStatement<
+	pub type SignedStatement = statement::SignedStatement<
 		CommittedCandidateReceipt,
 		CandidateHash,
 		ValidatorIndex,
 		ValidatorSignature,
 	>;
 
+	/// Signed statements about candidates, in compact form.
+	pub type CompactSignedStatement =
+		statement::CompactSignedStatement<CandidateHash, ValidatorIndex, ValidatorSignature>;
+
 	/// Kinds of misbehavior, along with proof.
-	pub type Misbehavior = generic::Misbehavior<
+	pub type Misbehavior = statement::Misbehavior<
 		CommittedCandidateReceipt,
 		CandidateHash,
 		ValidatorIndex,
 		ValidatorSignature,
 	>;
 
+	/// A snapshot of a statement table.
+	#[cfg(feature = "std")]
+	pub type TableSnapshot = crate::generic::TableSnapshot<
+		CommittedCandidateReceipt,
+		CandidateHash,
+		primitives::CoreIndex,
+		ValidatorIndex,
+		ValidatorSignature,
+	>;
+
+	/// A proof of misbehavior for submission to the runtime.
+	pub type MisbehaviorProof =
+		proof::MisbehaviorProof<CandidateHash, ValidatorIndex, ValidatorSignature>;
+
 	/// A summary of import of a statement.
-	pub type Summary = generic::Summary<CandidateHash, CoreIndex>;
+	#[cfg(feature = "std")]
+	pub type Summary = crate::generic::Summary<CandidateHash, primitives::CoreIndex>;
 
 	impl<'a> From<&'a Statement> for PrimitiveStatement {
 		fn from(s: &'a Statement) -> PrimitiveStatement {
 			match *s {
-				generic::Statement::Valid(s) => PrimitiveStatement::Valid(s),
-				generic::Statement::Seconded(ref s) => PrimitiveStatement::Seconded(s.hash()),
+				statement::Statement::Valid(s) => PrimitiveStatement::Valid(s),
+				statement::Statement::Seconded(ref s) => PrimitiveStatement::Seconded(s.hash()),
 			}
 		}
 	}
@@ -0,0 +1,236 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Candidates kept in a memory-mapped file.
+//!
+//! Hundreds of full candidates may be buffered while they await availability. A
+//! [`MappedCandidates`] keeps them encoded in a file instead, which is mapped into memory, so
+//! that the operating system can page them out. Used as the
+//! [`CandidateProvider`](crate::generic::CandidateProvider) of a table, the table only keeps
+//! the digests of the candidates, and the store only keeps their offsets in the file.
+
+use std::{
+	collections::HashMap,
+	fs::{File, OpenOptions},
+	io::{self, Seek, SeekFrom, Write},
+	path::Path,
+	sync::{Arc, Mutex},
+};
+
+use memmap2::Mmap;
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	generic::{CandidateProvider, Context},
+	LOCK_PROOF,
+};
+
+struct Inner<Digest> {
+	file: File,
+	// the mapping of the file, remapped when it doesn't cover a candidate yet.
+	map: Option<Mmap>,
+	len: usize,
+	// the offset and length of every candidate in the file.
+	index: HashMap<Digest, (usize, usize)>,
+}
+
+/// Encoded candidates in a memory-mapped file, by digest.
+///
+/// The file is only valid for as long as the store exists: the offsets of the candidates are
+/// kept in memory.
+pub struct MappedCandidates<Ctx: Context> {
+	inner: Mutex<Inner<Ctx::Digest>>,
+}
+
+impl<Ctx: Context> MappedCandidates<Ctx>
+where
+	Ctx::Candidate: Encode + Decode,
+{
+	/// Create a store in a new file at `path`, truncating any existing file.
+	pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(path.as_ref())?;
+
+		Ok(MappedCandidates {
+			inner: Mutex::new(Inner { file, map: None, len: 0, index: HashMap::new() }),
+		})
+	}
+
+	/// Store a candidate, unless it is stored already.
+	///
+	/// Candidates should be stored before statements seconding them are imported into a table
+	/// they are provided to.
+	pub fn insert(&self, candidate: &Ctx::Candidate) -> io::Result<()> {
+		let mut inner = self.inner.lock().expect(LOCK_PROOF);
+		let digest = Ctx::candidate_digest(candidate);
+		if inner.index.contains_key(&digest) {
+			return Ok(())
+		}
+
+		let encoded = candidate.encode();
+		let offset = inner.len;
+		write_at(&mut inner.file, offset, &encoded)?;
+		inner.len += encoded.len();
+		inner.index.insert(digest, (offset, encoded.len()));
+		Ok(())
+	}
+
+	/// Get a candidate by digest.
+	pub fn get(&self, digest: &Ctx::Digest) -> io::Result<Option<Ctx::Candidate>> {
+		let mut inner = self.inner.lock().expect(LOCK_PROOF);
+		let (offset, len) = match inner.index.get(digest) {
+			Some(&location) => location,
+			None => return Ok(None),
+		};
+
+		if inner.map.as_ref().map_or(true, |map| map.len() < offset + len) {
+			inner.file.flush()?;
+			// SAFETY: the file can change underneath the mapping only if another process
+			// modifies it. this process only appends to it, which doesn't affect the mapped
+			// part.
+			inner.map = Some(unsafe { Mmap::map(&inner.file)? });
+		}
+
+		let map = inner.map.as_ref().expect("mapped above; qed");
+		Ctx::Candidate::decode(&mut &map[offset..offset + len])
+			.map(Some)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	/// The number of stored candidates.
+	pub fn len(&self) -> usize {
+		self.inner.lock().expect(LOCK_PROOF).index.len()
+	}
+
+	/// Whether no candidates are stored.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Remove all candidates, e.g. once the round they are about concluded.
+	pub fn clear(&self) -> io::Result<()> {
+		let mut inner = self.inner.lock().expect(LOCK_PROOF);
+		inner.map = None;
+		inner.file.set_len(0)?;
+		// the file isn't opened for appending, so writes continue at the old offset otherwise.
+		inner.file.seek(SeekFrom::Start(0))?;
+		inner.len = 0;
+		inner.index.clear();
+		Ok(())
+	}
+}
+
+// write `bytes` at `offset` of `file`. a write which failed part-way moved the cursor past the
+// stored candidates, so the cursor is moved back to the end of them before every write.
+fn write_at(file: &mut (impl Write + Seek), offset: usize, bytes: &[u8]) -> io::Result<()> {
+	file.seek(SeekFrom::Start(offset as u64))?;
+	file.write_all(bytes)
+}
+
+impl<Ctx: Context> CandidateProvider<Ctx> for MappedCandidates<Ctx>
+where
+	Ctx::Candidate: Encode + Decode,
+	Ctx::Digest: Send,
+{
+	fn candidate(&self, digest: &Ctx::Digest) -> Option<Arc<Ctx::Candidate>> {
+		self.get(digest).ok().flatten().map(Arc::new)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, valid},
+		test_utils::{TestCandidate, TestContext},
+	};
+
+	#[test]
+	fn candidates_are_provided_from_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = Arc::new(
+			MappedCandidates::<TestContext>::create(dir.path().join("candidates")).unwrap(),
+		);
+		let (context, mut table) = context_and_table();
+		table.set_candidate_provider(store.clone());
+
+		store.insert(&TestCandidate::new(0, 100)).unwrap();
+		store.insert(&TestCandidate::new(0, 100)).unwrap();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		assert_eq!(store.len(), 1);
+
+		let attested = table.attested_candidates(&context, 2);
+		assert_eq!(attested[0].candidate, TestCandidate::new(0, 100));
+
+		// a candidate stored after the file was mapped.
+		let late = TestCandidate::new(1, 101);
+		store.insert(&late).unwrap();
+		assert_eq!(store.get(&late.digest()).unwrap(), Some(late));
+
+		store.clear().unwrap();
+		assert!(store.is_empty());
+		assert!(table.attested_candidates(&context, 2).is_empty());
+
+		// candidates stored after clearing are written from the start of the file.
+		store.insert(&late).unwrap();
+		assert_eq!(store.get(&late.digest()).unwrap(), Some(late));
+		let path = dir.path().join("candidates");
+		assert_eq!(std::fs::metadata(path).unwrap().len(), late.encode().len() as u64);
+	}
+
+	#[test]
+	fn writes_resume_after_partial_failure() {
+		// a file which fails to write anything past `capacity` bytes.
+		struct FullFile {
+			inner: io::Cursor<Vec<u8>>,
+			capacity: u64,
+		}
+
+		impl Write for FullFile {
+			fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+				let room = self.capacity.saturating_sub(self.inner.position()) as usize;
+				if room == 0 {
+					return Err(io::Error::other("disk full"))
+				}
+				self.inner.write(&buf[..buf.len().min(room)])
+			}
+
+			fn flush(&mut self) -> io::Result<()> {
+				Ok(())
+			}
+		}
+
+		impl Seek for FullFile {
+			fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+				self.inner.seek(pos)
+			}
+		}
+
+		let mut file = FullFile { inner: io::Cursor::new(Vec::new()), capacity: 6 };
+		write_at(&mut file, 0, b"abc").unwrap();
+		assert!(write_at(&mut file, 3, b"defgh").is_err());
+
+		// the next write starts at the end of the stored bytes, not where the failed one stopped.
+		file.capacity = u64::MAX;
+		write_at(&mut file, 3, b"xyz").unwrap();
+		assert_eq!(file.inner.into_inner(), b"abcxyz");
+	}
+}
@@ -0,0 +1,280 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of a table.
+//!
+//! A table records its metrics into the [`Metrics`] set with
+//! [`Table::set_metrics`](crate::Table::set_metrics). The default metrics record nothing.
+//! Metrics can only be registered with [`Metrics::register`] if the `metrics` feature is
+//! enabled, so that the table doesn't depend on Prometheus otherwise.
+//!
+//! The age of a candidate is measured from when it was first seconded: to when it reached a
+//! validity quorum and to when it first became includable. The table doesn't track the
+//! availability of candidates, so there is no age at an availability quorum.
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use prometheus_endpoint::{
+	exponential_buckets, register, Counter, CounterVec, Gauge, Histogram, HistogramOpts,
+	HistogramVec, Opts, PrometheusError, Registry, U64,
+};
+
+use crate::generic::Misbehavior;
+
+#[cfg(feature = "metrics")]
+struct MetricsInner {
+	statements_imported: CounterVec<U64>,
+	duplicates_dropped: Counter<U64>,
+	misbehavior_detected: CounterVec<U64>,
+	includable_candidates: Gauge<U64>,
+	pending_votes: Gauge<U64>,
+	import_duration: Histogram,
+	candidate_age: HistogramVec,
+	minimum_backing_votes: u32,
+}
+
+/// The metrics of a table.
+#[derive(Clone, Default)]
+pub struct Metrics(#[cfg(feature = "metrics")] Option<std::sync::Arc<MetricsInner>>);
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+	/// Register the metrics of a table with `registry`. Candidates are counted as includable
+	/// with `minimum_backing_votes`.
+	pub fn register(
+		registry: &Registry,
+		minimum_backing_votes: u32,
+	) -> Result<Self, PrometheusError> {
+		let metrics = MetricsInner {
+			statements_imported: register(
+				CounterVec::new(
+					Opts::new(
+						"polkadot_parachain_statement_table_statements_imported_total",
+						"Number of statements imported, by kind.",
+					),
+					&["kind"],
+				)?,
+				registry,
+			)?,
+			duplicates_dropped: register(
+				Counter::new(
+					"polkadot_parachain_statement_table_duplicates_dropped_total",
+					"Number of statements dropped because they were imported already.",
+				)?,
+				registry,
+			)?,
+			misbehavior_detected: register(
+				CounterVec::new(
+					Opts::new(
+						"polkadot_parachain_statement_table_misbehavior_detected_total",
+						"Number of misbehaving statements detected, by kind.",
+					),
+					&["kind"],
+				)?,
+				registry,
+			)?,
+			includable_candidates: register(
+				Gauge::new(
+					"polkadot_parachain_statement_table_includable_candidates",
+					"Number of includable candidates.",
+				)?,
+				registry,
+			)?,
+			pending_votes: register(
+				Gauge::new(
+					"polkadot_parachain_statement_table_pending_votes",
+					"Number of votes on announced candidates whose body is pending.",
+				)?,
+				registry,
+			)?,
+			import_duration: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"polkadot_parachain_statement_table_import_duration_seconds",
+						"Time spent importing a statement.",
+					)
+					.buckets(exponential_buckets(0.000_001, 4.0, 10)?),
+				)?,
+				registry,
+			)?,
+			candidate_age: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"polkadot_parachain_statement_table_candidate_age_seconds",
+						"Time from a candidate being first seconded to reaching a milestone.",
+					)
+					.buckets(exponential_buckets(0.01, 2.0, 12)?),
+					&["milestone"],
+				)?,
+				registry,
+			)?,
+			minimum_backing_votes,
+		};
+
+		Ok(Metrics(Some(std::sync::Arc::new(metrics))))
+	}
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+impl Metrics {
+	pub(crate) fn on_statement_imported(&self, seconded: bool) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			let kind = if seconded { "seconded" } else { "valid" };
+			metrics.statements_imported.with_label_values(&[kind]).inc();
+		}
+	}
+
+	pub(crate) fn on_duplicate_dropped(&self) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			metrics.duplicates_dropped.inc();
+		}
+	}
+
+	pub(crate) fn on_misbehavior<C, D, A, S>(&self, misbehavior: &Misbehavior<C, D, A, S>) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			metrics
+				.misbehavior_detected
+				.with_label_values(&[misbehavior.kind().as_str()])
+				.inc();
+		}
+	}
+
+	// whether the metrics are recorded.
+	pub(crate) fn is_enabled(&self) -> bool {
+		self.minimum_backing_votes().is_some()
+	}
+
+	// the minimum backing votes of includable candidates, if the metrics are recorded.
+	pub(crate) fn minimum_backing_votes(&self) -> Option<u32> {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			return Some(metrics.minimum_backing_votes)
+		}
+		None
+	}
+
+	pub(crate) fn on_includable_changed(&self, includable: bool) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			if includable {
+				metrics.includable_candidates.inc();
+			} else {
+				metrics.includable_candidates.dec();
+			}
+		}
+	}
+
+	pub(crate) fn set_pending_votes(&self, count: usize) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			metrics.pending_votes.set(count as u64);
+		}
+	}
+
+	pub(crate) fn on_import(&self, duration: Duration) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			metrics.import_duration.observe(duration.as_secs_f64());
+		}
+	}
+
+	pub(crate) fn on_includable(&self, validity_quorum_age: Duration, includable_age: Duration) {
+		#[cfg(feature = "metrics")]
+		if let Some(metrics) = &self.0 {
+			let age = |milestone| metrics.candidate_age.with_label_values(&[milestone]);
+			age("validity_quorum").observe(validity_quorum_age.as_secs_f64());
+			age("includable").observe(includable_age.as_secs_f64());
+		}
+	}
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, seconded_other, valid, DIGEST},
+		test_utils::TestCandidate,
+	};
+
+	#[test]
+	fn table_records_metrics() {
+		let (context, mut table) = context_and_table();
+		let metrics = Metrics::register(&Registry::new(), 2).unwrap();
+		table.set_metrics(metrics.clone());
+
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		table.import_statement(&context, 0, valid(2));
+		table.import_statement(&context, 0, seconded_other(1));
+
+		let metrics = metrics.0.unwrap();
+		assert_eq!(metrics.statements_imported.with_label_values(&["seconded"]).get(), 1);
+		assert_eq!(metrics.statements_imported.with_label_values(&["valid"]).get(), 1);
+		assert_eq!(metrics.duplicates_dropped.get(), 1);
+		assert_eq!(
+			metrics.misbehavior_detected.with_label_values(&["multiple_candidates"]).get(),
+			1
+		);
+		assert_eq!(metrics.includable_candidates.get(), 1);
+		assert_eq!(metrics.pending_votes.get(), 0);
+		assert_eq!(metrics.import_duration.get_sample_count(), 4);
+
+		// the age of a candidate is recorded once, when it becomes includable on import, and
+		// querying candidates, of the table or of a fork sharing them, doesn't change the metrics.
+		for _ in 0..2 {
+			assert_eq!(table.clone().attested_candidates(&context, 2).len(), 1);
+			assert_eq!(table.attested_candidates(&context, 2).len(), 1);
+		}
+		for milestone in ["validity_quorum", "includable"] {
+			let age = metrics.candidate_age.with_label_values(&[milestone]);
+			assert_eq!(age.get_sample_count(), 1);
+		}
+		assert_eq!(metrics.includable_candidates.get(), 1);
+	}
+
+	#[test]
+	fn gauges_follow_the_table() {
+		let (context, mut table) = context_and_table();
+		let metrics = Metrics::register(&Registry::new(), 2).unwrap();
+		table.set_metrics(metrics.clone());
+		let metrics = metrics.0.unwrap();
+
+		let signature = seconded(1).signature;
+		assert!(table.note_announcement(&context, 0, DIGEST, 1, signature.clone()));
+		assert!(table.note_announcement(&context, 0, DIGEST, 1, signature));
+		assert_eq!(metrics.pending_votes.get(), 1);
+
+		table.import_body(&context, TestCandidate::new(0, 100)).unwrap();
+		assert_eq!(metrics.pending_votes.get(), 0);
+		assert_eq!(metrics.includable_candidates.get(), 0);
+
+		let marker = table.marker();
+		table.import_statement(&context, 0, valid(2));
+		assert_eq!(metrics.includable_candidates.get(), 1);
+
+		// reverted votes and evicted candidates are no longer counted.
+		table.revert_to(&context, marker);
+		assert_eq!(metrics.includable_candidates.get(), 0);
+		table.import_statement(&context, 0, valid(2));
+		assert_eq!(metrics.includable_candidates.get(), 1);
+		table.set_memory_limit(Some(crate::generic::MemoryLimit::new(0)));
+		assert_eq!(metrics.includable_candidates.get(), 0);
+	}
+}
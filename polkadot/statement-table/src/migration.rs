@@ -0,0 +1,156 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioning of the stored table state.
+//!
+//! The format of the state in a [`TableStore`] is versioned, so that it can change between
+//! releases without operators wiping their agreement state. The version is kept under its own
+//! key, and [`migrate`] upgrades older state in place, one version at a time, before the state
+//! is read. State written before versioning was introduced is at version 0.
+//!
+//! The [`StatementLog`](crate::wal::StatementLog) carries its own version in the header of the
+//! file, and is upgraded when it is opened.
+
+use std::{io, ops::ControlFlow};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::store::{invalid_data, StoreTransaction, TableStore};
+
+const LOG_TARGET: &str = "parachain::statement-table";
+
+const VERSION_KEY: &[u8] = b"version";
+
+/// The version of the state written by this release.
+pub const STORE_VERSION: u32 = 1;
+
+/// A migration of the state in a store from one version to the next.
+///
+/// Migrations should be idempotent: the new version is only recorded after a migration
+/// completes, so it is run again if the node stops in between.
+pub type Migration<Store> = fn(&Store) -> io::Result<()>;
+
+// the migrations of the current release, where the migration at index `i` upgrades the state from
+// version `i` to `i + 1`.
+fn migrations<Store: TableStore>() -> [Migration<Store>; STORE_VERSION as usize] {
+	// the format didn't change when versioning was introduced.
+	[|_| Ok(())]
+}
+
+/// The version of the state in `store`, or `None` if the store is empty.
+pub fn stored_version<Store: TableStore>(store: &Store) -> io::Result<Option<u32>> {
+	if let Some(value) = store.get(VERSION_KEY)? {
+		return u32::decode(&mut &value[..]).map(Some).map_err(invalid_data)
+	}
+
+	let mut empty = true;
+	store.keys_with_prefix(&[], &mut |_| {
+		empty = false;
+		ControlFlow::Break(())
+	})?;
+	Ok((!empty).then_some(0))
+}
+
+/// Upgrade the state in `store` to [`STORE_VERSION`], returning the version it was at.
+///
+/// Fails if the state was written by a newer release.
+pub fn migrate<Store: TableStore>(store: &Store) -> io::Result<u32> {
+	run_migrations(store, &migrations())
+}
+
+/// Upgrade the state in `store` with `migrations`, where the migration at index `i` upgrades
+/// the state from version `i` to `i + 1`, returning the version it was at.
+pub fn run_migrations<Store: TableStore>(
+	store: &Store,
+	migrations: &[Migration<Store>],
+) -> io::Result<u32> {
+	let latest = migrations.len() as u32;
+	let version = match stored_version(store)? {
+		Some(version) => version,
+		None => {
+			set_version(store, latest)?;
+			return Ok(latest)
+		},
+	};
+
+	if version > latest {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"stored table state is of a newer version",
+		))
+	}
+
+	for (from, migration) in (version..).zip(&migrations[version as usize..]) {
+		gum::info!(target: LOG_TARGET, from, to = from + 1, "Migrating stored table state");
+		migration(store)?;
+		set_version(store, from + 1)?;
+	}
+
+	Ok(version)
+}
+
+fn set_version<Store: TableStore>(store: &Store, version: u32) -> io::Result<()> {
+	let mut transaction = StoreTransaction::default();
+	transaction.put(VERSION_KEY.to_vec(), version.encode());
+	store.write(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::store::KvdbStore;
+	use std::sync::Arc;
+
+	fn put(store: &KvdbStore, key: &[u8], value: &[u8]) {
+		let mut transaction = StoreTransaction::default();
+		transaction.put(key.to_vec(), value.to_vec());
+		store.write(transaction).unwrap();
+	}
+
+	#[test]
+	fn stored_state_is_migrated() {
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		put(&store, b"candidate", b"old");
+		assert_eq!(stored_version(&store).unwrap(), Some(0));
+
+		let migrations: [Migration<KvdbStore>; 2] = [
+			|store| {
+				put(store, b"candidate", b"v1");
+				Ok(())
+			},
+			|store| {
+				assert_eq!(store.get(b"candidate")?, Some(b"v1".to_vec()));
+				put(store, b"candidate", b"v2");
+				Ok(())
+			},
+		];
+		assert_eq!(run_migrations(&store, &migrations).unwrap(), 0);
+		assert_eq!(stored_version(&store).unwrap(), Some(2));
+		assert_eq!(store.get(b"candidate").unwrap(), Some(b"v2".to_vec()));
+
+		// nothing left to migrate, and older releases refuse the state.
+		assert_eq!(run_migrations(&store, &migrations).unwrap(), 2);
+		assert!(run_migrations(&store, &migrations[..1]).is_err());
+	}
+
+	#[test]
+	fn empty_store_is_at_current_version() {
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		assert_eq!(stored_version(&store).unwrap(), None);
+		assert_eq!(migrate(&store).unwrap(), STORE_VERSION);
+		assert_eq!(stored_version(&store).unwrap(), Some(STORE_VERSION));
+	}
+}
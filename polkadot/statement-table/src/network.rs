@@ -0,0 +1,433 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An abstraction over the network.
+//!
+//! The agreement and gossip components don't depend on any networking stack. They can be
+//! connected to anything implementing [`Network`], be it the node's network service or the
+//! [`InMemoryNetwork`] used in tests.
+//!
+//! Networks carrying bytes are turned into networks carrying messages by an [`EncodedNetwork`],
+//! with the encoding chosen by a [`Codec`].
+
+use std::{
+	collections::HashMap,
+	hash::Hash,
+	marker::PhantomData,
+	pin::Pin,
+	sync::{Arc, Mutex},
+	task::{Context as TaskContext, Poll},
+};
+
+use futures::{channel::mpsc, Stream, StreamExt};
+
+use crate::{
+	generic::{Context, Summary, Table},
+	router::{batch::StatementBatchFor, StatementBatch, StatementGossip},
+	wire::Codec,
+	LOCK_PROOF,
+};
+
+/// An event of the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkEvent<PeerId, Message> {
+	/// A peer connected.
+	PeerConnected(PeerId),
+	/// A peer disconnected.
+	PeerDisconnected(PeerId),
+	/// A message was received from a peer.
+	Message(PeerId, Message),
+}
+
+/// A network carrying messages of type `Message`.
+pub trait Network<Message> {
+	/// The ID of a peer.
+	type PeerId: Clone + Eq + Hash;
+	/// A stream of network events.
+	type Events: Stream<Item = NetworkEvent<Self::PeerId, Message>> + Unpin;
+
+	/// Send a message to a peer.
+	fn send(&self, peer: &Self::PeerId, message: Message);
+
+	/// Send a message to all connected peers.
+	fn broadcast(&self, message: Message);
+
+	/// Get a stream of events. It starts with a [`NetworkEvent::PeerConnected`] for every
+	/// currently connected peer.
+	fn events(&self) -> Self::Events;
+}
+
+/// A stream of the messages received from a stream of network events.
+pub struct Messages<S>(S);
+
+/// Get the messages received from a stream of network events, ignoring other events.
+///
+/// This is what [`agree`](crate::agreement::agree) expects as incoming messages.
+pub fn messages<S>(events: S) -> Messages<S> {
+	Messages(events)
+}
+
+impl<PeerId, Message, S> Stream for Messages<S>
+where
+	S: Stream<Item = NetworkEvent<PeerId, Message>> + Unpin,
+{
+	type Item = Message;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Option<Message>> {
+		loop {
+			match self.0.poll_next_unpin(cx) {
+				Poll::Ready(Some(NetworkEvent::Message(_, message))) =>
+					return Poll::Ready(Some(message)),
+				Poll::Ready(Some(_)) => continue,
+				Poll::Ready(None) => return Poll::Ready(None),
+				Poll::Pending => return Poll::Pending,
+			}
+		}
+	}
+}
+
+/// A network carrying messages on top of a network carrying bytes, encoding the messages with a
+/// [`Codec`].
+///
+/// Received messages which can't be decoded are dropped.
+pub struct EncodedNetwork<N, C> {
+	network: N,
+	codec: C,
+}
+
+impl<N, C> EncodedNetwork<N, C> {
+	/// Carry messages over `network`, encoded with `codec`.
+	pub fn new(network: N, codec: C) -> Self {
+		EncodedNetwork { network, codec }
+	}
+
+	/// Get the underlying network.
+	pub fn inner(&self) -> &N {
+		&self.network
+	}
+}
+
+impl<M, N, C> Network<M> for EncodedNetwork<N, C>
+where
+	N: Network<Vec<u8>>,
+	C: Codec<M> + Clone + Unpin,
+{
+	type PeerId = N::PeerId;
+	type Events = Decoded<N::Events, C, M>;
+
+	fn send(&self, peer: &N::PeerId, message: M) {
+		self.network.send(peer, self.codec.encode(&message));
+	}
+
+	fn broadcast(&self, message: M) {
+		self.network.broadcast(self.codec.encode(&message));
+	}
+
+	fn events(&self) -> Self::Events {
+		Decoded { events: self.network.events(), codec: self.codec.clone(), _marker: PhantomData }
+	}
+}
+
+/// The events of an [`EncodedNetwork`].
+pub struct Decoded<S, C, M> {
+	events: S,
+	codec: C,
+	_marker: PhantomData<fn() -> M>,
+}
+
+impl<PeerId, M, S, C> Stream for Decoded<S, C, M>
+where
+	S: Stream<Item = NetworkEvent<PeerId, Vec<u8>>> + Unpin,
+	C: Codec<M> + Unpin,
+{
+	type Item = NetworkEvent<PeerId, M>;
+
+	fn poll_next(
+		mut self: Pin<&mut Self>,
+		cx: &mut TaskContext,
+	) -> Poll<Option<NetworkEvent<PeerId, M>>> {
+		loop {
+			let event = match self.events.poll_next_unpin(cx) {
+				Poll::Ready(Some(event)) => event,
+				Poll::Ready(None) => return Poll::Ready(None),
+				Poll::Pending => return Poll::Pending,
+			};
+
+			let event = match event {
+				NetworkEvent::PeerConnected(peer) => NetworkEvent::PeerConnected(peer),
+				NetworkEvent::PeerDisconnected(peer) => NetworkEvent::PeerDisconnected(peer),
+				NetworkEvent::Message(peer, encoded) => match self.codec.decode(&encoded) {
+					Ok(message) => NetworkEvent::Message(peer, message),
+					Err(_) => continue,
+				},
+			};
+
+			return Poll::Ready(Some(event))
+		}
+	}
+}
+
+/// Handle a network event with a statement gossip component, exchanging statements with peers
+/// in [`StatementBatch`]es.
+///
+/// Peers which connect are caught up on the statements in `table`. Returns the summaries of
+/// the statements imported into `table`.
+pub fn handle_gossip_event<Ctx, G, N>(
+	gossip: &mut G,
+	table: &mut Table<Ctx>,
+	context: &Ctx,
+	network: &N,
+	event: NetworkEvent<G::PeerId, StatementBatchFor<Ctx>>,
+) -> Vec<Summary<Ctx::Digest, Ctx::GroupId>>
+where
+	Ctx: Context,
+	G: StatementGossip<Ctx>,
+	N: Network<StatementBatchFor<Ctx>, PeerId = G::PeerId>,
+{
+	match event {
+		NetworkEvent::PeerConnected(peer) => {
+			gossip.peer_connected(peer.clone());
+			let statements = gossip.catch_up(table, &peer);
+			if !statements.is_empty() {
+				network.send(&peer, StatementBatch { statements });
+			}
+			Vec::new()
+		},
+		NetworkEvent::PeerDisconnected(peer) => {
+			gossip.peer_disconnected(&peer);
+			Vec::new()
+		},
+		NetworkEvent::Message(peer, batch) => {
+			let imported = gossip.import_batch(table, context, peer, batch);
+			for (to, batch) in imported.forward {
+				network.send(&to, batch);
+			}
+			imported.summaries
+		},
+	}
+}
+
+type Subscribers<PeerId, Message> = Vec<mpsc::UnboundedSender<NetworkEvent<PeerId, Message>>>;
+
+/// A network connecting endpoints within the same process, for tests.
+///
+/// All endpoints are connected to each other.
+pub struct InMemoryNetwork<PeerId, Message> {
+	endpoints: Arc<Mutex<HashMap<PeerId, Subscribers<PeerId, Message>>>>,
+}
+
+impl<PeerId, Message> Default for InMemoryNetwork<PeerId, Message> {
+	fn default() -> Self {
+		InMemoryNetwork { endpoints: Default::default() }
+	}
+}
+
+impl<PeerId: Clone + Eq + Hash, Message: Clone> InMemoryNetwork<PeerId, Message> {
+	/// Create a new network without endpoints.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add an endpoint with the given ID, connecting it to all others.
+	pub fn join(&self, id: PeerId) -> InMemoryEndpoint<PeerId, Message> {
+		let mut endpoints = self.endpoints.lock().expect(LOCK_PROOF);
+		for subscribers in endpoints.values_mut() {
+			notify(subscribers, NetworkEvent::PeerConnected(id.clone()));
+		}
+		endpoints.entry(id.clone()).or_default();
+
+		InMemoryEndpoint { id, endpoints: self.endpoints.clone() }
+	}
+}
+
+// Send an event to all subscribers, dropping those which went away.
+fn notify<PeerId: Clone, Message: Clone>(
+	subscribers: &mut Subscribers<PeerId, Message>,
+	event: NetworkEvent<PeerId, Message>,
+) {
+	subscribers.retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+}
+
+/// An endpoint of an [`InMemoryNetwork`]. It disconnects when dropped.
+pub struct InMemoryEndpoint<PeerId: Clone + Eq + Hash, Message> {
+	id: PeerId,
+	endpoints: Arc<Mutex<HashMap<PeerId, Subscribers<PeerId, Message>>>>,
+}
+
+impl<PeerId: Clone + Eq + Hash, Message: Clone> Network<Message>
+	for InMemoryEndpoint<PeerId, Message>
+{
+	type PeerId = PeerId;
+	type Events = mpsc::UnboundedReceiver<NetworkEvent<PeerId, Message>>;
+
+	fn send(&self, peer: &PeerId, message: Message) {
+		let mut endpoints = self.endpoints.lock().expect(LOCK_PROOF);
+		if let Some(subscribers) = endpoints.get_mut(peer) {
+			notify(subscribers, NetworkEvent::Message(self.id.clone(), message));
+		}
+	}
+
+	fn broadcast(&self, message: Message) {
+		let mut endpoints = self.endpoints.lock().expect(LOCK_PROOF);
+		for (_, subscribers) in endpoints.iter_mut().filter(|(id, _)| **id != self.id) {
+			notify(subscribers, NetworkEvent::Message(self.id.clone(), message.clone()));
+		}
+	}
+
+	fn events(&self) -> Self::Events {
+		let (tx, rx) = mpsc::unbounded();
+		let mut endpoints = self.endpoints.lock().expect(LOCK_PROOF);
+		for peer in endpoints.keys().filter(|id| **id != self.id) {
+			let _ = tx.unbounded_send(NetworkEvent::PeerConnected(peer.clone()));
+		}
+		endpoints.entry(self.id.clone()).or_default().push(tx);
+		rx
+	}
+}
+
+impl<PeerId: Clone + Eq + Hash, Message> Drop for InMemoryEndpoint<PeerId, Message> {
+	fn drop(&mut self) {
+		if let Ok(mut endpoints) = self.endpoints.lock() {
+			endpoints.remove(&self.id);
+			for subscribers in endpoints.values_mut() {
+				subscribers.retain(|tx| {
+					tx.unbounded_send(NetworkEvent::PeerDisconnected(self.id.clone())).is_ok()
+				});
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		agreement::{
+			agree,
+			tests::{contexts, TestContext as AgreementContext},
+			AgreementConfig, AgreementParams, ExponentialBackoff, SignedMessageFor, SystemTimer,
+		},
+		router::{
+			fetch::CandidateRequest,
+			tests::{context_and_table, seconded, valid},
+			GossipRouter,
+		},
+		test_utils::TestContext as TableContext,
+		wire::ScaleCodec,
+	};
+
+	#[test]
+	fn gossip_over_in_memory_network() {
+		let network = InMemoryNetwork::new();
+		let (context, mut table_a) = context_and_table();
+		let (_, mut table_b) = context_and_table();
+		let mut gossip_a = GossipRouter::<TableContext, u32>::new();
+		let mut gossip_b = GossipRouter::<TableContext, u32>::new();
+
+		table_a.import_statement(&context, 0, seconded(1));
+		table_a.import_statement(&context, 0, valid(2));
+
+		let a = network.join(10u32);
+		let b = network.join(11);
+		let (mut events_a, mut events_b) = (a.events(), b.events());
+
+		let mut imported = Vec::new();
+		loop {
+			let mut progress = false;
+			while let Ok(Some(event)) = events_a.try_next() {
+				progress = true;
+				handle_gossip_event(&mut gossip_a, &mut table_a, &context, &a, event);
+			}
+			while let Ok(Some(event)) = events_b.try_next() {
+				progress = true;
+				imported.extend(handle_gossip_event(
+					&mut gossip_b,
+					&mut table_b,
+					&context,
+					&b,
+					event,
+				));
+			}
+			if !progress {
+				break
+			}
+		}
+
+		assert_eq!(imported.len(), 2);
+		assert_eq!(imported[1].validity_votes, 2);
+
+		drop(a);
+		assert!(matches!(events_b.try_next(), Ok(Some(NetworkEvent::PeerDisconnected(10)))));
+	}
+
+	#[test]
+	fn messages_are_encoded_with_codec() {
+		let network = InMemoryNetwork::<u32, Vec<u8>>::new();
+		let a = EncodedNetwork::new(network.join(10), ScaleCodec);
+		let b = EncodedNetwork::new(network.join(11), ScaleCodec);
+		let mut events = Network::<CandidateRequest<u64>>::events(&b);
+		a.inner().send(&11, vec![0xff]);
+		a.send(&11, CandidateRequest { digest: 42u64 });
+
+		let received =
+			futures::executor::block_on(async { vec![events.next().await, events.next().await] });
+		assert_eq!(
+			received,
+			vec![
+				Some(NetworkEvent::PeerConnected(10)),
+				Some(NetworkEvent::Message(10, CandidateRequest { digest: 42 })),
+			]
+		);
+	}
+
+	#[test]
+	fn agreement_over_in_memory_network() {
+		let network = InMemoryNetwork::<u64, SignedMessageFor<AgreementContext>>::new();
+		let contexts = contexts(4, &[42, 43, 44, 45], &[42]);
+		// subscribe before any node starts, so that no message is missed.
+		let endpoints = contexts
+			.iter()
+			.map(|c| network.join(c.local_id))
+			.map(|endpoint| {
+				let events = endpoint.events();
+				(endpoint, events)
+			})
+			.collect::<Vec<_>>();
+
+		let handles = contexts
+			.into_iter()
+			.zip(endpoints)
+			.map(|(context, (endpoint, events))| {
+				std::thread::spawn(move || {
+					let incoming = messages(events);
+					let outgoing = |message| endpoint.broadcast(message);
+					futures::executor::block_on(agree(AgreementParams {
+						context,
+						incoming,
+						outgoing,
+						timer: SystemTimer,
+						round_timeout: ExponentialBackoff::default(),
+						config: AgreementConfig::default(),
+					}))
+				})
+			})
+			.collect::<Vec<_>>();
+
+		for handle in handles {
+			assert_eq!(handle.join().unwrap().proposal, 42);
+		}
+	}
+}
@@ -0,0 +1,157 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A durable outbox of misbehavior reports.
+//!
+//! Misbehavior drained from a table is only held in memory until it is reported, so a crash
+//! between detection and submission would lose the evidence. A [`MisbehaviorOutbox`] keeps the
+//! reports in a [`TableStore`] until their inclusion on chain is confirmed.
+//!
+//! Reports are identified by the hash of the misbehaving authority and the proof, so storing
+//! the same misbehavior twice, e.g. when it is detected again after a restart, keeps a single
+//! report.
+
+use std::{io, marker::PhantomData};
+
+use parity_scale_codec::{Decode, Encode};
+use sp_core::blake2_256;
+
+use crate::{
+	generic::{Context, MisbehaviorFor},
+	store::{invalid_data, StoreTransaction, TableStore},
+};
+
+const OUTBOX_PREFIX: &[u8] = b"outbox";
+
+/// The identifier of a misbehavior report, see [`ReportId::of`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct ReportId(pub [u8; 32]);
+
+impl ReportId {
+	/// The identifier of the report of `misbehavior` by `authority`.
+	pub fn of<AuthorityId: Encode, Misbehavior: Encode>(
+		authority: &AuthorityId,
+		misbehavior: &Misbehavior,
+	) -> Self {
+		ReportId(blake2_256(&(authority, misbehavior).encode()))
+	}
+}
+
+/// Misbehavior reports awaiting confirmation of their inclusion on chain.
+pub struct MisbehaviorOutbox<Ctx, Store> {
+	store: Store,
+	_marker: PhantomData<fn() -> Ctx>,
+}
+
+impl<Ctx: Context, Store: TableStore> MisbehaviorOutbox<Ctx, Store>
+where
+	Ctx::Candidate: Encode + Decode,
+	Ctx::Digest: Encode + Decode,
+	Ctx::AuthorityId: Encode + Decode,
+	Ctx::Signature: Encode + Decode,
+{
+	/// Create an outbox in `store`, holding the reports which are pending there already.
+	pub fn new(store: Store) -> Self {
+		MisbehaviorOutbox { store, _marker: PhantomData }
+	}
+
+	/// Store reports of misbehavior, e.g. as drained with
+	/// [`Table::drain_misbehaviors`](crate::Table::drain_misbehaviors), returning their
+	/// identifiers.
+	///
+	/// All reports are stored atomically, before any of them should be submitted.
+	pub fn push(
+		&self,
+		misbehavior: impl IntoIterator<Item = (Ctx::AuthorityId, MisbehaviorFor<Ctx>)>,
+	) -> io::Result<Vec<ReportId>> {
+		let mut transaction = StoreTransaction::default();
+		let ids = misbehavior
+			.into_iter()
+			.map(|report| {
+				let id = ReportId::of(&report.0, &report.1);
+				transaction.put(report_key(&id), report.encode());
+				id
+			})
+			.collect();
+
+		self.store.write(transaction)?;
+		Ok(ids)
+	}
+
+	/// Get the reports awaiting confirmation, ordered by identifier.
+	pub fn pending(&self) -> io::Result<Vec<(ReportId, Ctx::AuthorityId, MisbehaviorFor<Ctx>)>> {
+		self.store
+			.iter_with_prefix(OUTBOX_PREFIX)?
+			.into_iter()
+			.map(|(key, value)| {
+				let id =
+					ReportId::decode(&mut &key[OUTBOX_PREFIX.len()..]).map_err(invalid_data)?;
+				let (authority, misbehavior) =
+					<(Ctx::AuthorityId, MisbehaviorFor<Ctx>)>::decode(&mut &value[..])
+						.map_err(invalid_data)?;
+				Ok((id, authority, misbehavior))
+			})
+			.collect()
+	}
+
+	/// Note that the report with the given `id` was included on chain, removing it.
+	pub fn confirm(&self, id: &ReportId) -> io::Result<()> {
+		let mut transaction = StoreTransaction::default();
+		transaction.delete(report_key(id));
+		self.store.write(transaction)
+	}
+}
+
+fn report_key(id: &ReportId) -> Vec<u8> {
+	let mut key = OUTBOX_PREFIX.to_vec();
+	id.encode_to(&mut key);
+	key
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, seconded_other},
+		store::KvdbStore,
+		test_utils::TestContext,
+	};
+	use std::sync::Arc;
+
+	#[test]
+	fn reports_are_kept_until_confirmed() {
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		let outbox = MisbehaviorOutbox::<TestContext, _>::new(store.clone());
+
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, seconded_other(1));
+		let misbehavior: Vec<_> = table.drain_misbehaviors().collect();
+		let ids = outbox.push(misbehavior.clone()).unwrap();
+		assert_eq!(ids.len(), 1);
+
+		// the same report is kept once, and survives reopening the outbox.
+		outbox.push(misbehavior.clone()).unwrap();
+		let outbox = MisbehaviorOutbox::<TestContext, _>::new(store);
+		let (id, authority, proof) = outbox.pending().unwrap().remove(0);
+		assert_eq!(id, ids[0]);
+		assert_eq!((authority, proof), misbehavior[0].clone());
+		assert_eq!(outbox.pending().unwrap().len(), 1);
+
+		outbox.confirm(&id).unwrap();
+		assert!(outbox.pending().unwrap().is_empty());
+	}
+}
@@ -0,0 +1,182 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The progress of the groups towards inclusion.
+//!
+//! A [`ProgressTracker`] follows the [`TableEvent`]s of a table, and of the
+//! [`AvailabilityTable`](crate::availability::AvailabilityTable) publishing to the same channel,
+//! to tell how many groups have an includable candidate and how many are blocked on validity
+//! votes or on availability. Candidates removed from the table are forgotten, and so is
+//! everything else when agreement advances to a new round.
+
+use std::{collections::HashMap, hash::Hash};
+
+use crate::events::TableEvent;
+
+/// The progress of the groups with candidates towards inclusion, see
+/// [`ProgressTracker::progress`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupProgress {
+	/// The number of groups with a proposed candidate.
+	pub groups: usize,
+	/// The number of groups with an includable candidate which didn't turn out unavailable.
+	pub includable: usize,
+	/// The number of groups without an includable candidate, whose candidates still lack
+	/// validity votes.
+	pub blocked_on_validity: usize,
+	/// The number of groups whose candidates all turned out unavailable.
+	pub blocked_on_availability: usize,
+}
+
+// how far a candidate got towards inclusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CandidateProgress {
+	Proposed,
+	Includable,
+	Unavailable,
+}
+
+/// Tracks the progress of the groups towards inclusion in the current round.
+pub struct ProgressTracker<Digest, GroupId> {
+	groups: HashMap<GroupId, HashMap<Digest, CandidateProgress>>,
+}
+
+impl<Digest: Hash + Eq, GroupId: Hash + Eq> Default for ProgressTracker<Digest, GroupId> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<Digest: Hash + Eq, GroupId: Hash + Eq> ProgressTracker<Digest, GroupId> {
+	/// Create a tracker, not knowing of any candidate.
+	pub fn new() -> Self {
+		ProgressTracker { groups: HashMap::new() }
+	}
+
+	/// Handle an event of the table or of its availability table.
+	pub fn handle<AuthorityId>(&mut self, event: TableEvent<Digest, GroupId, AuthorityId>) {
+		match event {
+			TableEvent::CandidateProposed { digest, group_id } => {
+				self.groups
+					.entry(group_id)
+					.or_default()
+					.entry(digest)
+					.or_insert(CandidateProgress::Proposed);
+			},
+			TableEvent::CandidateIncludable { digest, group_id } => {
+				let progress = self.groups.entry(group_id).or_default().entry(digest);
+				let progress = progress.or_insert(CandidateProgress::Includable);
+				if *progress == CandidateProgress::Proposed {
+					*progress = CandidateProgress::Includable;
+				}
+			},
+			TableEvent::CandidateUnavailable { digest, group_id } => {
+				self.groups
+					.entry(group_id)
+					.or_default()
+					.insert(digest, CandidateProgress::Unavailable);
+			},
+			TableEvent::CandidateRemoved { digest, group_id } => {
+				if let Some(candidates) = self.groups.get_mut(&group_id) {
+					candidates.remove(&digest);
+					if candidates.is_empty() {
+						self.groups.remove(&group_id);
+					}
+				}
+			},
+			TableEvent::RoundAdvanced { .. } => self.reset(),
+			TableEvent::MisbehaviorDetected { .. } => {},
+		}
+	}
+
+	/// Forget all candidates, e.g. at the start of a round.
+	pub fn reset(&mut self) {
+		self.groups.clear();
+	}
+
+	/// The progress of the groups towards inclusion, as of the events handled so far.
+	pub fn progress(&self) -> GroupProgress {
+		let mut progress = GroupProgress { groups: self.groups.len(), ..Default::default() };
+		for candidates in self.groups.values() {
+			let has = |wanted| candidates.values().any(|progress| *progress == wanted);
+			if has(CandidateProgress::Includable) {
+				progress.includable += 1;
+			} else if has(CandidateProgress::Proposed) {
+				progress.blocked_on_validity += 1;
+			} else {
+				progress.blocked_on_availability += 1;
+			}
+		}
+
+		progress
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		agreement::RoundObserver,
+		events::TableEvents,
+		generic::MemoryLimit,
+		router::tests::{context_and_table, seconded, valid, DIGEST},
+	};
+
+	#[test]
+	fn progress_follows_the_table() {
+		let (context, mut table) = context_and_table();
+		let events = TableEvents::new(2);
+		let mut subscriber = events.subscribe();
+		table.set_events(events.clone());
+		let mut tracker = ProgressTracker::new();
+		let mut progress = |tracker: &mut ProgressTracker<_, _>| {
+			std::iter::from_fn(|| subscriber.try_next().ok().flatten())
+				.for_each(|event| tracker.handle(event));
+			tracker.progress()
+		};
+
+		table.import_statement(&context, 0, seconded(1));
+		assert_eq!(
+			progress(&mut tracker),
+			GroupProgress { groups: 1, blocked_on_validity: 1, ..Default::default() }
+		);
+
+		table.import_statement(&context, 0, valid(2));
+		assert_eq!(
+			progress(&mut tracker),
+			GroupProgress { groups: 1, includable: 1, ..Default::default() }
+		);
+
+		// candidates which don't become available block their group.
+		events.publish(|| TableEvent::CandidateUnavailable { digest: DIGEST, group_id: 0 });
+		assert_eq!(
+			progress(&mut tracker),
+			GroupProgress { groups: 1, blocked_on_availability: 1, ..Default::default() }
+		);
+
+		// evicted candidates are forgotten.
+		table.set_memory_limit(Some(MemoryLimit::new(0)));
+		assert_eq!(progress(&mut tracker), GroupProgress::default());
+
+		// and so is everything else in a new round.
+		table.set_memory_limit(None);
+		table.import_statement(&context, 0, seconded(1));
+		assert_eq!(progress(&mut tracker).groups, 1);
+		events.round_advanced(1);
+		assert_eq!(progress(&mut tracker), GroupProgress::default());
+	}
+}
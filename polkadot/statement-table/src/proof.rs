@@ -0,0 +1,601 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Proofs of misbehavior for submission to the runtime.
+//!
+//! [`Misbehavior`] carries whole candidates, which can be large and are not needed to check the
+//! proof: statements are signed over the digest of their candidate, see
+//! [`signing_payload`](crate::statement::signing_payload). A [`MisbehaviorProof`] only carries
+//! digests, so its size is bounded by the sizes of a digest, an authority ID and two
+//! signatures. Proofs are encoded as a [`VersionedMisbehaviorProof`], which lets the format
+//! evolve without breaking proofs created by older nodes.
+//!
+//! Test vectors of the encoding of proofs and of the payloads signed for statements are checked
+//! in at `res/misbehavior_proofs.txt`, to validate other implementations against.
+
+use alloc::vec::Vec;
+
+use parity_scale_codec::{Decode, DecodeAll, Encode, Error as CodecError};
+
+use crate::statement::{
+	digest_signing_payload, Context, GroupAssignment, Misbehavior, MisbehaviorFor,
+	MultipleCandidates, Statement, UnauthorizedStatement, ValidityDoubleVote,
+};
+
+/// A statement as it is signed: its kind and the digest of its candidate.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub enum ProofStatement<Digest> {
+	/// See [`Statement::Seconded`].
+	#[codec(index = 1)]
+	Seconded(Digest),
+	/// See [`Statement::Valid`].
+	#[codec(index = 2)]
+	Valid(Digest),
+}
+
+impl<Digest: Encode> ProofStatement<Digest> {
+	/// Get the bytes the authority signed for this statement, see
+	/// [`signing_payload`](crate::statement::signing_payload).
+	pub fn signing_payload<S: Encode>(&self, signing_context: &S) -> Vec<u8> {
+		match self {
+			ProofStatement::Seconded(digest) => digest_signing_payload(1, digest, signing_context),
+			ProofStatement::Valid(digest) => digest_signing_payload(2, digest, signing_context),
+		}
+	}
+}
+
+/// A proof of misbehavior of an authority, referring to candidates by digest only.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct MisbehaviorProof<Digest, AuthorityId, Signature> {
+	/// The misbehaving authority.
+	pub offender: AuthorityId,
+	/// The kind of misbehavior.
+	pub kind: ProofKind,
+	/// The signed statements proving the misbehavior. The second one is missing for
+	/// [`ProofKind::UnauthorizedStatement`].
+	pub first: (ProofStatement<Digest>, Signature),
+	/// See `first`.
+	pub second: Option<(ProofStatement<Digest>, Signature)>,
+}
+
+/// Type alias for a misbehavior proof corresponding to context type.
+pub type MisbehaviorProofFor<Ctx> = MisbehaviorProof<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// The kinds of [`Misbehavior`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Encode, Decode)]
+pub enum ProofKind {
+	/// See [`Misbehavior::ValidityDoubleVote`].
+	#[codec(index = 0)]
+	ValidityDoubleVote,
+	/// See [`Misbehavior::MultipleCandidates`].
+	#[codec(index = 1)]
+	MultipleCandidates,
+	/// See [`Misbehavior::UnauthorizedStatement`]. Whether the offender was authorized is not
+	/// part of the proof and is checked against the group assignment on verification.
+	#[codec(index = 2)]
+	UnauthorizedStatement,
+	/// See [`Misbehavior::DoubleSign`].
+	#[codec(index = 3)]
+	DoubleSign,
+}
+
+/// Reasons a [`MisbehaviorProof`] can fail verification.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ProofError {
+	/// The statements don't prove the claimed kind of misbehavior.
+	NotMisbehavior,
+	/// The group of a candidate is unknown, so whether the offender is a member of it can't be
+	/// established.
+	UnknownGroup,
+	/// A statement carries an invalid signature.
+	BadSignature,
+}
+
+impl<Digest, AuthorityId, Signature> MisbehaviorProof<Digest, AuthorityId, Signature>
+where
+	Digest: Encode + Clone + PartialEq,
+	Signature: PartialEq,
+{
+	/// Create a proof of misbehavior of `offender`, as detected by the table.
+	pub fn new<Ctx>(offender: AuthorityId, misbehavior: MisbehaviorFor<Ctx>) -> Self
+	where
+		Ctx: Context<Digest = Digest, AuthorityId = AuthorityId, Signature = Signature>,
+	{
+		let compact = |statement: Statement<Ctx::Candidate, Digest>| match statement {
+			Statement::Seconded(candidate) =>
+				ProofStatement::Seconded(Ctx::candidate_digest(&candidate)),
+			Statement::Valid(digest) => ProofStatement::Valid(digest),
+		};
+
+		let (kind, first, second) = match misbehavior {
+			Misbehavior::ValidityDoubleVote(ValidityDoubleVote::IssuedAndValidity(
+				(candidate, s1),
+				(digest, s2),
+			)) => (
+				ProofKind::ValidityDoubleVote,
+				(ProofStatement::Seconded(Ctx::candidate_digest(&candidate)), s1),
+				Some((ProofStatement::Valid(digest), s2)),
+			),
+			Misbehavior::MultipleCandidates(MultipleCandidates { first, second }) => (
+				ProofKind::MultipleCandidates,
+				(ProofStatement::Seconded(Ctx::candidate_digest(&first.0)), first.1),
+				Some((ProofStatement::Seconded(Ctx::candidate_digest(&second.0)), second.1)),
+			),
+			Misbehavior::UnauthorizedStatement(UnauthorizedStatement { statement }) => (
+				ProofKind::UnauthorizedStatement,
+				(compact(statement.statement), statement.signature),
+				None,
+			),
+			Misbehavior::DoubleSign(double_sign) => {
+				let (statement, s1, s2) = double_sign.deconstruct();
+				let statement = compact(statement);
+				(ProofKind::DoubleSign, (statement.clone(), s1), Some((statement, s2)))
+			},
+		};
+
+		MisbehaviorProof { offender, kind, first, second }
+	}
+
+	/// Verify that the proof shows misbehavior of the offender.
+	///
+	/// The groups of the candidates are looked up in `context`: statements are misbehavior only
+	/// if the offender is a member of the group, except for unauthorized statements which are
+	/// misbehavior only if it isn't. `allow_multiple_seconded` is that of the
+	/// [`Config`](crate::Config) of the tables producing the proofs.
+	///
+	/// `check_signature` is called with the offender, the signing payload of each statement
+	/// under `signing_context` and its signature.
+	pub fn verify<Ctx, S, F>(
+		&self,
+		context: &Ctx,
+		allow_multiple_seconded: bool,
+		signing_context: &S,
+		mut check_signature: F,
+	) -> Result<(), ProofError>
+	where
+		Ctx: GroupAssignment<Digest = Digest, AuthorityId = AuthorityId>,
+		S: Encode,
+		F: FnMut(&AuthorityId, &[u8], &Signature) -> bool,
+	{
+		use ProofStatement::{Seconded, Valid};
+
+		let conflicting = match (self.kind, &self.first, &self.second) {
+			(ProofKind::ValidityDoubleVote, (Seconded(d1), _), Some((Valid(d2), _))) => d1 == d2,
+			(ProofKind::MultipleCandidates, (Seconded(d1), _), Some((Seconded(d2), _))) =>
+				!allow_multiple_seconded && d1 != d2,
+			(ProofKind::UnauthorizedStatement, _, None) => true,
+			(ProofKind::DoubleSign, (st1, s1), Some((st2, s2))) => st1 == st2 && s1 != s2,
+			_ => false,
+		};
+		if !conflicting {
+			return Err(ProofError::NotMisbehavior)
+		}
+
+		let group_of = |statement: &ProofStatement<Digest>| {
+			let (Seconded(digest) | Valid(digest)) = statement;
+			context.candidate_group(digest).ok_or(ProofError::UnknownGroup)
+		};
+		let group = group_of(&self.first.0)?;
+		if let Some((second, _)) = &self.second {
+			// seconding candidates of different groups is allowed.
+			if group_of(second)? != group {
+				return Err(ProofError::NotMisbehavior)
+			}
+		}
+		let is_member = context.is_member_of(&self.offender, &group);
+		if is_member == (self.kind == ProofKind::UnauthorizedStatement) {
+			return Err(ProofError::NotMisbehavior)
+		}
+
+		for (statement, signature) in Some(&self.first).into_iter().chain(self.second.as_ref()) {
+			let payload = statement.signing_payload(signing_context);
+			if !check_signature(&self.offender, &payload, signature) {
+				return Err(ProofError::BadSignature)
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// A [`MisbehaviorProof`] of any supported version.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub enum VersionedMisbehaviorProof<Digest, AuthorityId, Signature> {
+	/// The first version.
+	#[codec(index = 1)]
+	V1(MisbehaviorProof<Digest, AuthorityId, Signature>),
+}
+
+impl<Digest, AuthorityId, Signature> From<MisbehaviorProof<Digest, AuthorityId, Signature>>
+	for VersionedMisbehaviorProof<Digest, AuthorityId, Signature>
+{
+	fn from(proof: MisbehaviorProof<Digest, AuthorityId, Signature>) -> Self {
+		VersionedMisbehaviorProof::V1(proof)
+	}
+}
+
+impl<Digest, AuthorityId, Signature> VersionedMisbehaviorProof<Digest, AuthorityId, Signature> {
+	/// Get the proof in the latest version.
+	pub fn into_latest(self) -> MisbehaviorProof<Digest, AuthorityId, Signature> {
+		match self {
+			VersionedMisbehaviorProof::V1(proof) => proof,
+		}
+	}
+}
+
+/// Decode an encoded [`VersionedMisbehaviorProof`], e.g. from the argument of a runtime call,
+/// into the latest version.
+pub fn decode_proof<Digest, AuthorityId, Signature>(
+	mut encoded: &[u8],
+) -> Result<MisbehaviorProof<Digest, AuthorityId, Signature>, CodecError>
+where
+	VersionedMisbehaviorProof<Digest, AuthorityId, Signature>: Decode,
+{
+	VersionedMisbehaviorProof::decode_all(&mut encoded).map(VersionedMisbehaviorProof::into_latest)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::{DoubleSign, SignedStatement},
+		test_utils::{
+			sign, AuthorityId, Digest, TestCandidate, TestContext, TestSignature, SIGNING_CONTEXT,
+		},
+	};
+	use primitives::{CandidateHash, ValidatorIndex, ValidatorSignature};
+	use sp_core::{sr25519, Pair};
+
+	const TEST_VECTORS: &str = include_str!("../res/misbehavior_proofs.txt");
+
+	// statements of test vectors are signed under a context encoded like the `SigningContext` of
+	// session 7 at parent hash `0x11..11`.
+	const VECTOR_SIGNING_CONTEXT: (u32, [u8; 32]) = (7, [0x11; 32]);
+
+	// a context of types encoded like the v2 primitives, with candidates identified by hash. The
+	// candidates `0xaa..aa` and `0xbb..bb` are assigned to group `0`, of which the offender of
+	// the test vectors is the only member.
+	struct VectorContext;
+
+	impl Context for VectorContext {
+		type AuthorityId = u32;
+		type Digest = [u8; 32];
+		type GroupId = u32;
+		type Signature = [u8; 64];
+		type Candidate = [u8; 32];
+
+		fn candidate_digest(candidate: &[u8; 32]) -> [u8; 32] {
+			*candidate
+		}
+
+		fn is_member_of(&self, authority: &u32, group: &u32) -> bool {
+			*authority == 5 && *group == 0
+		}
+
+		fn get_group_size(&self, group: &u32) -> Option<usize> {
+			(*group == 0).then_some(1)
+		}
+	}
+
+	impl GroupAssignment for VectorContext {
+		fn candidate_group(&self, digest: &[u8; 32]) -> Option<u32> {
+			[[0xaa; 32], [0xbb; 32]].contains(digest).then_some(0)
+		}
+	}
+
+	// the key of the offender of the test vectors, validator 5.
+	fn vector_key() -> sr25519::Pair {
+		sr25519::Pair::from_seed(&[0x05; 32])
+	}
+
+	// sr25519 signatures are randomized, so signing the same statement twice double signs it.
+	fn vector_signature(statement: ProofStatement<[u8; 32]>) -> [u8; 64] {
+		vector_key().sign(&statement.signing_payload(&VECTOR_SIGNING_CONTEXT)).0
+	}
+
+	fn check_vector_signature(offender: &u32, payload: &[u8], signature: &[u8; 64]) -> bool {
+		let signature = sr25519::Signature::from_raw(*signature);
+		*offender == 5 && sr25519::Pair::verify(&signature, payload, &vector_key().public())
+	}
+
+	// the test vectors, by name, in the order of `TEST_VECTORS`. Proofs are signed afresh.
+	fn test_vectors() -> Vec<(&'static str, Vec<u8>)> {
+		let (first, second) = ([0xaa; 32], [0xbb; 32]);
+		let seconded = |digest| vector_signature(ProofStatement::Seconded(digest));
+		let valid = |digest| vector_signature(ProofStatement::Valid(digest));
+		let proof = |misbehavior| {
+			let proof = MisbehaviorProof::new::<VectorContext>(5, misbehavior);
+			VersionedMisbehaviorProof::from(proof).encode()
+		};
+		let payload = |statement: ProofStatement<[u8; 32]>| {
+			statement.signing_payload(&VECTOR_SIGNING_CONTEXT)
+		};
+
+		vec![
+			("offender.public", vector_key().public().0.to_vec()),
+			("seconded.statement", ProofStatement::Seconded(first).encode()),
+			("seconded.payload", payload(ProofStatement::Seconded(first))),
+			("valid.statement", ProofStatement::Valid(first).encode()),
+			("valid.payload", payload(ProofStatement::Valid(first))),
+			(
+				"validity_double_vote.proof",
+				proof(Misbehavior::ValidityDoubleVote(ValidityDoubleVote::IssuedAndValidity(
+					(first, seconded(first)),
+					(first, valid(first)),
+				))),
+			),
+			(
+				"multiple_candidates.proof",
+				proof(Misbehavior::MultipleCandidates(MultipleCandidates {
+					first: (first, seconded(first)),
+					second: (second, seconded(second)),
+				})),
+			),
+			(
+				"unauthorized_statement.proof",
+				proof(Misbehavior::UnauthorizedStatement(UnauthorizedStatement {
+					statement: SignedStatement {
+						statement: Statement::Seconded(first),
+						signature: seconded(first),
+						sender: 5,
+					},
+				})),
+			),
+			(
+				"double_sign_seconded.proof",
+				proof(Misbehavior::DoubleSign(DoubleSign::Seconded(
+					first,
+					seconded(first),
+					seconded(first),
+				))),
+			),
+			(
+				"double_sign_validity.proof",
+				proof(Misbehavior::DoubleSign(DoubleSign::Validity(
+					first,
+					valid(first),
+					valid(first),
+				))),
+			),
+		]
+	}
+
+	// the checked-in test vectors, by name.
+	fn checked_in_vectors() -> Vec<(&'static str, Vec<u8>)> {
+		TEST_VECTORS
+			.lines()
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(|line| {
+				let (name, hex) = line.split_once(" = ").expect("vectors are `name = hex`; qed");
+				let bytes = (0..hex.len())
+					.step_by(2)
+					.map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("vectors are hex; qed"))
+					.collect();
+				(name, bytes)
+			})
+			.collect()
+	}
+
+	fn check_signature(offender: &AuthorityId, payload: &[u8], signature: &TestSignature) -> bool {
+		signature.is_valid(offender, payload)
+	}
+
+	// authorities `1` and `2` in group `2`, authority `1` in group `0`.
+	fn context() -> TestContext {
+		TestContext::builder().with_group(0, [1]).with_group(2, [1, 2]).build()
+	}
+
+	#[test]
+	fn proofs_roundtrip_and_verify() {
+		let context = context();
+		let (first, second) = (TestCandidate::new(2, 100), TestCandidate::new(2, 999));
+		let misbehavior = Misbehavior::MultipleCandidates(MultipleCandidates {
+			first: (first, sign(1, Statement::Seconded(first), 0).signature),
+			second: (second, sign(1, Statement::Seconded(second), 0).signature),
+		});
+		let proof = MisbehaviorProof::new::<TestContext>(1, misbehavior);
+		assert_eq!(proof.first.0, ProofStatement::Seconded(first.digest()));
+
+		let encoded = VersionedMisbehaviorProof::from(proof.clone()).encode();
+		let decoded = decode_proof::<Digest, AuthorityId, TestSignature>(&encoded).unwrap();
+		assert_eq!(decoded, proof);
+		assert_eq!(decoded.verify(&context, false, &SIGNING_CONTEXT, check_signature), Ok(()));
+
+		// the payloads are those signed for the full statements.
+		let mut payloads = Vec::new();
+		decoded
+			.verify(&context, false, &SIGNING_CONTEXT, |_, payload, _| {
+				payloads.push(payload.to_vec());
+				true
+			})
+			.unwrap();
+		assert_eq!(payloads[0], sign(1, Statement::Seconded(first), 0).signature.payload);
+
+		let forged = MisbehaviorProof { offender: 2, ..proof.clone() };
+		assert_eq!(
+			forged.verify(&context, false, &SIGNING_CONTEXT, check_signature),
+			Err(ProofError::BadSignature)
+		);
+
+		// tables which accept multiple seconded candidates don't report them.
+		assert_eq!(
+			proof.verify(&context, true, &SIGNING_CONTEXT, check_signature),
+			Err(ProofError::NotMisbehavior)
+		);
+	}
+
+	#[test]
+	fn test_vectors_are_stable() {
+		let expected = test_vectors();
+		let checked_in = checked_in_vectors();
+
+		// signatures are randomized, so proofs are compared without them.
+		let without_signatures = |name: &str, encoded: &[u8]| {
+			if !name.ends_with(".proof") {
+				return encoded.to_vec()
+			}
+			let mut proof = decode_proof::<[u8; 32], u32, [u8; 64]>(encoded).unwrap();
+			proof.first.1 = [0; 64];
+			if let Some(second) = &mut proof.second {
+				second.1 = [0; 64];
+			}
+			VersionedMisbehaviorProof::from(proof).encode()
+		};
+		let stable = expected.len() == checked_in.len() &&
+			expected.iter().zip(&checked_in).all(|((name, fresh), (checked_name, old))| {
+				name == checked_name &&
+					without_signatures(name, fresh) == without_signatures(name, old)
+			});
+
+		let regenerated: Vec<_> = expected
+			.iter()
+			.map(|(name, encoded)| {
+				let hex: String = encoded.iter().map(|byte| format!("{:02x}", byte)).collect();
+				format!("{} = {}", name, hex)
+			})
+			.collect();
+		assert!(stable, "encoding changed, the test vectors are now:\n{}", regenerated.join("\n"));
+	}
+
+	#[test]
+	fn test_vector_proofs_verify() {
+		let proofs = checked_in_vectors().into_iter().filter(|(n, _)| n.ends_with(".proof"));
+		for (name, encoded) in proofs {
+			let proof = decode_proof::<[u8; 32], u32, [u8; 64]>(&encoded).unwrap();
+			// the offender is a member of the group, so its statements are authorized.
+			let expected = match name {
+				"unauthorized_statement.proof" => Err(ProofError::NotMisbehavior),
+				_ => Ok(()),
+			};
+			let verified = proof.verify(
+				&VectorContext,
+				false,
+				&VECTOR_SIGNING_CONTEXT,
+				check_vector_signature,
+			);
+			assert_eq!(verified, expected, "{}", name);
+
+			// the v2 primitives decode and encode the same bytes.
+			let v2 = decode_proof::<CandidateHash, ValidatorIndex, ValidatorSignature>(&encoded)
+				.unwrap();
+			assert_eq!(VersionedMisbehaviorProof::from(v2).encode(), encoded, "{}", name);
+		}
+
+		// the checked-in proofs are signed with the key of the offender, not just any bytes.
+		let (_, mut forged) = checked_in_vectors()
+			.into_iter()
+			.find(|(name, _)| *name == "multiple_candidates.proof")
+			.unwrap();
+		*forged.last_mut().unwrap() ^= 1;
+		let forged = decode_proof::<[u8; 32], u32, [u8; 64]>(&forged).unwrap();
+		assert_eq!(
+			forged.verify(&VectorContext, false, &VECTOR_SIGNING_CONTEXT, check_vector_signature),
+			Err(ProofError::BadSignature)
+		);
+	}
+
+	#[test]
+	fn proofs_of_non_conflicting_statements_are_rejected() {
+		let signature = |statement, nonce| sign(1, statement, nonce).signature;
+		let misbehavior = Misbehavior::DoubleSign(DoubleSign::Validity(
+			100,
+			signature(Statement::Valid(100), 0),
+			signature(Statement::Valid(100), 1),
+		));
+		let context = context();
+		let proof = MisbehaviorProof::new::<TestContext>(1, misbehavior);
+		assert_eq!(proof.verify(&context, false, &SIGNING_CONTEXT, check_signature), Ok(()));
+
+		let same_signature = MisbehaviorProof {
+			second: Some((ProofStatement::Valid(100), signature(Statement::Valid(100), 0))),
+			..proof.clone()
+		};
+		assert_eq!(
+			same_signature.verify(&context, false, &SIGNING_CONTEXT, check_signature),
+			Err(ProofError::NotMisbehavior)
+		);
+
+		let same_candidate = MisbehaviorProof {
+			kind: ProofKind::MultipleCandidates,
+			first: (
+				ProofStatement::Seconded(100),
+				signature(Statement::Seconded(TestCandidate::new(0, 100)), 0),
+			),
+			second: Some((
+				ProofStatement::Seconded(100),
+				signature(Statement::Seconded(TestCandidate::new(0, 100)), 1),
+			)),
+			..proof
+		};
+		assert_eq!(
+			same_candidate.verify(&context, false, &SIGNING_CONTEXT, check_signature),
+			Err(ProofError::NotMisbehavior)
+		);
+	}
+
+	#[test]
+	fn membership_is_checked_against_the_group_assignment() {
+		let context = context();
+		let candidate = TestCandidate::new(2, 100);
+		let seconded = |sender| sign(sender, Statement::Seconded(candidate), 0);
+		let unauthorized = |sender| {
+			let misbehavior = Misbehavior::UnauthorizedStatement(UnauthorizedStatement {
+				statement: seconded(sender),
+			});
+			MisbehaviorProof::new::<TestContext>(sender, misbehavior)
+		};
+		let verify = |proof: MisbehaviorProofFor<TestContext>| {
+			proof.verify(&context, false, &SIGNING_CONTEXT, check_signature)
+		};
+
+		// a member can't be framed with its own statement, an outsider can be reported.
+		assert_eq!(verify(unauthorized(1)), Err(ProofError::NotMisbehavior));
+		assert_eq!(verify(unauthorized(3)), Ok(()));
+
+		// conflicting statements are only misbehavior of members.
+		let double_sign = |sender| {
+			let misbehavior = Misbehavior::DoubleSign(DoubleSign::Seconded(
+				candidate,
+				seconded(sender).signature,
+				sign(sender, Statement::Seconded(candidate), 1).signature,
+			));
+			MisbehaviorProof::new::<TestContext>(sender, misbehavior)
+		};
+		assert_eq!(verify(double_sign(2)), Ok(()));
+		assert_eq!(verify(double_sign(3)), Err(ProofError::NotMisbehavior));
+
+		// seconding one candidate in each of two groups is allowed.
+		let other = TestCandidate::new(0, 100);
+		let misbehavior = Misbehavior::MultipleCandidates(MultipleCandidates {
+			first: (candidate, seconded(1).signature),
+			second: (other, sign(1, Statement::Seconded(other), 0).signature),
+		});
+		let proof = MisbehaviorProof::new::<TestContext>(1, misbehavior);
+		assert_eq!(verify(proof), Err(ProofError::NotMisbehavior));
+
+		// without the group of the candidate, membership can't be established.
+		let unassigned = TestCandidate::new(7, 100);
+		let misbehavior = Misbehavior::UnauthorizedStatement(UnauthorizedStatement {
+			statement: sign(3, Statement::Seconded(unassigned), 0),
+		});
+		let proof = MisbehaviorProof::new::<TestContext>(3, misbehavior);
+		assert_eq!(verify(proof), Err(ProofError::UnknownGroup));
+	}
+}
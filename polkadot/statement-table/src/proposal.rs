@@ -0,0 +1,441 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Building proposals out of the statement table.
+//!
+//! A proposal is the set of includable candidates a block author puts forward. On congested
+//! chains the number of includable candidates can exceed what fits in a block, so the
+//! selection is bounded by a [`ProposalConfig`] and ordered by a [`SelectionStrategy`].
+//! Candidates which missed their availability deadline, see
+//! [`AvailabilityTable::unavailable_candidates`](crate::availability::AvailabilityTable::unavailable_candidates),
+//! are left out through the configuration as well.
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashSet},
+	hash::{BuildHasher, Hash},
+	time::Instant,
+};
+
+use parity_scale_codec::Encode;
+
+use crate::generic::{AttestedCandidateFor, Context, Table, TableView};
+
+#[cfg(feature = "tracing")]
+const LOG_TARGET: &str = "parachain::statement-table";
+
+/// Limits and preferences applied when building a proposal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProposalConfig<GroupId: Ord, Digest: Eq + Hash> {
+	/// The maximum number of candidates in a proposal. `None` means unbounded.
+	pub max_candidates: Option<usize>,
+	/// The maximum total encoded size of all candidates in a proposal, in bytes.
+	/// `None` means unbounded.
+	pub max_encoded_size: Option<usize>,
+	/// Priority of each group. Candidates of groups with a higher priority are preferred.
+	/// Groups which are not present have priority `0`.
+	pub group_priority: BTreeMap<GroupId, u32>,
+	/// The candidates which are unavailable this round, and aren't proposed.
+	pub unavailable: HashSet<Digest>,
+}
+
+impl<GroupId: Ord, Digest: Eq + Hash> Default for ProposalConfig<GroupId, Digest> {
+	fn default() -> Self {
+		ProposalConfig {
+			max_candidates: None,
+			max_encoded_size: None,
+			group_priority: BTreeMap::new(),
+			unavailable: HashSet::new(),
+		}
+	}
+}
+
+impl<GroupId: Ord, Digest: Eq + Hash> ProposalConfig<GroupId, Digest> {
+	/// Get the priority of a group.
+	pub fn priority_of(&self, group: &GroupId) -> u32 {
+		self.group_priority.get(group).copied().unwrap_or(0)
+	}
+}
+
+/// A strategy for ordering includable candidates by preference.
+///
+/// Candidates are taken greedily in the returned order until the limits of the
+/// [`ProposalConfig`] are reached. Candidates which would exceed the size limit are skipped.
+pub trait SelectionStrategy<Ctx: Context> {
+	/// Sort `candidates` so that the most preferred candidate comes first.
+	fn order(
+		&self,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		candidates: &mut Vec<AttestedCandidateFor<Ctx>>,
+	);
+}
+
+/// The default selection strategy.
+///
+/// Prefers candidates of groups with higher priority, then candidates with more validity
+/// votes. Ties are broken by group ID and then by candidate, so that all nodes building a
+/// proposal out of the same table select the same candidates.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PriorityThenBacking;
+
+impl<Ctx: Context> SelectionStrategy<Ctx> for PriorityThenBacking {
+	fn order(
+		&self,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		candidates: &mut Vec<AttestedCandidateFor<Ctx>>,
+	) {
+		candidates.sort_by(|a, b| {
+			config
+				.priority_of(&b.group_id)
+				.cmp(&config.priority_of(&a.group_id))
+				.then_with(|| b.validity_votes.len().cmp(&a.validity_votes.len()))
+				.then_with(|| a.group_id.cmp(&b.group_id))
+				.then_with(|| a.candidate.cmp(&b.candidate))
+		});
+	}
+}
+
+/// Why a group has no candidate in a proposal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupShortfall {
+	/// No candidate was seconded in the group.
+	NoCandidate,
+	/// No candidate of the group reached quorum. Reports the best-backed candidate.
+	InsufficientVotes {
+		/// How many validity votes the best-backed candidate has.
+		validity_votes: usize,
+		/// How many validity votes are needed.
+		validity_threshold: usize,
+	},
+	/// The group had an includable candidate, but it is unavailable this round, see
+	/// [`ProposalConfig::unavailable`].
+	Unavailable,
+	/// The group had an includable candidate, but it didn't fit within the limits of the
+	/// [`ProposalConfig`].
+	ExcludedByLimits,
+}
+
+/// A proposal along with a report on the groups which are not part of it.
+#[derive(Debug)]
+pub struct PartialProposal<Ctx: Context> {
+	/// The selected candidates, in order of preference.
+	pub candidates: Vec<AttestedCandidateFor<Ctx>>,
+	/// The groups without any candidate in the proposal, and why.
+	pub shortfalls: BTreeMap<Ctx::GroupId, GroupShortfall>,
+}
+
+impl<Ctx: Context, H: BuildHasher + Clone> Table<Ctx, H> {
+	/// Build a proposal out of the currently includable candidates, see
+	/// [`TableView::build_proposal`].
+	///
+	/// To avoid holding up imports while a proposal is built out of a large table, build it
+	/// out of a [`Table::view`] instead.
+	pub fn build_proposal<S: SelectionStrategy<Ctx>>(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		strategy: &S,
+	) -> Vec<AttestedCandidateFor<Ctx>>
+	where
+		AttestedCandidateFor<Ctx>: Encode,
+	{
+		self.view().build_proposal(context, minimum_backing_votes, config, strategy)
+	}
+
+	/// Build the best proposal possible right now and report on the groups which didn't make it
+	/// in, see [`TableView::build_partial_proposal`].
+	pub fn build_partial_proposal<S: SelectionStrategy<Ctx>>(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		strategy: &S,
+		expected_groups: &[Ctx::GroupId],
+	) -> PartialProposal<Ctx>
+	where
+		AttestedCandidateFor<Ctx>: Encode,
+	{
+		self.view().build_partial_proposal(
+			context,
+			minimum_backing_votes,
+			config,
+			strategy,
+			expected_groups,
+		)
+	}
+}
+
+impl<Ctx: Context, H: BuildHasher> TableView<Ctx, H> {
+	/// Build a proposal out of the includable candidates.
+	///
+	/// The candidates are ordered by `strategy` and then taken in order while they fit within
+	/// the limits of `config`. Candidates `config` marks as unavailable are left out.
+	pub fn build_proposal<S: SelectionStrategy<Ctx>>(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		strategy: &S,
+	) -> Vec<AttestedCandidateFor<Ctx>>
+	where
+		AttestedCandidateFor<Ctx>: Encode,
+	{
+		let started = Instant::now();
+		#[cfg(feature = "tracing")]
+		let _span = tracing::debug_span!(target: LOG_TARGET, "build_proposal", minimum_backing_votes)
+			.entered();
+		let mut candidates = self.attested_candidates(context, minimum_backing_votes);
+		candidates.retain(|attested| {
+			!config.unavailable.contains(&Ctx::candidate_digest(&attested.candidate))
+		});
+		strategy.order(config, &mut candidates);
+
+		let max_candidates = config.max_candidates.unwrap_or(usize::MAX);
+		let max_encoded_size = config.max_encoded_size.unwrap_or(usize::MAX);
+
+		let mut total_size = 0usize;
+		let mut proposal = Vec::new();
+		for candidate in candidates {
+			if proposal.len() >= max_candidates {
+				break
+			}
+
+			let size = candidate.encoded_size();
+			if total_size.saturating_add(size) > max_encoded_size {
+				continue
+			}
+
+			total_size += size;
+			proposal.push(candidate);
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::debug!(
+			target: LOG_TARGET,
+			candidates = proposal.len(),
+			encoded_size = total_size,
+			"Built proposal",
+		);
+		self.counters().proposals.record(started);
+		proposal
+	}
+
+	/// Build the best proposal possible right now and report on the groups which didn't make it
+	/// in.
+	///
+	/// This is meant to be used at a round deadline, where a block has to be authored even if
+	/// some groups haven't reached quorum. `expected_groups` are the groups which are
+	/// expected to back a candidate. They are reported with [`GroupShortfall::NoCandidate`]
+	/// if nothing was seconded in them.
+	pub fn build_partial_proposal<S: SelectionStrategy<Ctx>>(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+		config: &ProposalConfig<Ctx::GroupId, Ctx::Digest>,
+		strategy: &S,
+		expected_groups: &[Ctx::GroupId],
+	) -> PartialProposal<Ctx>
+	where
+		AttestedCandidateFor<Ctx>: Encode,
+	{
+		let candidates = self.build_proposal(context, minimum_backing_votes, config, strategy);
+		let included: BTreeSet<_> = candidates.iter().map(|c| c.group_id.clone()).collect();
+
+		let mut shortfalls = BTreeMap::new();
+		for digest in self.candidate_digests() {
+			let status = match self.candidate_status(digest, context, minimum_backing_votes) {
+				Some(status) => status,
+				None => continue,
+			};
+
+			if included.contains(&status.group_id) {
+				continue
+			}
+
+			let shortfall = if status.is_includable() && config.unavailable.contains(digest) {
+				GroupShortfall::Unavailable
+			} else if status.is_includable() {
+				GroupShortfall::ExcludedByLimits
+			} else {
+				GroupShortfall::InsufficientVotes {
+					validity_votes: status.validity_votes,
+					validity_threshold: status.validity_threshold,
+				}
+			};
+
+			shortfalls
+				.entry(status.group_id)
+				.and_modify(|existing| {
+					if shortfall_rank(&shortfall) > shortfall_rank(existing) {
+						*existing = shortfall.clone();
+					}
+				})
+				.or_insert(shortfall);
+		}
+
+		for group in expected_groups {
+			if !included.contains(group) {
+				shortfalls.entry(group.clone()).or_insert(GroupShortfall::NoCandidate);
+			}
+		}
+
+		PartialProposal { candidates, shortfalls }
+	}
+}
+
+// Orders shortfalls of the same group so that the one closest to inclusion is reported.
+fn shortfall_rank(shortfall: &GroupShortfall) -> (u8, usize) {
+	match *shortfall {
+		GroupShortfall::NoCandidate => (0, 0),
+		GroupShortfall::InsufficientVotes { validity_votes, .. } => (1, validity_votes),
+		GroupShortfall::Unavailable => (2, 0),
+		GroupShortfall::ExcludedByLimits => (3, 0),
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::{
+		generic::{Config, Statement},
+		test_utils::{sign, GroupId, TestCandidate, TestContext},
+	};
+
+	// Three groups of two authorities each. Authority `2 * g` seconds a candidate in group `g`
+	// and, if `backed`, authority `2 * g + 1` votes for it.
+	pub(crate) fn populated_table(backed: &[u32]) -> (TestContext, Table<TestContext>) {
+		let context = TestContext::builder().with_groups(3, 2).build();
+
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		for g in 0..3 {
+			let candidate = TestCandidate::new(g, 100 + u64::from(g));
+			table.import_statement(&context, g, sign(2 * g, Statement::Seconded(candidate), 0));
+
+			if backed.contains(&g) {
+				table.import_statement(
+					&context,
+					g,
+					sign(2 * g + 1, Statement::Valid(candidate.digest()), 0),
+				);
+			}
+		}
+
+		(context, table)
+	}
+
+	fn groups(proposal: &[AttestedCandidateFor<TestContext>]) -> Vec<GroupId> {
+		proposal.iter().map(|c| c.group_id).collect()
+	}
+
+	#[test]
+	fn default_config_includes_everything_includable() {
+		let (context, table) = populated_table(&[0, 2]);
+
+		let proposal =
+			table.build_proposal(&context, 1, &ProposalConfig::default(), &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![0, 2, 1]);
+
+		let proposal =
+			table.build_proposal(&context, 2, &ProposalConfig::default(), &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![0, 2]);
+	}
+
+	#[test]
+	fn group_priority_and_max_candidates_are_respected() {
+		let (context, table) = populated_table(&[0, 1, 2]);
+
+		let config = ProposalConfig {
+			max_candidates: Some(2),
+			group_priority: [(2, 10), (1, 5)].into_iter().collect(),
+			..Default::default()
+		};
+
+		let proposal = table.build_proposal(&context, 2, &config, &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![2, 1]);
+	}
+
+	#[test]
+	fn max_encoded_size_is_respected() {
+		let (context, table) = populated_table(&[0, 1, 2]);
+
+		let all =
+			table.build_proposal(&context, 2, &ProposalConfig::default(), &PriorityThenBacking);
+		let single_size = all[0].encoded_size();
+
+		let config =
+			ProposalConfig { max_encoded_size: Some(2 * single_size + 1), ..Default::default() };
+
+		let proposal = table.build_proposal(&context, 2, &config, &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![0, 1]);
+
+		let config = ProposalConfig { max_encoded_size: Some(0), ..Default::default() };
+		assert!(table.build_proposal(&context, 2, &config, &PriorityThenBacking).is_empty());
+	}
+
+	#[test]
+	fn partial_proposal_reports_shortfalls() {
+		let (context, table) = populated_table(&[0, 1]);
+
+		let config = ProposalConfig { max_candidates: Some(1), ..Default::default() };
+		let expected = [0, 1, 2, 3];
+		let partial =
+			table.build_partial_proposal(&context, 2, &config, &PriorityThenBacking, &expected);
+
+		assert_eq!(groups(&partial.candidates), vec![0]);
+		assert_eq!(
+			partial.shortfalls.into_iter().collect::<Vec<_>>(),
+			vec![
+				(1, GroupShortfall::ExcludedByLimits),
+				(2, GroupShortfall::InsufficientVotes { validity_votes: 1, validity_threshold: 2 }),
+				(3, GroupShortfall::NoCandidate),
+			]
+		);
+
+		// unavailable candidates are left out.
+		let unavailable = [TestCandidate::new(0, 100).digest()].into_iter().collect();
+		let config = ProposalConfig { unavailable, ..Default::default() };
+		let partial =
+			table.build_partial_proposal(&context, 2, &config, &PriorityThenBacking, &expected);
+		assert_eq!(groups(&partial.candidates), vec![1]);
+		assert_eq!(partial.shortfalls[&0], GroupShortfall::Unavailable);
+	}
+
+	#[test]
+	fn proposal_is_built_from_view_while_importing() {
+		let (context, mut table) = populated_table(&[0]);
+		let view = table.view();
+
+		// group 2 gets backed after the view was taken.
+		let vote = sign(5, Statement::Valid(TestCandidate::new(2, 102).digest()), 0);
+		assert!(table.import_statement(&context, 2, vote).is_some());
+
+		let config = ProposalConfig::default();
+		let proposal = view.build_proposal(&context, 2, &config, &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![0]);
+		assert_eq!(
+			view.candidate_status(&TestCandidate::new(2, 102).digest(), &context, 2)
+				.map(|status| status.validity_votes),
+			Some(1),
+		);
+
+		let proposal = table.build_proposal(&context, 2, &config, &PriorityThenBacking);
+		assert_eq!(groups(&proposal), vec![0, 2]);
+		assert_eq!(
+			view.get_candidate(&TestCandidate::new(1, 101).digest()),
+			table.get_candidate(&TestCandidate::new(1, 101).digest())
+		);
+	}
+}
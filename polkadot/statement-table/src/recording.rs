@@ -0,0 +1,362 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Recordings of the statements imported into a table, for regression tests.
+//!
+//! A [`Recorder`] set on a table with [`Table::set_recorder`] records every statement imported
+//! into it, along with its position in the order of import, the time since the recording
+//! started and whether the table imported it. It keeps a bounded number of the latest
+//! statements, dropping the oldest ones. A [`Recording`] can be taken at any time, e.g.
+//! when an incident is noticed in production, and attached to a report in its encoded form.
+//! [`Recording::replay`] imports the statements into a fresh table in the same order, and
+//! reports the statements whose import had another outcome than when they were recorded, so
+//! that a regression test can reproduce the incident and check the resulting table.
+//!
+//! Recordings are encoded like a [`StatementLog`](crate::wal::StatementLog), with the details of
+//! the import following the group and the statement in each record, so that a recording can be
+//! opened and replayed as a statement log as well.
+
+use std::{
+	collections::VecDeque,
+	hash::BuildHasher,
+	io::{self, BufReader},
+	sync::Mutex,
+	time::Instant,
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	generic::{Context, SignedStatement, Table},
+	wal::{self, LOG_VERSION},
+	LOCK_PROOF,
+};
+
+/// The number of statements a [`Recorder`] keeps by default.
+pub const DEFAULT_MAX_RECORDED: usize = 16 * 1024;
+
+/// A statement imported into a table, as recorded by a [`Recorder`].
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct RecordedStatement<Group, Candidate, Digest, AuthorityId, Signature> {
+	/// The group the statement was imported for.
+	pub group_id: Group,
+	/// The statement.
+	pub statement: SignedStatement<Candidate, Digest, AuthorityId, Signature>,
+	/// The position of the statement in the order of import, starting at 0.
+	pub sequence: u64,
+	/// The time from the start of the recording to the import, in microseconds.
+	pub elapsed_micros: u64,
+	/// Whether the table imported the statement, as opposed to ignoring it.
+	pub imported: bool,
+}
+
+/// Type alias for a recorded statement corresponding to context type.
+pub type RecordedStatementFor<Ctx> = RecordedStatement<
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// The statements recorded by a [`Recorder`], in order of import.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+pub struct Recording<Group, Candidate, Digest, AuthorityId, Signature> {
+	/// The recorded statements.
+	pub statements: Vec<RecordedStatement<Group, Candidate, Digest, AuthorityId, Signature>>,
+}
+
+/// Type alias for a recording corresponding to context type.
+pub type RecordingFor<Ctx> = Recording<
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// The outcome of [`Recording::replay`].
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Replay {
+	/// The number of replayed statements.
+	pub replayed: usize,
+	/// The sequence numbers of the statements whose import had another outcome than recorded.
+	pub diverged: Vec<u64>,
+}
+
+impl<Group, Candidate, Digest, AuthorityId, Signature>
+	Recording<Group, Candidate, Digest, AuthorityId, Signature>
+where
+	Group: Clone,
+	Candidate: Clone,
+	Digest: Clone,
+	AuthorityId: Clone,
+	Signature: Clone,
+{
+	/// Import the recorded statements into `table`, in order.
+	///
+	/// Replayed into a fresh table, this reconstructs the state of the recorded table, unless
+	/// the recording was taken from a table which already had statements or the recorder
+	/// dropped some of them.
+	pub fn replay<Ctx, S>(&self, context: &Ctx, table: &mut Table<Ctx, S>) -> Replay
+	where
+		Ctx: Context<
+			GroupId = Group,
+			Candidate = Candidate,
+			Digest = Digest,
+			AuthorityId = AuthorityId,
+			Signature = Signature,
+		>,
+		S: BuildHasher + Clone,
+	{
+		let mut replay = Replay::default();
+		for recorded in &self.statements {
+			let summary = table.import_statement(
+				context,
+				recorded.group_id.clone(),
+				recorded.statement.clone(),
+			);
+			if summary.is_some() != recorded.imported {
+				replay.diverged.push(recorded.sequence);
+			}
+			replay.replayed += 1;
+		}
+
+		replay
+	}
+
+	/// Encode the recording, e.g. to attach it to a report.
+	pub fn to_bytes(&self) -> io::Result<Vec<u8>>
+	where
+		RecordedStatement<Group, Candidate, Digest, AuthorityId, Signature>: Encode,
+	{
+		let mut encoded = wal::log_header();
+		for recorded in &self.statements {
+			wal::encode_record(&mut encoded, recorded)?;
+		}
+		Ok(encoded)
+	}
+
+	/// Decode a recording encoded with [`Recording::to_bytes`].
+	pub fn from_bytes(encoded: &[u8]) -> io::Result<Self>
+	where
+		RecordedStatement<Group, Candidate, Digest, AuthorityId, Signature>: Decode,
+	{
+		let invalid = |error| io::Error::new(io::ErrorKind::InvalidData, error);
+		let version = wal::parse_header(encoded).ok_or_else(|| invalid("not a recording"))?;
+		if version > LOG_VERSION {
+			return Err(invalid("recording is of a newer version"))
+		}
+
+		let records = &encoded[wal::log_header().len()..];
+		let mut statements = Vec::new();
+		let len = wal::read_records(BufReader::new(records), |recorded| {
+			statements.push(recorded);
+			Ok(())
+		})?;
+		if len != records.len() as u64 {
+			return Err(invalid("recording is truncated"))
+		}
+
+		Ok(Recording { statements })
+	}
+}
+
+/// Records the statements imported into a table, see [`Table::set_recorder`].
+pub struct Recorder<Ctx: Context> {
+	started: Instant,
+	max_statements: usize,
+	inner: Mutex<Inner<Ctx>>,
+}
+
+struct Inner<Ctx: Context> {
+	next_sequence: u64,
+	statements: VecDeque<RecordedStatementFor<Ctx>>,
+}
+
+impl<Ctx: Context> Default for Recorder<Ctx> {
+	fn default() -> Self {
+		Self::new(DEFAULT_MAX_RECORDED)
+	}
+}
+
+impl<Ctx: Context> Recorder<Ctx> {
+	/// Start a recording which keeps the latest `max_statements` statements.
+	pub fn new(max_statements: usize) -> Self {
+		Recorder {
+			started: Instant::now(),
+			max_statements,
+			inner: Mutex::new(Inner { next_sequence: 0, statements: VecDeque::new() }),
+		}
+	}
+
+	/// Get the statements recorded so far, which the recorder still keeps.
+	pub fn recording(&self) -> RecordingFor<Ctx> {
+		let inner = self.inner.lock().expect(LOCK_PROOF);
+		Recording { statements: inner.statements.iter().cloned().collect() }
+	}
+
+	/// Take the statements recorded so far, and keep recording. Sequence numbers continue
+	/// across recordings taken from the same recorder.
+	pub fn take(&self) -> RecordingFor<Ctx> {
+		let statements = std::mem::take(&mut self.inner.lock().expect(LOCK_PROOF).statements);
+		Recording { statements: statements.into() }
+	}
+
+	pub(crate) fn record(
+		&self,
+		group_id: Ctx::GroupId,
+		statement: SignedStatement<Ctx::Candidate, Ctx::Digest, Ctx::AuthorityId, Ctx::Signature>,
+		imported: bool,
+	) {
+		let elapsed_micros = self.started.elapsed().as_micros() as u64;
+		let mut inner = self.inner.lock().expect(LOCK_PROOF);
+		let sequence = inner.next_sequence;
+		inner.next_sequence += 1;
+		if self.max_statements == 0 {
+			return
+		}
+		if inner.statements.len() == self.max_statements {
+			inner.statements.pop_front();
+		}
+		inner.statements.push_back(RecordedStatement {
+			group_id,
+			statement,
+			sequence,
+			elapsed_micros,
+			imported,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::{Config, SignedStatementFor, Statement},
+		test_utils::{sign, Digest, TestCandidate, TestContext},
+		wal::StatementLog,
+	};
+	use std::sync::Arc;
+
+	const DIGEST: Digest = (2 << 32) | 100;
+
+	fn statement(
+		statement: Statement<TestCandidate, Digest>,
+		sender: u32,
+	) -> SignedStatementFor<TestContext> {
+		sign(sender, statement, 0)
+	}
+
+	fn context() -> TestContext {
+		TestContext::builder().with_group(2, 1..=3).build()
+	}
+
+	#[test]
+	fn recording_is_replayed() {
+		let context = context();
+		let recorder = Arc::new(Recorder::default());
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		table.set_recorder(recorder.clone());
+
+		let candidate = TestCandidate::new(2, 100);
+		table.import_statement(&context, 2, statement(Statement::Seconded(candidate), 1));
+		table.import_statement(&context, 2, statement(Statement::Valid(DIGEST), 2));
+		// duplicate, and about an unknown candidate.
+		table.import_statement(&context, 2, statement(Statement::Valid(DIGEST), 2));
+		table.import_statement(&context, 2, statement(Statement::Valid(999), 3));
+
+		let recording = recorder.recording();
+		let sequences: Vec<_> = recording.statements.iter().map(|s| s.sequence).collect();
+		assert_eq!(sequences, vec![0, 1, 2, 3]);
+		let imported: Vec<_> = recording.statements.iter().map(|s| s.imported).collect();
+		assert_eq!(imported, vec![true, true, false, false]);
+		assert!(recording
+			.statements
+			.windows(2)
+			.all(|w| w[0].elapsed_micros <= w[1].elapsed_micros));
+
+		let encoded = recording.to_bytes().unwrap();
+		let decoded = RecordingFor::<TestContext>::from_bytes(&encoded).unwrap();
+		assert_eq!(decoded, recording);
+
+		// a recording is a statement log too.
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("recording");
+		std::fs::write(&path, &encoded).unwrap();
+		let log = StatementLog::<TestContext>::open(&path).unwrap();
+		let logged: Vec<_> =
+			recording.statements.iter().map(|s| (s.group_id, s.statement.clone())).collect();
+		assert_eq!(log.statements().unwrap(), logged);
+		assert!(RecordingFor::<TestContext>::from_bytes(&encoded[..encoded.len() - 1]).is_err());
+
+		let mut replayed = Table::new(Config { allow_multiple_seconded: false });
+		let replay = decoded.replay(&context, &mut replayed);
+		assert_eq!(replay, Replay { replayed: 4, diverged: Vec::new() });
+		assert_eq!(
+			replayed.attested_candidates(&context, 2),
+			table.attested_candidates(&context, 2)
+		);
+
+		// replaying into a table which already has the statements diverges.
+		let replay = decoded.replay(&context, &mut replayed);
+		assert_eq!(replay.diverged, vec![0, 1]);
+	}
+
+	#[test]
+	fn taking_recordings_continues_the_sequence() {
+		let context = context();
+		let recorder = Arc::new(Recorder::default());
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		table.set_recorder(recorder.clone());
+
+		table.import_statement(
+			&context,
+			2,
+			statement(Statement::Seconded(TestCandidate::new(2, 100)), 1),
+		);
+		assert_eq!(recorder.take().statements.len(), 1);
+		table.import_statement(&context, 2, statement(Statement::Valid(DIGEST), 2));
+
+		let recording = recorder.take();
+		assert_eq!(recording.statements.len(), 1);
+		assert_eq!(recording.statements[0].sequence, 1);
+		assert!(recorder.recording().statements.is_empty());
+
+		let mut encoded = recording.to_bytes().unwrap();
+		encoded[4] = LOG_VERSION as u8 + 1;
+		assert!(RecordingFor::<TestContext>::from_bytes(&encoded).is_err());
+		assert!(RecordingFor::<TestContext>::from_bytes(b"stlg").is_err());
+	}
+
+	#[test]
+	fn recorder_keeps_the_latest_statements() {
+		let context = context();
+		let recorder = Arc::new(Recorder::new(2));
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		table.set_recorder(recorder.clone());
+
+		let candidate = TestCandidate::new(2, 100);
+		table.import_statement(&context, 2, statement(Statement::Seconded(candidate), 1));
+		for sender in 2..=3 {
+			table.import_statement(&context, 2, statement(Statement::Valid(DIGEST), sender));
+		}
+
+		let sequences: Vec<_> =
+			recorder.recording().statements.iter().map(|s| s.sequence).collect();
+		assert_eq!(sequences, vec![1, 2]);
+	}
+}
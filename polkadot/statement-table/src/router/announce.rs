@@ -0,0 +1,305 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Two-phase announcement of large candidates.
+//!
+//! Flooding a large candidate to every peer is wasteful, as most of them may never need it.
+//! Instead, only an [`Announcement`] of a statement seconding it is gossiped: the digest of the
+//! candidate along with the signature of the seconder. Peers interested in the candidate pull
+//! its body from the peers which announced it, using a [`BodyFetcher`].
+//!
+//! Announced candidates are tracked by the [`Table`] until their body arrives, see
+//! [`Table::note_announcement`].
+
+use std::{
+	collections::{HashMap, VecDeque},
+	hash::Hash,
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::generic::{Context, SignedStatementFor, Statement, Table};
+
+use super::fetch::{CandidateRequest, FetchRequest, ResponseOutcome, ResponseOutcomeFor};
+
+/// Announcement of a statement seconding a candidate, without the candidate itself.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Announcement<Digest, AuthorityId, Signature> {
+	/// The digest of the seconded candidate.
+	pub digest: Digest,
+	/// The authority which seconded the candidate.
+	pub sender: AuthorityId,
+	/// The signature of the statement seconding the candidate.
+	pub signature: Signature,
+}
+
+/// Type alias for an announcement corresponding to context type.
+pub type AnnouncementFor<Ctx> = Announcement<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+impl<Digest, AuthorityId: Clone, Signature: Clone> Announcement<Digest, AuthorityId, Signature> {
+	/// Get the announcement of a statement seconding a candidate.
+	///
+	/// Returns `None` if the statement doesn't second a candidate.
+	pub fn of<Ctx>(statement: &SignedStatementFor<Ctx>) -> Option<Self>
+	where
+		Ctx: Context<Digest = Digest, AuthorityId = AuthorityId, Signature = Signature>,
+	{
+		match &statement.statement {
+			Statement::Seconded(candidate) => Some(Announcement {
+				digest: Ctx::candidate_digest(candidate),
+				sender: statement.sender.clone(),
+				signature: statement.signature.clone(),
+			}),
+			Statement::Valid(_) => None,
+		}
+	}
+}
+
+/// Response to a [`CandidateRequest`] for the body of an announced candidate.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct BodyResponse<Candidate> {
+	/// The requested candidate.
+	pub candidate: Option<Candidate>,
+}
+
+/// Answer a request for the body of a candidate out of the local table.
+pub fn answer_body_request<Ctx: Context>(
+	table: &Table<Ctx>,
+	request: &CandidateRequest<Ctx::Digest>,
+) -> BodyResponse<Ctx::Candidate> {
+	BodyResponse { candidate: table.get_candidate(&request.digest).cloned() }
+}
+
+/// Decides which candidates are announced rather than gossiped in full.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnounceConfig {
+	/// The encoded size in bytes above which candidates are announced.
+	pub max_inline_size: usize,
+}
+
+impl Default for AnnounceConfig {
+	fn default() -> Self {
+		AnnounceConfig { max_inline_size: 64 * 1024 }
+	}
+}
+
+impl AnnounceConfig {
+	/// Whether statements seconding `candidate` should be announced rather than gossiped in
+	/// full.
+	pub fn should_announce<Candidate: Encode>(&self, candidate: &Candidate) -> bool {
+		candidate.encoded_size() > self.max_inline_size
+	}
+}
+
+// An announced candidate whose body is wanted.
+struct Wanted<PeerId> {
+	// Peers which announced the candidate and weren't asked for it yet.
+	announcers: VecDeque<PeerId>,
+	// The peer currently asked for the candidate.
+	in_flight: Option<PeerId>,
+}
+
+/// Tracks the peers which announced candidates and pulls the bodies of wanted candidates from
+/// them.
+pub struct BodyFetcher<Ctx: Context, PeerId> {
+	announcers: HashMap<Ctx::Digest, VecDeque<PeerId>>,
+	wanted: HashMap<Ctx::Digest, Wanted<PeerId>>,
+}
+
+impl<Ctx: Context, PeerId: Eq + Hash + Clone> Default for BodyFetcher<Ctx, PeerId> {
+	fn default() -> Self {
+		BodyFetcher { announcers: HashMap::new(), wanted: HashMap::new() }
+	}
+}
+
+impl<Ctx: Context, PeerId: Eq + Hash + Clone> BodyFetcher<Ctx, PeerId> {
+	/// Create a new fetcher.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Import an announcement received from `peer` into the table. The signature should be
+	/// checked, as for [`Table::import_statement`].
+	///
+	/// Returns a request to send if the body of the candidate is wanted and not being fetched
+	/// already.
+	pub fn note_announcement(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: PeerId,
+		group_id: Ctx::GroupId,
+		announcement: AnnouncementFor<Ctx>,
+	) -> Option<FetchRequest<PeerId, Ctx::Digest>> {
+		let Announcement { digest, sender, signature } = announcement;
+		if !table.note_announcement(context, group_id, digest.clone(), sender, signature) {
+			return None
+		}
+
+		match self.wanted.get_mut(&digest) {
+			Some(wanted) => {
+				if wanted.in_flight.as_ref() != Some(&peer) && !wanted.announcers.contains(&peer) {
+					wanted.announcers.push_back(peer);
+				}
+				Self::next_request(digest, wanted)
+			},
+			None => {
+				let announcers = self.announcers.entry(digest).or_default();
+				if !announcers.contains(&peer) {
+					announcers.push_back(peer);
+				}
+				None
+			},
+		}
+	}
+
+	/// Start pulling the body of an announced candidate.
+	///
+	/// Returns a request to send, if there is a peer to ask.
+	pub fn want(&mut self, digest: &Ctx::Digest) -> Option<FetchRequest<PeerId, Ctx::Digest>> {
+		let wanted = self
+			.wanted
+			.entry(digest.clone())
+			.or_insert_with(|| Wanted { announcers: VecDeque::new(), in_flight: None });
+		wanted.announcers.extend(self.announcers.remove(digest).into_iter().flatten());
+
+		Self::next_request(digest.clone(), wanted)
+	}
+
+	/// Handle the response of `peer` to a request for the body of the candidate with the given
+	/// `digest`.
+	pub fn on_response(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: &PeerId,
+		digest: &Ctx::Digest,
+		response: BodyResponse<Ctx::Candidate>,
+	) -> ResponseOutcomeFor<Ctx, PeerId> {
+		let wanted = match self.wanted.get_mut(digest) {
+			Some(wanted) if wanted.in_flight.as_ref() == Some(peer) => wanted,
+			_ => return ResponseOutcome::Unexpected,
+		};
+		wanted.in_flight = None;
+
+		let summary = response
+			.candidate
+			.filter(|candidate| &Ctx::candidate_digest(candidate) == digest)
+			.and_then(|candidate| table.import_body(context, candidate));
+
+		match summary {
+			Some(summary) => {
+				self.wanted.remove(digest);
+				ResponseOutcome::Imported(vec![summary])
+			},
+			None => ResponseOutcome::Retry(Self::next_request(digest.clone(), wanted)),
+		}
+	}
+
+	/// Note that a peer disconnected. Returns the requests to send to other peers instead.
+	pub fn peer_disconnected(&mut self, peer: &PeerId) -> Vec<FetchRequest<PeerId, Ctx::Digest>> {
+		self.announcers.retain(|_, announcers| {
+			announcers.retain(|p| p != peer);
+			!announcers.is_empty()
+		});
+
+		let mut requests = Vec::new();
+		for (digest, wanted) in self.wanted.iter_mut() {
+			wanted.announcers.retain(|p| p != peer);
+			if wanted.in_flight.as_ref() == Some(peer) {
+				wanted.in_flight = None;
+				requests.extend(Self::next_request(digest.clone(), wanted));
+			}
+		}
+
+		requests
+	}
+
+	/// Forget about the candidate with the given digest, e.g. because its body was received
+	/// otherwise.
+	pub fn cancel(&mut self, digest: &Ctx::Digest) {
+		self.announcers.remove(digest);
+		self.wanted.remove(digest);
+	}
+
+	fn next_request(
+		digest: Ctx::Digest,
+		wanted: &mut Wanted<PeerId>,
+	) -> Option<FetchRequest<PeerId, Ctx::Digest>> {
+		if wanted.in_flight.is_some() {
+			return None
+		}
+
+		let peer = wanted.announcers.pop_front()?;
+		wanted.in_flight = Some(peer.clone());
+		Some(FetchRequest { peer, request: CandidateRequest { digest } })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, DIGEST},
+		test_utils::{Digest, TestCandidate, TestContext},
+	};
+
+	fn request(peer: u32) -> Option<FetchRequest<u32, Digest>> {
+		Some(FetchRequest { peer, request: CandidateRequest { digest: DIGEST } })
+	}
+
+	#[test]
+	fn wanted_bodies_are_pulled_from_announcers() {
+		let (context, mut table) = context_and_table();
+		let (_, mut remote) = context_and_table();
+		remote.import_statement(&context, 0, seconded(1));
+
+		let mut fetcher = BodyFetcher::new();
+		let announcement = Announcement::of::<TestContext>(&seconded(1)).unwrap();
+		let other = Announcement::of::<TestContext>(&seconded(2)).unwrap();
+
+		assert_eq!(fetcher.note_announcement(&mut table, &context, 10u32, 0, announcement), None);
+		assert!(table.is_body_pending(&DIGEST));
+		assert_eq!(table.pending_bodies(), vec![(DIGEST, 0)]);
+
+		assert_eq!(fetcher.want(&DIGEST), request(10));
+		assert_eq!(fetcher.note_announcement(&mut table, &context, 11, 0, other), None);
+
+		// peer 10 sends a different candidate, ask peer 11.
+		let response = BodyResponse { candidate: Some(TestCandidate::new(0, 101)) };
+		let outcome = fetcher.on_response(&mut table, &context, &10, &DIGEST, response);
+		assert_eq!(outcome, ResponseOutcome::Retry(request(11)));
+
+		let response = answer_body_request(&remote, &CandidateRequest { digest: DIGEST });
+		match fetcher.on_response(&mut table, &context, &11, &DIGEST, response) {
+			ResponseOutcome::Imported(summaries) => assert_eq!(summaries[0].validity_votes, 2),
+			outcome => panic!("unexpected outcome {:?}", outcome),
+		}
+		assert!(!table.is_body_pending(&DIGEST));
+		assert_eq!(table.get_candidate(&DIGEST), Some(&TestCandidate::new(0, 100)));
+	}
+
+	#[test]
+	fn large_candidates_are_announced() {
+		let config = AnnounceConfig { max_inline_size: 8 };
+		assert!(!config.should_announce(&0u64));
+		assert!(config.should_announce(&vec![0u8; 8]));
+	}
+}
@@ -0,0 +1,153 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Batching of statements on the wire.
+//!
+//! Statements are small, so sending each of them in a message of its own is dominated by
+//! per-message overhead. A [`Batcher`] collects the statements destined for each peer into a
+//! [`StatementBatch`], which is imported at once by [`StatementGossip::import_batch`].
+//!
+//! [`StatementGossip::import_batch`]: super::StatementGossip::import_batch
+
+use std::{collections::HashMap, hash::Hash};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::generic::{Context, SignedStatement, SignedStatementFor, Summary};
+
+/// Multiple statements sent to a peer in a single message.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct StatementBatch<GroupId, Candidate, Digest, AuthorityId, Signature> {
+	/// The statements, along with the group of the candidate they refer to.
+	pub statements: Vec<(GroupId, SignedStatement<Candidate, Digest, AuthorityId, Signature>)>,
+}
+
+/// Type alias for a statement batch corresponding to context type.
+pub type StatementBatchFor<Ctx> = StatementBatch<
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// The outcome of importing a [`StatementBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchImport<PeerId, GroupId, Candidate, Digest, AuthorityId, Signature> {
+	/// The summaries of all statements which were imported into the table.
+	pub summaries: Vec<Summary<Digest, GroupId>>,
+	/// The batches to forward to other peers.
+	pub forward: Vec<(PeerId, StatementBatch<GroupId, Candidate, Digest, AuthorityId, Signature>)>,
+}
+
+/// Type alias for the outcome of importing a batch corresponding to context type.
+pub type BatchImportFor<Ctx, PeerId> = BatchImport<
+	PeerId,
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// Collects outgoing statements into batches per peer.
+pub struct Batcher<Ctx: Context, PeerId> {
+	max_batch_len: usize,
+	pending: HashMap<PeerId, Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>>,
+}
+
+impl<Ctx: Context, PeerId: Hash + Eq + Clone> Batcher<Ctx, PeerId> {
+	/// Create a new batcher producing batches of at most `max_batch_len` statements.
+	pub fn new(max_batch_len: usize) -> Self {
+		Batcher { max_batch_len: max_batch_len.max(1), pending: HashMap::new() }
+	}
+
+	/// Queue a statement for `peer`. Returns a batch to send if one is full.
+	pub fn push(
+		&mut self,
+		peer: PeerId,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<(PeerId, StatementBatchFor<Ctx>)> {
+		let pending = self.pending.entry(peer.clone()).or_default();
+		pending.push((group_id, statement));
+		if pending.len() < self.max_batch_len {
+			return None
+		}
+
+		let statements = self.pending.remove(&peer).unwrap_or_default();
+		Some((peer, StatementBatch { statements }))
+	}
+
+	/// Queue a statement for each of `peers`. Returns the batches to send which are full.
+	pub fn push_to_all(
+		&mut self,
+		peers: impl IntoIterator<Item = PeerId>,
+		group_id: &Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Vec<(PeerId, StatementBatchFor<Ctx>)> {
+		peers
+			.into_iter()
+			.filter_map(|peer| self.push(peer, group_id.clone(), statement.clone()))
+			.collect()
+	}
+
+	/// Take all batches which aren't empty.
+	pub fn flush(&mut self) -> Vec<(PeerId, StatementBatchFor<Ctx>)> {
+		self.pending
+			.drain()
+			.map(|(peer, statements)| (peer, StatementBatch { statements }))
+			.collect()
+	}
+
+	/// Drop the statements queued for a peer, e.g. because it disconnected.
+	pub fn remove_peer(&mut self, peer: &PeerId) {
+		self.pending.remove(peer);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{seconded, valid},
+		test_utils::TestContext,
+	};
+
+	#[test]
+	fn batches_are_bounded_and_flushed() {
+		let mut batcher = Batcher::<TestContext, u32>::new(2);
+
+		assert_eq!(batcher.push_to_all([10, 11], &0, &seconded(1)), Vec::new());
+		let full = batcher.push(10, 0, valid(2));
+		assert_eq!(
+			full,
+			Some((10, StatementBatch { statements: vec![(0, seconded(1)), (0, valid(2))] }))
+		);
+
+		batcher.push(12, 0, valid(2));
+		batcher.remove_peer(&12);
+		assert_eq!(
+			batcher.flush(),
+			vec![(11, StatementBatch { statements: vec![(0, seconded(1))] })]
+		);
+		assert_eq!(batcher.flush(), Vec::new());
+
+		let batch = StatementBatch { statements: vec![(0, seconded(1))] };
+		let encoded = batch.encode();
+		assert_eq!(StatementBatchFor::<TestContext>::decode(&mut &encoded[..]), Ok(batch));
+	}
+}
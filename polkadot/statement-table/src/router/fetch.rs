@@ -0,0 +1,368 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fetching candidates by digest.
+//!
+//! Validity votes can arrive before the candidate they refer to, which the table can't import.
+//! Such votes are buffered by a [`CandidateFetcher`], which requests the candidate from the
+//! peers that sent votes referencing it. Once the candidate arrives it is imported into the
+//! table, followed by the buffered votes.
+//!
+//! Peers could claim any group for a candidate they don't send, so the group of a fetched
+//! candidate is taken from the [`GroupAssignment`] of the context instead.
+
+use std::{
+	collections::{HashMap, VecDeque},
+	hash::Hash,
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::generic::{
+	Context, GroupAssignment, SignedStatement, SignedStatementFor, Statement, Summary, Table,
+};
+
+/// Request for the candidate with the given digest.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct CandidateRequest<Digest> {
+	/// The digest of the requested candidate.
+	pub digest: Digest,
+}
+
+/// Response to a [`CandidateRequest`]: a statement seconding the candidate, if known.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct CandidateResponse<Candidate, Digest, AuthorityId, Signature> {
+	/// A statement seconding the requested candidate.
+	pub seconded: Option<SignedStatement<Candidate, Digest, AuthorityId, Signature>>,
+}
+
+/// Type alias for a candidate response corresponding to context type.
+pub type CandidateResponseFor<Ctx> = CandidateResponse<
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// Answer a [`CandidateRequest`] out of the local table.
+pub fn answer_request<Ctx: Context>(
+	table: &Table<Ctx>,
+	request: &CandidateRequest<Ctx::Digest>,
+) -> CandidateResponseFor<Ctx> {
+	CandidateResponse { seconded: table.seconded_statement(&request.digest).map(|(_, s)| s) }
+}
+
+/// A request to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchRequest<PeerId, Digest> {
+	/// The peer to send the request to.
+	pub peer: PeerId,
+	/// The request.
+	pub request: CandidateRequest<Digest>,
+}
+
+/// The outcome of handling a [`CandidateResponse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseOutcome<PeerId, Digest, GroupId> {
+	/// The candidate was imported, followed by the votes buffered for it. Contains the summaries
+	/// of all successful imports. If the table knew the candidate already, only the votes were
+	/// imported.
+	Imported(Vec<Summary<Digest, GroupId>>),
+	/// The response didn't contain the candidate. The request should be retried with another
+	/// peer, if there is one. Otherwise the buffered votes are dropped.
+	Retry(Option<FetchRequest<PeerId, Digest>>),
+	/// The response wasn't requested and was ignored.
+	Unexpected,
+}
+
+/// Type alias for the outcome of handling a response corresponding to context type.
+pub type ResponseOutcomeFor<Ctx, PeerId> =
+	ResponseOutcome<PeerId, <Ctx as Context>::Digest, <Ctx as Context>::GroupId>;
+
+/// Limits of a [`CandidateFetcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchConfig {
+	/// The maximum number of unknown candidates buffering votes. Votes on further unknown
+	/// candidates are dropped.
+	pub max_pending_candidates: usize,
+	/// The maximum number of votes buffered per unknown candidate.
+	pub max_votes_per_candidate: usize,
+}
+
+impl Default for FetchConfig {
+	fn default() -> Self {
+		FetchConfig { max_pending_candidates: 64, max_votes_per_candidate: 32 }
+	}
+}
+
+// An unknown candidate votes were received for.
+struct Pending<Ctx: Context, PeerId> {
+	group_id: Ctx::GroupId,
+	votes: Vec<SignedStatementFor<Ctx>>,
+	// Peers which sent votes on the candidate and weren't asked for it yet.
+	advertisers: VecDeque<PeerId>,
+	// The peer currently asked for the candidate.
+	in_flight: Option<PeerId>,
+}
+
+/// Buffers votes on unknown candidates and fetches the candidates from peers.
+pub struct CandidateFetcher<Ctx: Context, PeerId> {
+	config: FetchConfig,
+	pending: HashMap<Ctx::Digest, Pending<Ctx, PeerId>>,
+}
+
+impl<Ctx: GroupAssignment, PeerId: Eq + Hash + Clone> CandidateFetcher<Ctx, PeerId> {
+	/// Create a new fetcher.
+	pub fn new(config: FetchConfig) -> Self {
+		CandidateFetcher { config, pending: HashMap::new() }
+	}
+
+	/// The number of candidates currently being fetched.
+	pub fn pending_candidates(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Buffer a validity vote on a candidate the table doesn't know, received from `peer`.
+	///
+	/// Returns a request to send if the candidate isn't being fetched already. Votes which are
+	/// not validity votes, on candidates of no known group, or which exceed the limits, are
+	/// dropped.
+	pub fn note_unknown_vote(
+		&mut self,
+		context: &Ctx,
+		peer: PeerId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<FetchRequest<PeerId, Ctx::Digest>> {
+		let digest = match &statement.statement {
+			Statement::Valid(digest) => digest.clone(),
+			Statement::Seconded(_) => return None,
+		};
+		let group_id = context.candidate_group(&digest)?;
+
+		if !self.pending.contains_key(&digest) &&
+			self.pending.len() >= self.config.max_pending_candidates
+		{
+			return None
+		}
+
+		let pending = self.pending.entry(digest.clone()).or_insert_with(|| Pending {
+			group_id,
+			votes: Vec::new(),
+			advertisers: VecDeque::new(),
+			in_flight: None,
+		});
+
+		if pending.votes.len() < self.config.max_votes_per_candidate &&
+			!pending.votes.contains(&statement)
+		{
+			pending.votes.push(statement);
+		}
+
+		if pending.in_flight.as_ref() != Some(&peer) && !pending.advertisers.contains(&peer) {
+			pending.advertisers.push_back(peer);
+		}
+
+		Self::next_request(digest, pending)
+	}
+
+	/// Handle the response of `peer` to a request for the candidate with the given `digest`.
+	/// The signature of the statement in the response must already be checked.
+	pub fn on_response(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: &PeerId,
+		digest: &Ctx::Digest,
+		response: CandidateResponseFor<Ctx>,
+	) -> ResponseOutcomeFor<Ctx, PeerId> {
+		let pending = match self.pending.get_mut(digest) {
+			Some(pending) if pending.in_flight.as_ref() == Some(peer) => pending,
+			_ => return ResponseOutcome::Unexpected,
+		};
+		pending.in_flight = None;
+
+		let seconded = response.seconded.filter(|statement| match &statement.statement {
+			Statement::Seconded(candidate) => &Ctx::candidate_digest(candidate) == digest,
+			Statement::Valid(_) => false,
+		});
+
+		let summary = seconded.and_then(|statement| {
+			table.import_statement(context, pending.group_id.clone(), statement)
+		});
+		// a duplicate of a candidate the table got otherwise in the meantime is as good.
+		if summary.is_none() && !table.contains_candidate(digest) {
+			let retry = Self::next_request(digest.clone(), pending);
+			// with no peer left to ask, the candidate is given up on until votes arrive again.
+			if retry.is_none() {
+				self.pending.remove(digest);
+			}
+			return ResponseOutcome::Retry(retry)
+		}
+
+		let Pending { group_id, votes, .. } =
+			self.pending.remove(digest).expect("checked above; qed");
+		let mut summaries: Vec<_> = summary.into_iter().collect();
+		summaries.extend(
+			votes
+				.into_iter()
+				.filter_map(|vote| table.import_statement(context, group_id.clone(), vote)),
+		);
+
+		ResponseOutcome::Imported(summaries)
+	}
+
+	/// Note that a peer disconnected. Returns the requests to send to other peers instead.
+	/// Candidates no other peer can be asked for are given up on.
+	pub fn peer_disconnected(&mut self, peer: &PeerId) -> Vec<FetchRequest<PeerId, Ctx::Digest>> {
+		let mut requests = Vec::new();
+		self.pending.retain(|digest, pending| {
+			pending.advertisers.retain(|p| p != peer);
+			if pending.in_flight.as_ref() == Some(peer) {
+				pending.in_flight = None;
+				requests.extend(Self::next_request(digest.clone(), pending));
+			}
+			pending.in_flight.is_some()
+		});
+
+		requests
+	}
+
+	/// Stop fetching the candidate with the given digest, e.g. because it was imported
+	/// otherwise, and return the votes buffered for it.
+	pub fn cancel(&mut self, digest: &Ctx::Digest) -> Vec<SignedStatementFor<Ctx>> {
+		self.pending.remove(digest).map(|pending| pending.votes).unwrap_or_default()
+	}
+
+	fn next_request(
+		digest: Ctx::Digest,
+		pending: &mut Pending<Ctx, PeerId>,
+	) -> Option<FetchRequest<PeerId, Ctx::Digest>> {
+		if pending.in_flight.is_some() {
+			return None
+		}
+
+		let peer = pending.advertisers.pop_front()?;
+		pending.in_flight = Some(peer.clone());
+		Some(FetchRequest { peer, request: CandidateRequest { digest } })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, valid, DIGEST},
+		test_utils::{sign, Digest, TestCandidate, TestContext},
+	};
+
+	fn request(peer: u32) -> Option<FetchRequest<u32, Digest>> {
+		Some(FetchRequest { peer, request: CandidateRequest { digest: DIGEST } })
+	}
+
+	#[test]
+	fn buffered_votes_are_imported_with_fetched_candidate() {
+		let (context, mut table) = context_and_table();
+		let mut fetcher = CandidateFetcher::new(FetchConfig::default());
+
+		assert_eq!(fetcher.note_unknown_vote(&context, 10u32, valid(2)), request(10));
+		// already in flight.
+		assert_eq!(fetcher.note_unknown_vote(&context, 11, valid(2)), None);
+
+		// peer 10 doesn't have it, ask peer 11.
+		let outcome = fetcher.on_response(
+			&mut table,
+			&context,
+			&10,
+			&DIGEST,
+			CandidateResponse { seconded: None },
+		);
+		assert_eq!(outcome, ResponseOutcome::Retry(request(11)));
+
+		// peer 10 wasn't asked anymore.
+		let response = CandidateResponse { seconded: Some(seconded(1)) };
+		let outcome = fetcher.on_response(&mut table, &context, &10, &DIGEST, response.clone());
+		assert_eq!(outcome, ResponseOutcome::Unexpected);
+
+		match fetcher.on_response(&mut table, &context, &11, &DIGEST, response) {
+			ResponseOutcome::Imported(summaries) => {
+				assert_eq!(summaries.len(), 2);
+				assert_eq!(summaries[1].validity_votes, 2);
+			},
+			outcome => panic!("unexpected outcome {:?}", outcome),
+		}
+		assert_eq!(fetcher.pending_candidates(), 0);
+
+		// the table can now serve it to others.
+		let response = answer_request(&table, &CandidateRequest { digest: DIGEST });
+		assert_eq!(response.seconded, Some(seconded(1)));
+	}
+
+	#[test]
+	fn disconnected_peers_are_replaced() {
+		let (context, _) = context_and_table();
+		let mut fetcher = CandidateFetcher::<TestContext, u32>::new(FetchConfig::default());
+
+		assert_eq!(fetcher.note_unknown_vote(&context, 10, valid(2)), request(10));
+		assert_eq!(fetcher.note_unknown_vote(&context, 11, valid(2)), None);
+		assert_eq!(fetcher.note_unknown_vote(&context, 12, valid(1)), None);
+		assert_eq!(fetcher.peer_disconnected(&10), vec![request(11).unwrap()]);
+		assert_eq!(fetcher.peer_disconnected(&11), vec![request(12).unwrap()]);
+
+		// with no peer left to ask, the votes are dropped.
+		assert_eq!(fetcher.peer_disconnected(&12), Vec::new());
+		assert_eq!(fetcher.pending_candidates(), 0);
+		assert_eq!(fetcher.cancel(&DIGEST), Vec::new());
+	}
+
+	#[test]
+	fn pending_candidates_are_bounded() {
+		let (context, _) = context_and_table();
+		let config = FetchConfig { max_pending_candidates: 1, max_votes_per_candidate: 1 };
+		let mut fetcher = CandidateFetcher::<TestContext, u32>::new(config);
+
+		assert_eq!(fetcher.note_unknown_vote(&context, 10, valid(2)), request(10));
+		fetcher.note_unknown_vote(&context, 10, valid(1));
+
+		let other = sign(2, Statement::Valid(101), 0);
+		assert_eq!(fetcher.note_unknown_vote(&context, 10, other), None);
+		assert_eq!(fetcher.pending_candidates(), 1);
+		assert_eq!(fetcher.cancel(&DIGEST), vec![valid(2)]);
+	}
+
+	#[test]
+	fn candidates_are_fetched_for_their_assigned_group() {
+		let (context, mut table) = context_and_table();
+		let mut fetcher = CandidateFetcher::new(FetchConfig::default());
+
+		// votes on candidates of no known group are dropped.
+		let unassigned = sign(2, Statement::Valid(TestCandidate::new(5, 100).digest()), 0);
+		assert_eq!(fetcher.note_unknown_vote(&context, 10u32, unassigned), None);
+		assert_eq!(fetcher.pending_candidates(), 0);
+
+		// the candidate arrives otherwise while it is fetched, a duplicate response is as good.
+		assert_eq!(fetcher.note_unknown_vote(&context, 10, valid(2)), request(10));
+		table.import_statement(&context, 0, seconded(1));
+		let response = CandidateResponse { seconded: Some(seconded(1)) };
+		match fetcher.on_response(&mut table, &context, &10, &DIGEST, response) {
+			ResponseOutcome::Imported(summaries) => {
+				assert_eq!(summaries.len(), 1);
+				assert_eq!(summaries[0].validity_votes, 2);
+			},
+			outcome => panic!("unexpected outcome {:?}", outcome),
+		}
+		assert_eq!(fetcher.pending_candidates(), 0);
+	}
+}
@@ -0,0 +1,805 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Gossip of statements between peers.
+//!
+//! A [`StatementGossip`] sits between the network and the [`Table`]: statements received from
+//! peers are imported into the table and forwarded to the peers which don't know them yet.
+//! Like the rest of this crate it doesn't do any I/O itself, it only decides what to send to
+//! whom.
+//!
+//! Validity votes on candidates which aren't known yet are rejected by the table. A router with
+//! a [`CandidateFetcher`] hands them to it, which fetches the candidate and imports them
+//! afterwards.
+//!
+//! Large candidates can be announced by digest and pulled on demand rather than gossiped in
+//! full, see [`announce`].
+//!
+//! Statements can be queued in a bounded [`IngestionQueue`] before being handed to the router.
+//!
+//! Optionally, the statements of each authority imported per round are bounded by a
+//! [`RateLimiter`].
+//!
+//! What peers send affects their reputation, see [`PeerReputation`]. Which peers statements are
+//! sent to is decided by a [`PropagationPolicy`].
+
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	hash::Hash,
+};
+
+use futures::channel::mpsc;
+
+use crate::generic::{
+	Context, GroupAssignment, ImportError, SignedStatementFor, StatementMarker, Summary, Table,
+};
+
+use batch::{BatchImport, BatchImportFor, StatementBatchFor};
+use fetch::{CandidateResponseFor, FetchRequest, ResponseOutcome};
+
+pub mod announce;
+pub mod batch;
+pub mod fetch;
+pub mod queue;
+pub mod rate_limit;
+pub mod reputation;
+pub mod sync;
+pub mod topology;
+
+pub use announce::{AnnounceConfig, Announcement, BodyFetcher};
+pub use batch::{Batcher, StatementBatch};
+pub use fetch::{CandidateFetcher, FetchConfig};
+pub use queue::{IngestionQueue, QueueConfig};
+pub use rate_limit::{RateLimitConfig, RateLimiter};
+pub use reputation::{PeerEvent, PeerReputation, PeerStats, ReputationChange};
+pub use sync::TableSync;
+pub use topology::{Flood, GroupFirst, PropagationPolicy};
+
+/// Identifies a statement independently of its signature.
+///
+/// Two statements with the same fingerprint but different signatures are a double sign, which
+/// the table detects. For the purpose of deciding which peers know a statement they are the
+/// same, but the router still hands the second signature to the table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatementFingerprint<Digest, AuthorityId> {
+	/// The digest of the candidate the statement refers to.
+	pub digest: Digest,
+	/// Whether the statement seconds the candidate, rather than only attesting its validity.
+	pub seconded: bool,
+	/// The authority which issued the statement.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a statement fingerprint corresponding to context type.
+pub type FingerprintFor<Ctx> =
+	StatementFingerprint<<Ctx as Context>::Digest, <Ctx as Context>::AuthorityId>;
+
+impl<Digest, AuthorityId: Clone> StatementFingerprint<Digest, AuthorityId> {
+	/// Get the fingerprint of a signed statement.
+	pub fn of<Ctx>(statement: &SignedStatementFor<Ctx>) -> Self
+	where
+		Ctx: Context<Digest = Digest, AuthorityId = AuthorityId>,
+		Digest: Clone,
+	{
+		StatementFingerprint {
+			digest: statement.digest::<Ctx>(),
+			seconded: statement.statement.is_seconded(),
+			sender: statement.sender.clone(),
+		}
+	}
+}
+
+/// The outcome of importing a statement received from a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GossipImport<PeerId, Digest, GroupId> {
+	/// The summary of importing the statement into the table, see [`Table::import_statement`].
+	pub summary: Option<Summary<Digest, GroupId>>,
+	/// The peers the statement should be forwarded to.
+	pub forward_to: Vec<PeerId>,
+}
+
+/// Type alias for the outcome of importing a statement corresponding to context type.
+pub type GossipImportFor<Ctx, PeerId> =
+	GossipImport<PeerId, <Ctx as Context>::Digest, <Ctx as Context>::GroupId>;
+
+/// Routes statements between the network and the table.
+pub trait StatementGossip<Ctx: Context> {
+	/// The ID of a peer.
+	type PeerId: Hash + Eq + Clone;
+
+	/// Note that a peer connected.
+	fn peer_connected(&mut self, peer: Self::PeerId);
+
+	/// Note that a peer disconnected.
+	fn peer_disconnected(&mut self, peer: &Self::PeerId);
+
+	/// Handle a statement about a candidate of `group_id` received from `peer`. The signature
+	/// of the statement must already be checked.
+	///
+	/// Novel statements are imported into `table`. Returns `None` if the statement was already
+	/// known, or rejected by the table before, in which case it is neither imported nor
+	/// forwarded.
+	fn import_from_peer(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: Self::PeerId,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<GossipImportFor<Ctx, Self::PeerId>>;
+
+	/// Handle a batch of statements received from `peer`. The signatures of the statements must
+	/// already be checked.
+	///
+	/// Every statement is handled as by [`StatementGossip::import_from_peer`], and the
+	/// statements to forward are batched per peer.
+	fn import_batch(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: Self::PeerId,
+		batch: StatementBatchFor<Ctx>,
+	) -> BatchImportFor<Ctx, Self::PeerId> {
+		let mut summaries = Vec::new();
+		let mut forward = HashMap::<_, Vec<_>>::new();
+		for (group_id, statement) in batch.statements {
+			let imported = self.import_from_peer(
+				table,
+				context,
+				peer.clone(),
+				group_id.clone(),
+				statement.clone(),
+			);
+
+			if let Some(GossipImport { summary, forward_to }) = imported {
+				summaries.extend(summary);
+				for to in forward_to {
+					forward.entry(to).or_default().push((group_id.clone(), statement.clone()));
+				}
+			}
+		}
+
+		BatchImport {
+			summaries,
+			forward: forward
+				.into_iter()
+				.map(|(peer, statements)| (peer, StatementBatch { statements }))
+				.collect(),
+		}
+	}
+
+	/// Get the statements in `table` which `peer` isn't known to have, and note that it will.
+	///
+	/// This lets peers which connected late, or missed statements, catch up without a full
+	/// resync. It should be called when a peer connects, and may be called again later.
+	fn catch_up(
+		&mut self,
+		table: &Table<Ctx>,
+		peer: &Self::PeerId,
+	) -> Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>;
+
+	/// Note that `peer` is the given authority. This lets the router prefer peers which need a
+	/// statement most.
+	fn note_peer_authority(&mut self, peer: &Self::PeerId, authority: Ctx::AuthorityId);
+
+	/// Note a statement about a candidate of `group_id` issued by the local authority, and get
+	/// the peers to send it to.
+	fn broadcast_local(
+		&mut self,
+		context: &Ctx,
+		group_id: &Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Vec<Self::PeerId>;
+
+	/// Note that `peer` sent a statement with an invalid signature.
+	fn note_invalid_signature(&mut self, peer: Self::PeerId);
+}
+
+// What is known about a peer.
+struct PeerData<Ctx: Context> {
+	// The authority the peer is, if known.
+	authority: Option<Ctx::AuthorityId>,
+	// Statements the peer knows.
+	known: HashSet<FingerprintFor<Ctx>>,
+	// The point in the table's statements up to which the peer caught up.
+	caught_up: StatementMarker,
+}
+
+impl<Ctx: Context> Default for PeerData<Ctx> {
+	fn default() -> Self {
+		PeerData { authority: None, known: HashSet::new(), caught_up: StatementMarker::default() }
+	}
+}
+
+// The maximum number of statements remembered as rejected by the table.
+const MAX_REJECTED: usize = 1024;
+
+/// The default [`StatementGossip`], which sends statements to peers not known to have them,
+/// as selected by a [`PropagationPolicy`].
+///
+/// A peer is known to have a statement once it sent it to us or we sent it to the peer.
+pub struct GossipRouter<Ctx: Context, PeerId, P = Flood> {
+	// Statements which were imported into the table or issued locally, with their signature.
+	known: HashMap<FingerprintFor<Ctx>, Ctx::Signature>,
+	// Statements the table rejected for good, along with the group they were received for, in
+	// the order they were rejected. Relays of these are dropped without importing them again.
+	rejected: HashSet<(Ctx::GroupId, FingerprintFor<Ctx>)>,
+	rejected_order: VecDeque<(Ctx::GroupId, FingerprintFor<Ctx>)>,
+	peers: HashMap<PeerId, PeerData<Ctx>>,
+	reputation: PeerReputation<PeerId>,
+	rate_limiter: Option<RateLimiter<Ctx::AuthorityId>>,
+	fetcher: Option<CandidateFetcher<Ctx, PeerId>>,
+	// Requests for candidates the fetcher made, until they are taken to be sent.
+	fetch_requests: Vec<FetchRequest<PeerId, Ctx::Digest>>,
+	policy: P,
+}
+
+impl<Ctx: Context, PeerId, P: Default> Default for GossipRouter<Ctx, PeerId, P> {
+	fn default() -> Self {
+		Self::with_policy(P::default())
+	}
+}
+
+impl<Ctx: Context, PeerId> GossipRouter<Ctx, PeerId> {
+	/// Create a new router without any peers, flooding statements.
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl<Ctx: Context, PeerId, P> GossipRouter<Ctx, PeerId, P> {
+	/// Create a new router without any peers, propagating statements according to `policy`.
+	pub fn with_policy(policy: P) -> Self {
+		GossipRouter {
+			known: HashMap::new(),
+			rejected: HashSet::new(),
+			rejected_order: VecDeque::new(),
+			peers: HashMap::new(),
+			reputation: PeerReputation::default(),
+			rate_limiter: None,
+			fetcher: None,
+			fetch_requests: Vec::new(),
+			policy,
+		}
+	}
+}
+
+impl<Ctx: Context, PeerId: Hash + Eq + Clone, P: PropagationPolicy<Ctx>>
+	GossipRouter<Ctx, PeerId, P>
+{
+	/// Limit the number of statements imported per authority per round. Excess statements are
+	/// dropped without being imported or forwarded, unless they conflict with statements of
+	/// their sender in the table.
+	pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+		self.rate_limiter = Some(RateLimiter::new(config));
+		self
+	}
+
+	/// Start a new round of rate limiting. Returns the number of statements dropped per
+	/// authority in the round which ended.
+	pub fn new_round(&mut self) -> HashMap<Ctx::AuthorityId, usize> {
+		self.rate_limiter
+			.as_mut()
+			.map(|limiter| limiter.new_round())
+			.unwrap_or_default()
+	}
+
+	/// The number of statements of `authority` dropped by rate limiting in the current round.
+	pub fn dropped_statements(&self, authority: &Ctx::AuthorityId) -> usize {
+		self.rate_limiter.as_ref().map_or(0, |limiter| limiter.dropped(authority))
+	}
+
+	/// Whether `peer` is known to have the statement with the given fingerprint.
+	pub fn peer_knows(&self, peer: &PeerId, fingerprint: &FingerprintFor<Ctx>) -> bool {
+		self.peers.get(peer).map_or(false, |data| data.known.contains(fingerprint))
+	}
+
+	/// Get a stream of all future reputation changes of peers.
+	pub fn reputation_changes(&mut self) -> mpsc::UnboundedReceiver<(PeerId, ReputationChange)> {
+		self.reputation.changes()
+	}
+
+	/// Get the statistics of a connected peer.
+	pub fn peer_stats(&self, peer: &PeerId) -> Option<&PeerStats> {
+		self.reputation.stats(peer)
+	}
+
+	// Select the peers to send the statement to out of those which don't know it, and note
+	// that they will.
+	fn route(
+		&mut self,
+		context: &Ctx,
+		group_id: &Ctx::GroupId,
+		fingerprint: &FingerprintFor<Ctx>,
+	) -> Vec<PeerId> {
+		let unaware = self
+			.peers
+			.iter()
+			.filter(|(_, data)| !data.known.contains(fingerprint))
+			.map(|(peer, data)| (peer.clone(), data.authority.clone()))
+			.collect::<Vec<_>>();
+
+		let selected = self.policy.select(context, group_id, &unaware);
+		for peer in &selected {
+			if let Some(data) = self.peers.get_mut(peer) {
+				data.known.insert(fingerprint.clone());
+			}
+		}
+
+		selected
+	}
+
+	// Remember that the table rejected the statement with the given fingerprint for good, as
+	// long as there is room.
+	fn note_rejected(&mut self, group_id: Ctx::GroupId, fingerprint: FingerprintFor<Ctx>) {
+		let key = (group_id, fingerprint);
+		if !self.rejected.insert(key.clone()) {
+			return
+		}
+
+		self.rejected_order.push_back(key);
+		if self.rejected_order.len() > MAX_REJECTED {
+			if let Some(oldest) = self.rejected_order.pop_front() {
+				self.rejected.remove(&oldest);
+			}
+		}
+	}
+}
+
+impl<Ctx: GroupAssignment, PeerId: Hash + Eq + Clone, P: PropagationPolicy<Ctx>>
+	GossipRouter<Ctx, PeerId, P>
+{
+	/// Fetch the candidates of validity votes the table doesn't know yet, and import the votes
+	/// once they arrive. The requests to send are taken with
+	/// [`GossipRouter::take_fetch_requests`].
+	pub fn with_fetcher(mut self, config: FetchConfig) -> Self {
+		self.fetcher = Some(CandidateFetcher::new(config));
+		self
+	}
+
+	/// Take the requests for candidates to send to peers.
+	pub fn take_fetch_requests(&mut self) -> Vec<FetchRequest<PeerId, Ctx::Digest>> {
+		std::mem::take(&mut self.fetch_requests)
+	}
+
+	/// Handle the response of `peer` to a request for the candidate with the given `digest`.
+	/// The signature of the statement in the response must already be checked.
+	///
+	/// Returns the summaries of importing the candidate and the votes buffered for it. If the
+	/// response didn't contain the candidate, the request to retry with is queued.
+	pub fn on_candidate_response(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: &PeerId,
+		digest: &Ctx::Digest,
+		response: CandidateResponseFor<Ctx>,
+	) -> Vec<Summary<Ctx::Digest, Ctx::GroupId>> {
+		let Some(fetcher) = self.fetcher.as_mut() else { return Vec::new() };
+		match fetcher.on_response(table, context, peer, digest, response) {
+			ResponseOutcome::Imported(summaries) => summaries,
+			ResponseOutcome::Retry(retry) => {
+				self.fetch_requests.extend(retry);
+				Vec::new()
+			},
+			ResponseOutcome::Unexpected => Vec::new(),
+		}
+	}
+
+	// Import the votes buffered by the fetcher for a candidate the table got otherwise.
+	fn import_buffered(&mut self, table: &mut Table<Ctx>, context: &Ctx, digest: &Ctx::Digest) {
+		let Some(fetcher) = self.fetcher.as_mut() else { return };
+		let Some(group_id) = context.candidate_group(digest) else { return };
+		for vote in fetcher.cancel(digest) {
+			table.import_statement(context, group_id.clone(), vote);
+		}
+	}
+}
+
+impl<Ctx, PeerId, P> StatementGossip<Ctx> for GossipRouter<Ctx, PeerId, P>
+where
+	Ctx: GroupAssignment,
+	PeerId: Hash + Eq + Clone,
+	P: PropagationPolicy<Ctx>,
+{
+	type PeerId = PeerId;
+
+	fn peer_connected(&mut self, peer: PeerId) {
+		self.peers.entry(peer).or_default();
+	}
+
+	fn peer_disconnected(&mut self, peer: &PeerId) {
+		self.peers.remove(peer);
+		self.reputation.remove(peer);
+		if let Some(fetcher) = self.fetcher.as_mut() {
+			self.fetch_requests.extend(fetcher.peer_disconnected(peer));
+		}
+	}
+
+	fn import_from_peer(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: PeerId,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<GossipImportFor<Ctx, PeerId>> {
+		let fingerprint = StatementFingerprint::of::<Ctx>(&statement);
+		let duplicate = self
+			.peers
+			.get_mut(&peer)
+			.map_or(false, |data| !data.known.insert(fingerprint.clone()));
+
+		if !context.is_member_of(&statement.sender, &group_id) {
+			self.reputation.note(peer.clone(), PeerEvent::Unauthorized);
+		} else if duplicate {
+			self.reputation.note(peer.clone(), PeerEvent::Duplicate);
+		}
+
+		// a second signature on a known statement is a double sign, which is left to the table.
+		if self.known.get(&fingerprint) == Some(&statement.signature) ||
+			self.rejected.contains(&(group_id.clone(), fingerprint.clone()))
+		{
+			return None
+		}
+
+		// statements which may reveal misbehavior are left to the table regardless of the limit.
+		if let Some(limiter) = self.rate_limiter.as_mut() {
+			if !table.may_conflict(&group_id, &statement) && !limiter.admit(&statement.sender) {
+				return Some(GossipImport { summary: None, forward_to: Vec::new() })
+			}
+		}
+
+		// statements which the table doesn't accept yet, e.g. validity votes on unknown
+		// candidates, are not remembered so that they can be imported once they are acceptable.
+		let (summary, forward_to) =
+			match table.try_import_statement_ref(context, group_id.clone(), &statement) {
+				Ok(summary) => {
+					self.reputation.note(peer, PeerEvent::Novel);
+					self.known.entry(fingerprint.clone()).or_insert(statement.signature);
+					if fingerprint.seconded {
+						self.import_buffered(table, context, &fingerprint.digest);
+					}
+					(Some(summary), self.route(context, &group_id, &fingerprint))
+				},
+				Err(ImportError::UnknownCandidate) => {
+					if let Some(fetcher) = self.fetcher.as_mut() {
+						self.fetch_requests
+							.extend(fetcher.note_unknown_vote(context, peer, statement));
+					}
+					(None, Vec::new())
+				},
+				Err(ImportError::Duplicate) => {
+					self.known.entry(fingerprint).or_insert(statement.signature);
+					(None, Vec::new())
+				},
+				Err(ImportError::UnknownSigner | ImportError::Misbehavior(_)) => {
+					self.note_rejected(group_id, fingerprint);
+					(None, Vec::new())
+				},
+				Err(_) => (None, Vec::new()),
+			};
+
+		Some(GossipImport { summary, forward_to })
+	}
+
+	fn catch_up(
+		&mut self,
+		table: &Table<Ctx>,
+		peer: &PeerId,
+	) -> Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)> {
+		let data = match self.peers.get_mut(peer) {
+			Some(data) => data,
+			None => return Vec::new(),
+		};
+
+		let (statements, marker) = table.statements_since(data.caught_up);
+		data.caught_up = marker;
+		statements
+			.into_iter()
+			.filter(|(_, statement)| data.known.insert(StatementFingerprint::of::<Ctx>(statement)))
+			.collect()
+	}
+
+	fn note_peer_authority(&mut self, peer: &PeerId, authority: Ctx::AuthorityId) {
+		if let Some(data) = self.peers.get_mut(peer) {
+			data.authority = Some(authority);
+		}
+	}
+
+	fn broadcast_local(
+		&mut self,
+		context: &Ctx,
+		group_id: &Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Vec<PeerId> {
+		let fingerprint = StatementFingerprint::of::<Ctx>(statement);
+		self.known
+			.entry(fingerprint.clone())
+			.or_insert_with(|| statement.signature.clone());
+		self.route(context, group_id, &fingerprint)
+	}
+
+	fn note_invalid_signature(&mut self, peer: PeerId) {
+		self.reputation.note(peer, PeerEvent::InvalidSignature);
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::{
+		generic::{Config, Statement},
+		test_utils::{sign, Digest, TestCandidate, TestContext},
+	};
+
+	// Two authorities in group 0.
+	pub(crate) fn context_and_table() -> (TestContext, Table<TestContext>) {
+		let context = TestContext::builder().with_group(0, [1, 2]).build();
+		(context, Table::new(Config { allow_multiple_seconded: false }))
+	}
+
+	// the digest of the candidate of the fixtures.
+	pub(crate) const DIGEST: Digest = 100;
+
+	pub(crate) fn seconded(sender: u32) -> SignedStatementFor<TestContext> {
+		sign(sender, Statement::Seconded(TestCandidate::new(0, 100)), 0)
+	}
+
+	// seconds another candidate of group 0, which conflicts with `seconded`.
+	pub(crate) fn seconded_other(sender: u32) -> SignedStatementFor<TestContext> {
+		sign(sender, Statement::Seconded(TestCandidate::new(0, 101)), 0)
+	}
+
+	pub(crate) fn valid(sender: u32) -> SignedStatementFor<TestContext> {
+		sign(sender, Statement::Valid(DIGEST), 0)
+	}
+
+	// the statement signed again, with another signature.
+	pub(crate) fn double_signed(
+		statement: SignedStatementFor<TestContext>,
+	) -> SignedStatementFor<TestContext> {
+		sign(statement.sender, statement.statement, statement.signature.nonce.wrapping_add(1))
+	}
+
+	fn sorted(mut peers: Vec<u32>) -> Vec<u32> {
+		peers.sort();
+		peers
+	}
+
+	#[test]
+	fn novel_statements_are_imported_and_forwarded() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		for peer in [10u32, 11, 12] {
+			router.peer_connected(peer);
+		}
+
+		let imported = router
+			.import_from_peer(&mut table, &context, 10, 0, seconded(1))
+			.expect("statement is novel");
+		assert_eq!(imported.summary.unwrap().validity_votes, 1);
+		assert_eq!(sorted(imported.forward_to), vec![11, 12]);
+
+		// peers relaying it back don't cause it to be imported or forwarded again.
+		assert_eq!(router.import_from_peer(&mut table, &context, 11, 0, seconded(1)), None);
+		assert!(router.peer_knows(&11, &StatementFingerprint::of::<TestContext>(&seconded(1))));
+
+		// a new peer still gets it when something else is sent.
+		router.peer_connected(13);
+		assert_eq!(sorted(router.broadcast_local(&context, &0, &valid(2))), vec![10, 11, 12, 13]);
+		assert_eq!(router.broadcast_local(&context, &0, &valid(2)), Vec::<u32>::new());
+
+		router.peer_disconnected(&13);
+		assert!(!router.peer_knows(&13, &StatementFingerprint::of::<TestContext>(&valid(2))));
+	}
+
+	#[test]
+	fn statements_rejected_by_table_are_not_remembered() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		for peer in [10u32, 11, 12] {
+			router.peer_connected(peer);
+		}
+
+		// validity vote on a candidate not known yet.
+		let imported = router.import_from_peer(&mut table, &context, 10, 0, valid(2));
+		assert_eq!(imported, Some(GossipImport { summary: None, forward_to: Vec::new() }));
+
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		let imported = router
+			.import_from_peer(&mut table, &context, 11, 0, valid(2))
+			.expect("statement wasn't imported before");
+		assert_eq!(imported.summary.unwrap().validity_votes, 2);
+		// peer 10 sent it before.
+		assert_eq!(imported.forward_to, vec![12]);
+	}
+
+	#[test]
+	fn candidates_of_unknown_votes_are_fetched() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new().with_fetcher(FetchConfig::default());
+		router.peer_connected(10u32);
+		router.peer_connected(11);
+
+		// the candidate is fetched from the peers which sent votes on it.
+		router.import_from_peer(&mut table, &context, 10, 0, valid(2));
+		router.import_from_peer(&mut table, &context, 11, 0, valid(2));
+		let requests = router.take_fetch_requests();
+		assert_eq!(requests.len(), 1);
+		assert_eq!((requests[0].peer, requests[0].request.digest), (10, DIGEST));
+
+		// peer 10 doesn't have it, peer 11 is asked next.
+		let response = fetch::CandidateResponse { seconded: None };
+		assert!(router
+			.on_candidate_response(&mut table, &context, &10, &DIGEST, response)
+			.is_empty());
+		let requests = router.take_fetch_requests();
+		assert_eq!(requests.iter().map(|r| r.peer).collect::<Vec<_>>(), vec![11]);
+
+		let response = fetch::CandidateResponse { seconded: Some(seconded(1)) };
+		let summaries = router.on_candidate_response(&mut table, &context, &11, &DIGEST, response);
+		assert_eq!(summaries.last().unwrap().validity_votes, 2);
+
+		// votes buffered until the candidate is gossiped are imported along with it.
+		let (_, mut table) = context_and_table();
+		let mut router = GossipRouter::new().with_fetcher(FetchConfig::default());
+		router.import_from_peer(&mut table, &context, 10, 0, valid(2));
+		router.import_from_peer(&mut table, &context, 11, 0, seconded(1));
+		let status = table.candidate_status(&DIGEST, &context, 2).unwrap();
+		assert_eq!(status.validity_votes, 2);
+	}
+
+	#[test]
+	fn double_signs_reach_the_table() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		router.peer_connected(10u32);
+		router.peer_connected(11);
+
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		let double_signed = double_signed(seconded(1));
+		let imported = router.import_from_peer(&mut table, &context, 11, 0, double_signed.clone());
+		assert_eq!(imported, Some(GossipImport { summary: None, forward_to: Vec::new() }));
+		assert_eq!(table.get_misbehavior()[&1].len(), 1);
+
+		// relays of the rejected statement aren't imported again.
+		assert_eq!(router.import_from_peer(&mut table, &context, 10, 0, double_signed), None);
+		assert_eq!(table.get_misbehavior()[&1].len(), 1);
+	}
+
+	#[test]
+	fn statements_exceeding_rate_limit_are_dropped() {
+		let (context, mut table) = context_and_table();
+		let config = RateLimitConfig { max_statements_per_round: 1, ..Default::default() };
+		let mut router = GossipRouter::new().with_rate_limit(config);
+		router.peer_connected(10u32);
+		router.peer_connected(11);
+
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		let other = sign(1, Statement::Seconded(TestCandidate::new(1, 100)), 0);
+		let imported = router.import_from_peer(&mut table, &context, 10, 1, other);
+		assert_eq!(imported, Some(GossipImport { summary: None, forward_to: Vec::new() }));
+		assert_eq!(router.dropped_statements(&1), 1);
+
+		// statements revealing misbehavior reach the table regardless of the limit.
+		router.import_from_peer(&mut table, &context, 10, 0, seconded_other(1));
+		router.import_from_peer(&mut table, &context, 10, 0, double_signed(seconded(1)));
+		assert_eq!(table.get_misbehavior()[&1].len(), 2);
+		assert_eq!(router.dropped_statements(&1), 1);
+
+		// other authorities aren't affected.
+		let imported = router.import_from_peer(&mut table, &context, 10, 0, valid(2));
+		assert_eq!(imported.unwrap().forward_to, vec![11]);
+
+		assert_eq!(router.new_round(), [(1, 1)].into_iter().collect());
+		assert_eq!(router.dropped_statements(&1), 0);
+	}
+
+	#[test]
+	fn peer_behavior_affects_reputation() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		let mut changes = router.reputation_changes();
+		router.peer_connected(10u32);
+
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		router.note_invalid_signature(10);
+
+		// authority 1 isn't a member of group 1.
+		router.import_from_peer(&mut table, &context, 10, 1, valid(1));
+
+		assert_eq!(
+			router.peer_stats(&10),
+			Some(&PeerStats { novel: 1, duplicates: 1, unauthorized: 1, invalid_signatures: 1 })
+		);
+
+		let mut received = Vec::new();
+		while let Ok(Some((peer, change))) = changes.try_next() {
+			assert_eq!(peer, 10);
+			received.push(change);
+		}
+		assert_eq!(
+			received,
+			vec![
+				reputation::BENEFIT_NOVEL_STATEMENT,
+				reputation::COST_DUPLICATE_STATEMENT,
+				reputation::COST_INVALID_SIGNATURE,
+				reputation::COST_UNAUTHORIZED_STATEMENT,
+			]
+		);
+
+		router.peer_disconnected(&10);
+		assert_eq!(router.peer_stats(&10), None);
+	}
+
+	#[test]
+	fn propagation_follows_policy() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::with_policy(GroupFirst { max_others: 0 });
+		for peer in [10u32, 11, 12] {
+			router.peer_connected(peer);
+		}
+		router.note_peer_authority(&11, 2);
+
+		let imported = router
+			.import_from_peer(&mut table, &context, 10, 0, seconded(1))
+			.expect("statement is novel");
+		assert_eq!(imported.forward_to, vec![11]);
+
+		// peer 12 didn't get it and may still be sent it later.
+		let fingerprint = StatementFingerprint::of::<TestContext>(&seconded(1));
+		assert!(!router.peer_knows(&12, &fingerprint));
+	}
+
+	#[test]
+	fn batches_are_imported_and_forwarded_in_batches() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		for peer in [10u32, 11] {
+			router.peer_connected(peer);
+		}
+
+		let batch =
+			StatementBatch { statements: vec![(0, seconded(1)), (0, valid(2)), (0, seconded(1))] };
+		let imported = router.import_batch(&mut table, &context, 10, batch);
+
+		assert_eq!(imported.summaries.len(), 2);
+		assert_eq!(
+			imported.forward,
+			vec![(11, StatementBatch { statements: vec![(0, seconded(1)), (0, valid(2))] })]
+		);
+	}
+
+	#[test]
+	fn late_peers_catch_up() {
+		let (context, mut table) = context_and_table();
+		let mut router = GossipRouter::new();
+		router.peer_connected(10u32);
+
+		router.import_from_peer(&mut table, &context, 10, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+
+		router.peer_connected(11);
+		assert_eq!(router.catch_up(&table, &11), vec![(0, seconded(1)), (0, valid(2))]);
+		assert_eq!(router.catch_up(&table, &11), Vec::new());
+
+		// peer 10 sent the seconded statement itself.
+		assert_eq!(router.catch_up(&table, &10), vec![(0, valid(2))]);
+		assert_eq!(router.catch_up(&table, &12), Vec::new());
+	}
+}
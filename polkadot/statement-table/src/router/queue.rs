@@ -0,0 +1,254 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A bounded queue of statements waiting to be imported.
+//!
+//! Statements received from the network are queued before being handed to the router, so that
+//! a flood of statements can't exhaust memory. When the queue is full, statements which help
+//! a candidate become includable are preferred. The depth of the queue is exposed so that the
+//! networking layer can slow peers down before statements get dropped.
+
+use std::{
+	cmp::{Ordering, Reverse},
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+};
+
+use crate::generic::{Context, SignedStatementFor, Statement, Table};
+
+/// The priority of a queued statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+	/// A validity vote on a candidate which isn't known yet.
+	Low,
+	/// A seconded statement, or a validity vote on a candidate far from being includable.
+	Normal,
+	/// A validity vote on a candidate close to being includable.
+	High,
+}
+
+/// Configuration of an [`IngestionQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueConfig {
+	/// The maximum number of queued statements.
+	pub capacity: usize,
+	/// The queue depth from which it is considered congested.
+	pub congestion_threshold: usize,
+	/// Validity votes on candidates missing at most this many votes get [`Priority::High`].
+	pub nearly_includable_within: usize,
+	/// The minimum number of backing votes, see [`Table::candidate_status`].
+	pub minimum_backing_votes: u32,
+}
+
+impl Default for QueueConfig {
+	fn default() -> Self {
+		QueueConfig {
+			capacity: 4096,
+			congestion_threshold: 3072,
+			nearly_includable_within: 1,
+			minimum_backing_votes: 2,
+		}
+	}
+}
+
+/// The outcome of pushing a statement to an [`IngestionQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushOutcome<PeerId> {
+	/// The statement was queued.
+	Queued,
+	/// The statement was queued, dropping a less important one sent by the given peer.
+	QueuedEvicting(PeerId),
+	/// The queue is full of more important statements and the statement was dropped.
+	Dropped,
+}
+
+struct Queued<Ctx: Context, PeerId> {
+	peer: PeerId,
+	group_id: Ctx::GroupId,
+	statement: SignedStatementFor<Ctx>,
+}
+
+/// A bounded priority queue of statements received from peers.
+///
+/// Statements of the same priority are dequeued in the order they were queued.
+pub struct IngestionQueue<Ctx: Context, PeerId> {
+	config: QueueConfig,
+	// Keyed by priority, then by age, so that the last entry is the next to dequeue and the
+	// first entry the next to evict.
+	entries: BTreeMap<(Priority, Reverse<u64>), Queued<Ctx, PeerId>>,
+	per_peer: HashMap<PeerId, usize>,
+	next_seq: u64,
+}
+
+impl<Ctx: Context, PeerId: Hash + Eq + Clone> IngestionQueue<Ctx, PeerId> {
+	/// Create a new, empty queue.
+	pub fn new(config: QueueConfig) -> Self {
+		IngestionQueue { config, entries: BTreeMap::new(), per_peer: HashMap::new(), next_seq: 0 }
+	}
+
+	/// The number of queued statements.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Whether no statements are queued.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// The number of queued statements sent by `peer`.
+	pub fn peer_depth(&self, peer: &PeerId) -> usize {
+		self.per_peer.get(peer).copied().unwrap_or(0)
+	}
+
+	/// Whether the queue is congested, in which case peers should be slowed down.
+	pub fn is_congested(&self) -> bool {
+		self.len() >= self.config.congestion_threshold
+	}
+
+	/// Get the priority of a statement, given the current state of the table.
+	pub fn priority(
+		&self,
+		table: &Table<Ctx>,
+		context: &Ctx,
+		statement: &SignedStatementFor<Ctx>,
+	) -> Priority {
+		let digest = match &statement.statement {
+			Statement::Seconded(_) => return Priority::Normal,
+			Statement::Valid(digest) => digest,
+		};
+
+		match table.candidate_status(digest, context, self.config.minimum_backing_votes) {
+			None => Priority::Low,
+			Some(status)
+				if status.missing_validity_votes() <= self.config.nearly_includable_within =>
+				Priority::High,
+			Some(_) => Priority::Normal,
+		}
+	}
+
+	/// Queue a statement received from `peer`. Its priority is determined by the current state
+	/// of `table`.
+	pub fn push(
+		&mut self,
+		table: &Table<Ctx>,
+		context: &Ctx,
+		peer: PeerId,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> PushOutcome<PeerId> {
+		let priority = self.priority(table, context, &statement);
+		let mut outcome = PushOutcome::Queued;
+
+		if self.entries.len() >= self.config.capacity {
+			let lowest = match self.entries.keys().next() {
+				Some(lowest) => *lowest,
+				None => return PushOutcome::Dropped,
+			};
+
+			if lowest.0.cmp(&priority) != Ordering::Less {
+				return PushOutcome::Dropped
+			}
+
+			if let Some(evicted) = self.entries.remove(&lowest) {
+				self.forget(&evicted.peer);
+				outcome = PushOutcome::QueuedEvicting(evicted.peer);
+			}
+		}
+
+		*self.per_peer.entry(peer.clone()).or_insert(0) += 1;
+		self.entries
+			.insert((priority, Reverse(self.next_seq)), Queued { peer, group_id, statement });
+		self.next_seq += 1;
+		outcome
+	}
+
+	/// Take the most important statement, along with the peer which sent it and the group of
+	/// the candidate it refers to.
+	pub fn pop(&mut self) -> Option<(PeerId, Ctx::GroupId, SignedStatementFor<Ctx>)> {
+		let key = *self.entries.keys().next_back()?;
+		let Queued { peer, group_id, statement } = self.entries.remove(&key)?;
+		self.forget(&peer);
+		Some((peer, group_id, statement))
+	}
+
+	fn forget(&mut self, peer: &PeerId) {
+		if let Some(depth) = self.per_peer.get_mut(peer) {
+			*depth -= 1;
+			if *depth == 0 {
+				self.per_peer.remove(peer);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, valid},
+		test_utils::{sign, TestCandidate},
+	};
+
+	fn config(capacity: usize) -> QueueConfig {
+		QueueConfig { capacity, congestion_threshold: 3, ..Default::default() }
+	}
+
+	#[test]
+	fn nearly_includable_candidates_come_first() {
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+
+		let mut queue = IngestionQueue::new(config(10));
+		let unknown = sign(2, Statement::Valid(TestCandidate::new(5, 5).digest()), 0);
+
+		assert_eq!(queue.priority(&table, &context, &unknown), Priority::Low);
+		assert_eq!(queue.priority(&table, &context, &seconded(2)), Priority::Normal);
+		assert_eq!(queue.priority(&table, &context, &valid(2)), Priority::High);
+
+		queue.push(&table, &context, 10u32, 0, unknown.clone());
+		queue.push(&table, &context, 10, 0, seconded(2));
+		assert!(!queue.is_congested());
+		queue.push(&table, &context, 11, 0, valid(2));
+		assert!(queue.is_congested());
+		assert_eq!(queue.peer_depth(&10), 2);
+
+		assert_eq!(queue.pop(), Some((11, 0, valid(2))));
+		assert_eq!(queue.pop(), Some((10, 0, seconded(2))));
+		assert_eq!(queue.pop(), Some((10, 0, unknown)));
+		assert_eq!(queue.pop(), None);
+		assert_eq!(queue.peer_depth(&10), 0);
+	}
+
+	#[test]
+	fn full_queue_evicts_less_important_statements() {
+		let (context, mut table) = context_and_table();
+		let mut queue = IngestionQueue::new(config(1));
+
+		// unknown candidate.
+		assert_eq!(queue.push(&table, &context, 10u32, 0, valid(2)), PushOutcome::Queued);
+		assert_eq!(
+			queue.push(&table, &context, 11, 0, seconded(1)),
+			PushOutcome::QueuedEvicting(10)
+		);
+		assert_eq!(queue.push(&table, &context, 12, 0, seconded(2)), PushOutcome::Dropped);
+
+		table.import_statement(&context, 0, seconded(1));
+		assert_eq!(queue.push(&table, &context, 12, 0, valid(2)), PushOutcome::QueuedEvicting(11));
+		assert_eq!(queue.len(), 1);
+		assert_eq!(queue.peer_depth(&10), 0);
+	}
+}
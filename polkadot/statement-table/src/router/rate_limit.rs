@@ -0,0 +1,116 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rate limiting of statements per authority.
+//!
+//! An honest authority only issues a handful of statements per round, one per candidate of its
+//! group. A compromised one may issue as many as it likes, all validly signed. A
+//! [`RateLimiter`] bounds how many statements of each authority are imported per round, and
+//! counts the ones it drops.
+//!
+//! Dropping statements must not hide misbehavior: statements which conflict with statements of
+//! their sender the table already has are imported regardless of the limit, see
+//! [`Table::may_conflict`](crate::Table::may_conflict).
+
+use std::{collections::HashMap, hash::Hash};
+
+/// Limits of a [`RateLimiter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitConfig {
+	/// The maximum number of statements imported per authority per round.
+	pub max_statements_per_round: usize,
+	/// The maximum number of authorities counted per round. Statements of further authorities
+	/// are dropped without being counted.
+	pub max_authorities: usize,
+}
+
+impl Default for RateLimitConfig {
+	fn default() -> Self {
+		RateLimitConfig { max_statements_per_round: 64, max_authorities: 1024 }
+	}
+}
+
+/// Counts the statements of each authority in the current round.
+pub struct RateLimiter<AuthorityId> {
+	config: RateLimitConfig,
+	admitted: HashMap<AuthorityId, usize>,
+	dropped: HashMap<AuthorityId, usize>,
+}
+
+impl<AuthorityId: Hash + Eq + Clone> RateLimiter<AuthorityId> {
+	/// Create a new rate limiter.
+	pub fn new(config: RateLimitConfig) -> Self {
+		RateLimiter { config, admitted: HashMap::new(), dropped: HashMap::new() }
+	}
+
+	/// Note a statement issued by `authority`. Returns whether it is within the limit and
+	/// should be imported; otherwise it is counted as dropped.
+	pub fn admit(&mut self, authority: &AuthorityId) -> bool {
+		if !self.admitted.contains_key(authority) &&
+			self.admitted.len() >= self.config.max_authorities
+		{
+			return false
+		}
+
+		let admitted = self.admitted.entry(authority.clone()).or_default();
+		if *admitted < self.config.max_statements_per_round {
+			*admitted += 1;
+			true
+		} else {
+			*self.dropped.entry(authority.clone()).or_default() += 1;
+			false
+		}
+	}
+
+	/// The number of statements of `authority` dropped in the current round.
+	pub fn dropped(&self, authority: &AuthorityId) -> usize {
+		self.dropped.get(authority).copied().unwrap_or_default()
+	}
+
+	/// Start a new round, resetting all limits. Returns the number of statements dropped per
+	/// authority in the round which ended.
+	pub fn new_round(&mut self) -> HashMap<AuthorityId, usize> {
+		self.admitted.clear();
+		std::mem::take(&mut self.dropped)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn excess_statements_are_dropped_until_next_round() {
+		let config = RateLimitConfig { max_statements_per_round: 2, max_authorities: 2 };
+		let mut limiter = RateLimiter::new(config);
+
+		assert!(limiter.admit(&1u32));
+		assert!(limiter.admit(&1));
+		assert!(!limiter.admit(&1));
+		assert!(!limiter.admit(&1));
+		assert!(limiter.admit(&2));
+		assert_eq!(limiter.dropped(&1), 2);
+		assert_eq!(limiter.dropped(&2), 0);
+
+		// further authorities aren't counted.
+		assert!(!limiter.admit(&3));
+		assert_eq!(limiter.dropped(&3), 0);
+
+		assert_eq!(limiter.new_round(), [(1, 2)].into_iter().collect());
+		assert_eq!(limiter.dropped(&1), 0);
+		assert!(limiter.admit(&1));
+	}
+}
@@ -0,0 +1,135 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reputation of peers, based on the statements they send.
+//!
+//! The router notes what each peer sends and emits [`ReputationChange`]s, which the networking
+//! layer can use to deprioritize or disconnect spammy peers.
+
+use std::{collections::HashMap, hash::Hash};
+
+use futures::channel::mpsc;
+
+/// A change of a peer's reputation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReputationChange {
+	/// The value of the change. Negative values are costs, positive ones benefits.
+	pub value: i32,
+	/// The reason of the change.
+	pub reason: &'static str,
+}
+
+impl ReputationChange {
+	/// Create a new reputation change.
+	pub const fn new(value: i32, reason: &'static str) -> Self {
+		ReputationChange { value, reason }
+	}
+}
+
+/// Cost of sending a statement with an invalid signature.
+pub const COST_INVALID_SIGNATURE: ReputationChange =
+	ReputationChange::new(-500, "Statement with invalid signature");
+/// Cost of sending a statement from an authority outside of the candidate's group.
+pub const COST_UNAUTHORIZED_STATEMENT: ReputationChange =
+	ReputationChange::new(-300, "Unauthorized statement");
+/// Cost of sending a statement the peer is known to have already.
+pub const COST_DUPLICATE_STATEMENT: ReputationChange =
+	ReputationChange::new(-20, "Duplicate statement");
+/// Benefit of being the first to send a statement.
+pub const BENEFIT_NOVEL_STATEMENT: ReputationChange = ReputationChange::new(10, "Novel statement");
+
+/// Statistics about the statements a peer sent.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PeerStats {
+	/// Statements which were new to us.
+	pub novel: u64,
+	/// Statements the peer was known to have already.
+	pub duplicates: u64,
+	/// Statements from authorities outside of the candidate's group.
+	pub unauthorized: u64,
+	/// Statements with invalid signatures.
+	pub invalid_signatures: u64,
+}
+
+/// Something a peer did which affects its reputation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerEvent {
+	/// Sent a statement which was new to us.
+	Novel,
+	/// Sent a statement it was known to have already.
+	Duplicate,
+	/// Sent a statement from an authority outside of the candidate's group.
+	Unauthorized,
+	/// Sent a statement with an invalid signature.
+	InvalidSignature,
+}
+
+impl PeerEvent {
+	/// The reputation change caused by the event.
+	pub fn reputation_change(&self) -> ReputationChange {
+		match self {
+			PeerEvent::Novel => BENEFIT_NOVEL_STATEMENT,
+			PeerEvent::Duplicate => COST_DUPLICATE_STATEMENT,
+			PeerEvent::Unauthorized => COST_UNAUTHORIZED_STATEMENT,
+			PeerEvent::InvalidSignature => COST_INVALID_SIGNATURE,
+		}
+	}
+}
+
+/// Tracks peer statistics and publishes reputation changes.
+pub struct PeerReputation<PeerId> {
+	stats: HashMap<PeerId, PeerStats>,
+	subscribers: Vec<mpsc::UnboundedSender<(PeerId, ReputationChange)>>,
+}
+
+impl<PeerId> Default for PeerReputation<PeerId> {
+	fn default() -> Self {
+		PeerReputation { stats: HashMap::new(), subscribers: Vec::new() }
+	}
+}
+
+impl<PeerId: Hash + Eq + Clone> PeerReputation<PeerId> {
+	/// Get a stream of all future reputation changes.
+	pub fn changes(&mut self) -> mpsc::UnboundedReceiver<(PeerId, ReputationChange)> {
+		let (tx, rx) = mpsc::unbounded();
+		self.subscribers.push(tx);
+		rx
+	}
+
+	/// Get the statistics of a peer.
+	pub fn stats(&self, peer: &PeerId) -> Option<&PeerStats> {
+		self.stats.get(peer)
+	}
+
+	/// Note an event caused by a peer.
+	pub fn note(&mut self, peer: PeerId, event: PeerEvent) {
+		let stats = self.stats.entry(peer.clone()).or_default();
+		match event {
+			PeerEvent::Novel => stats.novel += 1,
+			PeerEvent::Duplicate => stats.duplicates += 1,
+			PeerEvent::Unauthorized => stats.unauthorized += 1,
+			PeerEvent::InvalidSignature => stats.invalid_signatures += 1,
+		}
+
+		let change = event.reputation_change();
+		self.subscribers.retain(|tx| tx.unbounded_send((peer.clone(), change)).is_ok());
+	}
+
+	/// Forget the statistics of a peer.
+	pub fn remove(&mut self, peer: &PeerId) {
+		self.stats.remove(peer);
+	}
+}
@@ -0,0 +1,329 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Catching up with the table of a peer.
+//!
+//! A node joining mid-round would otherwise only learn the statements of the round as gossip
+//! trickles in. Instead, it sends a [`SyncRequest`] to a peer, which answers with at most a
+//! requested number of statements of its table for a relay parent since a [`StatementMarker`],
+//! or from the beginning, through [`answer_sync_request`]. A [`TableSync`] checks the signatures
+//! of the statements in the response before importing them, and remembers the marker of every
+//! peer per relay parent, so that a later request only returns the statements the peer imported
+//! since. Requests which aren't answered in time can be sent again.
+
+use std::{
+	collections::HashMap,
+	hash::Hash,
+	time::{Duration, Instant},
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	generic::{Context, SignedStatementFor, StatementMarker, Summary, Table},
+	verify::{verify_statements, VerifyContext},
+};
+
+/// Request for the statements of a table.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SyncRequest<RelayParent> {
+	/// The relay parent of the table.
+	pub relay_parent: RelayParent,
+	/// The marker of the table after which statements are requested.
+	/// [`StatementMarker::default`] requests all of them.
+	pub since: StatementMarker,
+	/// The maximum number of statements to return.
+	pub limit: u32,
+}
+
+/// Response to a [`SyncRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SyncResponse<RelayParent, GroupId, Statement> {
+	/// The relay parent of the table.
+	pub relay_parent: RelayParent,
+	/// The statements imported after the requested marker, in order of import, along with the
+	/// group of the candidate they refer to.
+	pub statements: Vec<(GroupId, Statement)>,
+	/// The marker after the last of the statements.
+	pub marker: StatementMarker,
+}
+
+/// Type alias for a sync response corresponding to context type.
+pub type SyncResponseFor<Ctx, RelayParent> =
+	SyncResponse<RelayParent, <Ctx as Context>::GroupId, SignedStatementFor<Ctx>>;
+
+/// Answer a [`SyncRequest`] out of the local table for its relay parent.
+pub fn answer_sync_request<Ctx: Context, RelayParent: Clone>(
+	table: &Table<Ctx>,
+	request: &SyncRequest<RelayParent>,
+) -> SyncResponseFor<Ctx, RelayParent> {
+	let (statements, marker) =
+		table.statements_since_limited(request.since, request.limit as usize);
+	SyncResponse { relay_parent: request.relay_parent.clone(), statements, marker }
+}
+
+/// The outcome of handling a [`SyncResponse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome<Digest, GroupId> {
+	/// The statements with a valid signature were imported.
+	Imported {
+		/// The summaries of all successful imports.
+		summaries: Vec<Summary<Digest, GroupId>>,
+		/// The number of statements with an invalid signature, which the peer shouldn't have
+		/// sent.
+		invalid: usize,
+	},
+	/// The response wasn't requested and was ignored.
+	Unexpected,
+	/// The response held more statements than requested and was ignored.
+	Oversized,
+}
+
+/// Type alias for the outcome of handling a sync response corresponding to context type.
+pub type SyncOutcomeFor<Ctx> = SyncOutcome<<Ctx as Context>::Digest, <Ctx as Context>::GroupId>;
+
+/// Limits of a [`TableSync`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConfig {
+	/// The maximum number of statements requested at once. Responses with more statements are
+	/// ignored.
+	pub max_statements: u32,
+	/// The time after which an unanswered request can be sent again.
+	pub request_timeout: Duration,
+}
+
+impl Default for SyncConfig {
+	fn default() -> Self {
+		SyncConfig { max_statements: 1024, request_timeout: Duration::from_secs(5) }
+	}
+}
+
+// A request awaiting its response.
+struct InFlight {
+	since: StatementMarker,
+	sent_at: Instant,
+}
+
+/// Syncs the local table with the tables of peers.
+pub struct TableSync<PeerId, RelayParent> {
+	config: SyncConfig,
+	// the marker of the table of every peer synced with, per relay parent.
+	markers: HashMap<(PeerId, RelayParent), StatementMarker>,
+	in_flight: HashMap<(PeerId, RelayParent), InFlight>,
+}
+
+impl<PeerId: Eq + Hash + Clone, RelayParent: Eq + Hash + Clone> Default
+	for TableSync<PeerId, RelayParent>
+{
+	fn default() -> Self {
+		Self::new(SyncConfig::default())
+	}
+}
+
+impl<PeerId: Eq + Hash + Clone, RelayParent: Eq + Hash + Clone> TableSync<PeerId, RelayParent> {
+	/// Create a new sync, not having synced with any peer.
+	pub fn new(config: SyncConfig) -> Self {
+		TableSync { config, markers: HashMap::new(), in_flight: HashMap::new() }
+	}
+
+	/// Get a request to send to `peer`, for the statements of the table for `relay_parent` it
+	/// imported since the last sync with it. Returns `None` if a request to the peer for the
+	/// relay parent was sent less than the request timeout before `now`.
+	pub fn request(
+		&mut self,
+		peer: PeerId,
+		relay_parent: RelayParent,
+		now: Instant,
+	) -> Option<SyncRequest<RelayParent>> {
+		let key = (peer, relay_parent);
+		if let Some(in_flight) = self.in_flight.get(&key) {
+			if now.saturating_duration_since(in_flight.sent_at) < self.config.request_timeout {
+				return None
+			}
+		}
+
+		let since = self.markers.get(&key).copied().unwrap_or_default();
+		self.in_flight.insert(key.clone(), InFlight { since, sent_at: now });
+		Some(SyncRequest { relay_parent: key.1, since, limit: self.config.max_statements })
+	}
+
+	/// Handle the response of `peer` to a [`SyncRequest`], importing the statements with a valid
+	/// signature into `table`, in order. `table` must be the table for the relay parent of the
+	/// response.
+	///
+	/// The marker of the peer never goes back before that of the request, so that a peer can't
+	/// have the same statements requested over and over.
+	pub fn on_response<Ctx: VerifyContext>(
+		&mut self,
+		table: &mut Table<Ctx>,
+		context: &Ctx,
+		peer: &PeerId,
+		response: SyncResponseFor<Ctx, RelayParent>,
+	) -> SyncOutcomeFor<Ctx> {
+		let key = (peer.clone(), response.relay_parent);
+		let Some(in_flight) = self.in_flight.remove(&key) else { return SyncOutcome::Unexpected };
+		if response.statements.len() > self.config.max_statements as usize {
+			return SyncOutcome::Oversized
+		}
+
+		let verified = verify_statements(context, response.statements);
+		let summaries = verified
+			.valid
+			.into_iter()
+			.filter_map(|(group_id, statement)| {
+				table.import_statement(context, group_id, statement)
+			})
+			.collect();
+
+		self.markers.insert(key, response.marker.max(in_flight.since));
+		SyncOutcome::Imported { summaries, invalid: verified.invalid.len() }
+	}
+
+	/// Note that a peer disconnected, forgetting its markers.
+	pub fn peer_disconnected(&mut self, peer: &PeerId) {
+		self.markers.retain(|(p, _), _| p != peer);
+		self.in_flight.retain(|(p, _), _| p != peer);
+	}
+
+	/// Note that the table for a relay parent was dropped, forgetting the markers of all peers
+	/// for it.
+	pub fn relay_parent_dropped(&mut self, relay_parent: &RelayParent) {
+		self.markers.retain(|(_, r), _| r != relay_parent);
+		self.in_flight.retain(|(_, r), _| r != relay_parent);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::router::tests::{context_and_table, seconded, seconded_other, valid};
+
+	const RELAY_PARENT: u64 = 7;
+
+	#[test]
+	fn joining_node_catches_up_with_peer() {
+		let (context, mut peer_table) = context_and_table();
+		peer_table.import_statement(&context, 0, seconded(1));
+		peer_table.import_statement(&context, 0, valid(2));
+
+		let (_, mut table) = context_and_table();
+		let mut sync = TableSync::default();
+		let now = Instant::now();
+		let request = sync.request(10u32, RELAY_PARENT, now).unwrap();
+		assert_eq!(request.since, StatementMarker::default());
+		assert_eq!(sync.request(10, RELAY_PARENT, now), None);
+
+		let response = answer_sync_request(&peer_table, &request);
+		assert_eq!(
+			sync.on_response(&mut table, &context, &11, response.clone()),
+			SyncOutcome::Unexpected
+		);
+		let mut other_relay_parent = response.clone();
+		other_relay_parent.relay_parent = RELAY_PARENT + 1;
+		assert_eq!(
+			sync.on_response(&mut table, &context, &10, other_relay_parent),
+			SyncOutcome::Unexpected
+		);
+		match sync.on_response(&mut table, &context, &10, response) {
+			SyncOutcome::Imported { summaries, invalid } => {
+				assert_eq!(summaries.len(), 2);
+				assert_eq!(summaries[1].validity_votes, 2);
+				assert_eq!(invalid, 0);
+			},
+			outcome => panic!("unexpected outcome {:?}", outcome),
+		}
+		assert_eq!(table.snapshot(), peer_table.snapshot());
+
+		// the next request only returns what the peer imported since, and forgeries are dropped.
+		let other = seconded_other(2);
+		peer_table.import_statement(&context, 0, other.clone());
+		let request = sync.request(10, RELAY_PARENT, now).unwrap();
+		let mut response = answer_sync_request(&peer_table, &request);
+		assert_eq!(response.statements, vec![(0, other)]);
+
+		let mut forged = valid(1);
+		forged.signature = valid(2).signature;
+		response.statements.push((0, forged));
+		match sync.on_response(&mut table, &context, &10, response) {
+			SyncOutcome::Imported { summaries, invalid } => {
+				assert_eq!(summaries.len(), 1);
+				assert_eq!(invalid, 1);
+			},
+			outcome => panic!("unexpected outcome {:?}", outcome),
+		}
+
+		sync.peer_disconnected(&10);
+		assert_eq!(sync.request(10, RELAY_PARENT, now).unwrap().since, StatementMarker::default());
+	}
+
+	#[test]
+	fn responses_are_bounded() {
+		let (context, mut peer_table) = context_and_table();
+		peer_table.import_statement(&context, 0, seconded(1));
+		peer_table.import_statement(&context, 0, valid(2));
+
+		let (_, mut table) = context_and_table();
+		let config = SyncConfig { max_statements: 1, ..SyncConfig::default() };
+		let mut sync = TableSync::new(config);
+		let now = Instant::now();
+
+		// statements are paged through.
+		let request = sync.request(10u32, RELAY_PARENT, now).unwrap();
+		let response = answer_sync_request(&peer_table, &request);
+		assert_eq!(response.statements, vec![(0, seconded(1))]);
+		sync.on_response(&mut table, &context, &10, response);
+
+		let request = sync.request(10, RELAY_PARENT, now).unwrap();
+		let response = answer_sync_request(&peer_table, &request);
+		assert_eq!(response.statements, vec![(0, valid(2))]);
+		sync.on_response(&mut table, &context, &10, response);
+		assert_eq!(table.snapshot(), peer_table.snapshot());
+
+		// a peer can't send more than requested, nor rewind its marker.
+		let request = sync.request(10, RELAY_PARENT, now).unwrap();
+		let mut response = answer_sync_request(&peer_table, &request);
+		response.statements = vec![(0, seconded(1)), (0, valid(2))];
+		assert_eq!(sync.on_response(&mut table, &context, &10, response), SyncOutcome::Oversized);
+
+		let since = request.since;
+		sync.request(10, RELAY_PARENT, now).unwrap();
+		let response = SyncResponse {
+			relay_parent: RELAY_PARENT,
+			statements: Vec::new(),
+			marker: Default::default(),
+		};
+		sync.on_response(&mut table, &context, &10, response);
+		assert_eq!(sync.request(10, RELAY_PARENT, now).unwrap().since, since);
+	}
+
+	#[test]
+	fn unanswered_requests_time_out() {
+		let config = SyncConfig::default();
+		let timeout = config.request_timeout;
+		let mut sync = TableSync::new(config);
+		let now = Instant::now();
+
+		assert!(sync.request(10u32, RELAY_PARENT, now).is_some());
+		assert_eq!(sync.request(10, RELAY_PARENT, now + timeout / 2), None);
+		// requests are scoped by relay parent.
+		assert!(sync.request(10, RELAY_PARENT + 1, now).is_some());
+		assert!(sync.request(10, RELAY_PARENT, now + timeout).is_some());
+
+		sync.relay_parent_dropped(&RELAY_PARENT);
+		assert!(sync.request(10, RELAY_PARENT, now + timeout).is_some());
+		assert_eq!(sync.request(10, RELAY_PARENT + 1, now + timeout / 2), None);
+	}
+}
@@ -0,0 +1,106 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Policies deciding which peers statements are propagated to.
+//!
+//! Statements about a candidate matter most to the members of the candidate's group, who vote
+//! on it. On large validator sets, sending every statement to every peer wastes bandwidth, so
+//! [`GroupFirst`] prefers group members and only sends to a few other peers.
+
+use crate::generic::Context;
+
+/// Decides which peers a statement is propagated to.
+pub trait PropagationPolicy<Ctx: Context> {
+	/// Select the peers to send a statement about a candidate of `group_id` to, most important
+	/// first.
+	///
+	/// `peers` are the connected peers which don't know the statement yet, along with the
+	/// authority they are known to be, if any.
+	fn select<PeerId: Clone>(
+		&self,
+		context: &Ctx,
+		group_id: &Ctx::GroupId,
+		peers: &[(PeerId, Option<Ctx::AuthorityId>)],
+	) -> Vec<PeerId>;
+}
+
+/// Send every statement to every peer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Flood;
+
+impl<Ctx: Context> PropagationPolicy<Ctx> for Flood {
+	fn select<PeerId: Clone>(
+		&self,
+		_context: &Ctx,
+		_group_id: &Ctx::GroupId,
+		peers: &[(PeerId, Option<Ctx::AuthorityId>)],
+	) -> Vec<PeerId> {
+		peers.iter().map(|(peer, _)| peer.clone()).collect()
+	}
+}
+
+/// Send statements to the members of the candidate's group first, and then to at most
+/// `max_others` other peers.
+///
+/// If no member of the group is among the peers, this falls back to flooding, so that the
+/// statement still reaches the group through others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupFirst {
+	/// The maximum number of peers outside of the group to send a statement to.
+	pub max_others: usize,
+}
+
+impl<Ctx: Context> PropagationPolicy<Ctx> for GroupFirst {
+	fn select<PeerId: Clone>(
+		&self,
+		context: &Ctx,
+		group_id: &Ctx::GroupId,
+		peers: &[(PeerId, Option<Ctx::AuthorityId>)],
+	) -> Vec<PeerId> {
+		let is_member = |authority: &Option<Ctx::AuthorityId>| {
+			authority.as_ref().map_or(false, |a| context.is_member_of(a, group_id))
+		};
+		let (members, others): (Vec<_>, Vec<_>) =
+			peers.iter().partition(|(_, authority)| is_member(authority));
+
+		let max_others = if members.is_empty() { others.len() } else { self.max_others };
+		members
+			.into_iter()
+			.chain(others.into_iter().take(max_others))
+			.map(|(peer, _)| peer.clone())
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::TestContext;
+
+	#[test]
+	fn group_members_are_preferred() {
+		let context = TestContext::builder().with_group(0, [1, 2]).with_group(1, [3]).build();
+		let peers = [(10u32, Some(3)), (11, None), (12, Some(1)), (13, Some(2))];
+
+		let policy = GroupFirst { max_others: 1 };
+		assert_eq!(policy.select(&context, &0, &peers), vec![12, 13, 10]);
+		assert_eq!(policy.select(&context, &1, &peers), vec![10, 11]);
+
+		// no member of group 2 is connected.
+		assert_eq!(policy.select(&context, &2, &peers), vec![10, 11, 12, 13]);
+		assert_eq!(Flood.select(&context, &0, &peers), vec![10, 11, 12, 13]);
+	}
+}
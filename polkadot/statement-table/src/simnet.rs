@@ -0,0 +1,809 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A deterministic simulation of validators agreeing on candidates, for tests.
+//!
+//! A [`Simnet`] runs validators in-process. Every validator keeps a statement table and an
+//! agreement state machine: the first validator of each group seconds a candidate, the others
+//! vote on it, and all validators then agree on a proposal built from their tables. Messages
+//! are delivered through a queue ordered by a virtual clock, on which round timeouts fire as
+//! well, so a simulation runs the same way every time and doesn't take real time.
+//!
+//! Validators can be given a faulty [`Role`], which tampers with the messages they send.
+//! Validators only accept agreement messages signed by their sender and statements signed by
+//! their issuer, and faulty validators aren't waited for, so that a simulation shows whether the
+//! honest validators agree despite faulty ones. Honest validators relay the statements of others,
+//! so that conflicting statements of an equivocating validator reach all of them, and they drop
+//! the candidates seconded by validators they caught misbehaving.
+//!
+//! The [`Network`] between validators can be degraded: links have latencies drawn from a range
+//! and lose messages, and groups of validators can be partitioned from the others for a while.
+//! Like gossip, validators periodically send their messages again, so that they catch up once
+//! lost messages get through. Randomness is drawn from a seeded generator, so degraded networks
+//! are deterministic too.
+
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap, HashSet},
+	rc::Rc,
+	time::Duration,
+};
+
+use crate::{
+	agreement::{
+		self, Action, ActionFor, AgreementConfig, CommittedFor, ExponentialBackoff, Machine,
+		Message, MessageFor, ProposalEvaluator, Round, SignedMessage, SignedMessageFor,
+		TableEvaluator,
+	},
+	generic::{
+		signing_payload, AttestedCandidateFor, Config, Context as _, SignedStatementFor, Statement,
+		Table,
+	},
+	proposal::{PriorityThenBacking, ProposalConfig},
+	test_utils::{sign, Digest, GroupId, SeededRng, TestCandidate, TestContext, SIGNING_CONTEXT},
+};
+
+// the number of validity votes candidates need to be includable.
+const MINIMUM_BACKING_VOTES: u32 = 2;
+
+// how often validators send their statements and agreement messages of the current round again.
+const REBROADCAST_PERIOD: Duration = Duration::from_secs(1);
+
+/// The index of a validator in a simulation.
+pub(crate) type ValidatorIndex = u64;
+
+/// A proposal of the simulated agreement.
+pub(crate) type Proposal = Vec<AttestedCandidateFor<TestContext>>;
+
+/// A message between simulated validators.
+#[derive(Debug, Clone)]
+pub(crate) enum SimMessage {
+	/// A statement on a candidate of the given group.
+	Statement(GroupId, SignedStatementFor<TestContext>),
+	/// A message of the agreement.
+	Agreement(SignedMessageFor<SimContext>),
+}
+
+impl SimMessage {
+	// whether the message received from `from` is authentic. statements may be relayed, so they
+	// only need to be signed by their sender.
+	fn is_authentic(&self, from: ValidatorIndex) -> bool {
+		match self {
+			SimMessage::Statement(_, statement) => statement.signature.is_valid(
+				&statement.sender,
+				&signing_payload::<TestContext, _>(&statement.statement, &SIGNING_CONTEXT),
+			),
+			SimMessage::Agreement(message) => message.sender == from && message.signature == from,
+		}
+	}
+
+	// whether the message is the given statement.
+	fn is_statement(&self, statement: &SignedStatementFor<TestContext>) -> bool {
+		matches!(self, SimMessage::Statement(_, sent) if sent == statement)
+	}
+}
+
+/// The behavior of a simulated validator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) enum Role {
+	/// Follows the protocol.
+	#[default]
+	Honest,
+	/// Sends conflicting statements and agreement messages to validators with odd indices.
+	Equivocator,
+	/// Sends nothing.
+	Withholder,
+	/// Sends nothing to the given validators.
+	Censor(HashSet<ValidatorIndex>),
+	/// Corrupts the signature of everything it sends.
+	SignatureMutator,
+}
+
+impl Role {
+	// the message a validator in this role sends to `to` instead of `message`, if any.
+	fn tamper(&self, to: ValidatorIndex, message: &SimMessage) -> Option<SimMessage> {
+		let mut message = message.clone();
+		match self {
+			Role::Honest => {},
+			Role::Withholder => return None,
+			Role::Censor(censored) if censored.contains(&to) => return None,
+			Role::Censor(_) => {},
+			Role::Equivocator if to % 2 == 1 => match &mut message {
+				SimMessage::Statement(_, statement) => {
+					if let Statement::Seconded(candidate) = &mut statement.statement {
+						candidate.body += 1000;
+						*statement = sign(statement.sender, statement.statement.clone(), 0);
+					}
+				},
+				SimMessage::Agreement(message) => match &mut message.message {
+					Message::Propose(_, proposal) => {
+						proposal.pop();
+					},
+					Message::Prevote(_, digest) | Message::Precommit(_, digest) =>
+						*digest = if digest.is_some() { None } else { Some(Vec::new()) },
+					Message::AdvanceRound(_) => {},
+				},
+			},
+			Role::Equivocator => {},
+			Role::SignatureMutator => match &mut message {
+				SimMessage::Statement(_, statement) => statement.signature.payload.push(0),
+				SimMessage::Agreement(message) => message.signature += 1,
+			},
+		}
+
+		Some(message)
+	}
+}
+
+/// A one-way link between two simulated validators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Link {
+	/// The minimum time messages take.
+	pub latency: Duration,
+	/// The maximum additional time messages take, drawn uniformly.
+	pub jitter: Duration,
+	/// The share of messages lost, in percent.
+	pub loss_percent: u32,
+}
+
+impl Link {
+	/// A link on which messages take `latency` and aren't lost.
+	pub(crate) fn reliable(latency: Duration) -> Self {
+		Link { latency, jitter: Duration::ZERO, loss_percent: 0 }
+	}
+}
+
+/// Random faults of message delivery on top of the links, within bounds.
+///
+/// Every statement and agreement message sent is subject to them, including ones sent again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Chaos {
+	/// The share of messages dropped, in percent.
+	pub drop_percent: u32,
+	/// The share of messages delivered twice, in percent.
+	pub duplicate_percent: u32,
+	/// The share of messages held back, in percent, so that messages sent after them overtake
+	/// them.
+	pub delay_percent: u32,
+	/// The maximum time a message is held back, drawn uniformly.
+	pub max_delay: Duration,
+}
+
+// a set of validators cut off from the others for a span of time.
+#[derive(Debug, Clone)]
+struct Partition {
+	nodes: HashSet<ValidatorIndex>,
+	from: Duration,
+	until: Duration,
+}
+
+/// The conditions of the network between simulated validators.
+#[derive(Debug, Clone)]
+pub(crate) struct Network {
+	default_link: Link,
+	links: HashMap<(ValidatorIndex, ValidatorIndex), Link>,
+	partitions: Vec<Partition>,
+	chaos: Chaos,
+	seed: u64,
+}
+
+impl Network {
+	/// A network of reliable links on which messages take `latency`.
+	pub(crate) fn new(latency: Duration) -> Self {
+		Network {
+			default_link: Link::reliable(latency),
+			links: HashMap::new(),
+			partitions: Vec::new(),
+			chaos: Chaos::default(),
+			seed: 0,
+		}
+	}
+
+	/// Use `link` between all validators without a link of their own.
+	pub(crate) fn with_default_link(mut self, link: Link) -> Self {
+		self.default_link = link;
+		self
+	}
+
+	/// Use `link` for messages from `from` to `to`.
+	pub(crate) fn with_link(
+		mut self,
+		from: ValidatorIndex,
+		to: ValidatorIndex,
+		link: Link,
+	) -> Self {
+		self.links.insert((from, to), link);
+		self
+	}
+
+	/// Cut `nodes` off from the other validators from `from` until `until` on the virtual clock.
+	///
+	/// Messages between both sides which would be delivered in that time are lost.
+	pub(crate) fn with_partition(
+		mut self,
+		nodes: impl IntoIterator<Item = ValidatorIndex>,
+		from: Duration,
+		until: Duration,
+	) -> Self {
+		self.partitions
+			.push(Partition { nodes: nodes.into_iter().collect(), from, until });
+		self
+	}
+
+	/// Subject the delivery of messages to `chaos`.
+	pub(crate) fn with_chaos(mut self, chaos: Chaos) -> Self {
+		self.chaos = chaos;
+		self
+	}
+
+	/// Seed the generator of latencies, losses and chaos.
+	pub(crate) fn with_seed(mut self, seed: u64) -> Self {
+		self.seed = seed;
+		self
+	}
+
+	fn link(&self, from: ValidatorIndex, to: ValidatorIndex) -> &Link {
+		self.links.get(&(from, to)).unwrap_or(&self.default_link)
+	}
+
+	fn is_partitioned(&self, from: ValidatorIndex, to: ValidatorIndex, at: Duration) -> bool {
+		self.partitions.iter().any(|partition| {
+			(partition.from..partition.until).contains(&at) &&
+				partition.nodes.contains(&from) != partition.nodes.contains(&to)
+		})
+	}
+}
+
+/// The table of a validator, shared by the validator and its agreement context.
+pub(crate) struct Shared {
+	table: RefCell<Table<TestContext>>,
+	context: TestContext,
+}
+
+impl Shared {
+	// whether the candidate was seconded by a validator caught misbehaving. such candidates are
+	// dropped, since other validators may have been sent a conflicting one.
+	fn is_dropped(
+		&self,
+		table: &Table<TestContext>,
+		attested: &AttestedCandidateFor<TestContext>,
+	) -> bool {
+		let digest = TestContext::candidate_digest(&attested.candidate);
+		let misbehavior = table.get_misbehavior();
+		table
+			.candidate_votes(&digest)
+			.any(|(authority, seconded)| seconded && misbehavior.contains_key(authority))
+	}
+}
+
+impl ProposalEvaluator<Proposal> for Shared {
+	fn evaluate(&self, proposal: &Proposal) -> bool {
+		let table = self.table.borrow();
+		let no_dropped = |proposal: &Proposal| {
+			!proposal.iter().any(|attested| self.is_dropped(&table, attested))
+		};
+		TableEvaluator::new(&table, &self.context, MINIMUM_BACKING_VOTES, no_dropped)
+			.evaluate(proposal)
+	}
+}
+
+/// The agreement context of a simulated validator, proposing the includable candidates of its
+/// table.
+pub(crate) struct SimContext {
+	local_id: ValidatorIndex,
+	n: u64,
+	shared: Rc<Shared>,
+}
+
+impl agreement::Context for SimContext {
+	type AuthorityId = ValidatorIndex;
+	type Digest = Vec<Digest>;
+	type Signature = u64;
+	type Proposal = Proposal;
+	type Evaluator = Shared;
+
+	fn proposal_digest(proposal: &Proposal) -> Vec<Digest> {
+		proposal
+			.iter()
+			.map(|attested| TestContext::candidate_digest(&attested.candidate))
+			.collect()
+	}
+
+	fn local_id(&self) -> ValidatorIndex {
+		self.local_id
+	}
+
+	fn num_authorities(&self) -> usize {
+		self.n as usize
+	}
+
+	fn is_authority(&self, id: &ValidatorIndex) -> bool {
+		*id < self.n
+	}
+
+	fn round_proposer(&self, round: Round) -> ValidatorIndex {
+		round as u64 % self.n
+	}
+
+	fn proposal(&self) -> Option<Proposal> {
+		let table = self.shared.table.borrow();
+		let mut proposal = table.build_proposal(
+			&self.shared.context,
+			MINIMUM_BACKING_VOTES,
+			&ProposalConfig::default(),
+			&PriorityThenBacking,
+		);
+		proposal.retain(|attested| !self.shared.is_dropped(&table, attested));
+		(!proposal.is_empty()).then_some(proposal)
+	}
+
+	fn evaluator(&self) -> &Shared {
+		&self.shared
+	}
+
+	fn sign_local(&self, message: MessageFor<Self>) -> SignedMessageFor<Self> {
+		SignedMessage { message, signature: self.local_id, sender: self.local_id }
+	}
+}
+
+struct SimNode {
+	role: Role,
+	context: SimContext,
+	machine: Machine<SimContext, ExponentialBackoff>,
+	// the candidates the validator seconded or voted on.
+	voted: HashSet<Digest>,
+	// the deadline of the machine for which a wake-up is scheduled.
+	deadline: Option<Duration>,
+	// the messages the validator sent, to send again.
+	sent: Vec<SimMessage>,
+}
+
+enum Event {
+	Deliver { from: ValidatorIndex, to: ValidatorIndex, message: SimMessage },
+	Wake { node: ValidatorIndex },
+	Rebroadcast { node: ValidatorIndex },
+}
+
+/// A simulation of validators agreeing on candidates.
+pub(crate) struct Simnet {
+	now: Duration,
+	// events by the time they happen at, in order of scheduling for the same time.
+	queue: BTreeMap<(Duration, u64), Event>,
+	scheduled: u64,
+	nodes: Vec<SimNode>,
+	network: Network,
+	rng: SeededRng,
+	round_timeout: ExponentialBackoff,
+}
+
+impl Simnet {
+	/// Create a simulation of `n` validators in groups of `group_size`, in order of index.
+	///
+	/// Messages take `latency` to be delivered.
+	pub(crate) fn new(n: u64, group_size: u64, latency: Duration) -> Self {
+		Self::with_roles(n, group_size, latency, [])
+	}
+
+	/// Create a simulation like [`Simnet::new`], in which the given validators have a faulty
+	/// role.
+	pub(crate) fn with_roles(
+		n: u64,
+		group_size: u64,
+		latency: Duration,
+		faulty: impl IntoIterator<Item = (ValidatorIndex, Role)>,
+	) -> Self {
+		Self::with_network(n, group_size, Network::new(latency), faulty)
+	}
+
+	/// Create a simulation like [`Simnet::with_roles`] on the given network.
+	pub(crate) fn with_network(
+		n: u64,
+		group_size: u64,
+		network: Network,
+		faulty: impl IntoIterator<Item = (ValidatorIndex, Role)>,
+	) -> Self {
+		let mut roles: HashMap<_, _> = faulty.into_iter().collect();
+		let group_of = |i: u64| (i / group_size) as GroupId;
+		let context = (0..n)
+			.step_by(group_size as usize)
+			.fold(TestContext::builder(), |builder, first| {
+				let members = first as u32..n.min(first + group_size) as u32;
+				builder.with_group(group_of(first), members)
+			})
+			.build();
+		let mut simnet = Simnet {
+			now: Duration::ZERO,
+			queue: BTreeMap::new(),
+			scheduled: 0,
+			nodes: Vec::new(),
+			rng: SeededRng::new(network.seed),
+			network,
+			round_timeout: ExponentialBackoff::default(),
+		};
+
+		for local_id in 0..n {
+			let shared = Rc::new(Shared {
+				table: RefCell::new(Table::new(Config { allow_multiple_seconded: false })),
+				context: context.clone(),
+			});
+			let context = SimContext { local_id, n, shared };
+			let (machine, actions) = Machine::new(
+				&context,
+				AgreementConfig::default(),
+				simnet.round_timeout.clone(),
+				simnet.now,
+			);
+			simnet.nodes.push(SimNode {
+				role: roles.remove(&local_id).unwrap_or_default(),
+				context,
+				machine,
+				voted: HashSet::new(),
+				deadline: None,
+				sent: Vec::new(),
+			});
+			simnet.apply(local_id, actions);
+			simnet.schedule(REBROADCAST_PERIOD, Event::Rebroadcast { node: local_id });
+		}
+
+		for local_id in (0..n).filter(|i| i % group_size == 0) {
+			let group = group_of(local_id);
+			let candidate = TestCandidate::new(group, 100 + u64::from(group));
+			let statement = sign(local_id as u32, Statement::Seconded(candidate), 0);
+			simnet.issue(local_id, group, statement);
+		}
+
+		simnet
+	}
+
+	/// The current time on the virtual clock.
+	pub(crate) fn now(&self) -> Duration {
+		self.now
+	}
+
+	/// The proposal committed by each validator, if any.
+	pub(crate) fn committed(&self) -> Vec<Option<&CommittedFor<SimContext>>> {
+		self.nodes.iter().map(|node| node.machine.agreement().committed()).collect()
+	}
+
+	/// The proposal committed by each honest validator, if any.
+	pub(crate) fn committed_by_honest(&self) -> Vec<Option<&CommittedFor<SimContext>>> {
+		self.nodes
+			.iter()
+			.filter(|node| node.role == Role::Honest)
+			.map(|node| node.machine.agreement().committed())
+			.collect()
+	}
+
+	/// Run the simulation until every honest validator committed a proposal or the virtual clock
+	/// reaches `deadline`, returning whether all of them committed.
+	pub(crate) fn run(&mut self, deadline: Duration) -> bool {
+		while self.committed_by_honest().iter().any(Option::is_none) {
+			let Some(entry) = self.queue.first_entry() else { break };
+			if entry.key().0 > deadline {
+				break
+			}
+
+			self.now = entry.key().0;
+			match entry.remove() {
+				Event::Deliver { from, to, message } => {
+					if !self.network.is_partitioned(from, to, self.now) {
+						self.deliver(from, to, message);
+					}
+				},
+				Event::Wake { node } => {
+					let node_data = &mut self.nodes[node as usize];
+					let actions = node_data.machine.advance_time(&node_data.context, self.now);
+					self.apply(node, actions);
+				},
+				Event::Rebroadcast { node } => {
+					self.rebroadcast(node);
+					self.schedule(REBROADCAST_PERIOD, Event::Rebroadcast { node });
+				},
+			}
+		}
+
+		self.committed_by_honest().iter().all(Option::is_some)
+	}
+
+	fn schedule(&mut self, after: Duration, event: Event) {
+		self.queue.insert((self.now + after, self.scheduled), event);
+		self.scheduled += 1;
+	}
+
+	fn broadcast(&mut self, from: ValidatorIndex, message: SimMessage) {
+		self.nodes[from as usize].sent.push(message.clone());
+		self.gossip(from, &message);
+	}
+
+	// send the statements and the agreement messages of the current round of `node` again, along
+	// with its wish to advance from the previous round, which lets others catch up.
+	fn rebroadcast(&mut self, node: ValidatorIndex) {
+		let node_data = &self.nodes[node as usize];
+		let round = node_data.machine.agreement().round();
+		let messages: Vec<_> = node_data
+			.sent
+			.iter()
+			.filter(|message| match message {
+				SimMessage::Statement(..) => true,
+				SimMessage::Agreement(message) => match message.message {
+					Message::AdvanceRound(advanced) => advanced + 1 >= round,
+					ref message => message.round() >= round,
+				},
+			})
+			.cloned()
+			.collect();
+		for message in messages {
+			self.gossip(node, &message);
+		}
+	}
+
+	// send a message to all other validators over the network.
+	fn gossip(&mut self, from: ValidatorIndex, message: &SimMessage) {
+		for to in (0..self.nodes.len() as u64).filter(|to| *to != from) {
+			let Some(message) = self.nodes[from as usize].role.tamper(to, message) else {
+				continue
+			};
+			let (link, chaos) = (*self.network.link(from, to), self.network.chaos);
+			if self.rng.chance(link.loss_percent) || self.rng.chance(chaos.drop_percent) {
+				continue
+			}
+
+			let copies = if self.rng.chance(chaos.duplicate_percent) { 2 } else { 1 };
+			for _ in 0..copies {
+				let mut latency = link.latency + self.random_duration(link.jitter);
+				if self.rng.chance(chaos.delay_percent) {
+					latency += self.random_duration(chaos.max_delay);
+				}
+				self.schedule(latency, Event::Deliver { from, to, message: message.clone() });
+			}
+		}
+	}
+
+	// a duration drawn uniformly up to `max`.
+	fn random_duration(&mut self, max: Duration) -> Duration {
+		Duration::from_nanos(self.rng.below(max.as_nanos() as u64 + 1))
+	}
+
+	// import a statement of the validator `from` into its own table and broadcast it.
+	fn issue(
+		&mut self,
+		from: ValidatorIndex,
+		group: GroupId,
+		statement: SignedStatementFor<TestContext>,
+	) {
+		let node = &mut self.nodes[from as usize];
+		let shared = &node.context.shared;
+		if let Some(summary) =
+			shared
+				.table
+				.borrow_mut()
+				.import_statement(&shared.context, group, statement.clone())
+		{
+			node.voted.insert(summary.candidate);
+		}
+		self.broadcast(from, SimMessage::Statement(group, statement));
+	}
+
+	fn deliver(&mut self, from: ValidatorIndex, to: ValidatorIndex, message: SimMessage) {
+		if !message.is_authentic(from) {
+			return
+		}
+
+		let node = &mut self.nodes[to as usize];
+		match message {
+			SimMessage::Statement(group, statement) => {
+				let shared = &node.context.shared;
+				let summary = shared.table.borrow_mut().import_statement(
+					&shared.context,
+					group,
+					statement.clone(),
+				);
+				let Some(summary) = summary else { return };
+				// vote on the candidates of the own group.
+				let vote = shared.context.is_member_of(&(to as u32), &summary.group_id) &&
+					node.voted.insert(summary.candidate);
+				if node.role == Role::Honest &&
+					!node.sent.iter().any(|sent| sent.is_statement(&statement))
+				{
+					self.broadcast(to, SimMessage::Statement(group, statement));
+				}
+				if vote {
+					let vote = sign(to as u32, Statement::Valid(summary.candidate), 0);
+					self.issue(to, summary.group_id, vote);
+				}
+			},
+			SimMessage::Agreement(message) => {
+				let actions = node.machine.import_message(&node.context, message, self.now);
+				self.apply(to, actions);
+			},
+		}
+	}
+
+	// broadcast the messages of the agreement of `node`, and schedule waking it up at its next
+	// deadline.
+	fn apply(&mut self, node: ValidatorIndex, actions: Vec<ActionFor<SimContext>>) {
+		for action in actions {
+			if let Action::Broadcast(message) = action {
+				self.broadcast(node, SimMessage::Agreement(message));
+			}
+		}
+
+		let node_data = &mut self.nodes[node as usize];
+		let deadline = node_data.machine.next_deadline();
+		if node_data.deadline != deadline {
+			node_data.deadline = deadline;
+			if let Some(deadline) = deadline {
+				self.schedule(deadline.saturating_sub(self.now), Event::Wake { node });
+			}
+		}
+	}
+}
+
+mod tests {
+	use super::*;
+
+	#[test]
+	fn validators_agree_on_backed_candidates() {
+		let mut simnet = Simnet::new(6, 3, Duration::from_millis(50));
+		assert!(simnet.run(Duration::from_secs(60)));
+
+		let committed = simnet.committed();
+		let proposal = &committed[0].unwrap().proposal;
+		assert_eq!(
+			<SimContext as agreement::Context>::proposal_digest(proposal),
+			vec![TestCandidate::new(0, 100).digest(), TestCandidate::new(1, 101).digest()]
+		);
+		for attested in proposal {
+			assert_eq!(attested.validity_votes.len(), 3);
+		}
+		assert!(committed.iter().all(|c| &c.unwrap().proposal == proposal));
+
+		// the simulation is deterministic, down to the order of signatures in justifications.
+		let mut again = Simnet::new(6, 3, Duration::from_millis(50));
+		assert!(again.run(Duration::from_secs(60)));
+		assert_eq!(again.now(), simnet.now());
+		assert_eq!(again.committed(), committed);
+	}
+
+	#[test]
+	fn honest_validators_agree_despite_faulty_ones() {
+		let roles = [
+			Role::Equivocator,
+			Role::Withholder,
+			Role::Censor((0..7).filter(|i| i % 2 == 1).collect()),
+			Role::SignatureMutator,
+		];
+		// 7 validators tolerate 2 faulty ones, as long as every group has enough honest members.
+		for role in roles {
+			let faulty = [(1, role.clone()), (4, role.clone())];
+			let mut simnet = Simnet::with_roles(7, 3, Duration::from_millis(50), faulty);
+			assert!(simnet.run(Duration::from_secs(600)), "no agreement with {:?}", role);
+
+			let committed = simnet.committed_by_honest();
+			assert_eq!(committed.len(), 5);
+			let proposal = &committed[0].unwrap().proposal;
+			assert!(committed.iter().all(|c| &c.unwrap().proposal == proposal), "{:?}", role);
+		}
+	}
+
+	#[test]
+	fn honest_validators_drop_candidates_of_equivocating_seconders() {
+		// the seconders of the first two groups send different candidates to the validators with
+		// odd and even indices. relayed statements expose them to every honest validator, which
+		// then drops both candidates and agrees on those of the other groups.
+		let faulty = [(0, Role::Equivocator), (2, Role::Equivocator)];
+		let mut simnet = Simnet::with_roles(7, 2, Duration::from_millis(50), faulty);
+		assert!(simnet.run(Duration::from_secs(600)));
+
+		let committed = simnet.committed_by_honest();
+		assert_eq!(committed.len(), 5);
+		let proposal = &committed[0].unwrap().proposal;
+		assert_eq!(
+			<SimContext as agreement::Context>::proposal_digest(proposal),
+			vec![TestCandidate::new(2, 102).digest(), TestCandidate::new(3, 103).digest()]
+		);
+		assert!(committed.iter().all(|c| &c.unwrap().proposal == proposal));
+	}
+
+	#[test]
+	fn validators_agree_over_lossy_links() {
+		let link = Link {
+			latency: Duration::from_millis(20),
+			jitter: Duration::from_millis(300),
+			loss_percent: 20,
+		};
+		let network = || {
+			Network::new(Duration::ZERO)
+				.with_default_link(link)
+				.with_link(0, 1, Link { loss_percent: 90, ..link })
+				.with_seed(42)
+		};
+
+		let mut simnet = Simnet::with_network(7, 3, network(), []);
+		assert!(simnet.run(Duration::from_secs(600)));
+		let committed = simnet.committed();
+		assert!(committed.iter().all(|c| c.unwrap().proposal == committed[0].unwrap().proposal));
+
+		// a simulation is reproduced from the seed of its network.
+		let mut again = Simnet::with_network(7, 3, network(), []);
+		assert!(again.run(Duration::from_secs(600)));
+		assert_eq!(again.now(), simnet.now());
+		assert_eq!(again.committed(), committed);
+
+		let mut reseeded = Simnet::with_network(7, 3, network().with_seed(43), []);
+		assert!(reseeded.run(Duration::from_secs(600)));
+		assert_ne!(reseeded.now(), simnet.now());
+	}
+
+	#[test]
+	fn validators_agree_despite_chaos() {
+		let chaos = Chaos {
+			drop_percent: 10,
+			duplicate_percent: 20,
+			delay_percent: 30,
+			max_delay: Duration::from_secs(3),
+		};
+
+		for seed in 0..8 {
+			let network = Network::new(Duration::from_millis(50)).with_chaos(chaos).with_seed(seed);
+			let mut simnet = Simnet::with_network(7, 3, network, []);
+			assert!(simnet.run(Duration::from_secs(600)), "seed {seed}");
+
+			let committed = simnet.committed();
+			assert!(committed
+				.iter()
+				.all(|c| c.unwrap().proposal == committed[0].unwrap().proposal));
+		}
+	}
+
+	#[test]
+	fn validators_catch_up_after_partition_heals() {
+		// neither side of the partition forms a supermajority.
+		let heal = Duration::from_secs(30);
+		let network =
+			Network::new(Duration::from_millis(50)).with_partition(0..3, Duration::ZERO, heal);
+		let mut simnet = Simnet::with_network(7, 3, network, []);
+		assert!(simnet.run(Duration::from_secs(600)));
+		assert!(simnet.now() >= heal);
+
+		let committed = simnet.committed();
+		assert!(committed.iter().all(|c| c.unwrap().proposal == committed[0].unwrap().proposal));
+	}
+
+	#[test]
+	fn isolated_validator_catches_up_with_majority() {
+		let heal = Duration::from_secs(30);
+		let network =
+			Network::new(Duration::from_millis(50)).with_partition([6], Duration::ZERO, heal);
+		let mut simnet = Simnet::with_network(7, 3, network, []);
+		assert!(!simnet.run(Duration::from_secs(20)));
+		let committed = simnet.committed();
+		assert!(committed[..6].iter().all(Option::is_some));
+		assert!(committed[6].is_none());
+
+		assert!(simnet.run(Duration::from_secs(600)));
+		let committed = simnet.committed();
+		assert!(committed.iter().all(|c| c.unwrap().proposal == committed[0].unwrap().proposal));
+	}
+
+	#[test]
+	fn too_many_faulty_validators_stall_agreement() {
+		let faulty = [(0, Role::Withholder), (3, Role::Withholder), (6, Role::Withholder)];
+		let mut simnet = Simnet::with_roles(7, 3, Duration::from_millis(50), faulty);
+		assert!(!simnet.run(Duration::from_secs(600)));
+		assert!(simnet.committed_by_honest().iter().all(Option::is_none));
+	}
+}
@@ -0,0 +1,623 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Statements, misbehavior and attestations.
+//!
+//! These are the parts of the table needed to check what it produces: the signing payloads of
+//! statements, proofs of misbehavior and the quorums of attested candidates. They only depend on
+//! `core` and `alloc`, so they build without the `std` feature and the runtime checks statements
+//! with the same code as the nodes producing them. The table itself requires `std`, and
+//! `generic` re-exports everything defined here.
+
+use alloc::vec::Vec;
+use core::{
+	fmt::{self, Debug},
+	hash::Hash,
+};
+
+use parity_scale_codec::{Decode, Encode};
+use primitives::{
+	effective_minimum_backing_votes, ValidatorSignature,
+	ValidityAttestation as PrimitiveValidityAttestation,
+};
+
+/// Context for the statement table.
+pub trait Context {
+	/// An authority ID
+	type AuthorityId: Debug + Hash + Eq + Clone;
+	/// The digest (hash or other unique attribute) of a candidate.
+	type Digest: Debug + Hash + Eq + Clone;
+	/// The group ID type
+	type GroupId: Debug + Hash + Ord + Eq + Clone;
+	/// A signature type.
+	type Signature: Debug + Eq + Clone;
+	/// Candidate type. In practice this will be a candidate receipt.
+	type Candidate: Debug + Ord + Eq + Clone;
+
+	/// get the digest of a candidate.
+	fn candidate_digest(candidate: &Self::Candidate) -> Self::Digest;
+
+	/// Whether a authority is a member of a group.
+	/// Members are meant to submit candidates and vote on validity.
+	fn is_member_of(&self, authority: &Self::AuthorityId, group: &Self::GroupId) -> bool;
+
+	/// Get a validator group size.
+	fn get_group_size(&self, group: &Self::GroupId) -> Option<usize>;
+
+	/// The weight of the votes of an authority, e.g. its stake. Every authority weighs `1` by
+	/// default.
+	fn vote_weight(&self, _authority: &Self::AuthorityId) -> u64 {
+		1
+	}
+
+	/// Whether a candidate may be seconded in a group, e.g. whether its para is scheduled on
+	/// the core of the group. Statements seconding other candidates are rejected, but not
+	/// noted as misbehavior. All candidates are accepted by default.
+	fn is_acceptable(&self, _candidate: &Self::Candidate, _group: &Self::GroupId) -> bool {
+		true
+	}
+}
+
+/// A [`Context`] which knows the group each candidate is assigned to, e.g. from the core its
+/// para is scheduled on, so that statements can be checked without trusting the group claimed
+/// alongside them.
+pub trait GroupAssignment: Context {
+	/// Get the group the candidate with the given digest is assigned to, if it is known.
+	fn candidate_group(&self, digest: &Self::Digest) -> Option<Self::GroupId>;
+}
+
+/// A [`Context`] which knows its validators and the members of its groups, so that validators
+/// which didn't issue any statement can be told apart.
+pub trait ValidatorSet: Context {
+	/// Get the validators which are members of any group, in a stable order.
+	fn validators(&self) -> Vec<Self::AuthorityId>;
+
+	/// Get the members of a group, in a stable order.
+	fn group_members(&self, group: &Self::GroupId) -> Vec<Self::AuthorityId>;
+}
+
+/// Statements circulated among peers.
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement<Candidate, Digest> {
+	/// Broadcast by an authority to indicate that this is its candidate for inclusion.
+	///
+	/// Broadcasting two different candidate messages per round is not allowed.
+	#[codec(index = 1)]
+	Seconded(Candidate),
+	/// Broadcast by a authority to attest that the candidate with given digest is valid.
+	#[codec(index = 2)]
+	Valid(Digest),
+}
+
+impl<Candidate, Digest: Clone> Statement<Candidate, Digest> {
+	/// Get the digest of the candidate the statement refers to, whatever its kind.
+	pub fn digest<Ctx>(&self) -> Digest
+	where
+		Ctx: Context<Candidate = Candidate, Digest = Digest>,
+	{
+		match self {
+			Statement::Seconded(candidate) => Ctx::candidate_digest(candidate),
+			Statement::Valid(digest) => digest.clone(),
+		}
+	}
+
+	/// Get the candidate, if the statement seconds one.
+	pub fn candidate(&self) -> Option<&Candidate> {
+		match self {
+			Statement::Seconded(candidate) => Some(candidate),
+			Statement::Valid(_) => None,
+		}
+	}
+
+	/// Whether the statement seconds a candidate.
+	pub fn is_seconded(&self) -> bool {
+		matches!(self, Statement::Seconded(_))
+	}
+}
+
+/// A signed statement.
+#[derive(PartialEq, Eq, Debug, Clone, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignedStatement<Candidate, Digest, AuthorityId, Signature> {
+	/// The statement.
+	pub statement: Statement<Candidate, Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+impl<Candidate, Digest: Clone, AuthorityId, Signature>
+	SignedStatement<Candidate, Digest, AuthorityId, Signature>
+{
+	/// Get the digest of the candidate the statement refers to, whatever its kind.
+	pub fn digest<Ctx>(&self) -> Digest
+	where
+		Ctx: Context<Candidate = Candidate, Digest = Digest>,
+	{
+		self.statement.digest::<Ctx>()
+	}
+}
+
+/// Type alias for a signed statement corresponding to context type.
+pub type SignedStatementFor<Ctx> = SignedStatement<
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+// prefix of signing payloads, separating statements from other signed data.
+const STATEMENT_SIGNING_MAGIC: [u8; 4] = *b"BKNG";
+
+/// Get the bytes an authority signs for `statement`.
+///
+/// A statement seconding a candidate is signed over the digest of the candidate, so that its
+/// signature can be checked without the candidate, and so that it commits to the same bytes as
+/// a validity statement would, except for the kind. `signing_context` separates signatures
+/// made in different rounds or on different forks, e.g. by a session index and a parent hash.
+///
+/// The payload is the magic `BKNG`, the kind of statement (1 for seconded, 2 for valid), the
+/// digest and `signing_context`, SCALE-encoded. With a candidate hash as digest and a
+/// `SigningContext` from the primitives, this is the payload of the equivalent
+/// `CompactStatement`.
+pub fn signing_payload<Ctx, S>(
+	statement: &Statement<Ctx::Candidate, Ctx::Digest>,
+	signing_context: &S,
+) -> Vec<u8>
+where
+	Ctx: Context,
+	Ctx::Digest: Encode,
+	S: Encode,
+{
+	match statement {
+		Statement::Seconded(candidate) =>
+			digest_signing_payload(1, &Ctx::candidate_digest(candidate), signing_context),
+		Statement::Valid(digest) => digest_signing_payload(2, digest, signing_context),
+	}
+}
+
+// the signing payload of a statement of the given kind, see `signing_payload`.
+pub(crate) fn digest_signing_payload<D: Encode, S: Encode>(
+	kind: u8,
+	digest: &D,
+	signing_context: &S,
+) -> Vec<u8> {
+	(STATEMENT_SIGNING_MAGIC, kind, digest, signing_context).encode()
+}
+
+/// A statement which refers to its candidate by digest only.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompactStatement<Digest> {
+	/// See [`Statement::Valid`].
+	#[codec(index = 2)]
+	Valid(Digest),
+}
+
+/// A signed statement in compact form, which never carries a candidate.
+///
+/// Validity statements dominate gossip traffic. In this form they have a small, fixed size: the
+/// kind tag, the digest, the signature and the sender. With the types of [`v2`](crate::v2) that
+/// is 1 + 32 + 64 + 4 = 101 bytes, while a statement seconding a candidate receipt takes several
+/// hundred bytes.
+///
+/// The encoding is the same as that of the equivalent [`SignedStatement`], so either can be
+/// decoded from the other.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompactSignedStatement<Digest, AuthorityId, Signature> {
+	/// The statement.
+	pub statement: CompactStatement<Digest>,
+	/// The signature.
+	pub signature: Signature,
+	/// The sender.
+	pub sender: AuthorityId,
+}
+
+/// Type alias for a compact signed statement corresponding to context type.
+pub type CompactSignedStatementFor<Ctx> = CompactSignedStatement<
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+impl<Candidate, Digest, AuthorityId, Signature>
+	From<CompactSignedStatement<Digest, AuthorityId, Signature>>
+	for SignedStatement<Candidate, Digest, AuthorityId, Signature>
+{
+	fn from(compact: CompactSignedStatement<Digest, AuthorityId, Signature>) -> Self {
+		let statement = match compact.statement {
+			CompactStatement::Valid(digest) => Statement::Valid(digest),
+		};
+		SignedStatement { statement, signature: compact.signature, sender: compact.sender }
+	}
+}
+
+impl<Candidate, Digest, AuthorityId, Signature>
+	TryFrom<SignedStatement<Candidate, Digest, AuthorityId, Signature>>
+	for CompactSignedStatement<Digest, AuthorityId, Signature>
+{
+	/// Statements carrying a candidate are returned as they are.
+	type Error = SignedStatement<Candidate, Digest, AuthorityId, Signature>;
+
+	fn try_from(
+		signed: SignedStatement<Candidate, Digest, AuthorityId, Signature>,
+	) -> Result<Self, Self::Error> {
+		match signed.statement {
+			Statement::Valid(digest) => Ok(CompactSignedStatement {
+				statement: CompactStatement::Valid(digest),
+				signature: signed.signature,
+				sender: signed.sender,
+			}),
+			Statement::Seconded(_) => Err(signed),
+		}
+	}
+}
+
+/// Misbehavior: voting more than one way on candidate validity.
+///
+/// Since there are three possible ways to vote, a double vote is possible in
+/// three possible combinations (unordered)
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValidityDoubleVote<Candidate, Digest, Signature> {
+	/// Implicit vote by issuing and explicitly voting validity.
+	#[codec(index = 0)]
+	IssuedAndValidity((Candidate, Signature), (Digest, Signature)),
+}
+
+impl<Candidate, Digest, Signature> ValidityDoubleVote<Candidate, Digest, Signature> {
+	/// Deconstruct this misbehavior into two `(Statement, Signature)` pairs, erasing the
+	/// information about precisely what the problem was.
+	pub fn deconstruct<Ctx>(
+		self,
+	) -> ((Statement<Candidate, Digest>, Signature), (Statement<Candidate, Digest>, Signature))
+	where
+		Ctx: Context<Candidate = Candidate, Digest = Digest, Signature = Signature>,
+		Candidate: Debug + Ord + Eq + Clone,
+		Digest: Debug + Hash + Eq + Clone,
+		Signature: Debug + Eq + Clone,
+	{
+		match self {
+			Self::IssuedAndValidity((c, s1), (d, s2)) =>
+				((Statement::Seconded(c), s1), (Statement::Valid(d), s2)),
+		}
+	}
+}
+
+/// Misbehavior: multiple signatures on same statement.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DoubleSign<Candidate, Digest, Signature> {
+	/// On candidate.
+	#[codec(index = 0)]
+	Seconded(Candidate, Signature, Signature),
+	/// On validity.
+	#[codec(index = 1)]
+	Validity(Digest, Signature, Signature),
+}
+
+impl<Candidate, Digest, Signature> DoubleSign<Candidate, Digest, Signature> {
+	/// Deconstruct this misbehavior into a statement with two signatures, erasing the information
+	/// about precisely where in the process the issue was detected.
+	pub fn deconstruct(self) -> (Statement<Candidate, Digest>, Signature, Signature) {
+		match self {
+			Self::Seconded(candidate, a, b) => (Statement::Seconded(candidate), a, b),
+			Self::Validity(digest, a, b) => (Statement::Valid(digest), a, b),
+		}
+	}
+}
+
+/// Misbehavior: declaring multiple candidates.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultipleCandidates<Candidate, Signature> {
+	/// The first candidate seen.
+	pub first: (Candidate, Signature),
+	/// The second candidate seen.
+	pub second: (Candidate, Signature),
+}
+
+/// Misbehavior: submitted statement for wrong group.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnauthorizedStatement<Candidate, Digest, AuthorityId, Signature> {
+	/// A signed statement which was submitted without proper authority.
+	pub statement: SignedStatement<Candidate, Digest, AuthorityId, Signature>,
+}
+
+/// Different kinds of misbehavior. All of these kinds of malicious misbehavior
+/// are easily provable and extremely disincentivized.
+#[derive(PartialEq, Eq, Debug, Clone, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Misbehavior<Candidate, Digest, AuthorityId, Signature> {
+	/// Voted invalid and valid on validity.
+	#[codec(index = 0)]
+	ValidityDoubleVote(ValidityDoubleVote<Candidate, Digest, Signature>),
+	/// Submitted multiple candidates.
+	#[codec(index = 1)]
+	MultipleCandidates(MultipleCandidates<Candidate, Signature>),
+	/// Submitted a message that was unauthorized.
+	#[codec(index = 2)]
+	UnauthorizedStatement(UnauthorizedStatement<Candidate, Digest, AuthorityId, Signature>),
+	/// Submitted two valid signatures for the same message.
+	#[codec(index = 3)]
+	DoubleSign(DoubleSign<Candidate, Digest, Signature>),
+}
+
+impl<Candidate, Digest, AuthorityId, Signature>
+	Misbehavior<Candidate, Digest, AuthorityId, Signature>
+{
+	/// The kind of misbehavior.
+	pub fn kind(&self) -> MisbehaviorKind {
+		match self {
+			Misbehavior::ValidityDoubleVote(_) => MisbehaviorKind::ValidityDoubleVote,
+			Misbehavior::MultipleCandidates(_) => MisbehaviorKind::MultipleCandidates,
+			Misbehavior::UnauthorizedStatement(_) => MisbehaviorKind::UnauthorizedStatement,
+			Misbehavior::DoubleSign(_) => MisbehaviorKind::DoubleSign,
+		}
+	}
+}
+
+/// The kind of a [`Misbehavior`], without its proof.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MisbehaviorKind {
+	/// See [`Misbehavior::ValidityDoubleVote`].
+	#[codec(index = 0)]
+	ValidityDoubleVote,
+	/// See [`Misbehavior::MultipleCandidates`].
+	#[codec(index = 1)]
+	MultipleCandidates,
+	/// See [`Misbehavior::UnauthorizedStatement`].
+	#[codec(index = 2)]
+	UnauthorizedStatement,
+	/// See [`Misbehavior::DoubleSign`].
+	#[codec(index = 3)]
+	DoubleSign,
+}
+
+impl MisbehaviorKind {
+	/// The name of the kind of misbehavior, e.g. to label metrics and logs with.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			MisbehaviorKind::ValidityDoubleVote => "validity_double_vote",
+			MisbehaviorKind::MultipleCandidates => "multiple_candidates",
+			MisbehaviorKind::UnauthorizedStatement => "unauthorized_statement",
+			MisbehaviorKind::DoubleSign => "double_sign",
+		}
+	}
+}
+
+impl fmt::Display for MisbehaviorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// Type alias for misbehavior corresponding to context type.
+pub type MisbehaviorFor<Ctx> = Misbehavior<
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::Digest,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// How far a candidate is from being includable.
+///
+/// The statement table only knows the validity of the candidate. Its availability is filled in
+/// by the availability table, with `AvailabilityTable::complete_status`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandidateStatus<Group, AuthorityId> {
+	/// The group that the candidate is in.
+	pub group_id: Group,
+	/// How many validity votes are currently witnessed.
+	pub validity_votes: usize,
+	/// How many of the validity votes are implicit, from authorities seconding the candidate.
+	pub seconded_votes: usize,
+	/// The summed weight of the validity votes, see [`Context::vote_weight`].
+	pub validity_weight: u64,
+	/// How many validity votes are needed for the candidate to be includable.
+	///
+	/// This is `usize::MAX` if the size of the group is unknown.
+	pub validity_threshold: usize,
+	/// How many distinct chunks of the candidate are attested, or `None` if its availability
+	/// isn't known.
+	pub available_chunks: Option<usize>,
+	/// How many distinct chunks need to be attested for the candidate to be available, or
+	/// `None` if its availability isn't known.
+	pub availability_threshold: Option<usize>,
+	/// The authorities which flagged the candidate invalid.
+	///
+	/// Statements only attest the validity of candidates, so the table can't know of any and
+	/// leaves this empty. It is for callers which learn of invalidity by other means, e.g.
+	/// disputes.
+	pub flagged_invalid_by: Vec<AuthorityId>,
+}
+
+impl<Group, AuthorityId> CandidateStatus<Group, AuthorityId> {
+	/// Whether the candidate has enough validity votes to be included.
+	pub fn is_includable(&self) -> bool {
+		self.validity_votes >= self.validity_threshold
+	}
+
+	/// How many more validity votes are needed for the candidate to be includable.
+	pub fn missing_validity_votes(&self) -> usize {
+		self.validity_threshold.saturating_sub(self.validity_votes)
+	}
+
+	/// How many more chunks need to be attested for the candidate to be available, or `None` if
+	/// its availability isn't known.
+	pub fn missing_chunks(&self) -> Option<usize> {
+		Some(self.availability_threshold?.saturating_sub(self.available_chunks?))
+	}
+}
+
+/// Type alias for the status of a candidate corresponding to context type.
+pub type CandidateStatusFor<Ctx> =
+	CandidateStatus<<Ctx as Context>::GroupId, <Ctx as Context>::AuthorityId>;
+
+/// A validity attestation.
+#[derive(Clone, PartialEq, Eq, Debug, Decode, Encode)]
+pub enum ValidityAttestation<Signature> {
+	/// implicit validity attestation by issuing.
+	/// This corresponds to issuance of a `Candidate` statement.
+	Implicit(Signature),
+	/// An explicit attestation. This corresponds to issuance of a
+	/// `Valid` statement.
+	Explicit(Signature),
+}
+
+impl Into<PrimitiveValidityAttestation> for ValidityAttestation<ValidatorSignature> {
+	fn into(self) -> PrimitiveValidityAttestation {
+		match self {
+			Self::Implicit(s) => PrimitiveValidityAttestation::Implicit(s),
+			Self::Explicit(s) => PrimitiveValidityAttestation::Explicit(s),
+		}
+	}
+}
+
+/// An attested-to candidate.
+///
+/// This is self-contained: it carries the candidate, the group it was backed in and every
+/// signature backing it, so it can be verified without access to the table which produced it.
+#[derive(Clone, PartialEq, Eq, Debug, Decode, Encode)]
+pub struct AttestedCandidate<Group, Candidate, AuthorityId, Signature> {
+	/// The group ID that the candidate is in.
+	pub group_id: Group,
+	/// The candidate data.
+	pub candidate: Candidate,
+	/// Validity attestations.
+	pub validity_votes: Vec<(AuthorityId, ValidityAttestation<Signature>)>,
+}
+
+/// Type alias for the attested candidate corresponding to context type.
+pub type AttestedCandidateFor<Ctx> = AttestedCandidate<
+	<Ctx as Context>::GroupId,
+	<Ctx as Context>::Candidate,
+	<Ctx as Context>::AuthorityId,
+	<Ctx as Context>::Signature,
+>;
+
+/// Reasons an [`AttestedCandidate`] can fail verification.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum AttestationError<AuthorityId> {
+	/// The group of the candidate is unknown.
+	UnknownGroup,
+	/// An attestation was made by an authority which isn't a member of the group.
+	UnauthorizedVote(AuthorityId),
+	/// An authority attested more than once.
+	DuplicateVote(AuthorityId),
+	/// An attestation carries an invalid signature.
+	BadSignature(AuthorityId),
+	/// Not enough attestations for the candidate to be includable.
+	InsufficientVotes {
+		/// How many attestations are present.
+		have: usize,
+		/// How many attestations are needed.
+		need: usize,
+	},
+}
+
+impl<Group, Candidate, AuthorityId, Signature>
+	AttestedCandidate<Group, Candidate, AuthorityId, Signature>
+where
+	Candidate: Clone,
+	AuthorityId: Eq + Clone,
+	Signature: Clone,
+{
+	/// Reconstruct the signed statements backing this candidate, in the order of the validity
+	/// votes.
+	///
+	/// Implicit attestations correspond to `Seconded` statements and explicit ones to `Valid`
+	/// statements on the candidate digest.
+	pub fn signed_statements<Ctx>(
+		&self,
+	) -> Vec<SignedStatement<Candidate, Ctx::Digest, AuthorityId, Signature>>
+	where
+		Ctx: Context<
+			GroupId = Group,
+			Candidate = Candidate,
+			AuthorityId = AuthorityId,
+			Signature = Signature,
+		>,
+	{
+		let digest = Ctx::candidate_digest(&self.candidate);
+		self.validity_votes
+			.iter()
+			.map(|(sender, attestation)| {
+				let (statement, signature) = match attestation {
+					ValidityAttestation::Implicit(s) =>
+						(Statement::Seconded(self.candidate.clone()), s),
+					ValidityAttestation::Explicit(s) => (Statement::Valid(digest.clone()), s),
+				};
+
+				SignedStatement { statement, signature: signature.clone(), sender: sender.clone() }
+			})
+			.collect()
+	}
+
+	/// Verify that this is a valid attestation of an includable candidate.
+	///
+	/// Checks that every attesting authority is a distinct member of the group, that every
+	/// signature passes `check_signature` and that there are at least as many attestations as
+	/// the effective backing threshold of the group.
+	pub fn verify<Ctx, F>(
+		&self,
+		context: &Ctx,
+		minimum_backing_votes: u32,
+		mut check_signature: F,
+	) -> Result<(), AttestationError<AuthorityId>>
+	where
+		Ctx: Context<
+			GroupId = Group,
+			Candidate = Candidate,
+			AuthorityId = AuthorityId,
+			Signature = Signature,
+		>,
+		F: FnMut(&SignedStatement<Candidate, Ctx::Digest, AuthorityId, Signature>) -> bool,
+	{
+		let group_size =
+			context.get_group_size(&self.group_id).ok_or(AttestationError::UnknownGroup)?;
+
+		// groups are small, so a vector does as well as a set, and needs no hasher.
+		let mut seen = Vec::with_capacity(self.validity_votes.len());
+		for statement in self.signed_statements::<Ctx>() {
+			if !context.is_member_of(&statement.sender, &self.group_id) {
+				return Err(AttestationError::UnauthorizedVote(statement.sender))
+			}
+
+			if seen.contains(&statement.sender) {
+				return Err(AttestationError::DuplicateVote(statement.sender))
+			}
+			seen.push(statement.sender.clone());
+
+			if !check_signature(&statement) {
+				return Err(AttestationError::BadSignature(statement.sender))
+			}
+		}
+
+		let need = effective_minimum_backing_votes(group_size, minimum_backing_votes);
+		if seen.len() < need {
+			return Err(AttestationError::InsufficientVotes { have: seen.len(), need })
+		}
+
+		Ok(())
+	}
+}
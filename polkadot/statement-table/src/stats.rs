@@ -0,0 +1,180 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Counters of the work done by a table.
+//!
+//! Every [`Table`](crate::Table) counts how often, and for how long, it imports statements,
+//! tallies the votes on candidates and builds proposals. The counters are read with
+//! [`Table::stats`](crate::Table::stats). The time each validator takes to vote can be tracked
+//! as well, see [`Table::vote_latency`](crate::Table::vote_latency), and is reported along with
+//! the rest of its participation by
+//! [`TableInspector::validators`](crate::inspect::TableInspector::validators).
+
+use std::{
+	sync::atomic::{AtomicU64, Ordering},
+	time::{Duration, Instant},
+};
+
+/// How often an operation was performed, and the total time spent on it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperationStats {
+	/// The number of times the operation was performed.
+	pub count: u64,
+	/// The total time spent on the operation.
+	pub time: Duration,
+}
+
+impl OperationStats {
+	/// The average time spent on the operation, or zero if it was never performed.
+	pub fn average(&self) -> Duration {
+		match self.count {
+			0 => Duration::ZERO,
+			count => self.time / count.min(u32::MAX as u64) as u32,
+		}
+	}
+
+	// note that the operation was performed, taking `time`.
+	pub(crate) fn record(&mut self, time: Duration) {
+		self.count += 1;
+		self.time += time;
+	}
+}
+
+/// The work done by a table since it was created, or since its stats were reset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats {
+	/// Imports of statements.
+	pub imports: OperationStats,
+	/// Tallies of the votes on a candidate, to check whether it is includable.
+	pub tallies: OperationStats,
+	/// Proposals built out of the table.
+	pub proposals: OperationStats,
+}
+
+// an operation counter, usable from behind a shared reference.
+#[derive(Debug, Default)]
+pub(crate) struct Counter {
+	count: AtomicU64,
+	nanos: AtomicU64,
+}
+
+impl Counter {
+	// note that an operation which started at `started` is done.
+	pub(crate) fn record(&self, started: Instant) {
+		let nanos = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+		self.count.fetch_add(1, Ordering::Relaxed);
+		self.nanos.fetch_add(nanos, Ordering::Relaxed);
+	}
+
+	fn get(&self) -> OperationStats {
+		OperationStats {
+			count: self.count.load(Ordering::Relaxed),
+			time: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+		}
+	}
+
+	fn reset(&self) {
+		self.count.store(0, Ordering::Relaxed);
+		self.nanos.store(0, Ordering::Relaxed);
+	}
+}
+
+// a copy of the counter as of now, counting on its own from then on.
+impl Clone for Counter {
+	fn clone(&self) -> Self {
+		Counter {
+			count: AtomicU64::new(self.count.load(Ordering::Relaxed)),
+			nanos: AtomicU64::new(self.nanos.load(Ordering::Relaxed)),
+		}
+	}
+}
+
+// the counters of a table.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Stats {
+	pub(crate) imports: Counter,
+	pub(crate) tallies: Counter,
+	pub(crate) proposals: Counter,
+}
+
+impl Stats {
+	pub(crate) fn get(&self) -> TableStats {
+		TableStats {
+			imports: self.imports.get(),
+			tallies: self.tallies.get(),
+			proposals: self.proposals.get(),
+		}
+	}
+
+	pub(crate) fn reset(&self) {
+		self.imports.reset();
+		self.tallies.reset();
+		self.proposals.reset();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		generic::Statement,
+		inspect::TableInspector,
+		proposal::PriorityThenBacking,
+		router::tests::{context_and_table, seconded, seconded_other, valid},
+		test_utils::sign,
+		ProposalConfig,
+	};
+
+	#[test]
+	fn table_work_is_counted() {
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+
+		let proposal =
+			table.build_proposal(&context, 2, &ProposalConfig::default(), &PriorityThenBacking);
+		assert_eq!(proposal.len(), 1);
+
+		let stats = table.stats();
+		assert_eq!(stats.imports.count, 2);
+		assert_eq!(stats.tallies.count, 1);
+		assert_eq!(stats.proposals.count, 1);
+		assert!(stats.imports.average() <= stats.imports.time);
+
+		table.reset_stats();
+		assert_eq!(table.stats(), Default::default());
+	}
+
+	#[test]
+	fn vote_latency_is_tracked() {
+		let (context, mut table) = context_and_table();
+		table.import_statement(&context, 0, seconded(1));
+		table.import_statement(&context, 0, valid(2));
+		// votes aren't timed unless asked for.
+		assert_eq!(table.vote_latency(&2), Default::default());
+
+		table.set_vote_latency_tracking(true);
+		table.import_statement(&context, 0, seconded_other(2));
+		table.import_statement(&context, 0, sign(1, Statement::Valid(101), 0));
+		assert_eq!(table.vote_latency(&1).count, 1);
+		assert_eq!(table.vote_latency(&2).count, 0);
+
+		let validators = TableInspector::new(&table, &context, 2).validators();
+		assert_eq!(validators[0].vote_latency, table.vote_latency(&1));
+		assert_eq!(validators[0].awaiting_vote, 0);
+		assert_eq!(table.vote_latency(&3), Default::default());
+	}
+}
@@ -0,0 +1,636 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistence of a statement table.
+//!
+//! A [`PersistentTable`] writes the candidates and votes it imports through to a
+//! [`TableStore`], so that a restarted validator can resume the current round from the store
+//! instead of waiting for the statements to be gossiped again.
+//!
+//! Each candidate is stored once, along with its group, and each vote is stored under the
+//! digest of its candidate and its sender. Misbehavior is not stored: statements which are
+//! detected as misbehavior are not imported, so they are not persisted either.
+//!
+//! Candidates which are evicted from the table stay in the store, along with their votes, until
+//! the store is compacted, see [`PersistentTable::compact`] and [`compact_periodically`].
+
+use std::{
+	io,
+	ops::ControlFlow,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use kvdb::{DBTransaction, KeyValueDB};
+use parity_scale_codec::{Decode, Encode};
+
+use crate::{
+	agreement::Timer,
+	generic::{
+		Config, Context, MemoryLimit, SignedStatement, SignedStatementFor, Statement, Summary,
+		Table,
+	},
+	LOCK_PROOF,
+};
+
+const LOG_TARGET: &str = "parachain::statement-table";
+
+const CANDIDATE_PREFIX: &[u8] = b"candidate";
+const VOTE_PREFIX: &[u8] = b"vote";
+
+/// A change to a [`TableStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreOp {
+	/// Store `value` under `key`.
+	Put {
+		/// The key.
+		key: Vec<u8>,
+		/// The value.
+		value: Vec<u8>,
+	},
+	/// Remove the value stored under `key`.
+	Delete {
+		/// The key.
+		key: Vec<u8>,
+	},
+	/// Remove all values stored under keys starting with `prefix`.
+	DeletePrefix {
+		/// The prefix.
+		prefix: Vec<u8>,
+	},
+}
+
+/// A set of changes to a [`TableStore`], which are applied atomically.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StoreTransaction {
+	/// The changes, in order.
+	pub ops: Vec<StoreOp>,
+}
+
+impl StoreTransaction {
+	/// Store `value` under `key`.
+	pub fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.ops.push(StoreOp::Put { key, value });
+	}
+
+	/// Remove the value stored under `key`.
+	pub fn delete(&mut self, key: Vec<u8>) {
+		self.ops.push(StoreOp::Delete { key });
+	}
+
+	/// Remove all values stored under keys starting with `prefix`.
+	pub fn delete_prefix(&mut self, prefix: Vec<u8>) {
+		self.ops.push(StoreOp::DeletePrefix { prefix });
+	}
+}
+
+/// A key-value store a table is persisted to.
+pub trait TableStore {
+	/// Get the value stored under `key`.
+	fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+	/// Get all keys starting with `prefix`, along with their values, ordered by key.
+	fn iter_with_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+	/// Call `f` with the keys starting with `prefix`, ordered by key, until it breaks.
+	///
+	/// The keys aren't collected, so the store can be scanned in a single pass without being held
+	/// in memory.
+	fn keys_with_prefix(
+		&self,
+		prefix: &[u8],
+		f: &mut dyn FnMut(&[u8]) -> ControlFlow<()>,
+	) -> io::Result<()>;
+
+	/// Apply the changes of `transaction` atomically.
+	fn write(&self, transaction: StoreTransaction) -> io::Result<()>;
+}
+
+/// A [`TableStore`] in a column of a [`KeyValueDB`].
+#[derive(Clone)]
+pub struct KvdbStore {
+	db: Arc<dyn KeyValueDB>,
+	column: u32,
+}
+
+impl KvdbStore {
+	/// Store the table in `column` of `db`, which should not be used for anything else.
+	pub fn new(db: Arc<dyn KeyValueDB>, column: u32) -> Self {
+		KvdbStore { db, column }
+	}
+}
+
+impl TableStore for KvdbStore {
+	fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+		self.db.get(self.column, key)
+	}
+
+	fn iter_with_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+		self.db
+			.iter_with_prefix(self.column, prefix)
+			.map(|entry| entry.map(|(key, value)| (key.to_vec(), value)))
+			.collect()
+	}
+
+	// kvdb can't iterate over keys alone, so the values are read along with them.
+	fn keys_with_prefix(
+		&self,
+		prefix: &[u8],
+		f: &mut dyn FnMut(&[u8]) -> ControlFlow<()>,
+	) -> io::Result<()> {
+		for entry in self.db.iter_with_prefix(self.column, prefix) {
+			let (key, _) = entry?;
+			if f(&key).is_break() {
+				break
+			}
+		}
+
+		Ok(())
+	}
+
+	fn write(&self, transaction: StoreTransaction) -> io::Result<()> {
+		let mut db_transaction = DBTransaction::with_capacity(transaction.ops.len());
+		for op in transaction.ops {
+			match op {
+				StoreOp::Put { key, value } => db_transaction.put_vec(self.column, &key, value),
+				StoreOp::Delete { key } => db_transaction.delete(self.column, &key),
+				StoreOp::DeletePrefix { prefix } =>
+					db_transaction.delete_prefix(self.column, &prefix),
+			}
+		}
+
+		self.db.write(db_transaction)
+	}
+}
+
+// a vote as it is stored, under the digest of its candidate and its sender.
+#[derive(Encode, Decode)]
+enum StoredVote<Signature> {
+	#[codec(index = 1)]
+	Seconded(Signature),
+	#[codec(index = 2)]
+	Valid(Signature),
+}
+
+/// A [`Table`] whose candidates and votes are written through to a [`TableStore`].
+pub struct PersistentTable<Ctx: Context, Store> {
+	table: Table<Ctx>,
+	store: Store,
+	allow_multiple_seconded: bool,
+}
+
+impl<Ctx: Context, Store: TableStore> PersistentTable<Ctx, Store>
+where
+	Ctx::Candidate: Encode + Decode,
+	Ctx::Digest: Encode,
+	Ctx::GroupId: Encode + Decode,
+	Ctx::AuthorityId: Encode + Decode,
+	Ctx::Signature: Encode + Decode,
+{
+	/// Open a table from a `Config`, restoring the candidates and votes in `store`.
+	///
+	/// The votes are imported again, seconding votes first, so the restored table holds the
+	/// same candidates and votes as the one which was persisted. The stored state is upgraded
+	/// to the current version first, see [`migrate`](crate::migration::migrate).
+	pub fn open(context: &Ctx, config: Config, store: Store) -> io::Result<Self> {
+		crate::migration::migrate(&store)?;
+		let allow_multiple_seconded = config.allow_multiple_seconded;
+		let mut table = Table::new(config);
+		let mut valid = Vec::new();
+		for (_, value) in store.iter_with_prefix(CANDIDATE_PREFIX)? {
+			let (group_id, candidate) =
+				<(Ctx::GroupId, Ctx::Candidate)>::decode(&mut &value[..]).map_err(invalid_data)?;
+			let prefix = vote_prefix::<Ctx>(&Ctx::candidate_digest(&candidate));
+			for (key, value) in store.iter_with_prefix(&prefix)? {
+				let sender =
+					Ctx::AuthorityId::decode(&mut &key[prefix.len()..]).map_err(invalid_data)?;
+				match StoredVote::decode(&mut &value[..]).map_err(invalid_data)? {
+					StoredVote::Seconded(signature) => {
+						let statement = SignedStatement {
+							statement: Statement::Seconded(candidate.clone()),
+							signature,
+							sender,
+						};
+						table.import_statement(context, group_id.clone(), statement);
+					},
+					StoredVote::Valid(signature) => {
+						let statement = SignedStatement {
+							statement: Statement::Valid(Ctx::candidate_digest(&candidate)),
+							signature,
+							sender,
+						};
+						valid.push((group_id.clone(), statement));
+					},
+				}
+			}
+		}
+
+		for (group_id, statement) in valid {
+			table.import_statement(context, group_id, statement);
+		}
+
+		Ok(PersistentTable { table, store, allow_multiple_seconded })
+	}
+
+	/// Import a signed statement, as with [`Table::import_statement`], and write it through to
+	/// the store if it was imported.
+	///
+	/// Statements seconding an announced candidate import the announcements as well, which are
+	/// written along with it. If writing fails, the imports are undone.
+	pub fn import_statement(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> io::Result<Option<Summary<Ctx::Digest, Ctx::GroupId>>> {
+		let digest = statement.digest::<Ctx>();
+		self.write_through(context, &digest, |table| {
+			table.import_statement(context, group_id, statement)
+		})
+	}
+
+	/// Note an announcement of a candidate, as with [`Table::note_announcement`], and write it
+	/// through to the store if it was imported as a statement seconding a known candidate.
+	///
+	/// If writing fails, the import is undone.
+	pub fn note_announcement(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		digest: Ctx::Digest,
+		sender: Ctx::AuthorityId,
+		signature: Ctx::Signature,
+	) -> io::Result<bool> {
+		self.write_through(context, &digest.clone(), |table| {
+			table.note_announcement(context, group_id, digest, sender, signature)
+		})
+	}
+
+	/// Provide the body of an announced candidate, as with [`Table::import_body`], and write
+	/// the announcements imported as statements seconding it through to the store.
+	///
+	/// If writing fails, the imports are undone.
+	pub fn import_body(
+		&mut self,
+		context: &Ctx,
+		candidate: Ctx::Candidate,
+	) -> io::Result<Option<Summary<Ctx::Digest, Ctx::GroupId>>> {
+		let digest = Ctx::candidate_digest(&candidate);
+		self.write_through(context, &digest, |table| table.import_body(context, candidate))
+	}
+
+	// apply `import` to the table, and write the votes it imported on the candidate with the
+	// given `digest` through to the store. the candidate is only written if it is new.
+	fn write_through<R>(
+		&mut self,
+		context: &Ctx,
+		digest: &Ctx::Digest,
+		import: impl FnOnce(&mut Table<Ctx>) -> R,
+	) -> io::Result<R> {
+		let known = self.table.contains_candidate(digest);
+		let marker = self.table.marker();
+		let result = import(&mut self.table);
+
+		let mut transaction = StoreTransaction::default();
+		let mut put_candidate = !known;
+		for (group_id, statement) in self.table.statements_since(marker).0 {
+			let vote_digest = statement.digest::<Ctx>();
+			let vote = match statement.statement {
+				Statement::Seconded(candidate) => {
+					if put_candidate && &vote_digest == digest {
+						put_candidate = false;
+						transaction.put(
+							candidate_key::<Ctx>(&vote_digest),
+							(&group_id, &candidate).encode(),
+						);
+					}
+					StoredVote::Seconded(statement.signature)
+				},
+				Statement::Valid(_) => StoredVote::Valid(statement.signature),
+			};
+			let mut key = vote_prefix::<Ctx>(&vote_digest);
+			statement.sender.encode_to(&mut key);
+			transaction.put(key, vote.encode());
+		}
+
+		if transaction.ops.is_empty() {
+			return Ok(result)
+		}
+
+		if let Err(e) = self.store.write(transaction) {
+			self.table.revert_to(context, marker);
+			return Err(e)
+		}
+
+		Ok(result)
+	}
+
+	/// Bound the memory used by the table, see [`Table::set_memory_limit`].
+	///
+	/// Evicted candidates are removed from the store when it is compacted.
+	pub fn set_memory_limit(&mut self, limit: Option<MemoryLimit<Ctx::Candidate>>) {
+		self.table.set_memory_limit(limit);
+	}
+
+	/// Remove everything from the table and the store, e.g. at the end of a round.
+	pub fn clear(&mut self) -> io::Result<()> {
+		let mut transaction = StoreTransaction::default();
+		transaction.delete_prefix(CANDIDATE_PREFIX.to_vec());
+		transaction.delete_prefix(VOTE_PREFIX.to_vec());
+		self.store.write(transaction)?;
+
+		self.table = Table::new(Config { allow_multiple_seconded: self.allow_multiple_seconded });
+		Ok(())
+	}
+
+	/// Access the table.
+	pub fn table(&self) -> &Table<Ctx> {
+		&self.table
+	}
+
+	/// Access the store.
+	pub fn store(&self) -> &Store {
+		&self.store
+	}
+
+	/// Consume the persistent table, returning the table and the store.
+	pub fn into_inner(self) -> (Table<Ctx>, Store) {
+		(self.table, self.store)
+	}
+}
+
+impl<Ctx: Context, Store: TableStore> PersistentTable<Ctx, Store>
+where
+	Ctx::Digest: Decode,
+{
+	/// Remove up to `max_removals` entries from the store which are superseded, returning how
+	/// many were removed.
+	///
+	/// These are the candidates which are no longer in the table, and the votes on them. Votes
+	/// are stored under their candidate and sender, so a duplicate vote overwrites the stored
+	/// one rather than taking up more space. A pass which removes fewer than `max_removals`
+	/// entries has caught up.
+	///
+	/// The keys are scanned once per pass, stopping once `max_removals` entries are found. Whether
+	/// the values are read along with them depends on the store.
+	pub fn compact(&mut self, max_removals: usize) -> io::Result<usize> {
+		let mut transaction = StoreTransaction::default();
+		for prefix in [CANDIDATE_PREFIX, VOTE_PREFIX] {
+			let mut decoded = Ok(());
+			self.store.keys_with_prefix(prefix, &mut |key| {
+				if transaction.ops.len() >= max_removals {
+					return ControlFlow::Break(())
+				}
+
+				match Ctx::Digest::decode(&mut &key[prefix.len()..]) {
+					Ok(digest) if !self.table.contains_candidate(&digest) =>
+						transaction.delete(key.to_vec()),
+					Ok(_) => (),
+					Err(e) => {
+						decoded = Err(invalid_data(e));
+						return ControlFlow::Break(())
+					},
+				}
+				ControlFlow::Continue(())
+			})?;
+			decoded?;
+		}
+
+		let removed = transaction.ops.len();
+		if removed > 0 {
+			self.store.write(transaction)?;
+		}
+
+		Ok(removed)
+	}
+}
+
+/// How often to compact a store, and how much in each pass, see [`compact_periodically`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionConfig {
+	/// The time between compaction passes.
+	pub interval: Duration,
+	/// The maximum number of entries removed in a pass. This bounds the I/O of each pass, and
+	/// the time it holds the table.
+	pub max_removals: usize,
+}
+
+impl Default for CompactionConfig {
+	fn default() -> Self {
+		CompactionConfig { interval: Duration::from_secs(30), max_removals: 1000 }
+	}
+}
+
+/// Compact the store of `table` every `config.interval`, e.g. in a background task.
+///
+/// Passes are small and spaced out, so that they don't compete with imports for the table or
+/// for I/O. This only returns if compacting fails.
+pub async fn compact_periodically<Ctx, Store, T>(
+	table: &Mutex<PersistentTable<Ctx, Store>>,
+	config: CompactionConfig,
+	timer: T,
+) -> io::Error
+where
+	Ctx: Context,
+	Ctx::Digest: Decode,
+	Store: TableStore,
+	T: Timer,
+{
+	loop {
+		timer.delay(config.interval).await;
+
+		let compacted = table.lock().expect(LOCK_PROOF).compact(config.max_removals);
+		match compacted {
+			Ok(removed) => gum::trace!(target: LOG_TARGET, removed, "Compacted table store"),
+			Err(e) => return e,
+		}
+	}
+}
+
+fn candidate_key<Ctx: Context>(digest: &Ctx::Digest) -> Vec<u8>
+where
+	Ctx::Digest: Encode,
+{
+	let mut key = CANDIDATE_PREFIX.to_vec();
+	digest.encode_to(&mut key);
+	key
+}
+
+fn vote_prefix<Ctx: Context>(digest: &Ctx::Digest) -> Vec<u8>
+where
+	Ctx::Digest: Encode,
+{
+	let mut prefix = VOTE_PREFIX.to_vec();
+	digest.encode_to(&mut prefix);
+	prefix
+}
+
+pub(crate) fn invalid_data(error: parity_scale_codec::Error) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		router::tests::{context_and_table, seconded, seconded_other, valid, DIGEST},
+		test_utils::{sign, TestCandidate, TestContext},
+	};
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	// a store which records the transactions written to it, and fails to write them on demand.
+	#[derive(Default)]
+	struct FlakyStore {
+		inner: Mutex<Vec<StoreTransaction>>,
+		failing: AtomicBool,
+	}
+
+	impl TableStore for &FlakyStore {
+		fn get(&self, _: &[u8]) -> io::Result<Option<Vec<u8>>> {
+			Ok(None)
+		}
+
+		fn iter_with_prefix(&self, _: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+			Ok(Vec::new())
+		}
+
+		fn keys_with_prefix(
+			&self,
+			_: &[u8],
+			_: &mut dyn FnMut(&[u8]) -> ControlFlow<()>,
+		) -> io::Result<()> {
+			Ok(())
+		}
+
+		fn write(&self, transaction: StoreTransaction) -> io::Result<()> {
+			if self.failing.load(Ordering::Relaxed) {
+				return Err(io::Error::other("disk full"))
+			}
+			self.inner.lock().unwrap().push(transaction);
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn table_is_restored_from_store() {
+		let (context, _) = context_and_table();
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		let config = || Config { allow_multiple_seconded: false };
+
+		let mut table = PersistentTable::open(&context, config(), store.clone()).unwrap();
+		assert!(table.import_statement(&context, 0, seconded(1)).unwrap().is_some());
+		assert!(table.import_statement(&context, 0, valid(2)).unwrap().is_some());
+		// duplicates are not written again.
+		assert!(table.import_statement(&context, 0, valid(2)).unwrap().is_none());
+		assert_eq!(store.iter_with_prefix(VOTE_PREFIX).unwrap().len(), 2);
+		assert!(store.get(&candidate_key::<TestContext>(&100)).unwrap().is_some());
+
+		let restored = PersistentTable::open(&context, config(), store.clone()).unwrap();
+		let status = restored.table().candidate_status(&100, &context, 2).unwrap();
+		assert_eq!(status.validity_votes, 2);
+		assert_eq!(status.seconded_votes, 1);
+		assert_eq!(restored.table().get_candidate(&100), Some(&TestCandidate::new(0, 100)));
+
+		let (_, store) = restored.into_inner();
+		let mut table = PersistentTable::open(&context, config(), store.clone()).unwrap();
+		table.clear().unwrap();
+		assert!(table.table().get_candidate(&100).is_none());
+		// only the version of the stored state is left.
+		assert_eq!(store.iter_with_prefix(&[]).unwrap().len(), 1);
+	}
+
+	#[test]
+	fn failed_writes_are_undone() {
+		let (context, _) = context_and_table();
+		let store = FlakyStore::default();
+		let config = Config { allow_multiple_seconded: false };
+		let mut table = PersistentTable::open(&context, config, &store).unwrap();
+
+		store.failing.store(true, Ordering::Relaxed);
+		assert!(table.import_statement(&context, 0, seconded(1)).is_err());
+		assert!(!table.table().contains_candidate(&DIGEST));
+		table.table().assert_invariants(&context);
+
+		// announced seconders are written along with the statement providing the body, and the
+		// candidate is written once.
+		store.failing.store(false, Ordering::Relaxed);
+		let announcement = seconded(2).signature;
+		assert!(table.note_announcement(&context, 0, DIGEST, 2, announcement).unwrap());
+		let summary = table.import_statement(&context, 0, seconded(1)).unwrap().unwrap();
+		assert_eq!(summary.validity_votes, 2);
+		table.table().assert_invariants(&context);
+
+		let written = store.inner.lock().unwrap().pop().unwrap();
+		let puts = |prefix: &[u8]| {
+			written
+				.ops
+				.iter()
+				.filter(|op| matches!(op, StoreOp::Put { key, .. } if key.starts_with(prefix)))
+				.count()
+		};
+		assert_eq!(puts(CANDIDATE_PREFIX), 1);
+		assert_eq!(puts(VOTE_PREFIX), 2);
+	}
+
+	#[test]
+	fn evicted_candidates_are_compacted() {
+		let (context, _) = context_and_table();
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		let mut table = PersistentTable::open(
+			&context,
+			Config { allow_multiple_seconded: true },
+			store.clone(),
+		)
+		.unwrap();
+		for statement in [seconded(1), seconded_other(1)] {
+			table.import_statement(&context, 0, statement).unwrap();
+		}
+		table.import_statement(&context, 0, valid(2)).unwrap();
+		assert_eq!(table.compact(10).unwrap(), 0);
+
+		// keeps the candidate with two votes.
+		let limit = table.table().memory_usage() - 1;
+		table.set_memory_limit(Some(MemoryLimit::new(limit)));
+		assert!(table.table().get_candidate(&101).is_none());
+
+		assert_eq!(table.compact(1).unwrap(), 1);
+		assert_eq!(table.compact(10).unwrap(), 1);
+		assert_eq!(table.compact(10).unwrap(), 0);
+		// the candidate, its votes and the version of the stored state.
+		assert_eq!(store.iter_with_prefix(&[]).unwrap().len(), 4);
+	}
+
+	#[test]
+	fn compaction_scans_all_keys() {
+		let (context, _) = context_and_table();
+		let store = KvdbStore::new(Arc::new(kvdb_memorydb::create(1)), 0);
+		let config = Config { allow_multiple_seconded: true };
+		let mut table = PersistentTable::open(&context, config, store.clone()).unwrap();
+
+		let candidates = 300;
+		for body in 0..candidates {
+			let statement = sign(1, Statement::Seconded(TestCandidate::new(0, body)), 0);
+			table.import_statement(&context, 0, statement).unwrap().unwrap();
+		}
+		table.set_memory_limit(Some(MemoryLimit::new(0)));
+
+		// a pass stops once it found `max_removals` entries.
+		assert_eq!(table.compact(10).unwrap(), 10);
+		let removed = table.compact(usize::MAX).unwrap();
+		assert_eq!(removed, 2 * candidates as usize - 10);
+		assert_eq!(store.iter_with_prefix(&[]).unwrap().len(), 1);
+	}
+}
@@ -0,0 +1,1022 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A mock [`Context`] for tests of code using a [`Table`](crate::Table).
+//!
+//! A [`TestContextBuilder`] assigns authorities to groups, and optionally the availability
+//! guarantors of groups, the weights of authorities and the thresholds candidates need to
+//! reach. The [`TestContext`] it builds signs statements verifiably and knows the number of
+//! validity votes candidates need to be includable, so tests only have to describe their
+//! groups. It is the context of the tests of this crate as well.
+//!
+//! A [`Workload`] generates large streams of statements in such a context, with configurable
+//! shares of duplicate and invalid statements, for soak tests and capacity planning.
+//!
+//! [`mutations`] derives a corpus of tampered statements from valid ones, and
+//! [`accepted_mutations`] checks that the signature verification of a
+//! [`VerifyContext`] rejects them.
+//!
+//! The [`simple`] context is the one of the tests of the table itself.
+//!
+//! This module is available to other crates with the `test-utils` feature.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use parity_scale_codec::{Decode, DecodeAll, Encode};
+
+use crate::{
+	availability::{
+		AvailabilityBitfield, AvailabilityContext, ChunkAttestation, ChunkIndex, ChunkRevocation,
+		ErasureParams, SamplingAttestation, SignedAvailabilityBitfield,
+		SignedAvailabilityBitfieldFor, SignedChunkAttestation, SignedChunkAttestationFor,
+		SignedChunkRevocation, SignedChunkRevocationFor, SignedSamplingAttestation,
+		SignedSamplingAttestationFor,
+	},
+	generic::{
+		signing_payload, Context, GroupAssignment, SignedStatement, SignedStatementFor, Statement,
+		ValidatorSet,
+	},
+	verify::VerifyContext,
+};
+
+/// The signing context statements are signed under in a [`TestContext`].
+pub const SIGNING_CONTEXT: u32 = 0;
+
+/// An authority in a [`TestContext`].
+pub type AuthorityId = u32;
+
+/// A group in a [`TestContext`].
+pub type GroupId = u32;
+
+/// The digest of a [`TestCandidate`].
+pub type Digest = u64;
+
+/// A candidate in a [`TestContext`].
+///
+/// Like the hash of a real candidate, its digest commits to the group it is meant for, see
+/// [`TestCandidate::digest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct TestCandidate {
+	/// The group the candidate is meant for.
+	pub group_id: GroupId,
+	/// The body of the candidate.
+	pub body: Digest,
+}
+
+impl TestCandidate {
+	/// The candidate of `group_id` with the given `body`.
+	pub fn new(group_id: GroupId, body: Digest) -> Self {
+		TestCandidate { group_id, body }
+	}
+
+	/// The digest of the candidate: the group in the upper half and the lower half of the body
+	/// in the lower half, so that the digests of candidates of group `0` are their bodies.
+	pub fn digest(&self) -> Digest {
+		(Digest::from(self.group_id) << 32) | (self.body & Digest::from(u32::MAX))
+	}
+}
+
+/// A signature in a [`TestContext`], which carries the signed payload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct TestSignature {
+	/// The signer.
+	pub signer: AuthorityId,
+	/// The signed payload.
+	pub payload: Vec<u8>,
+	/// Randomness, to make distinct signatures of the same payload.
+	pub nonce: u8,
+}
+
+impl TestSignature {
+	/// Whether this is a signature of `signer` on `payload`.
+	pub fn is_valid(&self, signer: &AuthorityId, payload: &[u8]) -> bool {
+		self.signer == *signer && self.payload == payload
+	}
+}
+
+/// Sign `statement` as `sender` under [`SIGNING_CONTEXT`], randomized by `nonce`.
+///
+/// Signing doesn't depend on the groups of a [`TestContext`], so fixtures can sign statements
+/// without one.
+pub fn sign(
+	sender: AuthorityId,
+	statement: Statement<TestCandidate, Digest>,
+	nonce: u8,
+) -> SignedStatementFor<TestContext> {
+	let payload = signing_payload::<TestContext, _>(&statement, &SIGNING_CONTEXT);
+	SignedStatement {
+		statement,
+		signature: TestSignature { signer: sender, payload, nonce },
+		sender,
+	}
+}
+
+/// Builds a [`TestContext`].
+#[derive(Debug, Clone)]
+pub struct TestContextBuilder {
+	members: BTreeMap<GroupId, BTreeSet<AuthorityId>>,
+	guarantors: BTreeMap<GroupId, Vec<AuthorityId>>,
+	group_sizes: HashMap<GroupId, usize>,
+	weights: HashMap<AuthorityId, u64>,
+	data_chunks: HashMap<GroupId, u32>,
+	minimum_backing_votes: u32,
+}
+
+impl Default for TestContextBuilder {
+	fn default() -> Self {
+		TestContextBuilder {
+			members: BTreeMap::new(),
+			guarantors: BTreeMap::new(),
+			group_sizes: HashMap::new(),
+			weights: HashMap::new(),
+			data_chunks: HashMap::new(),
+			minimum_backing_votes: 2,
+		}
+	}
+}
+
+impl TestContextBuilder {
+	/// A builder of a context without groups, in which candidates need 2 validity votes.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add `members` to the group `group_id`. Authorities can be members of several groups.
+	pub fn with_group(
+		mut self,
+		group_id: GroupId,
+		members: impl IntoIterator<Item = AuthorityId>,
+	) -> Self {
+		self.members.entry(group_id).or_default().extend(members);
+		self
+	}
+
+	/// Add `count` groups of `size` authorities each, numbered after the groups and authorities
+	/// added before.
+	pub fn with_groups(mut self, count: u32, size: u32) -> Self {
+		let first_group = self.members.keys().next_back().map_or(0, |group| group + 1);
+		let mut next_authority =
+			self.members.values().flatten().max().map_or(0, |authority| authority + 1);
+		for group_id in first_group..first_group + count {
+			self = self.with_group(group_id, next_authority..next_authority + size);
+			next_authority += size;
+		}
+
+		self
+	}
+
+	/// Add `validators` authorities, numbered after those added before, to `count` groups
+	/// round-robin, numbered after the groups added before.
+	pub fn with_interleaved_groups(mut self, count: u32, validators: u32) -> Self {
+		let first_group = self.members.keys().next_back().map_or(0, |group| group + 1);
+		let first_authority =
+			self.members.values().flatten().max().map_or(0, |authority| authority + 1);
+		for offset in 0..count {
+			let members = (0..validators).filter(|v| v % count == offset);
+			self = self.with_group(first_group + offset, members.map(|v| first_authority + v));
+		}
+
+		self
+	}
+
+	/// Make `guarantors` the availability guarantors of `group_id`, holding the chunks of its
+	/// candidates in order, instead of its members.
+	pub fn with_guarantors(
+		mut self,
+		group_id: GroupId,
+		guarantors: impl IntoIterator<Item = AuthorityId>,
+	) -> Self {
+		self.guarantors.insert(group_id, guarantors.into_iter().collect());
+		self
+	}
+
+	/// Give `authority` the given `weight`, instead of `1`, see [`Context::vote_weight`].
+	pub fn with_weight(mut self, authority: AuthorityId, weight: u64) -> Self {
+		self.weights.insert(authority, weight);
+		self
+	}
+
+	/// Report `size` as the size of the group `group_id` instead of its number of members, e.g.
+	/// for groups whose members aren't all known.
+	pub fn with_group_size(mut self, group_id: GroupId, size: usize) -> Self {
+		self.group_sizes.insert(group_id, size);
+		self
+	}
+
+	/// Encode the data of candidates of `group_id` into chunks of which `data_chunks` are
+	/// needed to reconstruct it, instead of a third of the chunks plus one.
+	pub fn with_data_chunks(mut self, group_id: GroupId, data_chunks: u32) -> Self {
+		self.data_chunks.insert(group_id, data_chunks);
+		self
+	}
+
+	/// Require `votes` validity votes for candidates to be includable, see
+	/// [`TestContext::minimum_backing_votes`].
+	pub fn with_minimum_backing_votes(mut self, votes: u32) -> Self {
+		self.minimum_backing_votes = votes;
+		self
+	}
+
+	/// Build the context.
+	pub fn build(self) -> TestContext {
+		let TestContextBuilder {
+			members,
+			guarantors,
+			group_sizes,
+			weights,
+			data_chunks,
+			minimum_backing_votes,
+		} = self;
+		TestContext {
+			members,
+			guarantors,
+			group_sizes,
+			weights,
+			data_chunks,
+			minimum_backing_votes,
+		}
+	}
+}
+
+/// A mock [`Context`] of authorities in groups, see [`TestContextBuilder`].
+#[derive(Debug, Clone)]
+pub struct TestContext {
+	members: BTreeMap<GroupId, BTreeSet<AuthorityId>>,
+	guarantors: BTreeMap<GroupId, Vec<AuthorityId>>,
+	group_sizes: HashMap<GroupId, usize>,
+	weights: HashMap<AuthorityId, u64>,
+	data_chunks: HashMap<GroupId, u32>,
+	minimum_backing_votes: u32,
+}
+
+impl TestContext {
+	/// A builder of a context.
+	pub fn builder() -> TestContextBuilder {
+		TestContextBuilder::new()
+	}
+
+	/// The members of `group_id`, in order.
+	pub fn members(&self, group_id: GroupId) -> impl Iterator<Item = AuthorityId> + '_ {
+		self.members.get(&group_id).into_iter().flatten().copied()
+	}
+
+	/// The groups, in order.
+	pub fn groups(&self) -> impl Iterator<Item = GroupId> + '_ {
+		self.members.keys().copied()
+	}
+
+	/// The authorities which are members of any group, in order.
+	pub fn authorities(&self) -> impl Iterator<Item = AuthorityId> + '_ {
+		self.members.values().flatten().copied().collect::<BTreeSet<_>>().into_iter()
+	}
+
+	/// The groups `authority` is a member of, in order.
+	pub fn groups_of(&self, authority: AuthorityId) -> impl Iterator<Item = GroupId> + '_ {
+		self.members
+			.iter()
+			.filter(move |(_, members)| members.contains(&authority))
+			.map(|(group_id, _)| *group_id)
+	}
+
+	/// The availability guarantors of `group_id`, in the order of the chunks they hold.
+	pub fn guarantors(&self, group_id: GroupId) -> impl Iterator<Item = AuthorityId> + '_ {
+		let guarantors = match self.guarantors.get(&group_id) {
+			Some(guarantors) => guarantors.clone(),
+			None => self.members(group_id).collect(),
+		};
+		guarantors.into_iter()
+	}
+
+	/// The number of validity votes candidates need to be includable, to pass to the
+	/// [`Table`](crate::Table) where it asks for `minimum_backing_votes`.
+	pub fn minimum_backing_votes(&self) -> u32 {
+		self.minimum_backing_votes
+	}
+
+	/// Sign `statement` as `sender`.
+	pub fn sign(
+		&self,
+		sender: AuthorityId,
+		statement: Statement<TestCandidate, Digest>,
+	) -> SignedStatementFor<Self> {
+		sign(sender, statement, 0)
+	}
+
+	/// Attest holding the chunk of the candidate with the given `digest` which is assigned to
+	/// `sender` in `group_id`, or chunk `0` if it isn't a guarantor of the group.
+	pub fn attest(
+		&self,
+		sender: AuthorityId,
+		digest: Digest,
+		group_id: GroupId,
+	) -> SignedChunkAttestationFor<Self> {
+		self.reattest(sender, digest, group_id, 0)
+	}
+
+	/// Attest as [`TestContext::attest`] does, with the given `sequence` number.
+	pub fn reattest(
+		&self,
+		sender: AuthorityId,
+		digest: Digest,
+		group_id: GroupId,
+		sequence: u32,
+	) -> SignedChunkAttestationFor<Self> {
+		let chunk = self.assigned_chunk(&sender, &group_id).unwrap_or(0);
+		let attestation = ChunkAttestation { candidate: digest, chunk, sequence };
+		let payload = attestation.signing_payload(&SIGNING_CONTEXT);
+		SignedChunkAttestation {
+			attestation,
+			signature: TestSignature { signer: sender, payload, nonce: 0 },
+			sender,
+		}
+	}
+
+	/// Sign `bitfield` as `sender`.
+	pub fn sign_bitfield(
+		&self,
+		sender: AuthorityId,
+		bitfield: AvailabilityBitfield,
+	) -> SignedAvailabilityBitfieldFor<Self> {
+		let payload = bitfield.signing_payload(&SIGNING_CONTEXT);
+		SignedAvailabilityBitfield {
+			bitfield,
+			signature: TestSignature { signer: sender, payload, nonce: 0 },
+			sender,
+		}
+	}
+
+	/// Sign `attestation` of sampled chunks as `sender`.
+	pub fn sign_sample(
+		&self,
+		sender: AuthorityId,
+		attestation: SamplingAttestation<Digest>,
+	) -> SignedSamplingAttestationFor<Self> {
+		let payload = attestation.signing_payload(&SIGNING_CONTEXT);
+		SignedSamplingAttestation {
+			attestation,
+			signature: TestSignature { signer: sender, payload, nonce: 0 },
+			sender,
+		}
+	}
+
+	/// Revoke `attestation` as its sender.
+	pub fn revoke(
+		&self,
+		attestation: &SignedChunkAttestationFor<Self>,
+	) -> SignedChunkRevocationFor<Self> {
+		let sender = attestation.sender;
+		let revocation = ChunkRevocation { revoked: attestation.attestation.clone() };
+		let payload = revocation.signing_payload(&SIGNING_CONTEXT);
+		SignedChunkRevocation {
+			revocation,
+			signature: TestSignature { signer: sender, payload, nonce: 0 },
+			sender,
+		}
+	}
+}
+
+impl Context for TestContext {
+	type AuthorityId = AuthorityId;
+	type Digest = Digest;
+	type GroupId = GroupId;
+	type Signature = TestSignature;
+	type Candidate = TestCandidate;
+
+	fn candidate_digest(candidate: &TestCandidate) -> Digest {
+		candidate.digest()
+	}
+
+	fn is_member_of(&self, authority: &AuthorityId, group: &GroupId) -> bool {
+		self.members.get(group).map_or(false, |members| members.contains(authority))
+	}
+
+	fn get_group_size(&self, group: &GroupId) -> Option<usize> {
+		self.group_sizes
+			.get(group)
+			.copied()
+			.or_else(|| self.members.get(group).map(BTreeSet::len).filter(|size| *size > 0))
+	}
+
+	fn vote_weight(&self, authority: &AuthorityId) -> u64 {
+		self.weights.get(authority).copied().unwrap_or(1)
+	}
+}
+
+// the group of a candidate is in the upper half of its digest, see `TestCandidate::digest`.
+impl GroupAssignment for TestContext {
+	fn candidate_group(&self, digest: &Digest) -> Option<GroupId> {
+		let group_id = (digest >> 32) as GroupId;
+		self.members.contains_key(&group_id).then_some(group_id)
+	}
+}
+
+impl ValidatorSet for TestContext {
+	fn validators(&self) -> Vec<AuthorityId> {
+		self.authorities().collect()
+	}
+
+	fn group_members(&self, group: &GroupId) -> Vec<AuthorityId> {
+		self.members(*group).collect()
+	}
+}
+
+// the guarantors of a group hold the chunks in order, one each, and any third of them plus one
+// reconstruct the data unless configured otherwise.
+impl AvailabilityContext for TestContext {
+	fn assigned_chunk(&self, authority: &AuthorityId, group: &GroupId) -> Option<ChunkIndex> {
+		self.guarantors(*group)
+			.position(|guarantor| guarantor == *authority)
+			.map(|i| i as ChunkIndex)
+	}
+
+	fn assignments(&self, authority: &AuthorityId) -> Vec<(GroupId, ChunkIndex)> {
+		self.members
+			.keys()
+			.chain(self.guarantors.keys())
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.filter_map(|group| Some((*group, self.assigned_chunk(authority, group)?)))
+			.collect()
+	}
+
+	fn erasure_params(&self, group: &GroupId) -> Option<ErasureParams> {
+		let total_chunks = self.guarantors(*group).count() as u32;
+		if total_chunks == 0 {
+			return None
+		}
+
+		let data_chunks = self
+			.data_chunks
+			.get(group)
+			.copied()
+			.unwrap_or(total_chunks.saturating_sub(1) / 3 + 1);
+		Some(ErasureParams { total_chunks, data_chunks })
+	}
+}
+
+impl VerifyContext for TestContext {
+	fn verify_statement(&self, statement: &SignedStatementFor<Self>) -> bool {
+		let payload = signing_payload::<Self, _>(&statement.statement, &SIGNING_CONTEXT);
+		statement.signature.is_valid(&statement.sender, &payload)
+	}
+}
+
+/// A small deterministic generator of random numbers, so that tests are reproduced from a seed.
+#[derive(Debug, Clone)]
+pub struct SeededRng(u64);
+
+impl SeededRng {
+	/// Create a generator from `seed`.
+	pub fn new(seed: u64) -> Self {
+		// the xorshift state must not be zero.
+		SeededRng((seed ^ 0x9e37_79b9_7f4a_7c15).max(1))
+	}
+
+	/// Get the next random number.
+	pub fn next_u64(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	/// Get a random number below `bound`, or 0 if `bound` is 0.
+	pub fn below(&mut self, bound: u64) -> u64 {
+		self.next_u64().checked_rem(bound).unwrap_or(0)
+	}
+
+	/// Whether an event happening in `percent` percent of cases happens.
+	pub fn chance(&mut self, percent: u32) -> bool {
+		self.below(100) < u64::from(percent)
+	}
+}
+
+/// The shape of the statements generated by a [`Workload`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkloadConfig {
+	/// The number of validators.
+	pub validators: u32,
+	/// The number of members of each group, at most `validators`. Validators left over are in
+	/// no group.
+	pub group_size: u32,
+	/// The number of candidates, assigned to the groups round-robin. Every candidate is
+	/// seconded by a member of its group and voted on by all the other members.
+	pub candidates: u64,
+	/// Whether validators second several candidates, which the table must allow, see
+	/// [`Config`](crate::Config). Otherwise, the members of a group take turns seconding its
+	/// candidates, and the workload ends early once all of them seconded one.
+	pub multiple_seconded: bool,
+	/// The share of statements which repeat a recent statement, in percent.
+	pub duplicate_percent: u32,
+	/// The share of statements which are invalid, in percent. Half of them carry a bad
+	/// signature, the other half are from validators outside of the group of their candidate.
+	pub invalid_percent: u32,
+	/// The seed of the randomness of the workload.
+	pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+	fn default() -> Self {
+		WorkloadConfig {
+			validators: 300,
+			group_size: 5,
+			candidates: 1000,
+			multiple_seconded: true,
+			duplicate_percent: 5,
+			invalid_percent: 1,
+			seed: 0,
+		}
+	}
+}
+
+// the number of candidates whose statements are interleaved.
+const ACTIVE_CANDIDATES: usize = 32;
+
+// the number of recent statements duplicates are drawn from.
+const RECENT_STATEMENTS: usize = 64;
+
+// a candidate with statements left to generate.
+struct ActiveCandidate {
+	candidate: TestCandidate,
+	// the members who haven't issued their statement yet, the seconder first.
+	remaining: Vec<AuthorityId>,
+}
+
+/// A stream of statements about many candidates, along with the group of their candidate.
+///
+/// Statements about a number of candidates are interleaved randomly, as they would arrive from
+/// the network, and are generated lazily, so that workloads can be larger than memory.
+pub struct Workload {
+	config: WorkloadConfig,
+	context: TestContext,
+	rng: SeededRng,
+	next_candidate: u64,
+	active: Vec<ActiveCandidate>,
+	recent: VecDeque<(GroupId, SignedStatementFor<TestContext>)>,
+}
+
+impl Workload {
+	/// Create a workload of the given shape.
+	pub fn new(config: WorkloadConfig) -> Self {
+		let group_size = config.group_size.clamp(1, config.validators.max(1));
+		let groups = (config.validators / group_size).max(1);
+		let context = TestContext::builder().with_groups(groups, group_size).build();
+		let rng = SeededRng::new(config.seed);
+		Workload {
+			config,
+			context,
+			rng,
+			next_candidate: 0,
+			active: Vec::new(),
+			recent: VecDeque::new(),
+		}
+	}
+
+	/// The context the statements are valid in.
+	pub fn context(&self) -> &TestContext {
+		&self.context
+	}
+
+	fn groups(&self) -> u32 {
+		self.context.members.len() as u32
+	}
+
+	// activate candidates until enough are active or all were.
+	fn activate(&mut self) {
+		while self.active.len() < ACTIVE_CANDIDATES && self.next_candidate < self.config.candidates
+		{
+			let group_id = (self.next_candidate % u64::from(self.groups())) as GroupId;
+			let turn = (self.next_candidate / u64::from(self.groups())) as usize;
+			let mut remaining: Vec<_> = self.context.members(group_id).collect();
+			let seconder = if self.config.multiple_seconded {
+				self.rng.below(remaining.len() as u64) as usize
+			} else if turn < remaining.len() {
+				turn
+			} else {
+				// every member seconded a candidate already.
+				break
+			};
+			remaining.swap(0, seconder);
+
+			let candidate = TestCandidate { group_id, body: self.next_candidate };
+			self.active.push(ActiveCandidate { candidate, remaining });
+			self.next_candidate += 1;
+		}
+	}
+
+	// the next valid statement, if any is left.
+	fn next_valid(&mut self) -> Option<(GroupId, SignedStatementFor<TestContext>)> {
+		self.activate();
+		let index = self.rng.below(self.active.len() as u64) as usize;
+		let active = self.active.get_mut(index)?;
+		let candidate = active.candidate;
+		let seconded = active.remaining.len() == self.context.members(candidate.group_id).count();
+		let sender = active.remaining.remove(0);
+		if active.remaining.is_empty() {
+			self.active.swap_remove(index);
+		}
+
+		let statement = if seconded {
+			Statement::Seconded(candidate)
+		} else {
+			Statement::Valid(candidate.digest())
+		};
+		Some((candidate.group_id, self.context.sign(sender, statement)))
+	}
+
+	// an invalid statement about an active candidate, if there is one to make.
+	fn invalid(&mut self) -> Option<(GroupId, SignedStatementFor<TestContext>)> {
+		let index = self.rng.below(self.active.len() as u64) as usize;
+		let candidate = self.active.get(index)?.candidate;
+		let statement = Statement::Valid(candidate.digest());
+
+		// when every validator is in the group of the candidate, the signature is always bad.
+		let outsiders: Vec<_> = (0..self.config.validators)
+			.filter(|sender| !self.context.is_member_of(sender, &candidate.group_id))
+			.collect();
+		if self.rng.chance(50) || outsiders.is_empty() {
+			let sender = self.context.members(candidate.group_id).next()?;
+			let mut signed = self.context.sign(sender, statement);
+			signed.signature.payload.push(0);
+			Some((candidate.group_id, signed))
+		} else {
+			let outsider = outsiders[self.rng.below(outsiders.len() as u64) as usize];
+			Some((candidate.group_id, self.context.sign(outsider, statement)))
+		}
+	}
+}
+
+impl Iterator for Workload {
+	type Item = (GroupId, SignedStatementFor<TestContext>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if !self.recent.is_empty() && self.rng.chance(self.config.duplicate_percent) {
+			let index = self.rng.below(self.recent.len() as u64) as usize;
+			return self.recent.get(index).cloned()
+		}
+
+		if !self.active.is_empty() && self.rng.chance(self.config.invalid_percent) {
+			if let Some(invalid) = self.invalid() {
+				return Some(invalid)
+			}
+		}
+
+		let Some(statement) = self.next_valid() else {
+			// the workload is over, without any more duplicates.
+			self.recent.clear();
+			return None
+		};
+		if self.recent.len() == RECENT_STATEMENTS {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(statement.clone());
+		Some(statement)
+	}
+}
+
+/// How a statement of [`mutations`] was derived from a valid one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mutation {
+	/// The bit with the given index of the encoded signature is flipped.
+	SignatureBit(usize),
+	/// The statement is the one of the valid statement with the given index, while the
+	/// signature and sender are kept.
+	Statement(usize),
+	/// The sender is the one of the valid statement with the given index.
+	Sender(usize),
+	/// A seconded statement turned into a validity statement on the same candidate, or the
+	/// other way around.
+	Kind,
+}
+
+/// Derive tampered statements from each of the valid `statements`, along with the index of the
+/// statement each was derived from.
+///
+/// Every bit of each signature is flipped, as long as the signature still decodes, and
+/// statements and senders are swapped between the statements. Mutations which leave a
+/// statement unchanged are skipped.
+pub fn mutations<Ctx: Context>(
+	statements: &[SignedStatementFor<Ctx>],
+) -> Vec<(usize, Mutation, SignedStatementFor<Ctx>)>
+where
+	Ctx::Signature: Encode + Decode,
+{
+	let mut mutated = Vec::new();
+	for (index, original) in statements.iter().enumerate() {
+		let mut push = |mutation, statement: SignedStatementFor<Ctx>| {
+			if statement != *original {
+				mutated.push((index, mutation, statement));
+			}
+		};
+
+		let encoded = original.signature.encode();
+		for bit in 0..encoded.len() * 8 {
+			let mut flipped = encoded.clone();
+			flipped[bit / 8] ^= 1 << (bit % 8);
+			if let Ok(signature) = Ctx::Signature::decode_all(&mut &flipped[..]) {
+				push(
+					Mutation::SignatureBit(bit),
+					SignedStatement { signature, ..original.clone() },
+				);
+			}
+		}
+
+		for (other_index, other) in statements.iter().enumerate() {
+			let statement = other.statement.clone();
+			push(
+				Mutation::Statement(other_index),
+				SignedStatement { statement, ..original.clone() },
+			);
+			let sender = other.sender.clone();
+			push(Mutation::Sender(other_index), SignedStatement { sender, ..original.clone() });
+		}
+
+		let kind = match &original.statement {
+			Statement::Seconded(candidate) =>
+				Some(Statement::Valid(Ctx::candidate_digest(candidate))),
+			Statement::Valid(digest) =>
+				statements.iter().find_map(|other| match &other.statement {
+					Statement::Seconded(candidate)
+						if Ctx::candidate_digest(candidate) == *digest =>
+						Some(Statement::Seconded(candidate.clone())),
+					_ => None,
+				}),
+		};
+		if let Some(statement) = kind {
+			push(Mutation::Kind, SignedStatement { statement, ..original.clone() });
+		}
+	}
+
+	mutated
+}
+
+/// The [`mutations`] of the valid `statements` whose signature `context` accepts.
+///
+/// Panics if any of `statements` isn't valid to begin with.
+pub fn accepted_mutations<Ctx: VerifyContext>(
+	context: &Ctx,
+	statements: &[SignedStatementFor<Ctx>],
+) -> Vec<(usize, Mutation, SignedStatementFor<Ctx>)>
+where
+	Ctx::Signature: Encode + Decode,
+{
+	let valid = context.verify_batch(statements);
+	assert!(valid.iter().all(|valid| *valid), "mutations must be derived from valid statements");
+
+	let mutated = mutations::<Ctx>(statements);
+	let signed: Vec<_> = mutated.iter().map(|(_, _, statement)| statement.clone()).collect();
+	let accepted = context.verify_batch(&signed);
+	mutated
+		.into_iter()
+		.zip(accepted)
+		.filter_map(|(m, accepted)| accepted.then_some(m))
+		.collect()
+}
+
+/// A simple [`Context`] of newtypes which can be encoded, for tests of the
+/// [`Table`](crate::Table) itself.
+///
+/// Authorities are assigned to groups by a map, and candidates are a group and a body, which is
+/// their digest as well.
+pub mod simple {
+	use std::collections::HashMap;
+
+	use parity_scale_codec::{Decode, Encode};
+
+	use crate::generic::Context;
+
+	/// An authority.
+	#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Encode, Decode)]
+	pub struct AuthorityId(pub u64);
+
+	/// A group.
+	#[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq, Encode, Decode)]
+	pub struct GroupId(pub u64);
+
+	/// A candidate, made of its group and its body.
+	#[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq, Encode, Decode)]
+	pub struct Candidate(pub u64, pub u64);
+
+	/// A signature, which is the authority signing.
+	#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Encode, Decode)]
+	pub struct Signature(pub u64);
+
+	/// The digest of a candidate, which is its body.
+	#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Encode, Decode)]
+	pub struct Digest(pub u64);
+
+	/// The context, assigning authorities to groups.
+	///
+	/// Candidates of para `0` aren't scheduled on any core, so the context doesn't accept them.
+	#[derive(Debug, PartialEq, Eq)]
+	pub struct TestContext {
+		/// The group of each authority.
+		pub authorities: HashMap<AuthorityId, GroupId>,
+	}
+
+	impl Context for TestContext {
+		type AuthorityId = AuthorityId;
+		type Digest = Digest;
+		type Candidate = Candidate;
+		type GroupId = GroupId;
+		type Signature = Signature;
+
+		fn candidate_digest(candidate: &Candidate) -> Digest {
+			Digest(candidate.1)
+		}
+
+		fn is_member_of(&self, authority: &AuthorityId, group: &GroupId) -> bool {
+			self.authorities.get(authority).map(|v| v == group).unwrap_or(false)
+		}
+
+		fn get_group_size(&self, group: &Self::GroupId) -> Option<usize> {
+			let count = self.authorities.values().filter(|g| *g == group).count();
+			if count == 0 {
+				None
+			} else {
+				Some(count)
+			}
+		}
+
+		fn is_acceptable(&self, candidate: &Candidate, _group: &GroupId) -> bool {
+			candidate.0 != 0
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::generic::{Config, Table};
+
+	#[test]
+	fn groups_are_numbered_consecutively() {
+		let context = TestContext::builder()
+			.with_group(0, [0, 1])
+			.with_groups(2, 3)
+			.with_group_size(3, 5)
+			.build();
+
+		assert_eq!(context.members(0).collect::<Vec<_>>(), vec![0, 1]);
+		assert_eq!(context.members(1).collect::<Vec<_>>(), vec![2, 3, 4]);
+		assert_eq!(context.members(2).collect::<Vec<_>>(), vec![5, 6, 7]);
+		assert!(context.is_member_of(&6, &2));
+		assert!(!context.is_member_of(&6, &1));
+		assert_eq!(context.get_group_size(&1), Some(3));
+		assert_eq!(context.get_group_size(&3), Some(5));
+		assert_eq!(context.get_group_size(&4), None);
+	}
+
+	#[test]
+	fn signed_statements_are_attested() {
+		let context =
+			TestContext::builder().with_groups(2, 3).with_minimum_backing_votes(3).build();
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		let candidate = TestCandidate { group_id: 1, body: 42 };
+
+		let seconded = context.sign(3, Statement::Seconded(candidate));
+		assert!(context.verify_statement(&seconded));
+		table.import_statement(&context, 1, seconded);
+		table.import_statement(&context, 1, context.sign(4, Statement::Valid(candidate.digest())));
+		assert!(table.attested_candidates(&context, context.minimum_backing_votes()).is_empty());
+
+		table.import_statement(&context, 1, context.sign(5, Statement::Valid(candidate.digest())));
+		let attested = table.attested_candidates(&context, context.minimum_backing_votes());
+		assert_eq!(attested.len(), 1);
+		assert_eq!(attested[0].validity_votes.len(), 3);
+	}
+
+	#[test]
+	fn validity_votes_are_weighted() {
+		let context = TestContext::builder().with_groups(1, 3).with_weight(1, 10).build();
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		let candidate = TestCandidate { group_id: 0, body: 42 };
+		let weight = |table: &Table<TestContext>| {
+			table
+				.candidate_status(&candidate.digest(), &context, 2)
+				.unwrap()
+				.validity_weight
+		};
+
+		table.import_statement(&context, 0, context.sign(0, Statement::Seconded(candidate)));
+		let marker = table.marker();
+		table.import_statement(&context, 0, context.sign(1, Statement::Valid(candidate.digest())));
+		assert_eq!(weight(&table), 11);
+		table.assert_invariants(&context);
+
+		table.revert_to(&context, marker);
+		assert_eq!(weight(&table), 1);
+		table.assert_invariants(&context);
+	}
+
+	#[test]
+	fn workloads_have_the_configured_shape() {
+		let config = WorkloadConfig {
+			validators: 23,
+			group_size: 5,
+			candidates: 200,
+			multiple_seconded: true,
+			duplicate_percent: 10,
+			invalid_percent: 10,
+			seed: 7,
+		};
+		let workload = Workload::new(config.clone());
+		let context = workload.context().clone();
+		let statements: Vec<_> = workload.collect();
+
+		let (valid, invalid): (Vec<_>, Vec<_>) = statements.iter().partition(|(group, s)| {
+			context.verify_statement(s) && context.is_member_of(&s.sender, group)
+		});
+		let unique: BTreeSet<_> = valid.iter().map(|(_, s)| s.encode()).collect();
+		assert_eq!(unique.len(), 200 * 5);
+		let duplicates = valid.len() - unique.len();
+		assert!((50..=200).contains(&duplicates), "{} duplicates", duplicates);
+		assert!((50..=200).contains(&invalid.len()), "{} invalid", invalid.len());
+
+		let mut table = Table::new(Config { allow_multiple_seconded: true });
+		for (group_id, statement) in valid {
+			table.import_statement(&context, *group_id, statement.clone());
+		}
+		let attested = table.attested_candidates(&context, 5);
+		assert_eq!(attested.len(), 200);
+		assert!(table.get_misbehavior().is_empty());
+
+		// workloads are reproduced from their seed.
+		let again: Vec<_> = Workload::new(config.clone()).collect();
+		assert_eq!(again, statements);
+		let reseeded: Vec<_> = Workload::new(WorkloadConfig { seed: 8, ..config }).collect();
+		assert_ne!(reseeded, statements);
+	}
+
+	#[test]
+	fn workloads_without_multiple_seconded_candidates() {
+		// the group size is clamped to the validators, who are all in the only group, so invalid
+		// statements have a bad signature.
+		let config = WorkloadConfig {
+			validators: 3,
+			group_size: 5,
+			candidates: 200,
+			multiple_seconded: false,
+			duplicate_percent: 0,
+			invalid_percent: 50,
+			seed: 7,
+		};
+		let workload = Workload::new(config);
+		let context = workload.context().clone();
+		assert_eq!(context.members(0).count(), 3);
+		let statements: Vec<_> = workload.collect();
+		assert!(statements.iter().any(|(_, s)| !context.verify_statement(s)));
+
+		let mut table = Table::new(Config { allow_multiple_seconded: false });
+		for (group_id, statement) in statements {
+			if context.verify_statement(&statement) {
+				table.import_statement(&context, group_id, statement);
+			}
+		}
+		assert_eq!(table.attested_candidates(&context, 3).len(), 3);
+		assert!(table.get_misbehavior().is_empty());
+	}
+
+	#[test]
+	fn tampered_statements_are_rejected() {
+		let context = TestContext::builder().with_groups(2, 3).build();
+		let candidate = |group_id| TestCandidate { group_id, body: 10 + u64::from(group_id) };
+		let statements = vec![
+			context.sign(0, Statement::Seconded(candidate(0))),
+			context.sign(1, Statement::Valid(candidate(0).digest())),
+			context.sign(2, Statement::Valid(candidate(0).digest())),
+			context.sign(3, Statement::Seconded(candidate(1))),
+			context.sign(4, Statement::Valid(candidate(1).digest())),
+		];
+
+		let corpus = mutations::<TestContext>(&statements);
+		for kind in
+			[Mutation::SignatureBit(0), Mutation::Statement(0), Mutation::Sender(0), Mutation::Kind]
+		{
+			let same_kind =
+				|m: &Mutation| std::mem::discriminant(m) == std::mem::discriminant(&kind);
+			assert!(corpus.iter().any(|(_, m, _)| same_kind(m)), "no {:?} mutations", kind);
+		}
+
+		// only the nonce, which is the last byte of a signature, isn't covered by it.
+		let accepted = accepted_mutations(&context, &statements);
+		assert_eq!(accepted.len(), 8 * statements.len());
+		for (index, mutation, _) in accepted {
+			let nonce = 8 * (statements[index].signature.encode().len() - 1);
+			assert!(matches!(mutation, Mutation::SignatureBit(bit) if bit >= nonce));
+		}
+	}
+}
@@ -0,0 +1,191 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Verification of statement signatures ahead of import.
+//!
+//! [`Table::import_statement`](crate::Table::import_statement) expects signatures to be checked
+//! already. When many statements arrive at once, checking them one by one is wasteful for
+//! signature schemes which support batch verification. A [`BatchVerifier`] collects incoming
+//! statements and checks their signatures together through [`VerifyContext::verify_batch`].
+
+use crate::generic::{Context, SignedStatementFor};
+
+/// A [`Context`] which can check the signatures of statements.
+pub trait VerifyContext: Context {
+	/// Check the signature of a single statement.
+	fn verify_statement(&self, statement: &SignedStatementFor<Self>) -> bool;
+
+	/// Check the signatures of a batch of statements, returning whether each of them is valid,
+	/// in order.
+	///
+	/// The default implementation checks each statement with
+	/// [`VerifyContext::verify_statement`]. Contexts with a signature scheme supporting batch
+	/// verification should override it.
+	fn verify_batch(&self, statements: &[SignedStatementFor<Self>]) -> Vec<bool> {
+		statements.iter().map(|statement| self.verify_statement(statement)).collect()
+	}
+}
+
+/// The outcome of verifying a batch of statements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedStatements<GroupId, Statement> {
+	/// The statements with a valid signature, along with their group. These can be imported.
+	pub valid: Vec<(GroupId, Statement)>,
+	/// The statements with an invalid signature, along with their group.
+	pub invalid: Vec<(GroupId, Statement)>,
+}
+
+/// Type alias for the outcome of verifying statements corresponding to context type.
+pub type VerifiedStatementsFor<Ctx> =
+	VerifiedStatements<<Ctx as Context>::GroupId, SignedStatementFor<Ctx>>;
+
+/// Check the signatures of `statements` together.
+///
+/// Statements for which `verify_batch` returns no result are considered invalid.
+pub fn verify_statements<Ctx: VerifyContext>(
+	context: &Ctx,
+	statements: Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>,
+) -> VerifiedStatementsFor<Ctx> {
+	let (groups, statements): (Vec<_>, Vec<_>) = statements.into_iter().unzip();
+	let mut results = context.verify_batch(&statements).into_iter();
+
+	let mut verified = VerifiedStatements { valid: Vec::new(), invalid: Vec::new() };
+	for entry in groups.into_iter().zip(statements) {
+		if results.next().unwrap_or(false) {
+			verified.valid.push(entry);
+		} else {
+			verified.invalid.push(entry);
+		}
+	}
+
+	verified
+}
+
+/// Collects incoming statements until enough of them are pending to be verified together.
+pub struct BatchVerifier<Ctx: Context> {
+	max_batch_len: usize,
+	pending: Vec<(Ctx::GroupId, SignedStatementFor<Ctx>)>,
+}
+
+impl<Ctx: VerifyContext> BatchVerifier<Ctx> {
+	/// Create a new verifier, checking statements in batches of at most `max_batch_len`.
+	pub fn new(max_batch_len: usize) -> Self {
+		BatchVerifier { max_batch_len: max_batch_len.max(1), pending: Vec::new() }
+	}
+
+	/// Queue a statement for verification. Returns the verified batch if it is full.
+	pub fn push(
+		&mut self,
+		context: &Ctx,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> Option<VerifiedStatementsFor<Ctx>> {
+		self.pending.push((group_id, statement));
+		if self.pending.len() < self.max_batch_len {
+			return None
+		}
+
+		Some(self.flush(context))
+	}
+
+	/// Verify all pending statements, e.g. at the end of a round of network events.
+	pub fn flush(&mut self, context: &Ctx) -> VerifiedStatementsFor<Ctx> {
+		verify_statements(context, std::mem::take(&mut self.pending))
+	}
+
+	/// The number of statements awaiting verification.
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::Table,
+		router::tests::{context_and_table, seconded, valid},
+		test_utils::{self, TestContext},
+	};
+	use std::cell::Cell;
+
+	// a test context counting the batches it verifies.
+	struct CountingContext {
+		inner: TestContext,
+		batches: Cell<usize>,
+	}
+
+	impl Context for CountingContext {
+		type AuthorityId = test_utils::AuthorityId;
+		type Digest = test_utils::Digest;
+		type GroupId = test_utils::GroupId;
+		type Signature = test_utils::TestSignature;
+		type Candidate = test_utils::TestCandidate;
+
+		fn candidate_digest(candidate: &Self::Candidate) -> Self::Digest {
+			TestContext::candidate_digest(candidate)
+		}
+
+		fn is_member_of(&self, authority: &Self::AuthorityId, group: &Self::GroupId) -> bool {
+			self.inner.is_member_of(authority, group)
+		}
+
+		fn get_group_size(&self, group: &Self::GroupId) -> Option<usize> {
+			self.inner.get_group_size(group)
+		}
+	}
+
+	impl VerifyContext for CountingContext {
+		fn verify_statement(&self, statement: &SignedStatementFor<Self>) -> bool {
+			self.inner.verify_statement(statement)
+		}
+
+		fn verify_batch(&self, statements: &[SignedStatementFor<Self>]) -> Vec<bool> {
+			self.batches.set(self.batches.get() + 1);
+			statements.iter().map(|statement| self.verify_statement(statement)).collect()
+		}
+	}
+
+	#[test]
+	fn statements_are_verified_in_batches() {
+		let context = CountingContext { inner: context_and_table().0, batches: Cell::new(0) };
+		let mut table = Table::new(Default::default());
+		let mut verifier = BatchVerifier::new(3);
+
+		let mut forged = valid(2);
+		forged.sender = 1;
+
+		assert_eq!(verifier.push(&context, 0, seconded(1)), None);
+		assert_eq!(verifier.push(&context, 0, forged.clone()), None);
+		assert_eq!(verifier.pending(), 2);
+		let verified = verifier.push(&context, 0, valid(2)).unwrap();
+		assert_eq!(context.batches.get(), 1);
+		assert_eq!(verifier.pending(), 0);
+
+		assert_eq!(verified.valid, vec![(0, seconded(1)), (0, valid(2))]);
+		assert_eq!(verified.invalid, vec![(0, forged)]);
+
+		let summaries: Vec<_> = verified
+			.valid
+			.into_iter()
+			.filter_map(|(group, statement)| table.import_statement(&context, group, statement))
+			.collect();
+		assert_eq!(summaries.last().unwrap().validity_votes, 2);
+
+		let empty = verifier.flush(&context);
+		assert!(empty.valid.is_empty() && empty.invalid.is_empty());
+	}
+}
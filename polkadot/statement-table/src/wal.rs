@@ -0,0 +1,403 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A write-ahead log of the statements imported into a table.
+//!
+//! A [`StatementLog`] is an append-only file of signed statements, along with their group.
+//! Statements are appended with [`StatementLog::import_statement`] once the table imported them
+//! or detected misbehavior in them, so duplicates and other statements the table ignores don't
+//! grow the log. Importing the logged statements again, in order, into a fresh table with
+//! [`StatementLog::replay`] then reconstructs the candidates, votes and detected misbehavior of
+//! the table after a crash. Misbehavior which was drained from the table is kept by the
+//! [`MisbehaviorOutbox`](crate::outbox::MisbehaviorOutbox) until it is reported.
+//!
+//! The log starts with a header of four magic bytes and the version of its format as a
+//! little-endian `u32`. Each record is the length of the encoded statement as a little-endian
+//! `u32`, the first four bytes of its blake2-256 hash, and the SCALE-encoded group and
+//! statement. A record which was only partly written before a crash is truncated when the log is
+//! opened, and a log of an older format is upgraded. Recordings of a
+//! [`Recorder`](crate::recording::Recorder) use the same format, with the details of the import
+//! encoded after each statement.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, BufReader, Read, Seek, SeekFrom, Write},
+	marker::PhantomData,
+	path::Path,
+};
+
+use parity_scale_codec::{Decode, Encode};
+use sp_core::blake2_256;
+
+use crate::generic::{Context, ImportError, SignedStatementFor, Summary, Table};
+
+const LOG_TARGET: &str = "parachain::statement-table";
+
+// the length of the length prefix and the checksum of a record.
+const HEADER_LEN: usize = 8;
+
+const LOG_MAGIC: &[u8; 4] = b"stlg";
+// the length of the magic bytes and the version of the log.
+const LOG_HEADER_LEN: usize = 8;
+
+/// The version of the log format written by this release. Logs without a header are at
+/// version 0.
+pub const LOG_VERSION: u32 = 1;
+
+// a logged statement, along with its group.
+type Entry<Ctx> = (<Ctx as Context>::GroupId, SignedStatementFor<Ctx>);
+
+/// An append-only log of signed statements.
+pub struct StatementLog<Ctx> {
+	file: File,
+	len: u64,
+	_marker: PhantomData<fn() -> Ctx>,
+}
+
+impl<Ctx: Context> StatementLog<Ctx>
+where
+	Ctx::Candidate: Encode + Decode,
+	Ctx::Digest: Encode + Decode,
+	Ctx::GroupId: Encode + Decode,
+	Ctx::AuthorityId: Encode + Decode,
+	Ctx::Signature: Encode + Decode,
+{
+	/// Open the log at `path`, creating it if it doesn't exist.
+	///
+	/// A partly written record at the end of the log is truncated. The log is read record by
+	/// record, so it isn't held in memory.
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let mut file =
+			OpenOptions::new().read(true).append(true).create(true).open(path.as_ref())?;
+		let file_len = file.metadata()?.len();
+		let mut log = StatementLog { len: 0, file: file.try_clone()?, _marker: PhantomData };
+
+		let mut header = Vec::with_capacity(LOG_HEADER_LEN);
+		(&mut file).take(LOG_HEADER_LEN as u64).read_to_end(&mut header)?;
+		match parse_header(&header) {
+			Some(version) =>
+				if version > LOG_VERSION {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"statement log is of a newer version",
+					))
+				},
+			None if header.is_empty() => {
+				log.file.write_all(&log_header())?;
+				log.len = LOG_HEADER_LEN as u64;
+				return Ok(log)
+			},
+			None => {
+				// version 0 logs only lack the header.
+				gum::info!(target: LOG_TARGET, from = 0, to = LOG_VERSION, "Migrating statement log");
+				drop(log);
+				migrate_unversioned::<Ctx>(path.as_ref(), file)?;
+				return Self::open(path)
+			},
+		}
+
+		let valid_len =
+			LOG_HEADER_LEN as u64 + read_records(BufReader::new(file), |_: Entry<Ctx>| Ok(()))?;
+		if valid_len < file_len {
+			gum::warn!(
+				target: LOG_TARGET,
+				truncated = file_len - valid_len,
+				"Truncating partly written records of the statement log",
+			);
+			log.file.set_len(valid_len)?;
+		}
+
+		log.len = valid_len;
+		Ok(log)
+	}
+
+	/// Import a statement into `table`, as with [`Table::import_statement`], and append the
+	/// statements the table imported to the log.
+	///
+	/// These are the statement itself, unless the table ignored it, and announcements of its
+	/// candidate it provided the body of. A statement in which the table detected misbehavior is
+	/// appended as well, so that replaying the log detects it again. If appending fails, the
+	/// imports are undone.
+	pub fn import_statement(
+		&mut self,
+		context: &Ctx,
+		table: &mut Table<Ctx>,
+		group_id: Ctx::GroupId,
+		statement: SignedStatementFor<Ctx>,
+	) -> io::Result<Option<Summary<Ctx::Digest, Ctx::GroupId>>> {
+		let marker = table.marker();
+		let result = table.try_import_statement_ref(context, group_id.clone(), &statement);
+
+		let mut records = Vec::new();
+		let mut encoded = table
+			.statements_since(marker)
+			.0
+			.into_iter()
+			.try_for_each(|entry| encode_record(&mut records, &entry));
+		if let (Ok(()), Err(ImportError::Misbehavior(_))) = (&encoded, &result) {
+			encoded = encode_record(&mut records, &(group_id, statement));
+		}
+		if let Err(e) = encoded.and_then(|()| self.write_records(&records)) {
+			table.revert_to(context, marker);
+			return Err(e)
+		}
+
+		Ok(result.ok())
+	}
+
+	/// Append a statement to the log, which was imported into the table.
+	///
+	/// The record is handed to the operating system, but only reaches the disk once the log is
+	/// synced with [`StatementLog::sync`].
+	pub fn append(
+		&mut self,
+		group_id: &Ctx::GroupId,
+		statement: &SignedStatementFor<Ctx>,
+	) -> io::Result<()> {
+		let mut record = Vec::new();
+		encode_record(&mut record, &(group_id, statement))?;
+		self.write_records(&record)
+	}
+
+	fn write_records(&mut self, records: &[u8]) -> io::Result<()> {
+		self.file.write_all(records)?;
+		self.len += records.len() as u64;
+		Ok(())
+	}
+
+	/// Flush the log to disk.
+	pub fn sync(&self) -> io::Result<()> {
+		self.file.sync_data()
+	}
+
+	/// Read all statements in the log, in the order they were appended.
+	pub fn statements(&self) -> io::Result<Vec<Entry<Ctx>>> {
+		let mut statements = Vec::new();
+		self.for_each_statement(|statement| {
+			statements.push(statement);
+			Ok(())
+		})?;
+
+		Ok(statements)
+	}
+
+	/// Import all statements in the log into `table`, in order, returning how many there were.
+	///
+	/// Replayed into a fresh table, this reconstructs the candidates and votes of the table the
+	/// statements were imported into. The statements are read one at a time.
+	pub fn replay(&self, context: &Ctx, table: &mut Table<Ctx>) -> io::Result<usize> {
+		let mut replayed = 0;
+		self.for_each_statement(|(group_id, statement)| {
+			table.import_statement(context, group_id, statement);
+			replayed += 1;
+			Ok(())
+		})?;
+
+		Ok(replayed)
+	}
+
+	fn for_each_statement(&self, f: impl FnMut(Entry<Ctx>) -> io::Result<()>) -> io::Result<()> {
+		let mut file = self.file.try_clone()?;
+		file.seek(SeekFrom::Start(LOG_HEADER_LEN as u64))?;
+		let records = BufReader::new(file.take(self.len - LOG_HEADER_LEN as u64));
+		read_records(records, f).map(|_| ())
+	}
+
+	/// Remove all statements from the log, e.g. once the round they are about concluded.
+	pub fn clear(&mut self) -> io::Result<()> {
+		self.file.set_len(LOG_HEADER_LEN as u64)?;
+		self.len = LOG_HEADER_LEN as u64;
+		Ok(())
+	}
+
+	/// The size of the log in bytes, including its header.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Whether the log holds no statements.
+	pub fn is_empty(&self) -> bool {
+		self.len == LOG_HEADER_LEN as u64
+	}
+}
+
+// rewrite the version 0 log at `path`, open as `file`, with a header. the migrated log is written
+// to a temporary file which then replaces the log, so that a crash leaves either of them intact.
+fn migrate_unversioned<Ctx: Context>(path: &Path, mut file: File) -> io::Result<()>
+where
+	Ctx::Candidate: Decode,
+	Ctx::Digest: Decode,
+	Ctx::GroupId: Decode,
+	Ctx::AuthorityId: Decode,
+	Ctx::Signature: Decode,
+{
+	file.seek(SeekFrom::Start(0))?;
+	let valid_len = read_records(BufReader::new(&mut file), |_: Entry<Ctx>| Ok(()))?;
+
+	let mut temp_path = path.as_os_str().to_owned();
+	temp_path.push(".migrating");
+	let mut temp = File::create(&temp_path)?;
+	temp.write_all(&log_header())?;
+	file.seek(SeekFrom::Start(0))?;
+	io::copy(&mut file.take(valid_len), &mut temp)?;
+	temp.sync_all()?;
+	drop(temp);
+
+	std::fs::rename(&temp_path, path)?;
+	if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+		File::open(dir)?.sync_all()?;
+	}
+	Ok(())
+}
+
+pub(crate) fn log_header() -> Vec<u8> {
+	let mut header = LOG_MAGIC.to_vec();
+	header.extend_from_slice(&LOG_VERSION.to_le_bytes());
+	header
+}
+
+// the version of the log starting with `header`, if it has a header.
+pub(crate) fn parse_header(header: &[u8]) -> Option<u32> {
+	let version = header.get(..LOG_HEADER_LEN)?.strip_prefix(LOG_MAGIC)?;
+	Some(u32::from_le_bytes(version.try_into().ok()?))
+}
+
+// append the record of `payload` to `records`.
+pub(crate) fn encode_record(records: &mut Vec<u8>, payload: &impl Encode) -> io::Result<()> {
+	let payload = payload.encode();
+	let len = u32::try_from(payload.len())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "statement too large"))?;
+
+	records.reserve(HEADER_LEN + payload.len());
+	records.extend_from_slice(&len.to_le_bytes());
+	records.extend_from_slice(&blake2_256(&payload)[..4]);
+	records.extend_from_slice(&payload);
+	Ok(())
+}
+
+// read the complete records at the start of `reader`, passing their payloads to `f`, and
+// return the length of the records. trailing bytes of a payload are ignored, so that a payload
+// can be read as any prefix of it.
+pub(crate) fn read_records<P: Decode>(
+	mut reader: impl Read,
+	mut f: impl FnMut(P) -> io::Result<()>,
+) -> io::Result<u64> {
+	let mut offset = 0;
+	let mut payload = Vec::new();
+	loop {
+		let mut header = [0; HEADER_LEN];
+		match reader.read_exact(&mut header) {
+			Ok(()) => (),
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+			Err(e) => return Err(e),
+		}
+
+		// the length of a partly written record may be garbage, so the payload is only
+		// allocated as it is read.
+		let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as u64;
+		payload.clear();
+		(&mut reader).take(len).read_to_end(&mut payload)?;
+		if payload.len() as u64 != len || blake2_256(&payload)[..4] != header[4..] {
+			break
+		}
+
+		match Decode::decode(&mut &payload[..]) {
+			Ok(payload) => f(payload)?,
+			Err(_) => break,
+		}
+		offset += HEADER_LEN as u64 + len;
+	}
+
+	Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		generic::Config,
+		router::tests::{context_and_table, seconded, seconded_other, valid},
+		test_utils::TestContext,
+	};
+
+	#[test]
+	fn table_is_reconstructed_from_log() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("statements");
+		let (context, mut table) = context_and_table();
+
+		let mut log = StatementLog::<TestContext>::open(&path).unwrap();
+		for statement in [seconded(1), valid(2)] {
+			log.import_statement(&context, &mut table, 0, statement).unwrap();
+		}
+		log.sync().unwrap();
+
+		// ignored statements aren't logged.
+		let logged = log.len();
+		assert!(log.import_statement(&context, &mut table, 0, valid(2)).unwrap().is_none());
+		assert_eq!(log.len(), logged);
+
+		// statements proving misbehavior are.
+		assert!(log
+			.import_statement(&context, &mut table, 0, seconded_other(1))
+			.unwrap()
+			.is_none());
+		assert_eq!(table.get_misbehavior()[&1].len(), 1);
+		assert!(log.len() > logged);
+
+		// a record which was only partly written is dropped.
+		let logged = log.len();
+		let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+		file.write_all(&[42, 0, 0, 0, 1]).unwrap();
+		drop(log);
+
+		let log = StatementLog::<TestContext>::open(&path).unwrap();
+		assert_eq!(log.len(), logged);
+		assert_eq!(std::fs::metadata(&path).unwrap().len(), logged);
+
+		let mut restored = crate::Table::new(Config { allow_multiple_seconded: false });
+		assert_eq!(log.replay(&context, &mut restored).unwrap(), 3);
+		assert_eq!(restored.state_root(), table.state_root());
+		assert_eq!(restored.get_misbehavior(), table.get_misbehavior());
+	}
+
+	#[test]
+	fn unversioned_log_is_migrated() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("statements");
+		let mut log = StatementLog::<TestContext>::open(&path).unwrap();
+		assert!(log.is_empty());
+		log.append(&0, &seconded(1)).unwrap();
+		log.append(&0, &valid(2)).unwrap();
+		drop(log);
+
+		// strip the header, as written by version 0.
+		let contents = std::fs::read(&path).unwrap();
+		assert_eq!(contents[..LOG_HEADER_LEN], log_header()[..]);
+		std::fs::write(&path, &contents[LOG_HEADER_LEN..]).unwrap();
+
+		let log = StatementLog::<TestContext>::open(&path).unwrap();
+		assert_eq!(std::fs::read(&path).unwrap(), contents);
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+		assert_eq!(log.statements().unwrap(), vec![(0, seconded(1)), (0, valid(2))]);
+
+		// a log of a newer version is refused.
+		let mut newer = log_header();
+		newer[4] = LOG_VERSION as u8 + 1;
+		std::fs::write(&path, newer).unwrap();
+		assert!(StatementLog::<TestContext>::open(&path).is_err());
+	}
+}
@@ -0,0 +1,199 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned wire format of protocol messages.
+//!
+//! Encoded messages are prefixed with the [`WIRE_VERSION`] they were encoded with. Messages of
+//! the previous version are still accepted and upgraded, see [`WireMessage`]. This way a change
+//! of the format can roll out across the validator set without all nodes upgrading at once:
+//! upgraded nodes understand both versions, and start sending the new one once all nodes are
+//! upgraded.
+//!
+//! This is the format of the default [`Codec`], [`ScaleCodec`]. Deployments can use another
+//! format by implementing [`Codec`], see [`EncodedNetwork`](crate::network::EncodedNetwork).
+
+use std::fmt::Debug;
+
+use parity_scale_codec::{Decode, DecodeAll, Encode, Error as CodecError, Input};
+
+use crate::{
+	agreement::{Justification, SignedMessage},
+	router::{
+		announce::{Announcement, BodyResponse},
+		fetch::{CandidateRequest, CandidateResponse},
+		StatementBatch,
+	},
+};
+
+/// The current version of the wire format.
+pub const WIRE_VERSION: u8 = 1;
+
+/// A protocol message with a versioned encoding.
+pub trait WireMessage: Encode + Decode {
+	/// The message as encoded in the previous version of the wire format.
+	type Previous: Decode;
+
+	/// Convert a message of the previous version.
+	fn upgrade(previous: Self::Previous) -> Self;
+}
+
+/// The previous version of messages which didn't exist in the previous version of the wire
+/// format. It can't be decoded.
+pub enum NoPrevious {}
+
+impl Decode for NoPrevious {
+	fn decode<I: Input>(_input: &mut I) -> Result<Self, CodecError> {
+		Err("message doesn't exist in the previous wire version".into())
+	}
+}
+
+/// Errors decoding a versioned message.
+#[derive(Debug)]
+pub enum WireError {
+	/// The message is empty.
+	Empty,
+	/// The message is of a version that isn't supported.
+	UnsupportedVersion(u8),
+	/// The message couldn't be decoded.
+	Codec(CodecError),
+}
+
+impl From<CodecError> for WireError {
+	fn from(e: CodecError) -> Self {
+		WireError::Codec(e)
+	}
+}
+
+/// Encode a message in the current version of the wire format.
+pub fn encode<M: WireMessage>(message: &M) -> Vec<u8> {
+	let mut encoded = Vec::with_capacity(1 + message.size_hint());
+	encoded.push(WIRE_VERSION);
+	message.encode_to(&mut encoded);
+	encoded
+}
+
+/// Decode a message of the current or the previous version of the wire format.
+pub fn decode<M: WireMessage>(encoded: &[u8]) -> Result<M, WireError> {
+	let (version, mut payload) = encoded.split_first().ok_or(WireError::Empty)?;
+	match *version {
+		WIRE_VERSION => Ok(M::decode_all(&mut payload)?),
+		v if v.checked_add(1) == Some(WIRE_VERSION) =>
+			Ok(M::upgrade(M::Previous::decode_all(&mut payload)?)),
+		v => Err(WireError::UnsupportedVersion(v)),
+	}
+}
+
+/// Encoding of messages of type `M` on the wire.
+pub trait Codec<M> {
+	/// Errors decoding a message.
+	type Error: Debug;
+
+	/// Encode a message.
+	fn encode(&self, message: &M) -> Vec<u8>;
+
+	/// Decode a message.
+	fn decode(&self, encoded: &[u8]) -> Result<M, Self::Error>;
+}
+
+/// The versioned SCALE encoding, see [`encode`] and [`decode`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScaleCodec;
+
+impl<M: WireMessage> Codec<M> for ScaleCodec {
+	type Error = WireError;
+
+	fn encode(&self, message: &M) -> Vec<u8> {
+		encode(message)
+	}
+
+	fn decode(&self, encoded: &[u8]) -> Result<M, WireError> {
+		decode(encoded)
+	}
+}
+
+// messages which were introduced in the current version of the wire format.
+macro_rules! impl_new_wire_message {
+	($($ty:ident<$($param:ident),+>),+ $(,)?) => {$(
+		impl<$($param),+> WireMessage for $ty<$($param),+>
+		where
+			Self: Encode + Decode,
+		{
+			type Previous = NoPrevious;
+
+			fn upgrade(previous: NoPrevious) -> Self {
+				match previous {}
+			}
+		}
+	)+};
+}
+
+impl_new_wire_message!(
+	StatementBatch<G, C, D, A, S>,
+	Announcement<D, A, S>,
+	CandidateRequest<D>,
+	CandidateResponse<C, D, A, S>,
+	BodyResponse<C>,
+	SignedMessage<P, D, A, S>,
+	Justification<D, A, S>,
+);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// a message whose encoding changed: version 0 carried a `u32`.
+	#[derive(Debug, PartialEq, Encode, Decode)]
+	struct Vote {
+		round: u64,
+	}
+
+	impl WireMessage for Vote {
+		type Previous = u32;
+
+		fn upgrade(previous: u32) -> Self {
+			Vote { round: previous.into() }
+		}
+	}
+
+	#[test]
+	fn current_and_previous_versions_are_decoded() {
+		let encoded = encode(&Vote { round: 7 });
+		assert_eq!(encoded[0], WIRE_VERSION);
+		assert_eq!(decode::<Vote>(&encoded).unwrap(), Vote { round: 7 });
+
+		let mut previous = vec![WIRE_VERSION - 1];
+		previous.extend(5u32.encode());
+		assert_eq!(decode::<Vote>(&previous).unwrap(), Vote { round: 5 });
+
+		assert!(matches!(decode::<Vote>(&[]), Err(WireError::Empty)));
+		assert!(matches!(
+			decode::<Vote>(&[WIRE_VERSION + 1, 0]),
+			Err(WireError::UnsupportedVersion(v)) if v == WIRE_VERSION + 1
+		));
+		// trailing bytes are rejected.
+		assert!(matches!(decode::<Vote>(&[encoded, vec![0]].concat()), Err(WireError::Codec(_))));
+	}
+
+	#[test]
+	fn messages_without_previous_version_are_rejected() {
+		let request = CandidateRequest { digest: 42u64 };
+		let mut encoded = encode(&request);
+		assert_eq!(decode::<CandidateRequest<u64>>(&encoded).unwrap(), request);
+
+		encoded[0] = WIRE_VERSION - 1;
+		assert!(matches!(decode::<CandidateRequest<u64>>(&encoded), Err(WireError::Codec(_))));
+	}
+}